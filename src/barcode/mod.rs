@@ -0,0 +1,73 @@
+mod svg;
+
+pub use self::svg::{code128_svg, ean13_svg, qr_svg};
+
+/// Validates a GTIN-8, UPC-A (GTIN-12), EAN-13 or GTIN-14 and returns it
+/// zero-padded to 14 digits, so the same item scanned as UPC-A or EAN-13
+/// resolves to one key.
+pub fn normalize_gtin(code: &str) -> Option<String> {
+    let code = code.trim();
+    if !matches!(code.len(), 8 | 12 | 13 | 14) || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let digits: Vec<u8> = code.bytes().map(|b| b - b'0').collect();
+    let (payload, check_digit) = digits.split_at(digits.len() - 1);
+    if gtin_check_digit(payload) != check_digit[0] {
+        return None;
+    }
+
+    Some(format!("{code:0>14}"))
+}
+
+/// GS1 mod-10 check digit: weights alternate 3 and 1 from the rightmost digit.
+pub fn gtin_check_digit(payload: &[u8]) -> u8 {
+    let sum: u32 = payload
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| *d as u32 * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+
+    ((10 - sum % 10) % 10) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digits_of_published_codes() {
+        let digits = |code: &str| code.bytes().map(|b| b - b'0').collect::<Vec<_>>();
+        assert_eq!(gtin_check_digit(&digits("400638133393")), 1);
+        assert_eq!(gtin_check_digit(&digits("03600029145")), 2);
+        assert_eq!(gtin_check_digit(&digits("9638507")), 4);
+    }
+
+    #[test]
+    fn normalizes_every_gtin_length_to_14_digits() {
+        assert_eq!(normalize_gtin("96385074").unwrap(), "00000096385074");
+        assert_eq!(normalize_gtin("036000291452").unwrap(), "00036000291452");
+        assert_eq!(normalize_gtin("4006381333931").unwrap(), "04006381333931");
+        assert_eq!(
+            normalize_gtin(" 00036000291452 ").unwrap(),
+            "00036000291452"
+        );
+    }
+
+    #[test]
+    fn upc_a_and_its_ean_13_are_the_same_item() {
+        assert_eq!(
+            normalize_gtin("036000291452"),
+            normalize_gtin("0036000291452")
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_check_digits_and_lengths() {
+        assert!(normalize_gtin("4006381333932").is_none());
+        assert!(normalize_gtin("400638133393").is_none());
+        assert!(normalize_gtin("40063813339A1").is_none());
+        assert!(normalize_gtin("").is_none());
+    }
+}
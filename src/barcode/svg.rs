@@ -0,0 +1,277 @@
+use qrcode::render::svg;
+use qrcode::QrCode;
+
+// Bar patterns are rendered as strings of modules, '1' for a bar and '0' for a space.
+
+const MODULE_WIDTH: usize = 2;
+const BAR_HEIGHT: usize = 80;
+const TEXT_HEIGHT: usize = 20;
+const QUIET_ZONE: usize = 10;
+
+// region: EAN-13
+const EAN_L: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011",
+    "0110111", "0001011",
+];
+const EAN_G: [&str; 10] = [
+    "0100111", "0110011", "0011011", "0100001", "0011101", "0111001", "0000101", "0010001",
+    "0001001", "0010111",
+];
+const EAN_R: [&str; 10] = [
+    "1110010", "1100110", "1101100", "1000010", "1011100", "1001110", "1010000", "1000100",
+    "1001000", "1110100",
+];
+/// L/G parity of the left half, selected by the first digit.
+const EAN_PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL",
+    "LGGLGL",
+];
+
+/// Renders a 13 digit code (a 12 digit UPC-A is zero-padded) as an EAN-13
+/// symbol. The code must already carry a valid check digit.
+pub fn ean13_svg(code: &str) -> Option<String> {
+    let (modules, code) = ean13_modules(code)?;
+
+    Some(render(&modules, &code))
+}
+
+/// The modules of an EAN-13 symbol, with the 13 digits they encode.
+fn ean13_modules(code: &str) -> Option<(String, String)> {
+    let code = match code.len() {
+        12 => format!("0{code}"),
+        13 => code.to_string(),
+        _ => return None,
+    };
+    let digits: Vec<usize> = code
+        .chars()
+        .map(|c| c.to_digit(10).map(|d| d as usize))
+        .collect::<Option<_>>()?;
+
+    let parity = EAN_PARITY[digits[0]];
+    let mut modules = String::from("101");
+    for (digit, parity) in digits[1..7].iter().zip(parity.chars()) {
+        modules.push_str(match parity {
+            'L' => EAN_L[*digit],
+            _ => EAN_G[*digit],
+        });
+    }
+    modules.push_str("01010");
+    for digit in &digits[7..] {
+        modules.push_str(EAN_R[*digit]);
+    }
+    modules.push_str("101");
+
+    Some((modules, code))
+}
+// endregion: EAN-13
+
+// region: Code 128
+const CODE128_PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+const CODE128_START_B: usize = 104;
+const CODE128_START_C: usize = 105;
+const CODE128_STOP: usize = 106;
+
+/// Renders printable ASCII as a Code 128 symbol, using code set C for
+/// all-digit data of even length and code set B otherwise.
+pub fn code128_svg(data: &str) -> Option<String> {
+    let mut modules = String::new();
+    for value in code128_values(data)? {
+        // Widths alternate bar, space, bar, ...
+        for (i, width) in CODE128_PATTERNS[value].chars().enumerate() {
+            let module = if i % 2 == 0 { '1' } else { '0' };
+            for _ in 0..width.to_digit(10).unwrap_or(0) {
+                modules.push(module);
+            }
+        }
+    }
+
+    Some(render(&modules, data))
+}
+
+/// The symbol values of `data`, from the start code to the stop code.
+fn code128_values(data: &str) -> Option<Vec<usize>> {
+    if data.is_empty() || !data.bytes().all(|b| (32..127).contains(&b)) {
+        return None;
+    }
+
+    let mut values = Vec::new();
+    let pairs = data.as_bytes().chunks_exact(2);
+    if pairs.remainder().is_empty() && data.bytes().all(|b| b.is_ascii_digit()) {
+        values.push(CODE128_START_C);
+        for pair in pairs {
+            values.push(((pair[0] - b'0') * 10 + (pair[1] - b'0')) as usize);
+        }
+    } else {
+        values.push(CODE128_START_B);
+        values.extend(data.bytes().map(|b| (b - 32) as usize));
+    }
+
+    let checksum = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v * i.max(1))
+        .sum::<usize>()
+        % 103;
+    values.push(checksum);
+    values.push(CODE128_STOP);
+
+    Some(values)
+}
+// endregion: Code 128
+
+// region: QR
+/// Renders `data` as a QR code, e.g. an otpauth:// link for an authenticator
+/// app. None if it is too long for one.
+pub fn qr_svg(data: &str) -> Option<String> {
+    let code = QrCode::new(data.as_bytes()).ok()?;
+
+    Some(
+        code.render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .quiet_zone(true)
+            .build(),
+    )
+}
+// endregion: QR
+
+fn render(modules: &str, text: &str) -> String {
+    let width = (modules.len() + QUIET_ZONE * 2) * MODULE_WIDTH;
+    let height = BAR_HEIGHT + TEXT_HEIGHT;
+
+    let mut svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}"><rect width="100%" height="100%" fill="#fff"/>"##
+    );
+
+    // Merge adjacent bar modules into a single rect.
+    let bytes = modules.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'1' {
+            let start = i;
+            while i < bytes.len() && bytes[i] == b'1' {
+                i += 1;
+            }
+            svg.push_str(&format!(
+                r##"<rect x="{}" y="0" width="{}" height="{BAR_HEIGHT}" fill="#000"/>"##,
+                (start + QUIET_ZONE) * MODULE_WIDTH,
+                (i - start) * MODULE_WIDTH
+            ));
+        } else {
+            i += 1;
+        }
+    }
+
+    svg.push_str(&format!(
+        r#"<text x="{}" y="{}" font-family="monospace" font-size="16" text-anchor="middle">{}</text></svg>"#,
+        width / 2,
+        height - 4,
+        escape(text)
+    ));
+
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ean13_encodes_a_published_code() {
+        // 4006381333931: first digit 4 gives the left half parity LGLLGG.
+        let expected = [
+            "101", "0001101", // L0
+            "0100111", // G0
+            "0101111", // L6
+            "0111101", // L3
+            "0001001", // G8
+            "0110011", // G1
+            "01010", "1000010", // R3
+            "1000010", // R3
+            "1000010", // R3
+            "1110100", // R9
+            "1000010", // R3
+            "1100110", // R1
+            "101",
+        ]
+        .concat();
+
+        let (modules, code) = ean13_modules("4006381333931").unwrap();
+        assert_eq!(code, "4006381333931");
+        assert_eq!(modules.len(), 95);
+        assert_eq!(modules, expected);
+    }
+
+    #[test]
+    fn ean13_pads_upc_a() {
+        let (modules, code) = ean13_modules("036000291452").unwrap();
+        assert_eq!(code, "0036000291452");
+        // First digit 0 encodes the left half all with L.
+        assert_eq!(&modules[3..10], EAN_L[0]);
+        assert_eq!(&modules[10..17], EAN_L[3]);
+    }
+
+    #[test]
+    fn ean13_rejects_other_lengths_and_letters() {
+        assert!(ean13_modules("12345").is_none());
+        assert!(ean13_modules("40063813339A1").is_none());
+    }
+
+    #[test]
+    fn code128_checksums_code_set_b() {
+        // Start B 104, then P J J 1 2 3 C as 48 42 42 17 18 19 35:
+        // (104 + 48 + 2*42 + 3*42 + 4*17 + 5*18 + 6*19 + 7*35) % 103 = 55.
+        assert_eq!(
+            code128_values("PJJ123C").unwrap(),
+            vec![104, 48, 42, 42, 17, 18, 19, 35, 55, 106]
+        );
+    }
+
+    #[test]
+    fn code128_uses_code_set_c_for_even_digits() {
+        // (105 + 12 + 2*34 + 3*56) % 103 = 44.
+        assert_eq!(
+            code128_values("123456").unwrap(),
+            vec![105, 12, 34, 56, 44, 106]
+        );
+        // An odd number of digits stays in code set B.
+        assert_eq!(code128_values("12345").unwrap()[0], CODE128_START_B);
+    }
+
+    #[test]
+    fn code128_start_and_stop_bars() {
+        let svg = code128_svg("A").unwrap();
+        // Start B is 11010010000; the first bar is two modules wide.
+        assert!(svg.contains(&format!(
+            r##"<rect x="{}" y="0" width="{}""##,
+            QUIET_ZONE * MODULE_WIDTH,
+            2 * MODULE_WIDTH
+        )));
+        assert_eq!(CODE128_PATTERNS[CODE128_STOP], "2331112");
+    }
+
+    #[test]
+    fn code128_rejects_empty_and_non_ascii() {
+        assert!(code128_values("").is_none());
+        assert!(code128_values("café").is_none());
+    }
+}
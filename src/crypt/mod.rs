@@ -1,17 +1,32 @@
 mod error;
 
 pub use self::error::{Error, Result};
-use bcrypt::{hash, DEFAULT_COST};
+use bcrypt::{hash, verify, DEFAULT_COST};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
 
 pub fn hash_value(input: &str) -> Result<String> {
     hash(input, DEFAULT_COST).map_err(|e| Error::BcrpytError(e.to_string()))
 }
 
 pub fn compare_hash(hash: &str, input: &str) -> Result<()> {
-    let val = hash_value(input)?;
-    if val != hash {
+    let matches = verify(input, hash).map_err(|e| Error::BcrpytError(e.to_string()))?;
+    if !matches {
         return Err(Error::ComparisonError("Inputs are not equal".to_string()));
     }
 
     Ok(())
 }
+
+/// A random token for a cookie or a link, 32 bytes as hex.
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// How a token is stored. Tokens are random, so a fast unsalted hash is enough
+/// and lets them be looked up by hash.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -1,14 +1,37 @@
-#[derive(Clone)]
+use crate::model::permissions::Permissions;
+
+/// Who a request is for, as verified by the auth layer from its session.
+#[derive(Debug, Clone)]
 pub struct Ctx {
     user_id: Option<i64>,
     organization_id: Option<i64>,
+    api_key_id: Option<i64>,
+    permissions: Vec<Permissions>,
 }
 
 impl Ctx {
-    pub fn new(user_id: i64, organization_id: i64) -> Self {
+    /// The user of a session; superusers have no organization.
+    pub fn new(user_id: i64, organization_id: Option<i64>, permissions: Vec<Permissions>) -> Self {
         Self {
             user_id: Some(user_id),
+            organization_id,
+            api_key_id: None,
+            permissions,
+        }
+    }
+
+    /// A machine calling with an API key of the organization, limited to the
+    /// key's scopes.
+    pub fn for_api_key(
+        organization_id: i64,
+        api_key_id: i64,
+        permissions: Vec<Permissions>,
+    ) -> Self {
+        Self {
+            user_id: None,
             organization_id: Some(organization_id),
+            api_key_id: Some(api_key_id),
+            permissions,
         }
     }
 
@@ -16,6 +39,8 @@ impl Ctx {
         Self {
             user_id: None,
             organization_id: None,
+            api_key_id: None,
+            permissions: Vec::new(),
         }
     }
 
@@ -26,4 +51,12 @@ impl Ctx {
     pub fn organization_id(&self) -> Option<i64> {
         self.organization_id
     }
+
+    pub fn api_key_id(&self) -> Option<i64> {
+        self.api_key_id
+    }
+
+    pub fn permissions(&self) -> &[Permissions] {
+        &self.permissions
+    }
 }
@@ -1,9 +1,33 @@
+DROP TABLE IF EXISTS inventory_log_serial_numbers;
+DROP TABLE IF EXISTS serial_numbers;
 DROP TABLE IF EXISTS inventory_logs;
+DROP TABLE IF EXISTS lots;
+DROP TABLE IF EXISTS product_units;
+DROP TABLE IF EXISTS product_barcodes;
 DROP TABLE IF EXISTS inventory_transactions;
+DROP TABLE IF EXISTS invoice_sequences;
+DROP TABLE IF EXISTS customers;
+DROP TABLE IF EXISTS price_list_items;
+DROP TABLE IF EXISTS price_lists;
+DROP TABLE IF EXISTS exchange_rates;
 DROP TABLE IF EXISTS product_categories;
-DROP TABLE IF EXISTS categories;
+DROP TABLE IF EXISTS product_variant_option_values;
+DROP TABLE IF EXISTS product_option_values;
+DROP TABLE IF EXISTS product_options;
 DROP TABLE IF EXISTS products;
+DROP TABLE IF EXISTS tax_classes;
+DROP TABLE IF EXISTS categories;
 DROP TABLE IF EXISTS warehouses;
+DROP TABLE IF EXISTS api_key_calls;
+DROP TABLE IF EXISTS api_key_permissions;
+DROP TABLE IF EXISTS api_keys;
+DROP TABLE IF EXISTS login_lockouts;
+DROP TABLE IF EXISTS login_attempts;
+DROP TABLE IF EXISTS password_reset_requests;
+DROP TABLE IF EXISTS user_recovery_codes;
+DROP TABLE IF EXISTS invitations;
+DROP TABLE IF EXISTS superuser_actions;
+DROP TABLE IF EXISTS sessions;
 DROP TABLE IF EXISTS user_permissions;
 DROP TABLE IF EXISTS permissions;
 DROP TABLE IF EXISTS users;
@@ -11,11 +35,33 @@ DROP TABLE IF EXISTS organizations;
 DROP TABLE IF EXISTS flyway_schema_history;
 DROP TYPE inventory_log_action;
 DROP TYPE inventory_transaction_action;
+DROP TYPE login_outcome;
+DROP TYPE superuser_action;
+DROP TYPE organization_status;
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE TYPE organization_status AS ENUM (
+  'ACTIVE', 'SUSPENDED', 'PENDING_DELETION'
+);
 
 CREATE TABLE IF NOT EXISTS organizations (
   id BIGSERIAL PRIMARY KEY NOT NULL,
   name VARCHAR(255) NOT NULL UNIQUE,
-  display_name VARCHAR(255) NOT NULL
+  display_name VARCHAR(255) NOT NULL,
+  -- ISO 4217 code valuation and reports are in
+  base_currency VARCHAR(3) NOT NULL DEFAULT 'PHP',
+  -- Whether product prices include tax; price lists have their own switch
+  prices_include_tax BOOLEAN NOT NULL DEFAULT FALSE,
+  -- Users of a suspended organization cannot log in
+  status organization_status NOT NULL DEFAULT 'ACTIVE',
+  -- Why it was suspended, told to its users
+  status_reason TEXT,
+  -- When a pending deletion runs
+  deletion_due_at TIMESTAMPTZ,
+  -- Users must set up two-factor authentication to log in
+  two_factor_required BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
 );
 
 CREATE TABLE IF NOT EXISTS users (
@@ -23,7 +69,16 @@ CREATE TABLE IF NOT EXISTS users (
   display_name VARCHAR(255) NOT NULL,
   username VARCHAR(255) NOT NULL,
   password TEXT NOT NULL,
+  -- Where password resets go; set from the invitation
+  email VARCHAR(255),
   organization_id BIGINT,
+  -- Deactivated users cannot log in; their rows stay for the audit trail
+  active BOOLEAN NOT NULL DEFAULT TRUE,
+  -- Base32 TOTP secret, in use once enabled
+  totp_secret TEXT,
+  totp_enabled_at TIMESTAMPTZ,
+  -- The last time step a code was accepted for, so codes work once
+  totp_last_step BIGINT,
 
   UNIQUE(username, organization_id),
 
@@ -57,6 +112,234 @@ CREATE TABLE IF NOT EXISTS user_permissions (
     ON DELETE CASCADE
 );
 
+-- The cookie holds the token; only its hash is stored
+CREATE TABLE IF NOT EXISTS sessions (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  token_hash TEXT NOT NULL UNIQUE,
+  user_id BIGINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMPTZ NOT NULL,
+  -- The superuser acting as user_id
+  impersonated_by BIGINT,
+  -- Password checked, second factor not yet; not a login
+  two_factor_pending BOOLEAN NOT NULL DEFAULT FALSE,
+  two_factor_attempts INT NOT NULL DEFAULT 0,
+
+  CONSTRAINT fk_sessions_users
+    FOREIGN KEY(user_id)
+    REFERENCES users(id)
+    ON DELETE CASCADE,
+
+  CONSTRAINT fk_sessions_impersonated_by
+    FOREIGN KEY(impersonated_by)
+    REFERENCES users(id)
+    ON DELETE CASCADE
+);
+
+CREATE TYPE superuser_action AS ENUM (
+  'CREATE_ORGANIZATION', 'SUSPEND_ORGANIZATION', 'REACTIVATE_ORGANIZATION', 'IMPERSONATE',
+  'SCHEDULE_DELETION', 'DELETE_ORGANIZATION'
+);
+
+-- What superusers did to organizations. Kept when the organization goes.
+CREATE TABLE IF NOT EXISTS superuser_actions (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  -- NULL for the deletion job
+  superuser_id BIGINT,
+  organization_id BIGINT,
+  organization_name VARCHAR(255) NOT NULL,
+  action superuser_action NOT NULL,
+  -- The user impersonated
+  target_user_id BIGINT,
+  reason TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+  CONSTRAINT fk_superuser_actions_superusers
+    FOREIGN KEY(superuser_id)
+    REFERENCES users(id)
+    ON DELETE SET NULL,
+
+  CONSTRAINT fk_superuser_actions_organizations
+    FOREIGN KEY(organization_id)
+    REFERENCES organizations(id)
+    ON DELETE SET NULL,
+
+  CONSTRAINT fk_superuser_actions_target_users
+    FOREIGN KEY(target_user_id)
+    REFERENCES users(id)
+    ON DELETE SET NULL
+);
+
+-- Single use. The link holds the token; only its hash is stored. The user is
+-- created when the invitee sets a password.
+CREATE TABLE IF NOT EXISTS invitations (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  token_hash TEXT NOT NULL UNIQUE,
+  display_name VARCHAR(255) NOT NULL,
+  username VARCHAR(255) NOT NULL,
+  email VARCHAR(255),
+  admin BOOLEAN NOT NULL DEFAULT FALSE,
+  organization_id BIGINT NOT NULL,
+  invited_by BIGINT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMPTZ NOT NULL,
+  accepted_at TIMESTAMPTZ,
+
+  CONSTRAINT fk_invitations_organizations
+    FOREIGN KEY(organization_id)
+    REFERENCES organizations(id)
+    ON DELETE CASCADE,
+  CONSTRAINT fk_invitations_users
+    FOREIGN KEY(invited_by)
+    REFERENCES users(id)
+    ON DELETE SET NULL
+);
+
+-- One-time codes for when the authenticator is lost; only their hashes are
+-- stored
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  user_id BIGINT NOT NULL,
+  code_hash TEXT NOT NULL,
+  used_at TIMESTAMPTZ,
+
+  UNIQUE(user_id, code_hash),
+
+  CONSTRAINT fk_user_recovery_codes_users
+    FOREIGN KEY(user_id)
+    REFERENCES users(id)
+    ON DELETE CASCADE
+);
+
+-- Every request, answered or not, so they can be rate limited per account and
+-- per address. Only requests that mailed a link have a token hash.
+CREATE TABLE IF NOT EXISTS password_reset_requests (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  -- NULL when no account matched
+  user_id BIGINT,
+  ip TEXT NOT NULL,
+  token_hash TEXT UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMPTZ NOT NULL,
+  used_at TIMESTAMPTZ,
+
+  CONSTRAINT fk_password_reset_requests_users
+    FOREIGN KEY(user_id)
+    REFERENCES users(id)
+    ON DELETE CASCADE
+);
+
+CREATE INDEX password_reset_requests_ip_idx ON password_reset_requests(ip, created_at);
+
+CREATE TYPE login_outcome AS ENUM (
+  'SUCCEEDED', 'FAILED', 'THROTTLED', 'LOCKED'
+);
+
+-- Every login attempt, for audit. Names are kept as typed, so attempts on
+-- users that do not exist are recorded too; those have no user or
+-- organization. Throttled and locked attempts never got their password
+-- checked.
+CREATE TABLE IF NOT EXISTS login_attempts (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  organization_name VARCHAR(255) NOT NULL,
+  username VARCHAR(255) NOT NULL,
+  organization_id BIGINT,
+  user_id BIGINT,
+  ip TEXT NOT NULL,
+  outcome login_outcome NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+  CONSTRAINT fk_login_attempts_organizations
+    FOREIGN KEY(organization_id)
+    REFERENCES organizations(id)
+    ON DELETE CASCADE,
+
+  CONSTRAINT fk_login_attempts_users
+    FOREIGN KEY(user_id)
+    REFERENCES users(id)
+    ON DELETE SET NULL
+);
+
+CREATE INDEX login_attempts_organization_idx ON login_attempts(organization_id, created_at);
+
+-- Failed logins in a row per name typed, whether or not the user exists, so
+-- that being throttled or locked out says nothing about which users do.
+CREATE TABLE IF NOT EXISTS login_lockouts (
+  organization_name VARCHAR(255) NOT NULL,
+  username VARCHAR(255) NOT NULL,
+  failures INT NOT NULL DEFAULT 0,
+  last_failed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  locked_until TIMESTAMPTZ,
+
+  PRIMARY KEY(organization_name, username)
+);
+
+-- For machines calling /api/v1. The caller holds the key; only its hash is
+-- stored, with its first characters so people can tell keys apart. Revoked
+-- keys are kept for their calls.
+CREATE TABLE IF NOT EXISTS api_keys (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  prefix VARCHAR(32) NOT NULL,
+  token_hash TEXT NOT NULL UNIQUE,
+  organization_id BIGINT NOT NULL,
+  created_by BIGINT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- NULL for a key that does not expire
+  expires_at TIMESTAMPTZ,
+  last_used_at TIMESTAMPTZ,
+  revoked_at TIMESTAMPTZ,
+
+  CONSTRAINT fk_api_keys_organizations
+    FOREIGN KEY(organization_id)
+    REFERENCES organizations(id)
+    ON DELETE CASCADE,
+  CONSTRAINT fk_api_keys_users
+    FOREIGN KEY(created_by)
+    REFERENCES users(id)
+    ON DELETE SET NULL
+);
+
+-- The scopes of a key, as permissions
+CREATE TABLE IF NOT EXISTS api_key_permissions (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  api_key_id BIGINT NOT NULL,
+  permission_id BIGINT NOT NULL,
+
+  UNIQUE(api_key_id, permission_id),
+
+  CONSTRAINT fk_api_key_permissions_api_keys
+    FOREIGN KEY(api_key_id)
+    REFERENCES api_keys(id)
+    ON DELETE CASCADE,
+  CONSTRAINT fk_api_key_permissions_permissions
+    FOREIGN KEY(permission_id)
+    REFERENCES permissions(id)
+    ON DELETE CASCADE
+);
+
+-- Every call made with a key
+CREATE TABLE IF NOT EXISTS api_key_calls (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  api_key_id BIGINT NOT NULL,
+  organization_id BIGINT NOT NULL,
+  method VARCHAR(16) NOT NULL,
+  path TEXT NOT NULL,
+  status SMALLINT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+  CONSTRAINT fk_api_key_calls_api_keys
+    FOREIGN KEY(api_key_id)
+    REFERENCES api_keys(id)
+    ON DELETE CASCADE,
+  CONSTRAINT fk_api_key_calls_organizations
+    FOREIGN KEY(organization_id)
+    REFERENCES organizations(id)
+    ON DELETE CASCADE
+);
+
+CREATE INDEX api_key_calls_organization_idx ON api_key_calls(organization_id, created_at);
+
 CREATE TABLE IF NOT EXISTS warehouses (
   id BIGSERIAL PRIMARY KEY NOT NULL,
   name VARCHAR(255) DEFAULT 'default',
@@ -70,6 +353,41 @@ CREATE TABLE IF NOT EXISTS warehouses (
 	  ON DELETE CASCADE
 );
 
+-- What a product is taxed at, e.g. standard, reduced or zero-rated VAT.
+CREATE TABLE IF NOT EXISTS tax_classes (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  -- Percent, 12 for 12%
+  rate NUMERIC NOT NULL CHECK (rate >= 0 AND rate < 100),
+  organization_id BIGINT NOT NULL,
+
+  UNIQUE(name, organization_id),
+
+  CONSTRAINT fk_tax_classes_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
+	  ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS categories (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  parent_id BIGINT,
+  organization_id BIGINT NOT NULL,
+
+  UNIQUE NULLS NOT DISTINCT (name, parent_id, organization_id),
+
+  CONSTRAINT fk_category_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_categories_categories
+    FOREIGN KEY(parent_id)
+	  REFERENCES categories(id)
+	  ON DELETE RESTRICT
+);
+
 CREATE TABLE IF NOT EXISTS products (
   id BIGSERIAL PRIMARY KEY NOT NULL,
   sku VARCHAR(255) NOT NULL,
@@ -79,44 +397,212 @@ CREATE TABLE IF NOT EXISTS products (
   description VARCHAR(255) NOT NULL,
   organization_id BIGINT NOT NULL,
   price NUMERIC NOT NULL DEFAULT 0,
+  lot_tracked BOOLEAN NOT NULL DEFAULT FALSE,
+  serialized BOOLEAN NOT NULL DEFAULT FALSE,
+  parent_id BIGINT,
+  base_unit VARCHAR(50) NOT NULL DEFAULT 'unit',
+  category_id BIGINT,
+  -- Untaxed without one
+  tax_class_id BIGINT,
+  search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', sku), 'A') ||
+    setweight(to_tsvector('simple', name), 'A') ||
+    setweight(to_tsvector('simple', brand), 'B') ||
+    setweight(to_tsvector('simple', description), 'C')
+  ) STORED,
 
   UNIQUE(sku, organization_id),
+  CHECK(NOT (lot_tracked AND serialized)),
 
   CONSTRAINT fk_products_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_products_products
+    FOREIGN KEY(parent_id)
+	  REFERENCES products(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_products_categories
+    FOREIGN KEY(category_id)
+	  REFERENCES categories(id)
+	  ON DELETE RESTRICT,
+
+  CONSTRAINT fk_products_tax_classes
+    FOREIGN KEY(tax_class_id)
+	  REFERENCES tax_classes(id)
+	  ON DELETE RESTRICT
+);
+
+CREATE INDEX IF NOT EXISTS idx_products_search_vector ON products USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS idx_products_search_trigram ON products USING GIN ((sku || ' ' || display_name) gin_trgm_ops);
+
+CREATE TABLE IF NOT EXISTS product_barcodes (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  product_id BIGINT NOT NULL,
+  -- As entered, e.g. a 12 digit UPC-A or 13 digit EAN-13.
+  code VARCHAR(14) NOT NULL,
+  -- Zero-padded to 14 digits so UPC-A and EAN-13 forms of one item collide.
+  gtin CHAR(14) NOT NULL,
+  organization_id BIGINT NOT NULL,
+
+  UNIQUE(gtin, organization_id),
+
+  CONSTRAINT fk_product_barcodes_products
+    FOREIGN KEY(product_id)
+	  REFERENCES products(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_product_barcodes_organizations
     FOREIGN KEY(organization_id)
 	  REFERENCES organizations(id)
 	  ON DELETE CASCADE
 );
 
-CREATE TABLE IF NOT EXISTS categories (
+CREATE TABLE IF NOT EXISTS product_units (
   id BIGSERIAL PRIMARY KEY NOT NULL,
-  name VARCHAR(255) NOT NULL UNIQUE,
+  product_id BIGINT NOT NULL,
+  name VARCHAR(50) NOT NULL,
+  conversion_factor BIGINT NOT NULL CHECK (conversion_factor > 0),
+
+  UNIQUE(product_id, name),
+
+  CONSTRAINT fk_product_units_products
+    FOREIGN KEY(product_id)
+	  REFERENCES products(id)
+	  ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS product_options (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  product_id BIGINT NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  position INTEGER NOT NULL,
+
+  UNIQUE(product_id, name),
+
+  CONSTRAINT fk_product_options_products
+    FOREIGN KEY(product_id)
+	  REFERENCES products(id)
+	  ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS product_option_values (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  product_option_id BIGINT NOT NULL,
+  value VARCHAR(255) NOT NULL,
+  position INTEGER NOT NULL,
+
+  UNIQUE(product_option_id, value),
+
+  CONSTRAINT fk_product_option_values_product_options
+    FOREIGN KEY(product_option_id)
+	  REFERENCES product_options(id)
+	  ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS product_variant_option_values (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  product_id BIGINT NOT NULL,
+  product_option_value_id BIGINT NOT NULL,
+
+  UNIQUE(product_id, product_option_value_id),
+
+  CONSTRAINT fk_product_variant_option_values_products
+    FOREIGN KEY(product_id)
+	  REFERENCES products(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_product_variant_option_values_product_option_values
+    FOREIGN KEY(product_option_value_id)
+	  REFERENCES product_option_values(id)
+	  ON DELETE CASCADE
+);
+
+-- Prices are in the organization's base currency.
+CREATE TABLE IF NOT EXISTS price_lists (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  description TEXT NOT NULL DEFAULT '',
+  -- Whether the prices include tax
+  tax_inclusive BOOLEAN NOT NULL DEFAULT FALSE,
   organization_id BIGINT NOT NULL,
 
-  CONSTRAINT fk_category_organizations
+  UNIQUE(name, organization_id),
+
+  CONSTRAINT fk_price_lists_organizations
     FOREIGN KEY(organization_id)
 	  REFERENCES organizations(id)
 	  ON DELETE CASCADE
 );
 
-CREATE TABLE IF NOT EXISTS product_categories (
+-- The price of a product from min_quantity units up, between valid_from and
+-- valid_to (both included, open ended when NULL).
+CREATE TABLE IF NOT EXISTS price_list_items (
   id BIGSERIAL PRIMARY KEY NOT NULL,
-  category_id BIGINT NOT NULL,
+  price_list_id BIGINT NOT NULL,
   product_id BIGINT NOT NULL,
+  min_quantity BIGINT NOT NULL DEFAULT 1 CHECK (min_quantity > 0),
+  price NUMERIC NOT NULL CHECK (price >= 0),
+  valid_from DATE,
+  valid_to DATE,
+  organization_id BIGINT NOT NULL,
 
-  CONSTRAINT fk_product_categories_categories
-    FOREIGN KEY(category_id)
-	  REFERENCES categories(id)
+  CHECK(valid_from IS NULL OR valid_to IS NULL OR valid_from <= valid_to),
+
+  CONSTRAINT fk_price_list_items_price_lists
+    FOREIGN KEY(price_list_id)
+	  REFERENCES price_lists(id)
 	  ON DELETE CASCADE,
 
-  CONSTRAINT fk_product_categories_products
+  CONSTRAINT fk_price_list_items_products
     FOREIGN KEY(product_id)
 	  REFERENCES products(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_price_list_items_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
+	  ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_price_list_items_product ON price_list_items (price_list_id, product_id);
+
+CREATE TABLE IF NOT EXISTS customers (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  name VARCHAR(255) NOT NULL,
+  -- Without one, customers pay the product price.
+  price_list_id BIGINT,
+  organization_id BIGINT NOT NULL,
+
+  UNIQUE(name, organization_id),
+
+  CONSTRAINT fk_customers_price_lists
+    FOREIGN KEY(price_list_id)
+	  REFERENCES price_lists(id)
+	  ON DELETE SET NULL,
+
+  CONSTRAINT fk_customers_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
 	  ON DELETE CASCADE
 );
 
 CREATE TYPE inventory_transaction_action AS ENUM (
-  'SALES', 'DEPOSIT', 'SALES_ROLLBACK', 'DEPOSIT_ROLLBACK'
+  'SALES', 'DEPOSIT', 'SALES_ROLLBACK', 'DEPOSIT_ROLLBACK', 'OPENING_BALANCE'
+);
+
+-- Number of the last invoice issued per organization. Counted up in the
+-- sale's transaction, so a sale that rolls back leaves no gap.
+CREATE TABLE IF NOT EXISTS invoice_sequences (
+  organization_id BIGINT PRIMARY KEY NOT NULL,
+  last_number BIGINT NOT NULL,
+
+  CONSTRAINT fk_invoice_sequences_organizations
+      FOREIGN KEY(organization_id)
+  	  REFERENCES organizations(id)
+  	  ON DELETE CASCADE
 );
 
 CREATE TABLE IF NOT EXISTS inventory_transactions (
@@ -124,11 +610,69 @@ CREATE TABLE IF NOT EXISTS inventory_transactions (
   timestamp TIMESTAMPTZ DEFAULT NOW() NOT NULL,
   organization_id BIGINT NOT NULL,
   action inventory_transaction_action NOT NULL,
+  -- Prices of the transaction are in this currency; one unit of it is
+  -- exchange_rate units of the organization's base currency.
+  currency VARCHAR(3) NOT NULL,
+  exchange_rate NUMERIC NOT NULL DEFAULT 1 CHECK (exchange_rate > 0),
+  customer_id BIGINT,
+  -- Sales only, sequential per organization
+  invoice_number BIGINT,
+  -- The API key it was made with, if any
+  api_key_id BIGINT,
+
+  UNIQUE(invoice_number, organization_id),
 
   CONSTRAINT fk_inventory_transactions_organizations
       FOREIGN KEY(organization_id)
   	  REFERENCES organizations(id)
-  	  ON DELETE CASCADE
+  	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_inventory_transactions_customers
+      FOREIGN KEY(customer_id)
+  	  REFERENCES customers(id)
+  	  ON DELETE SET NULL,
+
+  CONSTRAINT fk_inventory_transactions_api_keys
+      FOREIGN KEY(api_key_id)
+  	  REFERENCES api_keys(id)
+  	  ON DELETE SET NULL
+);
+
+-- Units of the organization's base currency per unit of currency, from
+-- effective_date until the next rate.
+CREATE TABLE IF NOT EXISTS exchange_rates (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  currency VARCHAR(3) NOT NULL,
+  rate NUMERIC NOT NULL CHECK (rate > 0),
+  effective_date DATE NOT NULL,
+  organization_id BIGINT NOT NULL,
+
+  UNIQUE(currency, effective_date, organization_id),
+
+  CONSTRAINT fk_exchange_rates_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
+	  ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS lots (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  lot_number VARCHAR(255) NOT NULL,
+  expiry_date DATE,
+  product_id BIGINT NOT NULL,
+  organization_id BIGINT NOT NULL,
+
+  UNIQUE(lot_number, product_id),
+
+  CONSTRAINT fk_lots_products
+    FOREIGN KEY(product_id)
+	  REFERENCES products(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_lots_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
+	  ON DELETE CASCADE
 );
 
 CREATE TYPE inventory_log_action AS ENUM (
@@ -142,9 +686,24 @@ CREATE TABLE IF NOT EXISTS inventory_logs (
   action inventory_log_action NOT NULL,
   timestamp TIMESTAMPTZ DEFAULT NOW() NOT NULL,
   price NUMERIC NOT NULL,
+  currency VARCHAR(3) NOT NULL,
+  exchange_rate NUMERIC NOT NULL DEFAULT 1 CHECK (exchange_rate > 0),
+  base_price NUMERIC GENERATED ALWAYS AS (price * exchange_rate) STORED,
   organization_id BIGINT NOT NULL,
   warehouse_id BIGINT NOT NULL,
   inventory_transaction_id BIGINT,
+  lot_id BIGINT,
+  unit_name VARCHAR(50),
+  unit_quantity BIGINT,
+  unit_conversion_factor BIGINT,
+  -- Sales lines only: the tax as charged, in the transaction's currency
+  tax_class_id BIGINT,
+  tax_rate NUMERIC,
+  net_amount NUMERIC,
+  tax_amount NUMERIC,
+  gross_amount NUMERIC,
+
+  CHECK(net_amount + tax_amount = gross_amount),
 
   CONSTRAINT fk_inventory_log_organizations
     FOREIGN KEY(organization_id)
@@ -163,11 +722,154 @@ CREATE TABLE IF NOT EXISTS inventory_logs (
 
   CONSTRAINT fk_inventory_logs_inventory_transactions
     FOREIGN KEY(inventory_transaction_id)
-    REFERENCES inventory_transactions(id)
+    REFERENCES inventory_transactions(id),
+
+  CONSTRAINT fk_inventory_logs_tax_classes
+    FOREIGN KEY(tax_class_id)
+    REFERENCES tax_classes(id)
+    ON DELETE SET NULL,
+
+  CONSTRAINT fk_inventory_logs_lots
+    FOREIGN KEY(lot_id)
+    REFERENCES lots(id)
+);
+
+CREATE TABLE IF NOT EXISTS serial_numbers (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  serial_number VARCHAR(255) NOT NULL,
+  product_id BIGINT NOT NULL,
+  organization_id BIGINT NOT NULL,
+
+  UNIQUE(serial_number, organization_id),
+
+  CONSTRAINT fk_serial_numbers_products
+    FOREIGN KEY(product_id)
+	  REFERENCES products(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_serial_numbers_organizations
+    FOREIGN KEY(organization_id)
+	  REFERENCES organizations(id)
+	  ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS inventory_log_serial_numbers (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  inventory_log_id BIGINT NOT NULL,
+  serial_number_id BIGINT NOT NULL,
+
+  UNIQUE(inventory_log_id, serial_number_id),
+
+  CONSTRAINT fk_inventory_log_serial_numbers_inventory_logs
+    FOREIGN KEY(inventory_log_id)
+	  REFERENCES inventory_logs(id)
+	  ON DELETE CASCADE,
+
+  CONSTRAINT fk_inventory_log_serial_numbers_serial_numbers
+    FOREIGN KEY(serial_number_id)
+	  REFERENCES serial_numbers(id)
+	  ON DELETE CASCADE
 );
 
 INSERT INTO permissions (id, name) VALUES (1, 'superuser');
 INSERT INTO permissions (id, name) VALUES (2, 'organization:*');
+INSERT INTO permissions (id, name) VALUES (3, 'sales:override_price');
+INSERT INTO permissions (id, name) VALUES (4, 'products:read');
+INSERT INTO permissions (id, name) VALUES (5, 'inventory:read');
+INSERT INTO permissions (id, name) VALUES (6, 'sales:write');
+INSERT INTO permissions (id, name) VALUES (7, 'deposits:write');
+INSERT INTO permissions (id, name) VALUES (8, 'reports:read');
 
 INSERT INTO users (id, display_name,  username,  password) VALUES (0, 'superuser', 'superuser', '$2b$12$e1RNRrjdu7b6jeg0AMN.9u3TgvfeqjSdc8uqGdIkdmRs6jh7JU0hi');
-INSERT INTO user_permissions (id, user_id, permission_id) VALUES (0, 0, 1);
\ No newline at end of file
+INSERT INTO user_permissions (id, user_id, permission_id) VALUES (0, 0, 1);
+-- Row-level security. The app queries as distrupify_app, which sees the rows
+-- of the organization in app.tenant, or every row when it is 'system'.
+-- Without app.tenant no tenant rows are visible. Tables without an
+-- organization_id follow the row they belong to.
+DO $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'distrupify_app') THEN
+    CREATE ROLE distrupify_app NOLOGIN NOSUPERUSER NOBYPASSRLS;
+  END IF;
+END
+$$;
+GRANT distrupify_app TO CURRENT_USER;
+GRANT USAGE ON SCHEMA public TO distrupify_app;
+GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA public TO distrupify_app;
+GRANT USAGE, SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO distrupify_app;
+
+CREATE OR REPLACE FUNCTION tenant_is_system() RETURNS BOOLEAN AS $$
+  SELECT COALESCE(current_setting('app.tenant', true), '') = 'system'
+$$ LANGUAGE SQL STABLE;
+
+CREATE OR REPLACE FUNCTION tenant_organization_id() RETURNS BIGINT AS $$
+  SELECT CASE
+    WHEN current_setting('app.tenant', true) ~ '^[0-9]+$'
+    THEN current_setting('app.tenant', true)::BIGINT
+  END
+$$ LANGUAGE SQL STABLE;
+
+DO $$
+DECLARE
+  t TEXT;
+BEGIN
+  FOREACH t IN ARRAY ARRAY[
+    'api_key_calls', 'api_keys', 'categories', 'customers', 'exchange_rates',
+    'inventory_logs', 'inventory_transactions', 'invitations',
+    'invoice_sequences', 'login_attempts', 'lots', 'price_list_items', 'price_lists',
+    'product_barcodes', 'products', 'serial_numbers', 'tax_classes', 'users',
+    'warehouses'
+  ] LOOP
+    EXECUTE format(
+      'CREATE POLICY tenant_isolation ON %I
+        USING (tenant_is_system() OR organization_id = tenant_organization_id())',
+      t
+    );
+  END LOOP;
+END
+$$;
+
+CREATE POLICY tenant_isolation ON organizations
+  USING (tenant_is_system() OR id = tenant_organization_id());
+
+CREATE POLICY tenant_isolation ON product_units
+  USING (product_id IN (SELECT id FROM products));
+CREATE POLICY tenant_isolation ON product_options
+  USING (product_id IN (SELECT id FROM products));
+CREATE POLICY tenant_isolation ON product_variant_option_values
+  USING (product_id IN (SELECT id FROM products));
+CREATE POLICY tenant_isolation ON product_option_values
+  USING (product_option_id IN (SELECT id FROM product_options));
+CREATE POLICY tenant_isolation ON inventory_log_serial_numbers
+  USING (inventory_log_id IN (SELECT id FROM inventory_logs));
+CREATE POLICY tenant_isolation ON user_permissions
+  USING (user_id IN (SELECT id FROM users));
+CREATE POLICY tenant_isolation ON sessions
+  USING (user_id IN (SELECT id FROM users));
+CREATE POLICY tenant_isolation ON user_recovery_codes
+  USING (user_id IN (SELECT id FROM users));
+CREATE POLICY tenant_isolation ON api_key_permissions
+  USING (api_key_id IN (SELECT id FROM api_keys));
+CREATE POLICY tenant_isolation ON password_reset_requests
+  USING (tenant_is_system() OR user_id IN (SELECT id FROM users));
+CREATE POLICY tenant_isolation ON login_lockouts
+  USING (tenant_is_system() OR (organization_name, username) IN (
+    SELECT o.name, u.username FROM users u
+    JOIN organizations o ON o.id = u.organization_id
+  ));
+CREATE POLICY tenant_isolation ON superuser_actions
+  USING (tenant_is_system());
+
+DO $$
+DECLARE
+  t TEXT;
+BEGIN
+  FOR t IN
+    SELECT tablename FROM pg_policies
+    WHERE schemaname = 'public' AND policyname = 'tenant_isolation'
+  LOOP
+    EXECUTE format('ALTER TABLE %I ENABLE ROW LEVEL SECURITY', t);
+    EXECUTE format('ALTER TABLE %I FORCE ROW LEVEL SECURITY', t);
+  END LOOP;
+END
+$$;
@@ -2,30 +2,44 @@ INSERT INTO organizations (id, name, display_name) VALUES (1, 'test-org', 'test
 
 INSERT INTO warehouses (name, organization_id) VALUES ('default',1);
 
-INSERT INTO users (id, display_name,  username,  password,  organization_id) VALUES (1, 'admin@test-org', 'admin@test-org', 'password', 1);
+INSERT INTO users (id, display_name,  username,  password,  organization_id) VALUES (1, 'admin@test-org', 'admin@test-org', '$2b$12$e1RNRrjdu7b6jeg0AMN.9u3TgvfeqjSdc8uqGdIkdmRs6jh7JU0hi', 1);
 
 INSERT INTO user_permissions (user_id, permission_id) VALUES (1, 2);
 
+-- Taxes
+INSERT INTO tax_classes (id, name, rate, organization_id) VALUES (1, 'VAT', 12, 1);
+INSERT INTO tax_classes (id, name, rate, organization_id) VALUES (2, 'Zero-rated', 0, 1);
+
 -- Products
-INSERT INTO products (id, sku, brand, name, display_name, description, organization_id, price) VALUES (1, 'sku-1', 'brand x', 'name', 'brand x name', 'description', 1, 100);
+INSERT INTO products (id, sku, brand, name, display_name, description, organization_id, price, tax_class_id) VALUES (1, 'sku-1', 'brand x', 'name', 'brand x name', 'description', 1, 100, 1);
 INSERT INTO products (id, sku, brand, name, display_name, description, organization_id, price) VALUES (2, 'sku-2', 'brand y', 'name', 'brand y name', 'description', 1, 530);
 
+-- Price lists
+INSERT INTO price_lists (id, name, description, organization_id) VALUES (1, 'Wholesale', 'Resellers', 1);
+INSERT INTO price_list_items (price_list_id, product_id, min_quantity, price, organization_id) VALUES (1, 1, 1, 90, 1);
+INSERT INTO price_list_items (price_list_id, product_id, min_quantity, price, organization_id) VALUES (1, 1, 10, 80, 1);
+
+INSERT INTO customers (id, name, price_list_id, organization_id) VALUES (1, 'Walk-in', NULL, 1);
+INSERT INTO customers (id, name, price_list_id, organization_id) VALUES (2, 'Reseller', 1, 1);
+
 -- Incoming
-INSERT INTO inventory_logs (id, quantity, product_id, action, price, organization_id, warehouse_id) VALUES (
+INSERT INTO inventory_logs (id, quantity, product_id, action, price, currency, organization_id, warehouse_id) VALUES (
   1,
   10,
   1,
   'INCOMING',
   50,
+  'PHP',
   1,
   1
 );
-INSERT INTO inventory_logs (id, quantity, product_id, action, price, organization_id, warehouse_id) VALUES (
+INSERT INTO inventory_logs (id, quantity, product_id, action, price, currency, organization_id, warehouse_id) VALUES (
   2,
   10,
   2,
   'INCOMING',
   300,
+  'PHP',
   1,
   1
 );
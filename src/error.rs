@@ -1,4 +1,4 @@
-use crate::{crypt, model};
+use crate::model;
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -11,7 +11,6 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
     Model(model::Error),
-    Crypt(crypt::Error),
 }
 
 impl From<model::Error> for Error {
@@ -20,12 +19,6 @@ impl From<model::Error> for Error {
     }
 }
 
-impl From<crypt::Error> for Error {
-    fn from(val: crypt::Error) -> Self {
-        Self::Crypt(val)
-    }
-}
-
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         // Create a placeholder Axum reponse.
@@ -40,7 +33,9 @@ impl IntoResponse for Error {
 
 impl core::fmt::Display for Error {
     fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
-        write!(fmt, "{self:?}")
+        match self {
+            Self::Model(e) => write!(fmt, "{e}"),
+        }
     }
 }
 
@@ -1,25 +1,64 @@
+mod barcode;
 mod crypt;
 mod ctx;
 mod error;
+mod mail;
 mod model;
+mod money;
+mod pdf;
+mod spreadsheet;
 mod web;
 
 use std::net::SocketAddr;
 
 use self::error::Result;
-use axum::Router;
+use axum::{middleware, Router};
 use crypt::hash_value;
+use model::organization_deletion::run_deletion_job;
 use model::ModelManager;
+use tower_cookies::CookieManagerLayer;
 use web::{
-    page_test::page_test_route, pages::categories::pages_cateogries,
-    pages::products::pages_products, routes_inventory_deposit::routes_inventory_deposit,
+    mw_auth::{mw_api_key_resolve, mw_ctx_resolve},
+    page_test::page_test_route,
+    pages::api_keys::pages_api_keys,
+    pages::categories::pages_cateogries,
+    pages::customers::pages_customers,
+    pages::exchange_rates::pages_exchange_rates,
+    pages::inventory_transactions::pages_inventory_transactions,
+    pages::invitations::pages_invitations,
+    pages::invoices::pages_invoices,
+    pages::lots::pages_lots,
+    pages::opening_balance::pages_opening_balance,
+    pages::password_reset::pages_password_reset,
+    pages::price_lists::pages_price_lists,
+    pages::product_barcodes::pages_product_barcodes,
+    pages::product_import::pages_product_import,
+    pages::product_units::pages_product_units,
+    pages::product_variants::pages_product_variants,
+    pages::products::pages_products,
+    pages::serial_numbers::pages_serial_numbers,
+    pages::superuser::pages_superuser,
+    pages::taxes::pages_taxes,
+    pages::two_factor::pages_two_factor,
+    pages::users::pages_users,
+    routes_auth::routes_auth,
+    routes_export::routes_export,
+    routes_inventory_deposit::routes_inventory_deposit,
+    routes_inventory_logs::routes_inventory_logs,
+    routes_inventory_sales::routes_inventory_sales,
+    routes_prices::routes_prices,
+    routes_products::routes_products,
+    routes_tax::routes_tax,
     routes_test::test_routes,
+    routes_valuation::routes_valuation,
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let mm = ModelManager::new().await?;
 
+    tokio::spawn(run_deletion_job(mm.clone()));
+
     // model::organization::provision_organization(
     //     &mm,
     //     model::organization::OrganizationForProvision {
@@ -67,16 +106,53 @@ async fn main() -> Result<()> {
 
     // println!("{:?}", r);
 
+    // Machines call these with an API key.
+    let routes_api = Router::new()
+        .merge(routes_inventory_deposit(mm.clone()))
+        .merge(routes_inventory_logs(mm.clone()))
+        .merge(routes_inventory_sales(mm.clone()))
+        .merge(routes_products(mm.clone()))
+        .merge(routes_prices(mm.clone()))
+        .merge(routes_tax(mm.clone()))
+        .merge(routes_export(mm.clone()))
+        .merge(routes_valuation(mm.clone()))
+        .layer(middleware::from_fn_with_state(
+            mm.clone(),
+            mw_api_key_resolve,
+        ));
+
     let routes_all = Router::new()
         .merge(pages_cateogries(mm.clone()))
         .merge(pages_products(mm.clone()))
+        .merge(pages_product_variants(mm.clone()))
+        .merge(pages_product_units(mm.clone()))
+        .merge(pages_product_barcodes(mm.clone()))
+        .merge(pages_product_import(mm.clone()))
+        .merge(pages_inventory_transactions(mm.clone()))
+        .merge(pages_invoices(mm.clone()))
+        .merge(pages_opening_balance(mm.clone()))
+        .merge(pages_exchange_rates(mm.clone()))
+        .merge(pages_price_lists(mm.clone()))
+        .merge(pages_customers(mm.clone()))
+        .merge(pages_taxes(mm.clone()))
+        .merge(pages_users(mm.clone()))
+        .merge(pages_api_keys(mm.clone()))
+        .merge(pages_two_factor(mm.clone()))
+        .merge(pages_invitations(mm.clone()))
+        .merge(pages_password_reset(mm.clone()))
+        .merge(pages_superuser(mm.clone()))
+        .merge(routes_auth(mm.clone()))
         .merge(test_routes(mm.clone()))
         .merge(page_test_route(mm.clone()))
-        .merge(routes_inventory_deposit(mm.clone()));
+        .merge(pages_lots(mm.clone()))
+        .merge(pages_serial_numbers(mm.clone()))
+        .merge(routes_api)
+        .layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
+        .layer(CookieManagerLayer::new());
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
     axum::Server::bind(&addr)
-        .serve(routes_all.into_make_service())
+        .serve(routes_all.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 
@@ -0,0 +1,315 @@
+use super::{
+    organization::{OrganizationState, OrganizationStatus},
+    permissions::{has_permission, Permissions, API_SCOPES},
+    tenant::system,
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::crypt::{hash_token, new_token};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+
+// API keys let machines, e.g. a storefront or a till, call /api/v1 for an
+// organization. A key is shown once when created; the table keeps its hash,
+// and its first characters so people can tell keys apart. Its scopes are
+// permissions, so a key can do no more than they allow. Revoking a key keeps
+// its row for the calls made with it, each of which is recorded. Callers
+// present a key before the organization is known, so looking one up works
+// across organizations.
+
+/// What every key starts with, so a leaked one is easy to recognize.
+const KEY_PREFIX: &str = "dfy_";
+
+/// How much of a key is kept to tell it apart.
+const SHOWN_KEY_LENGTH: usize = KEY_PREFIX.len() + 8;
+
+/// How many calls the settings show.
+const RECENT_CALLS: i64 = 50;
+
+// region: Structs
+#[derive(Debug)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<Permissions>,
+    /// None once the user is deleted.
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+#[derive(Debug)]
+pub struct ApiKeyForCreate {
+    pub name: String,
+    pub scopes: Vec<Permissions>,
+    /// None for a key that does not expire.
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct ApiKeyCall {
+    pub api_key_name: String,
+    pub api_key_prefix: String,
+    pub method: String,
+    pub path: String,
+    pub status: i16,
+    pub created_at: DateTime<Utc>,
+}
+// endregion: Structs
+
+// region: Methods
+/// Creates a key for the organization. Admins only. Returns the key, which is
+/// not shown again.
+pub async fn create_api_key(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    api_key_for_create: ApiKeyForCreate,
+) -> Result<String> {
+    let db = mm.db();
+    let (user_id, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let name = api_key_for_create.name.trim();
+    if name.is_empty() {
+        return Err(Error::InvalidApiKeyName {
+            name: name.to_string(),
+        });
+    }
+    let scopes = api_key_for_create.scopes;
+    if scopes.is_empty() || scopes.iter().any(|scope| !API_SCOPES.contains(scope)) {
+        return Err(Error::InvalidApiKeyScopes);
+    }
+    let expires_at = api_key_for_create
+        .expires_in_days
+        .map(|days| Utc::now() + Duration::days(days));
+
+    let token = format!("{KEY_PREFIX}{}", new_token());
+
+    let mut tx = db.begin().await?;
+
+    let api_key = sqlx::query!(
+        r#"INSERT INTO api_keys (name, prefix, token_hash, organization_id, created_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id;"#,
+        name,
+        &token[..SHOWN_KEY_LENGTH],
+        hash_token(&token),
+        organization_id,
+        user_id,
+        expires_at
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for scope in scopes {
+        sqlx::query!(
+            r#"INSERT INTO api_key_permissions (api_key_id, permission_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING;"#,
+            api_key.id,
+            scope as i64
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(token)
+}
+
+/// The organization's keys, revoked ones last. Admins only.
+pub async fn get_api_keys(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<ApiKey>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let api_keys = sqlx::query!(
+        r#"SELECT
+            k.id,
+            k.name,
+            k.prefix,
+            ARRAY(
+                SELECT kp.permission_id FROM api_key_permissions kp
+                WHERE kp.api_key_id = k.id
+                ORDER BY kp.permission_id
+            ) as "scopes!",
+            u.username as "created_by?",
+            k.created_at,
+            k.expires_at,
+            k.last_used_at,
+            k.revoked_at
+        FROM api_keys k
+        LEFT JOIN users u
+        ON u.id = k.created_by
+        WHERE k.organization_id = $1
+        ORDER BY k.revoked_at IS NOT NULL, k.created_at DESC;"#,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|k| ApiKey {
+        id: k.id,
+        name: k.name,
+        prefix: k.prefix,
+        scopes: permissions_of(k.scopes),
+        created_by: k.created_by,
+        created_at: k.created_at,
+        expires_at: k.expires_at,
+        last_used_at: k.last_used_at,
+        revoked_at: k.revoked_at,
+    })
+    .collect();
+
+    Ok(api_keys)
+}
+
+/// Stops a key from working. Admins only.
+pub async fn revoke_api_key(ctx: &Ctx, mm: &ModelManager, api_key_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let result = sqlx::query!(
+        r#"UPDATE api_keys
+        SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP)
+        WHERE id = $1
+        AND organization_id = $2;"#,
+        api_key_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::ApiKeyNotFound { api_key_id });
+    }
+
+    Ok(())
+}
+
+/// The latest calls made with the organization's keys. Admins only.
+pub async fn get_api_key_calls(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<ApiKeyCall>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let calls = sqlx::query_as!(
+        ApiKeyCall,
+        r#"SELECT
+            k.name as api_key_name,
+            k.prefix as api_key_prefix,
+            c.method,
+            c.path,
+            c.status,
+            c.created_at
+        FROM api_key_calls c
+        JOIN api_keys k
+        ON k.id = c.api_key_id
+        WHERE c.organization_id = $1
+        ORDER BY c.created_at DESC, c.id DESC
+        LIMIT $2;"#,
+        organization_id,
+        RECENT_CALLS
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(calls)
+}
+
+/// Who a live key is for, with its scopes. Fails for keys of an organization
+/// that is not active.
+pub async fn get_api_key_ctx(_ctx: &Ctx, mm: &ModelManager, token: &str) -> Result<Ctx> {
+    let api_key = system(async {
+        let db = mm.db();
+
+        sqlx::query!(
+            r#"SELECT
+                k.id,
+                k.organization_id,
+                o.status as "status: OrganizationStatus",
+                o.status_reason,
+                o.deletion_due_at,
+                ARRAY(
+                    SELECT kp.permission_id FROM api_key_permissions kp
+                    WHERE kp.api_key_id = k.id
+                ) as "scopes!"
+            FROM api_keys k
+            JOIN organizations o
+            ON o.id = k.organization_id
+            WHERE k.token_hash = $1
+            AND k.revoked_at IS NULL
+            AND (k.expires_at IS NULL OR k.expires_at > CURRENT_TIMESTAMP);"#,
+            hash_token(token)
+        )
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| Error::Unauhtorized("API key not found".to_string()))
+    })
+    .await?;
+
+    OrganizationState {
+        status: api_key.status,
+        status_reason: api_key.status_reason,
+        deletion_due_at: api_key.deletion_due_at,
+    }
+    .ensure_active(api_key.organization_id)?;
+
+    Ok(Ctx::for_api_key(
+        api_key.organization_id,
+        api_key.id,
+        permissions_of(api_key.scopes),
+    ))
+}
+
+/// Records a call made with the current key, which marks the key as used.
+pub async fn record_api_key_call(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    method: &str,
+    path: &str,
+    status: u16,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    let api_key_id = ctx
+        .api_key_id()
+        .ok_or_else(|| Error::Unauhtorized("API key not found".to_string()))?;
+
+    sqlx::query!(
+        r#"WITH used AS (
+            UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP WHERE id = $1
+        )
+        INSERT INTO api_key_calls (api_key_id, organization_id, method, path, status)
+        VALUES ($1, $2, $3, $4, $5);"#,
+        api_key_id,
+        organization_id,
+        method,
+        path,
+        status as i16
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+fn permissions_of(ids: Vec<i64>) -> Vec<Permissions> {
+    ids.into_iter()
+        .filter_map(|id| Permissions::try_from(id).ok())
+        .collect()
+}
+// endregion: Methods
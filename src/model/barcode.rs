@@ -0,0 +1,179 @@
+use super::{
+    products::{get_product_with_stock_level, ProductWithStockLevel},
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::barcode::normalize_gtin;
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+
+// region: Structs
+#[derive(Debug)]
+pub struct ProductBarcode {
+    pub id: i64,
+    pub code: String,
+}
+// endregion: Structs
+
+// region: Methods
+pub async fn get_product_barcodes(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+) -> Result<Vec<ProductBarcode>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let barcodes = sqlx::query_as!(
+        ProductBarcode,
+        r#"SELECT id, code FROM product_barcodes
+        WHERE product_id = $1
+        AND organization_id = $2
+        ORDER BY id;"#,
+        product_id,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(barcodes)
+}
+
+pub async fn get_product_barcode(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+    barcode_id: i64,
+) -> Result<ProductBarcode> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query_as!(
+        ProductBarcode,
+        r#"SELECT id, code FROM product_barcodes
+        WHERE id = $1
+        AND product_id = $2
+        AND organization_id = $3;"#,
+        barcode_id,
+        product_id,
+        organization_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(Error::BarcodeNotFound {
+        code: barcode_id.to_string(),
+    })
+}
+
+pub async fn create_product_barcode(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+    code: &str,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let code = code.trim();
+    let gtin = normalize_gtin(code).ok_or(Error::InvalidBarcode {
+        code: code.to_string(),
+    })?;
+
+    let result = sqlx::query!(
+        r#"INSERT INTO product_barcodes (product_id, code, gtin, organization_id)
+        SELECT id, $2, $3, $4 FROM products
+        WHERE id = $1
+        AND organization_id = $4
+        ON CONFLICT (gtin, organization_id) DO NOTHING;"#,
+        product_id,
+        code,
+        gtin,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        let product_exists = sqlx::query!(
+            r#"SELECT EXISTS (SELECT 1 FROM products WHERE id = $1 AND organization_id = $2) as "exists!";"#,
+            product_id,
+            organization_id
+        )
+        .fetch_one(db)
+        .await?
+        .exists;
+
+        if !product_exists {
+            return Err(Error::ProductNotFound { product_id });
+        }
+        return Err(Error::DuplicateBarcode {
+            code: code.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+pub async fn delete_product_barcode(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+    barcode_id: i64,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query!(
+        r#"DELETE FROM product_barcodes
+        WHERE id = $1
+        AND product_id = $2
+        AND organization_id = $3;"#,
+        barcode_id,
+        product_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Resolves a scanned code to a product. GTINs match in any of their
+/// UPC/EAN forms; anything else is tried as a SKU, which is what the Code 128
+/// product labels encode.
+pub async fn get_product_by_barcode(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    code: &str,
+) -> Result<ProductWithStockLevel> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let code = code.trim();
+    let product_id = sqlx::query!(
+        r#"SELECT product_id as "product_id!" FROM product_barcodes
+        WHERE gtin = $1
+        AND organization_id = $3
+        UNION ALL
+        SELECT id FROM products
+        WHERE sku = $2
+        AND organization_id = $3
+        LIMIT 1;"#,
+        normalize_gtin(code),
+        code,
+        organization_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|r| r.product_id);
+
+    let product = match product_id {
+        Some(product_id) => get_product_with_stock_level(ctx, mm, product_id).await?,
+        None => None,
+    };
+
+    product.ok_or(Error::BarcodeNotFound {
+        code: code.to_string(),
+    })
+}
+// endregion: Methods
@@ -1,7 +1,7 @@
 use super::user::get_user_ids;
 use super::ModelManager;
 use crate::ctx::Ctx;
-use crate::model::error::Result;
+use crate::model::error::{Error, Result};
 use sqlx::FromRow;
 
 // region: Structs
@@ -9,8 +9,19 @@ use sqlx::FromRow;
 pub struct Category {
     pub id: i64,
     pub name: String,
+    pub parent_id: Option<i64>,
 }
-// region: Structs
+
+/// A category positioned in the tree, as shown on the categories page.
+#[derive(Debug)]
+pub struct CategoryNode {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub depth: i32,
+    pub product_count: i64,
+}
+// endregion: Structs
 
 // region: Methods
 pub async fn get_category_by_id(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Category> {
@@ -19,7 +30,7 @@ pub async fn get_category_by_id(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result
 
     let category = sqlx::query_as!(
         Category,
-        r#"SELECT id, name FROM categories WHERE id = $1 AND organization_id = $2;"#,
+        r#"SELECT id, name, parent_id FROM categories WHERE id = $1 AND organization_id = $2;"#,
         id,
         organization_id
     )
@@ -29,15 +40,60 @@ pub async fn get_category_by_id(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result
     Ok(category)
 }
 
-pub async fn get_all_categories(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Category>> {
-    let db = mm.db();
+/// Every category ordered depth-first, children sorted by name under their parent.
+pub async fn get_category_tree(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<CategoryNode>> {
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
+    query_category_tree(mm, organization_id, None, None).await
+}
+
+pub async fn get_category_node_by_id(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    id: i64,
+) -> Result<CategoryNode> {
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    query_category_tree(mm, organization_id, Some(id), None)
+        .await?
+        .pop()
+        .ok_or(Error::CategoryNotFound { category_id: id })
+}
+
+async fn query_category_tree(
+    mm: &ModelManager,
+    organization_id: i64,
+    id: Option<i64>,
+    search: Option<String>,
+) -> Result<Vec<CategoryNode>> {
+    let db = mm.db();
+
     let categories = sqlx::query_as!(
-        Category,
-        r#"SELECT id, name FROM categories WHERE organization_id = $1
-            ORDER BY name;"#,
-        organization_id
+        CategoryNode,
+        r#"WITH RECURSIVE category_tree AS (
+            SELECT id, name, parent_id, 0 as depth, ARRAY[LOWER(name)]::text[] as path
+            FROM categories
+            WHERE parent_id IS NULL
+            AND organization_id = $1
+            UNION ALL
+            SELECT c.id, c.name, c.parent_id, ct.depth + 1, ct.path || LOWER(c.name)::text
+            FROM categories c
+            JOIN category_tree ct
+            ON c.parent_id = ct.id
+        )
+        SELECT
+            ct.id as "id!",
+            ct.name as "name!",
+            ct.parent_id,
+            ct.depth as "depth!",
+            (SELECT COUNT(*) FROM products p WHERE p.category_id = ct.id) as "product_count!"
+        FROM category_tree ct
+        WHERE ($2::int8 IS NULL OR ct.id = $2)
+        AND ($3::text IS NULL OR LOWER(ct.name) LIKE $3)
+        ORDER BY ct.path;"#,
+        organization_id,
+        id,
+        search
     )
     .fetch_all(db)
     .await?;
@@ -45,39 +101,98 @@ pub async fn get_all_categories(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Cate
     Ok(categories)
 }
 
-pub async fn create_category(ctx: &Ctx, mm: &ModelManager, name: String) -> Result<()> {
+pub struct CategoryForCreate {
+    pub name: String,
+    pub parent_id: Option<i64>,
+}
+pub async fn create_category(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    category_for_create: CategoryForCreate,
+) -> Result<()> {
     let db = mm.db();
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
-    sqlx::query!(
-        "INSERT INTO categories (name, organization_id) VALUES ($1, $2);",
-        name,
+    let result = sqlx::query!(
+        r#"INSERT INTO categories (name, parent_id, organization_id)
+        SELECT $1, $2, $3
+        WHERE $2::int8 IS NULL
+        OR EXISTS (SELECT 1 FROM categories WHERE id = $2 AND organization_id = $3);"#,
+        category_for_create.name,
+        category_for_create.parent_id,
         organization_id
     )
     .execute(db)
     .await?;
 
+    if result.rows_affected() == 0 {
+        return Err(Error::InvalidCategoryParent {
+            category_id: None,
+            parent_id: category_for_create.parent_id,
+        });
+    }
+
     Ok(())
 }
 
+/// Refuses to delete a category that still has products. Subcategories are
+/// moved up to the deleted category's parent.
 pub async fn delete_category(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
     let db = mm.db();
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
+    let mut tx = db.begin().await?;
+
+    let category = sqlx::query!(
+        r#"SELECT
+            name,
+            parent_id,
+            (SELECT COUNT(*) FROM products WHERE category_id = $1) as "product_count!"
+        FROM categories
+        WHERE id = $1
+        AND organization_id = $2
+        FOR UPDATE;"#,
+        id,
+        organization_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::CategoryNotFound { category_id: id })?;
+
+    if category.product_count > 0 {
+        return Err(Error::CategoryHasProducts {
+            category_id: id,
+            name: category.name,
+            product_count: category.product_count,
+        });
+    }
+
+    sqlx::query!(
+        "UPDATE categories SET parent_id = $1 WHERE parent_id = $2 AND organization_id = $3;",
+        category.parent_id,
+        id,
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
     sqlx::query!(
         "DELETE FROM categories WHERE id = $1 AND organization_id = $2;",
         id,
         organization_id
     )
-    .execute(db)
+    .execute(&mut *tx)
     .await?;
 
+    tx.commit().await?;
+
     Ok(())
 }
 
 pub struct CategoryForUpdate {
     pub id: i64,
     pub name: String,
+    pub parent_id: Option<i64>,
 }
 pub async fn update_category(
     ctx: &Ctx,
@@ -87,10 +202,40 @@ pub async fn update_category(
     let db = mm.db();
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
+    if let Some(parent_id) = category_for_update.parent_id {
+        // The new parent must exist and must not be the category itself or one of its descendants.
+        let valid_parent = sqlx::query!(
+            r#"WITH RECURSIVE descendants AS (
+                SELECT id FROM categories WHERE id = $1
+                UNION ALL
+                SELECT c.id FROM categories c
+                JOIN descendants d
+                ON c.parent_id = d.id
+            )
+            SELECT
+                EXISTS (SELECT 1 FROM categories WHERE id = $2 AND organization_id = $3)
+                AND NOT EXISTS (SELECT 1 FROM descendants WHERE id = $2) as "valid!";"#,
+            category_for_update.id,
+            parent_id,
+            organization_id
+        )
+        .fetch_one(db)
+        .await?
+        .valid;
+
+        if !valid_parent {
+            return Err(Error::InvalidCategoryParent {
+                category_id: Some(category_for_update.id),
+                parent_id: Some(parent_id),
+            });
+        }
+    }
+
     let category = sqlx::query_as!(
         Category,
-        "UPDATE categories SET name = $1 WHERE id = $2 AND organization_id = $3 RETURNING id, name;",
+        "UPDATE categories SET name = $1, parent_id = $2 WHERE id = $3 AND organization_id = $4 RETURNING id, name, parent_id;",
         category_for_update.name,
+        category_for_update.parent_id,
         category_for_update.id,
         organization_id
     )
@@ -104,23 +249,16 @@ pub async fn search_category(
     ctx: &Ctx,
     mm: &ModelManager,
     search_string: String,
-) -> Result<Vec<Category>> {
-    let db = mm.db();
+) -> Result<Vec<CategoryNode>> {
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
-    let categories = sqlx::query_as!(
-        Category,
-        r#"SELECT id, name FROM categories 
-           WHERE organization_id = $1 
-           AND LOWER(name) LIKE $2 
-           ORDER BY name;"#,
+    query_category_tree(
+        mm,
         organization_id,
-        format!("{}%", search_string.to_lowercase())
+        None,
+        Some(format!("{}%", search_string.to_lowercase())),
     )
-    .fetch_all(db)
-    .await?;
-
-    Ok(categories)
+    .await
 }
 // endregion: Methods
 
@@ -1,4 +1,46 @@
+use super::tenant::Tenant;
+use super::Result;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::future::Future;
+use tokio::sync::mpsc;
+
+/// Rows buffered per stream. The query pauses while the consumer, e.g. a slow
+/// download, is this far behind.
+const ROW_STREAM_BUFFER: usize = 64;
+
 #[allow(dead_code)]
 pub struct RowWithId {
     pub id: i64,
 }
+
+/// Runs `producer` on its own task, for the caller's tenant, and yields the
+/// rows it sends. A `fetch` stream borrows the pool, so this is how rows
+/// outlive the call that started the query, e.g. when streamed into a
+/// response body. An error returned by `producer` ends the stream as its last
+/// item.
+pub fn spawn_row_stream<T, F, Fut>(producer: F) -> BoxStream<'static, Result<T>>
+where
+    T: Send + 'static,
+    F: FnOnce(mpsc::Sender<Result<T>>) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(ROW_STREAM_BUFFER);
+    let error_sender = sender.clone();
+    let producer = producer(sender);
+    let tenant = Tenant::current();
+
+    tokio::spawn(async move {
+        let result = match tenant {
+            Some(tenant) => tenant.scope(producer).await,
+            None => producer.await,
+        };
+        if let Err(e) = result {
+            let _ = error_sender.send(Err(e)).await;
+        }
+    });
+
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|row| (row, receiver))
+    })
+    .boxed()
+}
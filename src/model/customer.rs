@@ -0,0 +1,190 @@
+use super::{user::get_user_ids, ModelManager};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
+use sqlx::PgConnection;
+
+// region: Structs
+#[derive(Debug)]
+pub struct Customer {
+    pub id: i64,
+    pub name: String,
+    pub price_list_id: Option<i64>,
+    pub price_list_name: Option<String>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct CustomerForCreate {
+    pub name: String,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub price_list_id: Option<i64>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct CustomerForUpdate {
+    /// No price list: the customer pays the product price.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub price_list_id: Option<i64>,
+}
+// endregion: Structs
+
+// region: Methods
+pub async fn get_customers(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Customer>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let customers = sqlx::query_as!(
+        Customer,
+        r#"SELECT c.id, c.name, c.price_list_id, pl.name as "price_list_name?"
+        FROM customers c
+        LEFT JOIN price_lists pl
+        ON pl.id = c.price_list_id
+        WHERE c.organization_id = $1
+        ORDER BY c.name;"#,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(customers)
+}
+
+pub async fn create_customer(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    customer_for_create: CustomerForCreate,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let CustomerForCreate {
+        name,
+        price_list_id,
+    } = customer_for_create;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(Error::InvalidCustomerName {
+            name: name.to_string(),
+        });
+    }
+
+    let mut tx = db.begin().await?;
+    if let Some(price_list_id) = price_list_id {
+        check_price_list(&mut tx, organization_id, price_list_id).await?;
+    }
+
+    let result = sqlx::query!(
+        r#"INSERT INTO customers (name, price_list_id, organization_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (name, organization_id) DO NOTHING;"#,
+        name,
+        price_list_id,
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::InvalidCustomerName {
+            name: name.to_string(),
+        });
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Assigns the customer a price list, or takes it away.
+pub async fn update_customer(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    customer_id: i64,
+    customer_for_update: CustomerForUpdate,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut tx = db.begin().await?;
+    if let Some(price_list_id) = customer_for_update.price_list_id {
+        check_price_list(&mut tx, organization_id, price_list_id).await?;
+    }
+
+    let result = sqlx::query!(
+        r#"UPDATE customers
+        SET price_list_id = $3
+        WHERE id = $1
+        AND organization_id = $2;"#,
+        customer_id,
+        organization_id,
+        customer_for_update.price_list_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::CustomerNotFound { customer_id });
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Past sales keep their lines but lose the customer.
+pub async fn delete_customer(ctx: &Ctx, mm: &ModelManager, customer_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query!(
+        "DELETE FROM customers WHERE id = $1 AND organization_id = $2;",
+        customer_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// The customer's price list, if they have one.
+pub(in crate::model) async fn customer_price_list(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    customer_id: i64,
+) -> Result<Option<i64>> {
+    let customer = sqlx::query!(
+        "SELECT price_list_id FROM customers WHERE id = $1 AND organization_id = $2;",
+        customer_id,
+        organization_id
+    )
+    .fetch_optional(conn)
+    .await?
+    .ok_or(Error::CustomerNotFound { customer_id })?;
+
+    Ok(customer.price_list_id)
+}
+
+async fn check_price_list(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    price_list_id: i64,
+) -> Result<()> {
+    sqlx::query!(
+        "SELECT id FROM price_lists WHERE id = $1 AND organization_id = $2;",
+        price_list_id,
+        organization_id
+    )
+    .fetch_optional(conn)
+    .await?
+    .ok_or(Error::PriceListNotFound { price_list_id })?;
+
+    Ok(())
+}
+// endregion: Methods
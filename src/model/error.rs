@@ -1,4 +1,7 @@
 use super::store;
+use crate::money::{Currency, Money};
+use crate::{crypt, mail, money};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 
@@ -8,7 +11,199 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[derive(Debug, Serialize)]
 pub enum Error {
     Store(store::Error),
+    Crypt(crypt::Error),
+    Mail(mail::Error),
+    Money(money::Error),
     Unauhtorized(String),
+    LotNumberRequired {
+        product_id: i64,
+    },
+    LotNotFound {
+        lot_id: i64,
+    },
+    /// Lines move at least one unit.
+    InvalidQuantity {
+        product_id: i64,
+        quantity: i64,
+    },
+    InsufficientStock {
+        product_id: i64,
+        requested: i64,
+        available: i64,
+    },
+    SerialNumberCountMismatch {
+        product_id: i64,
+        expected: i64,
+        actual: i64,
+    },
+    DuplicateSerialNumber {
+        serial_number: String,
+    },
+    SerialNumberAlreadyInStock {
+        serial_number: String,
+    },
+    SerialNumberNotInStock {
+        serial_number: String,
+    },
+    ProductNotFound {
+        product_id: i64,
+    },
+    InvalidVariantParent {
+        product_id: i64,
+    },
+    VariantsAlreadyExist {
+        product_id: i64,
+    },
+    NoVariantOptions,
+    InvalidUnit {
+        product_id: i64,
+        unit: String,
+    },
+    /// More base units than an inventory log holds.
+    QuantityTooLarge {
+        product_id: i64,
+        quantity: i64,
+    },
+    CategoryNotFound {
+        category_id: i64,
+    },
+    InvalidCategoryParent {
+        category_id: Option<i64>,
+        parent_id: Option<i64>,
+    },
+    CategoryHasProducts {
+        category_id: i64,
+        name: String,
+        product_count: i64,
+    },
+    InvalidBarcode {
+        code: String,
+    },
+    DuplicateBarcode {
+        code: String,
+    },
+    BarcodeNotFound {
+        code: String,
+    },
+    ProductImportInvalid {
+        error_count: usize,
+    },
+    OpeningBalanceNotAllowed,
+    OpeningBalanceInvalid {
+        error_count: usize,
+    },
+    InvalidOpeningBalanceDate {
+        date: NaiveDate,
+    },
+    BaseCurrencyLocked,
+    ExchangeRateNotFound {
+        currency: Currency,
+        date: NaiveDate,
+    },
+    InvalidExchangeRate {
+        currency: Currency,
+    },
+    CurrencyMismatch {
+        expected: Currency,
+        actual: Currency,
+    },
+    PriceListNotFound {
+        price_list_id: i64,
+    },
+    /// Empty, or already used by another price list.
+    InvalidPriceListName {
+        name: String,
+    },
+    InvalidPriceListItem {
+        product_id: i64,
+    },
+    CustomerNotFound {
+        customer_id: i64,
+    },
+    /// Empty, or already used by another customer.
+    InvalidCustomerName {
+        name: String,
+    },
+    PriceOverrideNotAllowed {
+        product_id: i64,
+        price: Money,
+        list_price: Money,
+    },
+    /// Empty name, or a rate outside 0 to 100.
+    InvalidTaxClass {
+        name: String,
+    },
+    TaxClassHasProducts {
+        tax_class_id: i64,
+        name: String,
+        product_count: i64,
+    },
+    /// Not a sale, or not in the organization.
+    InvoiceNotFound {
+        transaction_id: i64,
+    },
+    UserNotFound {
+        user_id: i64,
+    },
+    /// Empty name or username, or a username already in the organization.
+    InvalidUser {
+        username: String,
+    },
+    InvalidEmail {
+        email: String,
+    },
+    /// Too short to set.
+    InvalidPassword,
+    /// The change would leave the organization without an active admin.
+    LastActiveAdmin {
+        user_id: i64,
+    },
+    /// Unknown, used or expired; these are not told apart.
+    InvitationNotFound,
+    /// Unknown, used or expired; these are not told apart.
+    PasswordResetNotFound,
+    TooManyRequests,
+    /// Failed logins in a row; the name may try again after a wait.
+    LoginThrottled {
+        retry_after_seconds: i64,
+    },
+    /// Too many failed logins in a row, until then or an admin unlocks the
+    /// user.
+    LoginLocked {
+        locked_until: DateTime<Utc>,
+    },
+    OrganizationNotFound {
+        organization_id: i64,
+    },
+    /// Empty or taken.
+    InvalidOrganization {
+        name: String,
+    },
+    /// Suspended or pending deletion; `reason` is for its users.
+    OrganizationSuspended {
+        organization_id: i64,
+        reason: String,
+    },
+    /// Writing the final export of an organization failed.
+    Archive(String),
+    /// Suspending and impersonating must say why.
+    ReasonRequired,
+    InvalidApiKeyName {
+        name: String,
+    },
+    /// A key must be granted at least one scope, and only API scopes.
+    InvalidApiKeyScopes,
+    ApiKeyNotFound {
+        api_key_id: i64,
+    },
+    /// Wrong, already used or from another secret; these are not told apart.
+    InvalidTwoFactorCode,
+    TwoFactorAlreadyEnabled,
+    /// The organization requires two-factor authentication.
+    TwoFactorRequired,
+    /// Admins set it up for themselves before requiring it of everyone.
+    TwoFactorSetupRequired,
+    Totp(String),
     Sqlx(#[serde_as(as = "DisplayFromStr")] sqlx::Error),
 }
 
@@ -18,6 +213,24 @@ impl From<store::Error> for Error {
     }
 }
 
+impl From<crypt::Error> for Error {
+    fn from(val: crypt::Error) -> Self {
+        Self::Crypt(val)
+    }
+}
+
+impl From<mail::Error> for Error {
+    fn from(val: mail::Error) -> Self {
+        Self::Mail(val)
+    }
+}
+
+impl From<money::Error> for Error {
+    fn from(val: money::Error) -> Self {
+        Self::Money(val)
+    }
+}
+
 impl From<sqlx::Error> for Error {
     fn from(val: sqlx::Error) -> Self {
         Self::Sqlx(val)
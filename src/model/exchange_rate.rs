@@ -0,0 +1,265 @@
+use super::{organization::base_currency, user::get_user_ids, ModelManager};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::money::{Currency, Locale};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+use sqlx::types::BigDecimal;
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// region: Structs
+/// Units of the base currency one unit of `currency` is worth, from
+/// `effective_date` until the next rate of the same currency.
+#[derive(Debug)]
+pub struct ExchangeRate {
+    pub id: i64,
+    pub currency: Currency,
+    pub rate: BigDecimal,
+    pub effective_date: NaiveDate,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct ExchangeRateForCreate {
+    pub currency: Currency,
+    #[serde_as(as = "DisplayFromStr")]
+    pub rate: BigDecimal,
+    pub effective_date: NaiveDate,
+}
+
+/// One data row of an uploaded exchange rate file.
+#[derive(Debug, Default)]
+pub struct ExchangeRateImportRow {
+    /// Line number in the uploaded file, for the report.
+    pub line: usize,
+    pub currency: String,
+    pub rate: String,
+    pub effective_date: String,
+}
+
+#[derive(Debug)]
+pub struct ExchangeRateImportRowResult {
+    pub line: usize,
+    pub currency: String,
+    pub rate: String,
+    pub effective_date: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ExchangeRateImportReport {
+    pub rows: Vec<ExchangeRateImportRowResult>,
+}
+
+impl ExchangeRateImportReport {
+    pub fn error_count(&self) -> usize {
+        self.rows.iter().filter(|r| !r.errors.is_empty()).count()
+    }
+}
+// endregion: Structs
+
+// region: Methods
+/// Lists every rate, newest first.
+pub async fn get_exchange_rates(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<ExchangeRate>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let rates = sqlx::query_as!(
+        ExchangeRate,
+        r#"SELECT id, currency as "currency: Currency", rate, effective_date
+        FROM exchange_rates
+        WHERE organization_id = $1
+        ORDER BY effective_date DESC, currency;"#,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    // NUMERIC comes back padded to the column's scale.
+    Ok(rates
+        .into_iter()
+        .map(|r| ExchangeRate {
+            rate: r.rate.normalized(),
+            ..r
+        })
+        .collect())
+}
+
+/// Records a rate, replacing the one of the same currency and date.
+pub async fn create_exchange_rate(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    exchange_rate_for_create: ExchangeRateForCreate,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut conn = db.acquire().await?;
+    let base_currency = base_currency(&mut conn, organization_id).await?;
+
+    let ExchangeRateForCreate {
+        currency,
+        rate,
+        effective_date,
+    } = exchange_rate_for_create;
+    if currency == base_currency || rate <= BigDecimal::from(0) {
+        return Err(Error::InvalidExchangeRate { currency });
+    }
+
+    sqlx::query!(
+        r#"INSERT INTO exchange_rates (currency, rate, effective_date, organization_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (currency, effective_date, organization_id) DO UPDATE
+        SET rate = EXCLUDED.rate;"#,
+        currency as Currency,
+        rate,
+        effective_date,
+        organization_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_exchange_rate(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query!(
+        "DELETE FROM exchange_rates WHERE id = $1 AND organization_id = $2;",
+        id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Records every row in one transaction, replacing rates of the same
+/// currency and date. Nothing is written unless every row is valid; the
+/// report says which rows are not. Rates are read in `locale`.
+pub async fn import_exchange_rates(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    rows: Vec<ExchangeRateImportRow>,
+    locale: Locale,
+) -> Result<ExchangeRateImportReport> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut tx = db.begin().await?;
+    let base_currency = base_currency(&mut tx, organization_id).await?;
+
+    let mut first_seen: HashMap<(Currency, NaiveDate), usize> = HashMap::new();
+    let mut results = Vec::with_capacity(rows.len());
+    let mut rates = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let mut errors = Vec::new();
+
+        let currency = Currency::from_str(&row.currency).ok();
+        match currency {
+            None => errors.push(format!("Invalid currency \"{}\"", row.currency.trim())),
+            Some(currency) if currency == base_currency => {
+                errors.push(format!("{currency} is the base currency"))
+            }
+            Some(_) => {}
+        }
+
+        let rate = locale
+            .parse_amount(&row.rate)
+            .ok()
+            .filter(|r| *r > BigDecimal::from(0));
+        if rate.is_none() {
+            errors.push(format!("Invalid rate \"{}\"", row.rate.trim()));
+        }
+
+        let effective_date = NaiveDate::parse_from_str(row.effective_date.trim(), "%Y-%m-%d").ok();
+        if effective_date.is_none() {
+            errors.push(format!("Invalid date \"{}\"", row.effective_date.trim()));
+        }
+
+        if let (Some(currency), Some(effective_date)) = (currency, effective_date) {
+            match first_seen.get(&(currency, effective_date)) {
+                Some(line) => errors.push(format!("Same currency and date as line {line}")),
+                None => {
+                    first_seen.insert((currency, effective_date), row.line);
+                }
+            }
+        }
+
+        if let (true, Some(currency), Some(rate), Some(effective_date)) =
+            (errors.is_empty(), currency, rate, effective_date)
+        {
+            rates.push((currency, rate, effective_date));
+        }
+
+        results.push(ExchangeRateImportRowResult {
+            line: row.line,
+            currency: row.currency.trim().to_string(),
+            rate: row.rate.trim().to_string(),
+            effective_date: row.effective_date.trim().to_string(),
+            errors,
+        });
+    }
+
+    let report = ExchangeRateImportReport { rows: results };
+    if report.error_count() > 0 {
+        return Ok(report);
+    }
+
+    let currencies: Vec<Currency> = rates.iter().map(|(c, _, _)| *c).collect();
+    let values: Vec<BigDecimal> = rates.iter().map(|(_, r, _)| r.clone()).collect();
+    let effective_dates: Vec<NaiveDate> = rates.iter().map(|(_, _, d)| *d).collect();
+
+    sqlx::query!(
+        r#"INSERT INTO exchange_rates (currency, rate, effective_date, organization_id)
+        SELECT currency, rate, effective_date, $4
+        FROM UNNEST($1::varchar[], $2::numeric[], $3::date[])
+            as t(currency, rate, effective_date)
+        ON CONFLICT (currency, effective_date, organization_id) DO UPDATE
+        SET rate = EXCLUDED.rate;"#,
+        &currencies as &[Currency],
+        &values,
+        &effective_dates,
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+/// The rate in effect on `date`: the latest one on or before it.
+pub(in crate::model) async fn find_exchange_rate(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    currency: Currency,
+    date: NaiveDate,
+) -> Result<BigDecimal> {
+    let rate = sqlx::query!(
+        r#"SELECT rate
+        FROM exchange_rates
+        WHERE organization_id = $1
+        AND currency = $2
+        AND effective_date <= $3
+        ORDER BY effective_date DESC
+        LIMIT 1;"#,
+        organization_id,
+        currency as Currency,
+        date
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    rate.map(|r| r.rate)
+        .ok_or(Error::ExchangeRateNotFound { currency, date })
+}
+// endregion: Methods
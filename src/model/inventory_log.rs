@@ -1,7 +1,11 @@
-use super::{pageable::Pageable, ModelManager};
+use super::{common::spawn_row_stream, pageable::Pageable, tax::LineTax, ModelManager};
 use crate::model::Result;
+use crate::money::{Currency, Money};
 use crate::{ctx::Ctx, model::user::get_user_ids};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::stream::{BoxStream, TryStreamExt};
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
 use sqlx::{postgres::PgTypeInfo, types::BigDecimal};
 
 // region: Structs
@@ -26,8 +30,7 @@ impl sqlx::Type<sqlx::Postgres> for InventoryLogActions<'_> {
     }
 }
 
-#[derive(sqlx::FromRow)]
-pub struct InventoryLog {
+pub struct InventoryLogForDbResult {
     pub id: i64,
     pub quantity: i64,
     pub product_id: i64,
@@ -35,17 +38,97 @@ pub struct InventoryLog {
     pub action: InventoryLogAction,
     pub timestamp: DateTime<Utc>,
     pub price: BigDecimal,
+    pub currency: Currency,
+    pub base_price: BigDecimal,
+    pub base_currency: Currency,
     pub warehouse_id: i64,
     pub transaction_id: Option<i64>,
+    pub lot_id: Option<i64>,
+    pub lot_number: Option<String>,
+    pub unit_name: Option<String>,
+    pub unit_quantity: Option<i64>,
+    pub tax_class_id: Option<i64>,
+    pub tax_rate: Option<BigDecimal>,
+    pub net_amount: Option<BigDecimal>,
+    pub tax_amount: Option<BigDecimal>,
+    pub gross_amount: Option<BigDecimal>,
+}
+
+pub struct InventoryLog {
+    pub id: i64,
+    pub quantity: i64,
+    pub product_id: i64,
+    pub product_display_name: String,
+    pub action: InventoryLogAction,
+    pub timestamp: DateTime<Utc>,
+    /// As recorded, in the transaction's currency.
+    pub price: Money,
+    /// `price` in the organization's base currency.
+    pub base_price: Money,
+    pub warehouse_id: i64,
+    pub transaction_id: Option<i64>,
+    pub lot_id: Option<i64>,
+    pub lot_number: Option<String>,
+    pub unit_name: Option<String>,
+    pub unit_quantity: Option<i64>,
+    /// Sales lines only, in the transaction's currency.
+    pub tax: Option<LineTax>,
+}
+
+impl From<InventoryLogForDbResult> for InventoryLog {
+    fn from(value: InventoryLogForDbResult) -> Self {
+        let currency = value.currency;
+        let tax = match (
+            value.tax_rate,
+            value.net_amount,
+            value.tax_amount,
+            value.gross_amount,
+        ) {
+            (Some(rate), Some(net), Some(tax), Some(gross)) => Some(LineTax {
+                tax_class_id: value.tax_class_id,
+                rate: rate.normalized(),
+                net: Money::new(net, currency),
+                tax: Money::new(tax, currency),
+                gross: Money::new(gross, currency),
+            }),
+            _ => None,
+        };
+
+        Self {
+            id: value.id,
+            quantity: value.quantity,
+            product_id: value.product_id,
+            product_display_name: value.product_display_name,
+            action: value.action,
+            timestamp: value.timestamp,
+            price: Money::new(value.price, value.currency),
+            base_price: Money::new(value.base_price, value.base_currency),
+            warehouse_id: value.warehouse_id,
+            transaction_id: value.transaction_id,
+            lot_id: value.lot_id,
+            lot_number: value.lot_number,
+            unit_name: value.unit_name,
+            unit_quantity: value.unit_quantity,
+            tax,
+        }
+    }
 }
 
 // region: Create
+#[derive(Clone)]
 pub struct InventoryLogForCreate {
     pub quantity: i64,
     pub product_id: i64,
     pub action: InventoryLogAction,
-    pub price: f64,
+    pub price: Money,
     pub warehouse_id: i64,
+    pub lot_id: Option<i64>,
+    pub lot_number: Option<String>,
+    pub expiry_date: Option<NaiveDate>,
+    pub serial_numbers: Vec<String>,
+    pub unit_name: Option<String>,
+    pub unit_quantity: Option<i64>,
+    pub unit_conversion_factor: Option<i64>,
 }
 // endregion: Create
 
@@ -58,8 +141,8 @@ pub async fn get_logs(
     let db = mm.db();
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
-    let logs: Vec<InventoryLog> = sqlx::query_as!(
-        InventoryLog,
+    let logs = sqlx::query_as!(
+        InventoryLogForDbResult,
         r#"SELECT
             il.id,
             il.quantity,
@@ -68,11 +151,27 @@ pub async fn get_logs(
             il.action as "action: InventoryLogAction",
             il.timestamp,
             il.price,
+            il.currency as "currency: Currency",
+            il.base_price as "base_price!",
+            o.base_currency as "base_currency: Currency",
             il.warehouse_id,
-            il.inventory_transaction_id as transaction_id
+            il.inventory_transaction_id as transaction_id,
+            il.lot_id,
+            l.lot_number as "lot_number?",
+            il.unit_name,
+            il.unit_quantity,
+            il.tax_class_id,
+            il.tax_rate,
+            il.net_amount,
+            il.tax_amount,
+            il.gross_amount
         FROM inventory_logs il
         JOIN products p
         ON p.id = il.product_id
+        LEFT JOIN lots l
+        ON l.id = il.lot_id
+        JOIN organizations o
+        ON o.id = il.organization_id
         WHERE 
         il.organization_id = $1 
         OFFSET $2 
@@ -86,6 +185,92 @@ pub async fn get_logs(
 
     Ok(logs.into_iter().map(|l| l.into()).collect())
 }
+
+#[serde_as]
+#[derive(Debug, Default, Deserialize)]
+pub struct InventoryLogForSearch {
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub product_id: Option<i64>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub warehouse_id: Option<i64>,
+    /// First day included, in UTC.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    /// Last day included, in UTC.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
+}
+
+/// Streams the ledger in chronological order.
+pub fn export_logs(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    log_for_search: InventoryLogForSearch,
+) -> BoxStream<'static, Result<InventoryLog>> {
+    let (ctx, mm) = (ctx.clone(), mm.clone());
+
+    spawn_row_stream(|sender| async move {
+        let db = mm.db();
+        let (_, organization_id) = get_user_ids(&ctx, &mm).await?;
+
+        let mut logs = sqlx::query_as!(
+            InventoryLogForDbResult,
+            r#"SELECT
+                il.id,
+                il.quantity,
+                il.product_id,
+                p.display_name as product_display_name,
+                il.action as "action: InventoryLogAction",
+                il.timestamp,
+                il.price,
+                il.currency as "currency: Currency",
+                il.base_price as "base_price!",
+                o.base_currency as "base_currency: Currency",
+                il.warehouse_id,
+                il.inventory_transaction_id as transaction_id,
+                il.lot_id,
+                l.lot_number as "lot_number?",
+                il.unit_name,
+                il.unit_quantity,
+                il.tax_class_id,
+                il.tax_rate,
+                il.net_amount,
+                il.tax_amount,
+                il.gross_amount
+            FROM inventory_logs il
+            JOIN products p
+            ON p.id = il.product_id
+            LEFT JOIN lots l
+            ON l.id = il.lot_id
+            JOIN organizations o
+            ON o.id = il.organization_id
+            WHERE il.organization_id = $1
+            AND ($2::int8 IS NULL OR il.product_id = $2)
+            AND ($3::int8 IS NULL OR il.warehouse_id = $3)
+            AND ($4::date IS NULL OR il.timestamp >= $4::date)
+            AND ($5::date IS NULL OR il.timestamp < $5::date + 1)
+            ORDER BY il.timestamp, il.id;"#,
+            organization_id,
+            log_for_search.product_id,
+            log_for_search.warehouse_id,
+            log_for_search.from,
+            log_for_search.to
+        )
+        .fetch(db);
+
+        while let Some(log) = logs.try_next().await? {
+            if sender.send(Ok(log.into())).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    })
+}
 // region: Read
 
 // impl From<DepositForCreateItem> for InventoryLogForCreateOld {
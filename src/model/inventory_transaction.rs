@@ -1,15 +1,28 @@
-use std::collections::HashMap;
-
 use super::{
-    inventory_log::{self, InventoryLog, InventoryLogAction, InventoryLogForCreate},
-    pageable::Pageable,
+    common::spawn_row_stream,
+    customer::customer_price_list,
+    exchange_rate::find_exchange_rate,
+    inventory_log::{InventoryLogAction, InventoryLogForCreate},
+    invoice::next_invoice_number,
+    lot::resolve_lots,
+    organization::base_currency,
+    price_list::{can_override_price, check_prices},
+    serial_number::save_serial_numbers,
+    tax::line_taxes,
+    unit::resolve_units,
     user::get_user_ids,
     ModelManager,
 };
-use crate::model::error::Result;
+use crate::model::error::{Error, Result};
+use crate::money::{Currency, Money};
 use crate::{ctx::Ctx, model::inventory_log::InventoryLogActions};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::stream::{BoxStream, TryStreamExt};
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
 use sqlx::types::BigDecimal;
+use sqlx::PgConnection;
+use std::str::FromStr;
 
 // https://github.com/launchbadge/sqlx/issues/1004#issuecomment-854662251
 #[derive(sqlx::Type, Debug, Clone)]
@@ -22,13 +35,24 @@ pub enum InventoryTransactionAction {
     Deposit,
     SalesRollback,
     DepositRollback,
+    /// The stock an organization starts with. Only allowed before any other
+    /// movement.
+    OpeningBalance,
 }
 
-pub struct InventoryTransaction {
-    id: i64,
-    timestamp: DateTime<Utc>,
-    action: InventoryTransactionAction,
-    logs: Vec<InventoryLog>,
+impl FromStr for InventoryTransactionAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "SALES" => Ok(Self::Sales),
+            "DEPOSIT" => Ok(Self::Deposit),
+            "SALES_ROLLBACK" => Ok(Self::SalesRollback),
+            "DEPOSIT_ROLLBACK" => Ok(Self::DepositRollback),
+            "OPENING_BALANCE" => Ok(Self::OpeningBalance),
+            _ => Err(format!("unknown transaction action {s}")),
+        }
+    }
 }
 
 // region: Create
@@ -36,12 +60,26 @@ pub struct InventoryTransaction {
 pub struct InventoryTransactionLogForCreate {
     pub quantity: i64,
     pub product_id: i64,
-    pub price: f64,
+    pub price: Money,
     pub warehouse_id: i64,
+    pub lot_id: Option<i64>,
+    pub lot_number: Option<String>,
+    pub expiry_date: Option<NaiveDate>,
+    pub serial_numbers: Vec<String>,
+    pub unit_name: Option<String>,
 }
 
 pub struct InventoryTransactionForCreate {
     pub action: InventoryTransactionAction,
+    /// When the movement happened, if not now.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Currency of the prices, the organization's base currency if not set.
+    pub currency: Option<Currency>,
+    /// Units of the base currency per unit of `currency`. Looked up in the
+    /// exchange rates for the transaction date if not set.
+    pub exchange_rate: Option<BigDecimal>,
+    /// Who a sale is for; their price list decides what they pay.
+    pub customer_id: Option<i64>,
     pub logs: Vec<InventoryLogForCreate>,
 }
 
@@ -49,53 +87,53 @@ impl InventoryTransactionForCreate {
     pub fn new(action: InventoryTransactionAction) -> Self {
         Self {
             action,
+            timestamp: None,
+            currency: None,
+            exchange_rate: None,
+            customer_id: None,
             logs: Vec::new(),
         }
     }
 
-    pub fn add_log(&mut self, log: InventoryTransactionLogForCreate) -> () {
+    pub fn add_log(&mut self, log: InventoryTransactionLogForCreate) {
         let InventoryTransactionLogForCreate {
             price,
             product_id,
             quantity,
             warehouse_id,
+            lot_id,
+            lot_number,
+            expiry_date,
+            serial_numbers,
+            unit_name,
         } = log;
 
-        let new_log = match self.action {
-            InventoryTransactionAction::Deposit => InventoryLogForCreate {
-                quantity,
-                product_id,
-                action: InventoryLogAction::Incoming,
-                price,
-                warehouse_id,
-            },
-            InventoryTransactionAction::DepositRollback => InventoryLogForCreate {
-                quantity,
-                product_id,
-                action: InventoryLogAction::Outgoing,
-                price,
-                warehouse_id,
-            },
-            InventoryTransactionAction::Sales => InventoryLogForCreate {
-                quantity,
-                product_id,
-                action: InventoryLogAction::Outgoing,
-                price,
-                warehouse_id,
-            },
-            InventoryTransactionAction::SalesRollback => InventoryLogForCreate {
-                quantity,
-                product_id,
-                action: InventoryLogAction::Incoming,
-                price,
-                warehouse_id,
-            },
+        let action = match self.action {
+            InventoryTransactionAction::Deposit => InventoryLogAction::Incoming,
+            InventoryTransactionAction::DepositRollback => InventoryLogAction::Outgoing,
+            InventoryTransactionAction::Sales => InventoryLogAction::Outgoing,
+            InventoryTransactionAction::SalesRollback => InventoryLogAction::Incoming,
+            InventoryTransactionAction::OpeningBalance => InventoryLogAction::Incoming,
         };
 
-        self.logs.push(new_log);
+        self.logs.push(InventoryLogForCreate {
+            quantity,
+            product_id,
+            action,
+            price,
+            warehouse_id,
+            lot_id,
+            lot_number,
+            expiry_date,
+            serial_numbers,
+            unit_name,
+            unit_quantity: None,
+            unit_conversion_factor: None,
+        });
     }
 
-    pub async fn save(self, ctx: &Ctx, mm: &ModelManager) -> Result<()> {
+    /// Returns the id of the transaction.
+    pub async fn save(self, ctx: &Ctx, mm: &ModelManager) -> Result<i64> {
         create_inventory_transaction(ctx, mm, self).await
     }
 }
@@ -104,179 +142,379 @@ async fn create_inventory_transaction(
     ctx: &Ctx,
     mm: &ModelManager,
     transaction_for_create: InventoryTransactionForCreate,
-) -> Result<()> {
+) -> Result<i64> {
     let db = mm.db();
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
+    // Which way stock moves is up to the action, never the sign.
+    if let Some(log) = transaction_for_create
+        .logs
+        .iter()
+        .find(|log| log.quantity < 1)
+    {
+        return Err(Error::InvalidQuantity {
+            product_id: log.product_id,
+            quantity: log.quantity,
+        });
+    }
+
+    let mut tx = db.begin().await?;
+
+    // An opening balance locks the organization exclusively so no other
+    // movement can slip in between the check and the insert; every other
+    // transaction shares the lock.
+    match transaction_for_create.action {
+        InventoryTransactionAction::OpeningBalance => {
+            sqlx::query!(
+                "SELECT id FROM organizations WHERE id = $1 FOR UPDATE;",
+                organization_id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if has_movements(&mut tx, organization_id).await? {
+                return Err(Error::OpeningBalanceNotAllowed);
+            }
+        }
+        _ => {
+            sqlx::query!(
+                "SELECT id FROM organizations WHERE id = $1 FOR SHARE;",
+                organization_id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+        }
+    }
+
+    let (currency, exchange_rate) =
+        resolve_exchange_rate(&mut tx, organization_id, &transaction_for_create).await?;
+
+    let customer_id = transaction_for_create.customer_id;
+    let is_sales = matches!(
+        transaction_for_create.action,
+        InventoryTransactionAction::Sales
+    );
+    let is_taxed = matches!(
+        transaction_for_create.action,
+        InventoryTransactionAction::Sales | InventoryTransactionAction::SalesRollback
+    );
+    if let Some(customer_id) = customer_id {
+        customer_price_list(&mut tx, organization_id, customer_id).await?;
+    }
+
+    let invoice_number = match is_sales {
+        true => Some(next_invoice_number(&mut tx, organization_id).await?),
+        false => None,
+    };
+
     let transaction = sqlx::query!(
-        r#"INSERT INTO inventory_transactions (action, organization_id) 
-        VALUES ($1, $2) 
-        RETURNING id;"#,
+        r#"INSERT INTO inventory_transactions (action, timestamp, organization_id, currency, exchange_rate, customer_id, invoice_number, api_key_id) 
+        VALUES ($1, COALESCE($2, NOW()), $3, $4, $5, $6, $7, $8) 
+        RETURNING id, timestamp;"#,
         transaction_for_create.action as InventoryTransactionAction,
-        organization_id
+        transaction_for_create.timestamp,
+        organization_id,
+        currency as Currency,
+        exchange_rate,
+        customer_id,
+        invoice_number,
+        ctx.api_key_id()
     )
-    .fetch_one(db)
+    .fetch_one(&mut *tx)
     .await?;
 
-    let quantities: Vec<_> = transaction_for_create
-        .logs
+    let logs = resolve_units(&mut tx, organization_id, transaction_for_create.logs).await?;
+
+    if is_sales && !can_override_price(ctx, mm).await? {
+        let date = transaction.timestamp.date_naive();
+        check_prices(
+            &mut tx,
+            organization_id,
+            customer_id,
+            &logs,
+            &exchange_rate,
+            date,
+        )
+        .await?;
+    }
+    let logs = resolve_lots(&mut tx, organization_id, logs).await?;
+
+    // Taxed after lot allocation, so every stored line carries its own share.
+    let taxes = match is_taxed {
+        true => {
+            let date = transaction.timestamp.date_naive();
+            line_taxes(&mut tx, organization_id, customer_id, &logs, date)
+                .await?
+                .into_iter()
+                .map(Some)
+                .collect()
+        }
+        false => vec![None; logs.len()],
+    };
+
+    let ids: Vec<i64> = sqlx::query!(
+        r#"SELECT nextval('inventory_logs_id_seq') as "id!" FROM generate_series(1, $1);"#,
+        logs.len() as i32
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+
+    let quantities: Vec<_> = logs.iter().map(|l| l.quantity).collect();
+
+    let product_ids: Vec<_> = logs.iter().map(|l| l.product_id).collect();
+
+    let actions: Vec<_> = logs.iter().map(|l| l.action).collect();
+
+    let prices: Vec<_> = logs.iter().map(|l| l.price.amount().clone()).collect();
+
+    let organization_ids = vec![organization_id; logs.len()];
+    let transaction_ids = vec![transaction.id; logs.len()];
+
+    let warehouse_ids: Vec<_> = logs.iter().map(|l| l.warehouse_id).collect();
+
+    let lot_ids: Vec<_> = logs.iter().map(|l| l.lot_id).collect();
+
+    let unit_names: Vec<_> = logs.iter().map(|l| l.unit_name.clone()).collect();
+
+    let unit_quantities: Vec<_> = logs.iter().map(|l| l.unit_quantity).collect();
+
+    let unit_conversion_factors: Vec<_> = logs.iter().map(|l| l.unit_conversion_factor).collect();
+
+    let tax_class_ids: Vec<_> = taxes
         .iter()
-        .map(|l| l.quantity)
+        .map(|t| t.as_ref().and_then(|t| t.tax_class_id))
         .collect();
 
-    let product_ids: Vec<_> = transaction_for_create
-        .logs
+    let tax_rates: Vec<_> = taxes
         .iter()
-        .map(|l| l.product_id)
+        .map(|t| t.as_ref().map(|t| t.rate.clone()))
         .collect();
 
-    let actions: Vec<_> = transaction_for_create
-        .logs
+    let net_amounts: Vec<_> = taxes
         .iter()
-        .map(|l| l.action)
+        .map(|t| t.as_ref().map(|t| t.net.amount().clone()))
         .collect();
 
-    let prices: Vec<_> = transaction_for_create
-        .logs
+    let tax_amounts: Vec<_> = taxes
         .iter()
-        .map(|l| l.price)
+        .map(|t| t.as_ref().map(|t| t.tax.amount().clone()))
         .collect();
 
-    let organization_ids = vec![organization_id; transaction_for_create.logs.len()];
-    let transaction_ids = vec![transaction.id; transaction_for_create.logs.len()];
-
-    let warehouse_ids: Vec<_> = transaction_for_create
-        .logs
+    let gross_amounts: Vec<_> = taxes
         .iter()
-        .map(|l| l.warehouse_id)
+        .map(|t| t.as_ref().map(|t| t.gross.amount().clone()))
         .collect();
 
     sqlx::query!(
-        r#"INSERT INTO inventory_logs (quantity, product_id, action, price, organization_id, warehouse_id, inventory_transaction_id)
-        SELECT * FROM UNNEST($1::int8[], $2::int8[], $3::inventory_log_action[], $4::float8[], $5::int8[], $6::int8[], $7::int8[]);"#,
+        r#"INSERT INTO inventory_logs (id, quantity, product_id, action, price, organization_id, warehouse_id, inventory_transaction_id, lot_id, unit_name, unit_quantity, unit_conversion_factor, tax_class_id, tax_rate, net_amount, tax_amount, gross_amount, timestamp, currency, exchange_rate)
+        SELECT *, $13, $14, $15 FROM UNNEST($1::int8[], $2::int8[], $3::int8[], $4::inventory_log_action[], $5::numeric[], $6::int8[], $7::int8[], $8::int8[], $9::int8[], $10::varchar[], $11::int8[], $12::int8[], $16::int8[], $17::numeric[], $18::numeric[], $19::numeric[], $20::numeric[]);"#,
+        &ids,
         &quantities,
         &product_ids,
         InventoryLogActions(&actions) as _,
         &prices,
         &organization_ids,
         &warehouse_ids,
-        &transaction_ids
-    ).execute_many(db)
-    .await;
+        &transaction_ids,
+        &lot_ids as &[Option<i64>],
+        &unit_names as &[Option<String>],
+        &unit_quantities as &[Option<i64>],
+        &unit_conversion_factors as &[Option<i64>],
+        transaction.timestamp,
+        currency as Currency,
+        exchange_rate,
+        &tax_class_ids as &[Option<i64>],
+        &tax_rates as &[Option<BigDecimal>],
+        &net_amounts as &[Option<BigDecimal>],
+        &tax_amounts as &[Option<BigDecimal>],
+        &gross_amounts as &[Option<BigDecimal>]
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    save_serial_numbers(&mut tx, organization_id, &logs, &ids).await?;
+
+    tx.commit().await?;
+
+    Ok(transaction.id)
+}
+
+/// The transaction's currency and its rate to the base currency. Every price
+/// must be in that currency.
+async fn resolve_exchange_rate(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    transaction_for_create: &InventoryTransactionForCreate,
+) -> Result<(Currency, BigDecimal)> {
+    let base_currency = base_currency(conn, organization_id).await?;
+    let currency = transaction_for_create.currency.unwrap_or(base_currency);
+
+    if let Some(log) = transaction_for_create
+        .logs
+        .iter()
+        .find(|l| l.price.currency() != currency)
+    {
+        return Err(Error::CurrencyMismatch {
+            expected: currency,
+            actual: log.price.currency(),
+        });
+    }
+
+    let exchange_rate = match (
+        &transaction_for_create.exchange_rate,
+        currency == base_currency,
+    ) {
+        (_, true) => BigDecimal::from(1),
+        (Some(rate), false) if *rate > BigDecimal::from(0) => rate.clone(),
+        (Some(_), false) => return Err(Error::InvalidExchangeRate { currency }),
+        (None, false) => {
+            let date = transaction_for_create
+                .timestamp
+                .unwrap_or_else(Utc::now)
+                .date_naive();
+            find_exchange_rate(conn, organization_id, currency, date).await?
+        }
+    };
+
+    Ok((currency, exchange_rate))
+}
+
+/// Whether anything has moved in or out of the organization's stock yet.
+pub(in crate::model) async fn has_movements(
+    conn: &mut PgConnection,
+    organization_id: i64,
+) -> Result<bool> {
+    let has_movements = sqlx::query!(
+        r#"SELECT
+            EXISTS (SELECT 1 FROM inventory_transactions WHERE organization_id = $1)
+            OR EXISTS (SELECT 1 FROM inventory_logs WHERE organization_id = $1)
+            as "has_movements!";"#,
+        organization_id
+    )
+    .fetch_one(conn)
+    .await?
+    .has_movements;
 
-    Ok(())
+    Ok(has_movements)
 }
 // endregion:       Shared
 // endregion: Create
 
 // region: Read
-#[derive(sqlx::FromRow)]
-pub struct InventoryLogsWithTransactionAndProductForDbRow {
-    inventory_transaction_id: i64,
-    inventory_transaction_timestamp: DateTime<Utc>,
-    inventory_transaction_action: InventoryTransactionAction,
-
-    inventory_log_id: i64,
-    inventory_log_quantity: i64,
-    inventory_log_product_id: i64,
-    inventory_log_action: InventoryLogAction,
-    inventory_log_timestamp: DateTime<Utc>,
-    inventory_log_price: BigDecimal,
-    inventory_log_warehouse_id: i64,
-    inventory_log_transaction_id: Option<i64>,
-
-    product_sku: String,
-    product_brand: String,
-    product_name: String,
-    product_display_name: String,
-    product_description: String,
-    product_price: BigDecimal,
+// region:  Export
+struct InventoryTransactionSummaryForDbResult {
+    id: i64,
+    timestamp: DateTime<Utc>,
+    action: InventoryTransactionAction,
+    line_count: i64,
+    quantity: i64,
+    currency: Currency,
+    exchange_rate: BigDecimal,
+    value: BigDecimal,
+    base_currency: Currency,
+}
+
+/// A transaction with its lines rolled up, as exported for accounting.
+pub struct InventoryTransactionSummary {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub action: InventoryTransactionAction,
+    pub line_count: i64,
+    pub quantity: i64,
+    /// Units of the base currency per unit of the transaction's currency.
+    pub exchange_rate: BigDecimal,
+    /// Sum of quantity times price over the lines, in the transaction's
+    /// currency.
+    pub value: Money,
+    /// `value` in the organization's base currency.
+    pub base_value: Money,
+}
+
+impl From<InventoryTransactionSummaryForDbResult> for InventoryTransactionSummary {
+    fn from(value: InventoryTransactionSummaryForDbResult) -> Self {
+        let total = Money::new(value.value, value.currency);
+        Self {
+            id: value.id,
+            timestamp: value.timestamp,
+            action: value.action,
+            line_count: value.line_count,
+            quantity: value.quantity,
+            base_value: total.exchange(&value.exchange_rate, value.base_currency),
+            value: total,
+            exchange_rate: value.exchange_rate.normalized(),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Default, Deserialize)]
+pub struct InventoryTransactionForSearch {
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub action: Option<InventoryTransactionAction>,
+    /// First day included, in UTC.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    /// Last day included, in UTC.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
 }
 
-// region:  Deposit
-pub async fn get_all_deposit_transactions(
+/// Streams transaction summaries in chronological order.
+pub fn export_transactions(
     ctx: &Ctx,
     mm: &ModelManager,
-    pageable: Pageable,
-) -> Result<Vec<InventoryTransaction>> {
-    let db = mm.db();
-    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    transaction_for_search: InventoryTransactionForSearch,
+) -> BoxStream<'static, Result<InventoryTransactionSummary>> {
+    let (ctx, mm) = (ctx.clone(), mm.clone());
 
-    let deposits = sqlx::query_as!(
-        InventoryLogsWithTransactionAndProductForDbRow,
-        r#"SELECT
-            it.id as inventory_transaction_id,
-            it.timestamp as inventory_transaction_timestamp,
-            it.action as "inventory_transaction_action: InventoryTransactionAction",
-            
-            il.id as inventory_log_id,
-            il.quantity as inventory_log_quantity,
-            il.product_id as inventory_log_product_id,
-            il.action as "inventory_log_action: InventoryLogAction",
-            il.timestamp as inventory_log_timestamp,
-            il.price as inventory_log_price,
-            il.warehouse_id as inventory_log_warehouse_id,
-            il.inventory_transaction_id as inventory_log_transaction_id,
-
-            p.sku as product_sku,
-            p.brand as product_brand,
-            p.name as product_name,
-            p.display_name as product_display_name,
-            p.description as product_description,
-            p.price as product_price
-        FROM inventory_logs il
-        INNER JOIN inventory_transactions it
-        ON il.inventory_transaction_id = it.id
-        JOIN products p
-        ON il.product_id = p.id
-        WHERE
-            it.organization_id = $1
-        AND
-            it.action = $2
-        OFFSET $3
-        LIMIT $4;"#,
-        organization_id,
-        InventoryTransactionAction::Deposit as InventoryTransactionAction,
-        pageable.offset(),
-        pageable.size()
-    )
-    .fetch_all(db)
-    .await?;
+    spawn_row_stream(|sender| async move {
+        let db = mm.db();
+        let (_, organization_id) = get_user_ids(&ctx, &mm).await?;
 
-    // fold
-    let inventory_transactions: HashMap<i64, InventoryTransaction> =
-        deposits.iter().fold(HashMap::new(), |mut acc, val| {
-            acc.insert(
-                val.inventory_transaction_id,
-                InventoryTransaction {
-                    id: val.inventory_transaction_id,
-                    timestamp: val.inventory_transaction_timestamp,
-                    action: val.inventory_transaction_action.to_owned(),
-                    logs: Vec::new(),
-                },
-            );
-
-            let key = val.inventory_log_transaction_id.unwrap_or(-1);
-            let value = acc.get_mut(&key);
-            value.map(|v| {
-                v.logs.push(InventoryLog {
-                    id: val.inventory_log_id,
-                    quantity: val.inventory_log_quantity,
-                    product_id: val.inventory_log_product_id,
-                    product_display_name: val.product_display_name.to_owned(),
-                    action: val.inventory_log_action,
-                    timestamp: val.inventory_log_timestamp,
-                    price: val.inventory_log_price.to_owned(),
-                    warehouse_id: val.inventory_log_warehouse_id,
-                    transaction_id: val.inventory_log_transaction_id,
-                })
-            });
-
-            acc
-        });
+        let mut transactions = sqlx::query_as!(
+            InventoryTransactionSummaryForDbResult,
+            r#"SELECT
+                it.id,
+                it.timestamp,
+                it.action as "action: InventoryTransactionAction",
+                COUNT(il.id) as "line_count!",
+                COALESCE(SUM(il.quantity), 0)::int8 as "quantity!",
+                it.currency as "currency: Currency",
+                it.exchange_rate,
+                COALESCE(SUM(il.quantity * il.price), 0) as "value!",
+                o.base_currency as "base_currency: Currency"
+            FROM inventory_transactions it
+            JOIN organizations o
+            ON o.id = it.organization_id
+            LEFT JOIN inventory_logs il
+            ON il.inventory_transaction_id = it.id
+            WHERE it.organization_id = $1
+            AND ($2::inventory_transaction_action IS NULL OR it.action = $2)
+            AND ($3::date IS NULL OR it.timestamp >= $3::date)
+            AND ($4::date IS NULL OR it.timestamp < $4::date + 1)
+            GROUP BY it.id, it.timestamp, it.action, it.currency, it.exchange_rate, o.base_currency
+            ORDER BY it.timestamp, it.id;"#,
+            organization_id,
+            transaction_for_search.action as Option<InventoryTransactionAction>,
+            transaction_for_search.from,
+            transaction_for_search.to
+        )
+        .fetch(db);
+
+        while let Some(transaction) = transactions.try_next().await? {
+            if sender.send(Ok(transaction.into())).await.is_err() {
+                break;
+            }
+        }
 
-    let values: Vec<InventoryTransaction> = inventory_transactions.into_values().collect();
-    Ok(values)
+        Ok(())
+    })
 }
-// endregion:   Deposit
+// endregion:   Export
 // endregion: Read
@@ -0,0 +1,350 @@
+use super::{
+    permissions::{has_permission, Permissions},
+    tenant::system,
+    user::{get_user_ids, MIN_PASSWORD_LENGTH},
+    ModelManager,
+};
+use crate::crypt::{self, hash_token, new_token};
+use crate::ctx::Ctx;
+use crate::mail::{link, Mail};
+use crate::model::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
+use sqlx::PgConnection;
+
+// Users are invited rather than created: an admin names them and the invitee
+// sets their own password through a link. The link holds a random token and
+// the table its hash, so it works once and only until it expires. Inviting a
+// username again replaces its pending invitation, e.g. to resend one that
+// expired. The invitee has no session yet, so following a link looks across
+// organizations.
+
+const INVITATION_LIFETIME_DAYS: i64 = 7;
+
+// region: Structs
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct InvitationForCreate {
+    pub display_name: String,
+    pub username: String,
+    /// Where the link is mailed. Without one the admin hands it over.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub admin: bool,
+}
+
+/// How the invitee gets their link.
+#[derive(Debug)]
+pub enum InvitationDelivery {
+    Mailed {
+        email: String,
+    },
+    /// No email was given; the link is shown once, to whoever invited.
+    Link(String),
+}
+
+#[derive(Debug)]
+pub struct Invitation {
+    pub id: i64,
+    pub display_name: String,
+    pub username: String,
+    pub email: Option<String>,
+    pub admin: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What the invitee sees before setting a password.
+#[derive(Debug)]
+pub struct InvitationForAccept {
+    pub organization_name: String,
+    pub display_name: String,
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvitationForAcceptForm {
+    pub display_name: String,
+    pub password: String,
+}
+// endregion: Structs
+
+// region: Methods
+/// Invites a user to the organization. Admins only.
+pub async fn invite_user(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    invitation_for_create: InvitationForCreate,
+) -> Result<InvitationDelivery> {
+    let db = mm.db();
+    let (user_id, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let mut tx = db.begin().await?;
+    let token =
+        create_invitation(&mut tx, organization_id, user_id, &invitation_for_create).await?;
+    let organization = sqlx::query!(
+        "SELECT display_name FROM organizations WHERE id = $1;",
+        organization_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    deliver_invitation(
+        mm,
+        &organization.display_name,
+        invitation_for_create.email,
+        &token,
+    )
+    .await
+}
+
+/// Pending invitations of the organization. Admins only.
+pub async fn get_invitations(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Invitation>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let invitations = sqlx::query_as!(
+        Invitation,
+        r#"SELECT id, display_name, username, email, admin, expires_at
+        FROM invitations
+        WHERE organization_id = $1
+        AND accepted_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY username;"#,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(invitations)
+}
+
+/// Withdraws a pending invitation; its link stops working.
+pub async fn revoke_invitation(ctx: &Ctx, mm: &ModelManager, invitation_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    sqlx::query!(
+        r#"DELETE FROM invitations
+        WHERE id = $1
+        AND organization_id = $2
+        AND accepted_at IS NULL;"#,
+        invitation_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// The invitation of `token`, if it can still be accepted.
+pub async fn get_invitation(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    token: &str,
+) -> Result<InvitationForAccept> {
+    system(async {
+        let db = mm.db();
+
+        sqlx::query_as!(
+            InvitationForAccept,
+            r#"SELECT
+                o.display_name as organization_name,
+                i.display_name,
+                i.username
+            FROM invitations i
+            JOIN organizations o
+            ON o.id = i.organization_id
+            WHERE i.token_hash = $1
+            AND i.accepted_at IS NULL
+            AND i.expires_at > CURRENT_TIMESTAMP;"#,
+            hash_token(token)
+        )
+        .fetch_optional(db)
+        .await?
+        .ok_or(Error::InvitationNotFound)
+    })
+    .await
+}
+
+/// Creates the invited user with the password they chose and uses up the
+/// invitation. Returns the new user's id.
+pub async fn accept_invitation(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    token: &str,
+    form: InvitationForAcceptForm,
+) -> Result<i64> {
+    system(async {
+        let db = mm.db();
+
+        if form.password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(Error::InvalidPassword);
+        }
+        let password = crypt::hash_value(&form.password)?;
+
+        let mut tx = db.begin().await?;
+
+        let invitation = sqlx::query!(
+            r#"SELECT id, display_name, username, email, admin, organization_id
+            FROM invitations
+            WHERE token_hash = $1
+            AND accepted_at IS NULL
+            AND expires_at > CURRENT_TIMESTAMP
+            FOR UPDATE;"#,
+            hash_token(token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::InvitationNotFound)?;
+
+        let display_name = match form.display_name.trim() {
+            "" => invitation.display_name.as_str(),
+            display_name => display_name,
+        };
+
+        let user = sqlx::query!(
+            r#"INSERT INTO users (display_name, username, password, email, organization_id)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (username, organization_id) DO NOTHING
+            RETURNING id;"#,
+            display_name,
+            invitation.username,
+            password,
+            invitation.email,
+            invitation.organization_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::InvalidUser {
+            username: invitation.username.clone(),
+        })?;
+
+        if invitation.admin {
+            sqlx::query!(
+                "INSERT INTO user_permissions (user_id, permission_id) VALUES ($1, $2);",
+                user.id,
+                Permissions::OrganizationAll as i64
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE invitations SET accepted_at = CURRENT_TIMESTAMP WHERE id = $1;",
+            invitation.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(user.id)
+    })
+    .await
+}
+
+/// Records an invitation, replacing a pending one for the same username.
+/// Returns the token for the link.
+pub(in crate::model) async fn create_invitation(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    invited_by: Option<i64>,
+    invitation_for_create: &InvitationForCreate,
+) -> Result<String> {
+    let display_name = invitation_for_create.display_name.trim();
+    let username = invitation_for_create.username.trim();
+    let email = invitation_for_create.email.as_deref().map(str::trim);
+    let invalid = || Error::InvalidUser {
+        username: username.to_string(),
+    };
+    if display_name.is_empty() || username.is_empty() {
+        return Err(invalid());
+    }
+    if let Some(email) = email.filter(|email| !email.contains('@')) {
+        return Err(Error::InvalidEmail {
+            email: email.to_string(),
+        });
+    }
+
+    let taken = sqlx::query!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM users WHERE username = $1 AND organization_id = $2
+        ) as "taken!";"#,
+        username,
+        organization_id
+    )
+    .fetch_one(&mut *conn)
+    .await?
+    .taken;
+    if taken {
+        return Err(invalid());
+    }
+
+    sqlx::query!(
+        r#"DELETE FROM invitations
+        WHERE username = $1
+        AND organization_id = $2
+        AND accepted_at IS NULL;"#,
+        username,
+        organization_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let token = new_token();
+    let expires_at = Utc::now() + Duration::days(INVITATION_LIFETIME_DAYS);
+    sqlx::query!(
+        r#"INSERT INTO invitations
+            (token_hash, display_name, username, email, admin, organization_id, invited_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8);"#,
+        hash_token(&token),
+        display_name,
+        username,
+        email,
+        invitation_for_create.admin,
+        organization_id,
+        invited_by,
+        expires_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(token)
+}
+
+/// Mails the link if there is an address, else hands it back. Call once the
+/// invitation is committed.
+pub(in crate::model) async fn deliver_invitation(
+    mm: &ModelManager,
+    organization_name: &str,
+    email: Option<String>,
+    token: &str,
+) -> Result<InvitationDelivery> {
+    let url = link(&format!("/invite/{token}"));
+    let Some(email) = email.map(|email| email.trim().to_string()) else {
+        return Ok(InvitationDelivery::Link(url));
+    };
+
+    mm.mailer()
+        .send(Mail {
+            to: email.clone(),
+            subject: format!("You are invited to {organization_name}"),
+            body: format!(
+                "You have been invited to {organization_name} on Distrupify.\n\n\
+                Set your password to get started:\n{url}\n\n\
+                The link works once and expires in {INVITATION_LIFETIME_DAYS} days."
+            ),
+        })
+        .await?;
+
+    Ok(InvitationDelivery::Mailed { email })
+}
+// endregion: Methods
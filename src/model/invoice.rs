@@ -0,0 +1,238 @@
+use super::{user::get_user_ids, ModelManager};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::money::{Currency, Money};
+use chrono::{DateTime, Utc};
+use sqlx::types::BigDecimal;
+use sqlx::PgConnection;
+
+// Every sale is numbered when it is saved, from a counter that is locked and
+// counted up in the same transaction: numbers are sequential per organization
+// and a sale that fails gives its number back. Invoices and delivery notes are
+// read from the stored lines, so they print the same every time.
+
+// region: Structs
+struct InvoiceForDbResult {
+    transaction_id: i64,
+    invoice_number: i64,
+    timestamp: DateTime<Utc>,
+    organization_name: String,
+    customer_name: Option<String>,
+    currency: Currency,
+}
+
+struct InvoiceLineForDbResult {
+    sku: String,
+    product_display_name: String,
+    quantity: i64,
+    unit: String,
+    unit_price: BigDecimal,
+    lot_number: Option<String>,
+    serial_numbers: Vec<String>,
+    tax_rate: Option<BigDecimal>,
+    net_amount: Option<BigDecimal>,
+    tax_amount: Option<BigDecimal>,
+    gross_amount: Option<BigDecimal>,
+}
+
+#[derive(Debug)]
+pub struct InvoiceLine {
+    pub sku: String,
+    pub product_display_name: String,
+    /// In the unit sold.
+    pub quantity: i64,
+    pub unit: String,
+    /// Per unit sold.
+    pub unit_price: Money,
+    pub lot_number: Option<String>,
+    pub serial_numbers: Vec<String>,
+    /// None for untaxed lines.
+    pub tax_rate: Option<BigDecimal>,
+    pub net: Money,
+    pub tax: Money,
+    pub gross: Money,
+}
+
+impl InvoiceLine {
+    fn new(value: InvoiceLineForDbResult, currency: Currency) -> Self {
+        let unit_price = Money::new(value.unit_price, currency);
+        let amount = unit_price.times(value.quantity).rounded();
+        let amount_or = |stored: Option<BigDecimal>, default: &Money| match stored {
+            Some(stored) => Money::new(stored, currency),
+            None => default.clone(),
+        };
+
+        Self {
+            sku: value.sku,
+            product_display_name: value.product_display_name,
+            quantity: value.quantity,
+            unit: value.unit,
+            unit_price,
+            lot_number: value.lot_number,
+            serial_numbers: value.serial_numbers,
+            tax_rate: value.tax_rate.map(|r| r.normalized()),
+            net: amount_or(value.net_amount, &amount),
+            tax: amount_or(value.tax_amount, &Money::zero(currency)),
+            gross: amount_or(value.gross_amount, &amount),
+        }
+    }
+}
+
+/// Net and tax of the lines at one rate.
+#[derive(Debug)]
+pub struct InvoiceTaxRate {
+    pub rate: BigDecimal,
+    pub net: Money,
+    pub tax: Money,
+}
+
+/// A sale as printed on its invoice and delivery note, in the sale's currency.
+#[derive(Debug)]
+pub struct Invoice {
+    pub transaction_id: i64,
+    pub number: i64,
+    pub timestamp: DateTime<Utc>,
+    pub organization_name: String,
+    pub customer_name: Option<String>,
+    pub currency: Currency,
+    pub lines: Vec<InvoiceLine>,
+    pub total_net: Money,
+    pub total_gross: Money,
+    /// The taxed lines summed per rate, highest rate first.
+    pub tax_rates: Vec<InvoiceTaxRate>,
+}
+
+impl Invoice {
+    /// The number as printed, e.g. `INV-000042`.
+    pub fn display_number(&self) -> String {
+        format!("INV-{:06}", self.number)
+    }
+}
+
+// endregion: Structs
+
+// region: Methods
+/// The invoice of a sale. Other transactions have none.
+pub async fn get_invoice(ctx: &Ctx, mm: &ModelManager, transaction_id: i64) -> Result<Invoice> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let invoice = sqlx::query_as!(
+        InvoiceForDbResult,
+        r#"SELECT
+            it.id as transaction_id,
+            it.invoice_number as "invoice_number!",
+            it.timestamp,
+            o.display_name as organization_name,
+            c.name as "customer_name?",
+            it.currency as "currency: Currency"
+        FROM inventory_transactions it
+        JOIN organizations o
+        ON o.id = it.organization_id
+        LEFT JOIN customers c
+        ON c.id = it.customer_id
+        WHERE it.id = $1
+        AND it.organization_id = $2
+        AND it.invoice_number IS NOT NULL;"#,
+        transaction_id,
+        organization_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(Error::InvoiceNotFound { transaction_id })?;
+
+    let lines = sqlx::query_as!(
+        InvoiceLineForDbResult,
+        r#"SELECT
+            p.sku,
+            p.display_name as product_display_name,
+            COALESCE(il.unit_quantity, il.quantity) as "quantity!",
+            COALESCE(il.unit_name, p.base_unit) as "unit!",
+            il.price * COALESCE(il.unit_conversion_factor, 1) as "unit_price!",
+            l.lot_number as "lot_number?",
+            ARRAY(
+                SELECT sn.serial_number
+                FROM inventory_log_serial_numbers ilsn
+                JOIN serial_numbers sn
+                ON sn.id = ilsn.serial_number_id
+                WHERE ilsn.inventory_log_id = il.id
+                ORDER BY sn.serial_number
+            ) as "serial_numbers!",
+            il.tax_rate,
+            il.net_amount,
+            il.tax_amount,
+            il.gross_amount
+        FROM inventory_logs il
+        JOIN products p
+        ON p.id = il.product_id
+        LEFT JOIN lots l
+        ON l.id = il.lot_id
+        WHERE il.inventory_transaction_id = $1
+        ORDER BY il.id;"#,
+        transaction_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let lines: Vec<_> = lines
+        .into_iter()
+        .map(|l| InvoiceLine::new(l, invoice.currency))
+        .collect();
+
+    Ok(Invoice {
+        transaction_id: invoice.transaction_id,
+        number: invoice.invoice_number,
+        timestamp: invoice.timestamp,
+        organization_name: invoice.organization_name,
+        customer_name: invoice.customer_name,
+        currency: invoice.currency,
+        total_net: Money::sum(invoice.currency, lines.iter().map(|l| l.net.clone()))?,
+        total_gross: Money::sum(invoice.currency, lines.iter().map(|l| l.gross.clone()))?,
+        tax_rates: sum_tax_rates(&lines)?,
+        lines,
+    })
+}
+
+/// The taxed lines summed per rate, highest rate first.
+fn sum_tax_rates(lines: &[InvoiceLine]) -> Result<Vec<InvoiceTaxRate>> {
+    let mut rates: Vec<InvoiceTaxRate> = Vec::new();
+    for line in lines {
+        let Some(rate) = &line.tax_rate else {
+            continue;
+        };
+        match rates.iter_mut().find(|r| &r.rate == rate) {
+            Some(r) => {
+                r.net = r.net.plus(&line.net)?;
+                r.tax = r.tax.plus(&line.tax)?;
+            }
+            None => rates.push(InvoiceTaxRate {
+                rate: rate.clone(),
+                net: line.net.clone(),
+                tax: line.tax.clone(),
+            }),
+        }
+    }
+    rates.sort_by(|a, b| b.rate.cmp(&a.rate));
+    Ok(rates)
+}
+
+/// The organization's next invoice number. Holds the counter until the
+/// transaction ends, so sales are numbered one at a time.
+pub(in crate::model) async fn next_invoice_number(
+    conn: &mut PgConnection,
+    organization_id: i64,
+) -> Result<i64> {
+    let sequence = sqlx::query!(
+        r#"INSERT INTO invoice_sequences (organization_id, last_number)
+        VALUES ($1, 1)
+        ON CONFLICT (organization_id) DO UPDATE
+        SET last_number = invoice_sequences.last_number + 1
+        RETURNING last_number;"#,
+        organization_id
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(sequence.last_number)
+}
+// endregion: Methods
@@ -0,0 +1,216 @@
+use super::{
+    permissions::{has_permission, Permissions},
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgConnection;
+use std::net::IpAddr;
+
+// Every login attempt is recorded, and failed ones are counted per name
+// typed, existing or not. Past a few failures in a row each attempt must wait
+// twice as long as the last, and enough of them lock the name out for a
+// while, or until an admin unlocks the user. A login that succeeds starts the
+// count over. Throttling per address is done in front of the handlers, by
+// `web::mw_rate_limit`.
+
+/// Failures in a row before attempts must wait.
+const FREE_FAILURES: i32 = 3;
+const BASE_DELAY_SECONDS: i64 = 1;
+const MAX_DELAY_SECONDS: i64 = 60;
+
+/// Failures in a row that lock the name out.
+const LOCKOUT_FAILURES: i32 = 10;
+const LOCKOUT_MINUTES: i64 = 15;
+
+/// How many attempts the users page shows.
+const RECENT_ATTEMPTS: i64 = 50;
+
+// region: Structs
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "login_outcome", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LoginOutcome {
+    Succeeded,
+    Failed,
+    /// Came too soon after a failure.
+    Throttled,
+    Locked,
+}
+
+impl LoginOutcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LoginOutcome::Succeeded => "Succeeded",
+            LoginOutcome::Failed => "Failed",
+            LoginOutcome::Throttled => "Throttled",
+            LoginOutcome::Locked => "Locked out",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoginAttempt {
+    /// As typed.
+    pub username: String,
+    pub ip: String,
+    pub outcome: LoginOutcome,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Who a login attempt was for, as far as known.
+pub(in crate::model) struct LoginAttemptForCreate<'a> {
+    pub organization_name: &'a str,
+    pub username: &'a str,
+    pub organization_id: Option<i64>,
+    pub user_id: Option<i64>,
+    pub ip: IpAddr,
+}
+// endregion: Structs
+
+// region: Methods
+/// The latest login attempts on the organization's users. Admins only.
+pub async fn get_login_attempts(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<LoginAttempt>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let attempts = sqlx::query_as!(
+        LoginAttempt,
+        r#"SELECT
+            username,
+            ip,
+            outcome as "outcome: LoginOutcome",
+            created_at
+        FROM login_attempts
+        WHERE organization_id = $1
+        ORDER BY created_at DESC, id DESC
+        LIMIT $2;"#,
+        organization_id,
+        RECENT_ATTEMPTS
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(attempts)
+}
+
+/// Fails if the name may not try to log in yet, and holds its count until
+/// the transaction ends.
+pub(in crate::model) async fn check_login_allowed(
+    conn: &mut PgConnection,
+    organization_name: &str,
+    username: &str,
+) -> Result<()> {
+    let lockout = sqlx::query!(
+        r#"SELECT failures, last_failed_at, locked_until
+        FROM login_lockouts
+        WHERE organization_name = $1
+        AND username = $2
+        FOR UPDATE;"#,
+        organization_name,
+        username
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(lockout) = lockout else {
+        return Ok(());
+    };
+
+    let now = Utc::now();
+    if let Some(locked_until) = lockout.locked_until.filter(|until| *until > now) {
+        return Err(Error::LoginLocked { locked_until });
+    }
+    if lockout.failures >= FREE_FAILURES {
+        let doublings = (lockout.failures - FREE_FAILURES).min(16) as u32;
+        let delay = (BASE_DELAY_SECONDS << doublings).min(MAX_DELAY_SECONDS);
+        let allowed_at = lockout.last_failed_at + Duration::seconds(delay);
+        if allowed_at > now {
+            return Err(Error::LoginThrottled {
+                retry_after_seconds: (allowed_at - now).num_seconds().max(1),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Counts a failure in a row for the name, locking it out once there are
+/// enough; the count then starts over.
+pub(in crate::model) async fn count_failed_login(
+    conn: &mut PgConnection,
+    organization_name: &str,
+    username: &str,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO login_lockouts (organization_name, username, failures)
+        VALUES ($1, $2, 1)
+        ON CONFLICT (organization_name, username) DO UPDATE
+        SET failures = login_lockouts.failures + 1,
+            last_failed_at = CURRENT_TIMESTAMP;"#,
+        organization_name,
+        username
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE login_lockouts
+        SET failures = 0,
+            locked_until = CURRENT_TIMESTAMP + make_interval(mins => $3)
+        WHERE organization_name = $1
+        AND username = $2
+        AND failures >= $4;"#,
+        organization_name,
+        username,
+        LOCKOUT_MINUTES as i32,
+        LOCKOUT_FAILURES
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Starts the count of the name over and lifts any lockout.
+pub(in crate::model) async fn clear_failed_logins(
+    conn: &mut PgConnection,
+    organization_name: &str,
+    username: &str,
+) -> Result<()> {
+    sqlx::query!(
+        r#"DELETE FROM login_lockouts
+        WHERE organization_name = $1
+        AND username = $2;"#,
+        organization_name,
+        username
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub(in crate::model) async fn record_login_attempt(
+    conn: &mut PgConnection,
+    attempt: LoginAttemptForCreate<'_>,
+    outcome: LoginOutcome,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO login_attempts
+            (organization_name, username, organization_id, user_id, ip, outcome)
+        VALUES ($1, $2, $3, $4, $5, $6);"#,
+        attempt.organization_name,
+        attempt.username,
+        attempt.organization_id,
+        attempt.user_id,
+        attempt.ip.to_string(),
+        outcome as LoginOutcome
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+// endregion: Methods
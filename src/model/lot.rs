@@ -0,0 +1,553 @@
+use super::{
+    inventory_log::{InventoryLogAction, InventoryLogForCreate},
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use chrono::NaiveDate;
+use sqlx::PgConnection;
+use std::collections::HashMap;
+
+// region: Structs
+#[derive(Debug)]
+pub struct LotStockLevelForDbResult {
+    pub lot_number: String,
+    pub expiry_date: Option<NaiveDate>,
+    pub warehouse_id: i64,
+    pub quantity: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct LotStockLevel {
+    pub lot_number: String,
+    pub expiry_date: Option<NaiveDate>,
+    pub warehouse_id: i64,
+    pub quantity: i64,
+}
+
+impl From<LotStockLevelForDbResult> for LotStockLevel {
+    fn from(value: LotStockLevelForDbResult) -> Self {
+        Self {
+            lot_number: value.lot_number,
+            expiry_date: value.expiry_date,
+            warehouse_id: value.warehouse_id,
+            quantity: value.quantity.unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExpiringLotForDbResult {
+    pub lot_number: String,
+    pub expiry_date: Option<NaiveDate>,
+    pub product_sku: String,
+    pub product_display_name: String,
+    pub warehouse_id: i64,
+    pub quantity: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct ExpiringLot {
+    pub lot_number: String,
+    pub expiry_date: NaiveDate,
+    pub product_sku: String,
+    pub product_display_name: String,
+    pub warehouse_id: i64,
+    pub quantity: i64,
+}
+
+impl From<ExpiringLotForDbResult> for ExpiringLot {
+    fn from(value: ExpiringLotForDbResult) -> Self {
+        Self {
+            lot_number: value.lot_number,
+            expiry_date: value.expiry_date.unwrap_or(NaiveDate::MAX),
+            product_sku: value.product_sku,
+            product_display_name: value.product_display_name,
+            warehouse_id: value.warehouse_id,
+            quantity: value.quantity.unwrap_or(0),
+        }
+    }
+}
+// endregion: Structs
+
+// region: Read
+pub async fn get_stock_by_lot(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+) -> Result<Vec<LotStockLevel>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let lots = sqlx::query_as!(
+        LotStockLevelForDbResult,
+        r#"SELECT
+            l.lot_number,
+            l.expiry_date,
+            il.warehouse_id,
+            SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END) as quantity
+        FROM lots l
+        JOIN inventory_logs il
+        ON il.lot_id = l.id
+        WHERE l.product_id = $1
+        AND l.organization_id = $2
+        GROUP BY l.id, l.lot_number, l.expiry_date, il.warehouse_id
+        HAVING SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END) > 0
+        ORDER BY l.expiry_date NULLS LAST, l.id;"#,
+        product_id,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(lots.into_iter().map(|l| l.into()).collect())
+}
+
+pub async fn get_expiring_lots(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    within_days: i32,
+    category_id: Option<i64>,
+) -> Result<Vec<ExpiringLot>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let lots = sqlx::query_as!(
+        ExpiringLotForDbResult,
+        r#"WITH RECURSIVE category_tree AS (
+            SELECT id FROM categories WHERE id = $3 AND organization_id = $1
+            UNION ALL
+            SELECT c.id FROM categories c
+            JOIN category_tree ct
+            ON c.parent_id = ct.id
+        )
+        SELECT
+            l.lot_number,
+            l.expiry_date,
+            p.sku as product_sku,
+            p.display_name as product_display_name,
+            il.warehouse_id,
+            SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END) as quantity
+        FROM lots l
+        JOIN products p
+        ON p.id = l.product_id
+        JOIN inventory_logs il
+        ON il.lot_id = l.id
+        WHERE l.organization_id = $1
+        AND l.expiry_date <= CURRENT_DATE + $2::int4
+        AND ($3::int8 IS NULL OR p.category_id IN (SELECT id FROM category_tree))
+        GROUP BY l.id, l.lot_number, l.expiry_date, p.id, p.sku, p.display_name, il.warehouse_id
+        HAVING SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END) > 0
+        ORDER BY l.expiry_date, p.display_name;"#,
+        organization_id,
+        within_days,
+        category_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(lots.into_iter().map(|l| l.into()).collect())
+}
+// endregion: Read
+
+// region: Allocation
+/// Assigns a lot to every log of a lot-tracked product. Incoming logs create
+/// (or reuse) the lot by its number, outgoing logs use the requested lot or are
+/// split across lots first-expiring-first-out.
+pub(in crate::model) async fn resolve_lots(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    logs: Vec<InventoryLogForCreate>,
+) -> Result<Vec<InventoryLogForCreate>> {
+    let mut resolved = Vec::with_capacity(logs.len());
+    // Earlier lines are not stored yet, so what they take from a lot in a
+    // warehouse is kept here and left out of its balance.
+    let mut allocated: HashMap<(i64, i64), i64> = HashMap::new();
+
+    // Balances are read before they are drawn from, so transactions taking
+    // from the same product's lots go one at a time; in product order, so
+    // they cannot deadlock.
+    let mut outgoing_products: Vec<i64> = logs
+        .iter()
+        .filter(|log| matches!(log.action, InventoryLogAction::Outgoing))
+        .map(|log| log.product_id)
+        .collect();
+    outgoing_products.sort_unstable();
+    outgoing_products.dedup();
+    for product_id in outgoing_products {
+        lock_product_lots(conn, organization_id, product_id).await?;
+    }
+
+    for log in logs {
+        let lot_tracked = sqlx::query!(
+            "SELECT lot_tracked FROM products WHERE id = $1 AND organization_id = $2;",
+            log.product_id,
+            organization_id
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .lot_tracked;
+
+        if !lot_tracked {
+            resolved.push(InventoryLogForCreate {
+                lot_id: None,
+                ..log
+            });
+            continue;
+        }
+
+        match (log.action, log.lot_id) {
+            (InventoryLogAction::Incoming, Some(lot_id)) => {
+                get_lot_for_product(conn, organization_id, log.product_id, lot_id).await?;
+                resolved.push(log);
+            }
+            (InventoryLogAction::Incoming, None) => {
+                let lot_number = log
+                    .lot_number
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .ok_or(Error::LotNumberRequired {
+                        product_id: log.product_id,
+                    })?;
+
+                let lot = sqlx::query!(
+                    r#"INSERT INTO lots (lot_number, expiry_date, product_id, organization_id)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (lot_number, product_id)
+                    DO UPDATE SET expiry_date = COALESCE(EXCLUDED.expiry_date, lots.expiry_date)
+                    RETURNING id;"#,
+                    lot_number,
+                    log.expiry_date,
+                    log.product_id,
+                    organization_id
+                )
+                .fetch_one(&mut *conn)
+                .await?;
+
+                resolved.push(InventoryLogForCreate {
+                    lot_id: Some(lot.id),
+                    ..log
+                });
+            }
+            (InventoryLogAction::Outgoing, Some(lot_id)) => {
+                get_lot_for_product(conn, organization_id, log.product_id, lot_id).await?;
+
+                let available = sqlx::query!(
+                    r#"SELECT
+                        COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as "quantity!"
+                    FROM inventory_logs
+                    WHERE lot_id = $1
+                    AND warehouse_id = $2;"#,
+                    lot_id,
+                    log.warehouse_id
+                )
+                .fetch_one(&mut *conn)
+                .await?
+                .quantity;
+                let allocated = allocated.entry((lot_id, log.warehouse_id)).or_default();
+                let available = available - *allocated;
+
+                if available < log.quantity {
+                    return Err(Error::InsufficientStock {
+                        product_id: log.product_id,
+                        requested: log.quantity,
+                        available,
+                    });
+                }
+                *allocated += log.quantity;
+
+                resolved.push(log);
+            }
+            (InventoryLogAction::Outgoing, None) => {
+                let lots = sqlx::query!(
+                    r#"SELECT
+                        l.id,
+                        SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END) as "quantity!"
+                    FROM lots l
+                    JOIN inventory_logs il
+                    ON il.lot_id = l.id
+                    WHERE l.product_id = $1
+                    AND l.organization_id = $2
+                    AND il.warehouse_id = $3
+                    GROUP BY l.id, l.expiry_date
+                    HAVING SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END) > 0
+                    ORDER BY l.expiry_date NULLS LAST, l.id;"#,
+                    log.product_id,
+                    organization_id,
+                    log.warehouse_id
+                )
+                .fetch_all(&mut *conn)
+                .await?;
+
+                let lots: Vec<(i64, i64)> = lots
+                    .into_iter()
+                    .map(|lot| {
+                        let allocated = allocated
+                            .get(&(lot.id, log.warehouse_id))
+                            .copied()
+                            .unwrap_or_default();
+                        (lot.id, lot.quantity - allocated)
+                    })
+                    .filter(|(_, quantity)| *quantity > 0)
+                    .collect();
+
+                let available: i64 = lots.iter().map(|(_, quantity)| quantity).sum();
+                if available < log.quantity {
+                    return Err(Error::InsufficientStock {
+                        product_id: log.product_id,
+                        requested: log.quantity,
+                        available,
+                    });
+                }
+
+                let mut remaining = log.quantity;
+                for (lot_id, lot_quantity) in lots {
+                    if remaining == 0 {
+                        break;
+                    }
+
+                    let quantity = remaining.min(lot_quantity);
+                    remaining -= quantity;
+                    *allocated.entry((lot_id, log.warehouse_id)).or_default() += quantity;
+
+                    // Only keep the original unit when the split is still a whole number of it.
+                    let unit_quantity = log
+                        .unit_conversion_factor
+                        .filter(|factor| quantity % factor == 0)
+                        .map(|factor| quantity / factor);
+
+                    resolved.push(InventoryLogForCreate {
+                        quantity,
+                        lot_id: Some(lot_id),
+                        lot_number: None,
+                        expiry_date: None,
+                        serial_numbers: Vec::new(),
+                        unit_name: unit_quantity.and(log.unit_name.clone()),
+                        unit_quantity,
+                        unit_conversion_factor: unit_quantity.and(log.unit_conversion_factor),
+                        ..log.clone()
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Holds the product's lots until the transaction ends.
+async fn lock_product_lots(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    product_id: i64,
+) -> Result<()> {
+    sqlx::query!(
+        r#"SELECT id FROM lots
+        WHERE product_id = $1
+        AND organization_id = $2
+        ORDER BY id
+        FOR UPDATE;"#,
+        product_id,
+        organization_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_lot_for_product(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    product_id: i64,
+    lot_id: i64,
+) -> Result<()> {
+    sqlx::query!(
+        "SELECT id FROM lots WHERE id = $1 AND product_id = $2 AND organization_id = $3;",
+        lot_id,
+        product_id,
+        organization_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::LotNotFound { lot_id })?;
+
+    Ok(())
+}
+// endregion: Allocation
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::store::new_db_pool;
+    use crate::model::tenant::system;
+    use crate::money::{Currency, Money};
+    use chrono::Utc;
+
+    struct Seed {
+        product_id: i64,
+        warehouse_id: i64,
+        /// Expires first.
+        first_lot: i64,
+        second_lot: i64,
+    }
+
+    /// A lot-tracked product with 5 units in each of two lots.
+    async fn seed(conn: &mut PgConnection) -> Seed {
+        let name = format!("lots-{}", Utc::now().timestamp_nanos_opt().unwrap());
+        let organization_id: (i64,) = sqlx::query_as(
+            "INSERT INTO organizations (name, display_name) VALUES ($1, $1) RETURNING id;",
+        )
+        .bind(&name)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        let organization_id = organization_id.0;
+        let warehouse_id: (i64,) =
+            sqlx::query_as("INSERT INTO warehouses (organization_id) VALUES ($1) RETURNING id;")
+                .bind(organization_id)
+                .fetch_one(&mut *conn)
+                .await
+                .unwrap();
+        let product_id: (i64,) = sqlx::query_as(
+            "INSERT INTO products (sku, brand, name, display_name, description, organization_id, lot_tracked)
+            VALUES ('LOT', '', $1, $1, '', $2, TRUE) RETURNING id;",
+        )
+        .bind(&name)
+        .bind(organization_id)
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+
+        let mut lots = Vec::new();
+        for (lot_number, expiry_date) in [("A", "2030-01-01"), ("B", "2031-01-01")] {
+            let lot: (i64,) = sqlx::query_as(
+                "INSERT INTO lots (lot_number, expiry_date, product_id, organization_id)
+                VALUES ($1, $2::DATE, $3, $4) RETURNING id;",
+            )
+            .bind(lot_number)
+            .bind(expiry_date)
+            .bind(product_id.0)
+            .bind(organization_id)
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO inventory_logs (quantity, product_id, action, price, currency, organization_id, warehouse_id, lot_id)
+                VALUES (5, $1, 'INCOMING', 0, 'PHP', $2, $3, $4);",
+            )
+            .bind(product_id.0)
+            .bind(organization_id)
+            .bind(warehouse_id.0)
+            .bind(lot.0)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+            lots.push(lot.0);
+        }
+
+        Seed {
+            product_id: product_id.0,
+            warehouse_id: warehouse_id.0,
+            first_lot: lots[0],
+            second_lot: lots[1],
+        }
+    }
+
+    fn outgoing(seed: &Seed, quantity: i64, lot_id: Option<i64>) -> InventoryLogForCreate {
+        InventoryLogForCreate {
+            quantity,
+            product_id: seed.product_id,
+            action: InventoryLogAction::Outgoing,
+            price: Money::zero(Currency::PHP),
+            warehouse_id: seed.warehouse_id,
+            lot_id,
+            lot_number: None,
+            expiry_date: None,
+            serial_numbers: Vec::new(),
+            unit_name: None,
+            unit_quantity: None,
+            unit_conversion_factor: None,
+        }
+    }
+
+    /// Resolves `logs` against a fresh seed, rolled back afterwards.
+    async fn resolve(
+        logs: impl FnOnce(&Seed) -> Vec<InventoryLogForCreate>,
+    ) -> (Seed, Result<Vec<(Option<i64>, i64)>>) {
+        let db = new_db_pool().await.unwrap();
+        system(async {
+            let mut tx = db.begin().await.unwrap();
+            let seed = seed(&mut tx).await;
+            let organization_id: (i64,) =
+                sqlx::query_as("SELECT organization_id FROM products WHERE id = $1;")
+                    .bind(seed.product_id)
+                    .fetch_one(&mut *tx)
+                    .await
+                    .unwrap();
+            let resolved = resolve_lots(&mut tx, organization_id.0, logs(&seed))
+                .await
+                .map(|logs| logs.iter().map(|l| (l.lot_id, l.quantity)).collect());
+            tx.rollback().await.unwrap();
+            (seed, resolved)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn lines_on_one_lot_share_its_balance() {
+        let (_, resolved) = resolve(|seed| {
+            vec![
+                outgoing(seed, 3, Some(seed.first_lot)),
+                outgoing(seed, 3, Some(seed.first_lot)),
+            ]
+        })
+        .await;
+
+        assert!(matches!(
+            resolved,
+            Err(Error::InsufficientStock {
+                requested: 3,
+                available: 2,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn expiring_first_allocation_skips_what_earlier_lines_took() {
+        let (seed, resolved) =
+            resolve(|seed| vec![outgoing(seed, 4, None), outgoing(seed, 4, None)]).await;
+
+        assert_eq!(
+            resolved.unwrap(),
+            vec![
+                (Some(seed.first_lot), 4),
+                (Some(seed.first_lot), 1),
+                (Some(seed.second_lot), 3),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn lines_cannot_take_more_than_the_lots_hold() {
+        let (_, resolved) = resolve(|seed| {
+            vec![
+                outgoing(seed, 4, Some(seed.second_lot)),
+                outgoing(seed, 7, None),
+            ]
+        })
+        .await;
+
+        assert!(matches!(
+            resolved,
+            Err(Error::InsufficientStock {
+                requested: 7,
+                available: 6,
+                ..
+            })
+        ));
+    }
+}
@@ -1,31 +1,72 @@
+pub mod api_key;
+pub mod barcode;
 pub mod category;
 mod common;
+pub mod customer;
 mod error;
+pub mod exchange_rate;
 pub mod inventory_log;
 pub mod inventory_transaction;
+pub mod invitation;
+pub mod invoice;
+pub mod login_attempt;
+pub mod lot;
+pub mod opening_balance;
 pub mod organization;
+pub mod organization_deletion;
 pub mod pageable;
+pub mod password_reset;
 pub mod permissions;
+pub mod price_list;
+pub mod product_import;
+pub mod product_variant;
 pub mod products;
+pub mod serial_number;
+pub mod session;
 mod store;
+pub mod superuser_action;
+pub mod tax;
+pub mod tenant;
+pub mod two_factor;
+pub mod unit;
 pub mod user;
+pub mod valuation;
+pub mod warehouse;
 
 pub use self::error::{Error, Result};
+use self::session::{new_session_cache, SessionCache};
 use self::store::{new_db_pool, Db};
+use crate::mail::{mailer_from_env, Mailer};
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct ModelManager {
     db: Db,
+    mailer: Arc<dyn Mailer>,
+    sessions: SessionCache,
 }
 
 impl ModelManager {
     pub async fn new() -> Result<Self> {
         let db = new_db_pool().await?;
+        let mailer = mailer_from_env()?;
 
-        Ok(ModelManager { db })
+        Ok(ModelManager {
+            db,
+            mailer,
+            sessions: new_session_cache(),
+        })
     }
 
     pub(in crate::model) fn db(&self) -> &Db {
         &self.db
     }
+
+    pub(in crate::model) fn mailer(&self) -> Arc<dyn Mailer> {
+        self.mailer.clone()
+    }
+
+    pub(in crate::model) fn sessions(&self) -> &SessionCache {
+        &self.sessions
+    }
 }
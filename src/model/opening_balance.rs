@@ -0,0 +1,267 @@
+use super::{
+    inventory_transaction::{
+        has_movements, InventoryTransactionAction, InventoryTransactionForCreate,
+        InventoryTransactionLogForCreate,
+    },
+    organization::get_base_currency,
+    product_import::parse_price,
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::money::{Locale, Money};
+use chrono::{NaiveDate, Utc};
+use std::collections::HashMap;
+
+// region: Structs
+/// One data row of an uploaded opening balance file.
+#[derive(Debug, Default)]
+pub struct OpeningBalanceRow {
+    /// Line number in the uploaded file, for the report.
+    pub line: usize,
+    pub sku: String,
+    /// Warehouse name or id.
+    pub warehouse: String,
+    pub quantity: String,
+    pub unit_cost: String,
+    pub lot_number: String,
+    pub expiry_date: String,
+}
+
+#[derive(Debug)]
+pub struct OpeningBalanceRowResult {
+    pub line: usize,
+    pub sku: String,
+    pub warehouse: String,
+    pub quantity: i64,
+    pub unit_cost: Money,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct OpeningBalanceReport {
+    pub date: NaiveDate,
+    pub rows: Vec<OpeningBalanceRowResult>,
+    /// In the base currency, which unit costs are in.
+    pub total_value: Money,
+    /// Stock has already moved, so no opening balance can be recorded.
+    pub has_movements: bool,
+}
+
+impl OpeningBalanceReport {
+    pub fn error_count(&self) -> usize {
+        self.rows.iter().filter(|r| !r.errors.is_empty()).count()
+    }
+
+    pub fn total_quantity(&self) -> i64 {
+        self.rows.iter().map(|r| r.quantity).sum()
+    }
+}
+// endregion: Structs
+
+// region: Methods
+/// Whether the organization can still record its opening balance.
+pub async fn can_record_opening_balance(ctx: &Ctx, mm: &ModelManager) -> Result<bool> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut conn = db.acquire().await?;
+
+    Ok(!has_movements(&mut conn, organization_id).await?)
+}
+
+/// Validates every row without writing anything.
+pub async fn dry_run_opening_balance(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    date: NaiveDate,
+    rows: Vec<OpeningBalanceRow>,
+    locale: Locale,
+) -> Result<OpeningBalanceReport> {
+    let (report, _) = validate_rows(ctx, mm, date, rows, locale).await?;
+
+    Ok(report)
+}
+
+/// Records every row as one `OpeningBalance` transaction dated `date`.
+/// Nothing is written unless every row is valid and the organization has no
+/// other movements. Unit costs are read in `locale`.
+pub async fn apply_opening_balance(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    date: NaiveDate,
+    rows: Vec<OpeningBalanceRow>,
+    locale: Locale,
+) -> Result<OpeningBalanceReport> {
+    let (report, logs) = validate_rows(ctx, mm, date, rows, locale).await?;
+    if report.has_movements {
+        return Err(Error::OpeningBalanceNotAllowed);
+    }
+    if report.error_count() > 0 {
+        return Err(Error::OpeningBalanceInvalid {
+            error_count: report.error_count(),
+        });
+    }
+
+    let mut transaction =
+        InventoryTransactionForCreate::new(InventoryTransactionAction::OpeningBalance);
+    transaction.timestamp = date.and_hms_opt(0, 0, 0).map(|t| t.and_utc());
+    for log in logs {
+        transaction.add_log(log);
+    }
+    transaction.save(ctx, mm).await?;
+
+    Ok(report)
+}
+
+async fn validate_rows(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    date: NaiveDate,
+    rows: Vec<OpeningBalanceRow>,
+    locale: Locale,
+) -> Result<(OpeningBalanceReport, Vec<InventoryTransactionLogForCreate>)> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    if date > Utc::now().date_naive() {
+        return Err(Error::InvalidOpeningBalanceDate { date });
+    }
+
+    let has_movements = !can_record_opening_balance(ctx, mm).await?;
+    let base_currency = get_base_currency(ctx, mm).await?;
+
+    let skus: Vec<String> = rows.iter().map(|r| r.sku.trim().to_string()).collect();
+    let products: HashMap<String, (i64, bool, bool)> = sqlx::query!(
+        r#"SELECT id, sku, lot_tracked, serialized FROM products
+        WHERE organization_id = $1
+        AND sku = ANY($2);"#,
+        organization_id,
+        &skus
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|p| (p.sku, (p.id, p.lot_tracked, p.serialized)))
+    .collect();
+
+    let warehouses = sqlx::query!(
+        "SELECT id, name FROM warehouses WHERE organization_id = $1;",
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+    let find_warehouse = |warehouse: &str| {
+        warehouses
+            .iter()
+            .find(|w| {
+                w.id.to_string() == warehouse
+                    || w.name
+                        .as_deref()
+                        .is_some_and(|n| n.eq_ignore_ascii_case(warehouse))
+            })
+            .map(|w| w.id)
+    };
+
+    let mut first_seen: HashMap<(i64, i64, String), usize> = HashMap::new();
+    let mut results = Vec::with_capacity(rows.len());
+    let mut logs = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let mut errors = Vec::new();
+        let sku = row.sku.trim().to_string();
+        let warehouse = row.warehouse.trim().to_string();
+        let lot_number = row.lot_number.trim().to_string();
+
+        let product = products.get(&sku).copied();
+        match product {
+            None => errors.push(format!("Unknown SKU \"{sku}\"")),
+            Some((_, _, true)) => errors.push(
+                "Serialized products need their serial numbers, add them with a deposit"
+                    .to_string(),
+            ),
+            Some((_, true, _)) if lot_number.is_empty() => {
+                errors.push("Lot number is required for this product".to_string())
+            }
+            Some(_) => {}
+        }
+
+        let warehouse_id = find_warehouse(&warehouse);
+        if warehouse_id.is_none() {
+            errors.push(format!("Unknown warehouse \"{warehouse}\""));
+        }
+
+        let quantity = row.quantity.trim().parse::<i64>().ok().filter(|q| *q > 0);
+        if quantity.is_none() {
+            errors.push(format!("Invalid quantity \"{}\"", row.quantity.trim()));
+        }
+
+        let unit_cost = parse_price(&row.unit_cost, base_currency, locale);
+        if unit_cost.is_none() {
+            errors.push(format!("Invalid unit cost \"{}\"", row.unit_cost.trim()));
+        }
+
+        let expiry_date = match row.expiry_date.trim() {
+            "" => None,
+            expiry_date => {
+                let parsed = NaiveDate::parse_from_str(expiry_date, "%Y-%m-%d").ok();
+                if parsed.is_none() {
+                    errors.push(format!("Invalid expiry date \"{expiry_date}\""));
+                }
+                parsed
+            }
+        };
+
+        if let (Some((product_id, lot_tracked, _)), Some(warehouse_id)) = (product, warehouse_id) {
+            let lot_number = if lot_tracked {
+                lot_number
+            } else {
+                String::new()
+            };
+            let key = (product_id, warehouse_id, lot_number.clone());
+            match first_seen.get(&key) {
+                Some(line) => errors.push(format!("Same product and warehouse as line {line}")),
+                None => {
+                    first_seen.insert(key, row.line);
+                }
+            }
+
+            if let (true, Some(quantity), Some(unit_cost)) =
+                (errors.is_empty(), quantity, &unit_cost)
+            {
+                logs.push(InventoryTransactionLogForCreate {
+                    quantity,
+                    product_id,
+                    price: unit_cost.clone(),
+                    warehouse_id,
+                    lot_id: None,
+                    lot_number: Some(lot_number).filter(|l| !l.is_empty()),
+                    expiry_date,
+                    serial_numbers: Vec::new(),
+                    unit_name: None,
+                });
+            }
+        }
+
+        results.push(OpeningBalanceRowResult {
+            line: row.line,
+            sku,
+            warehouse,
+            quantity: quantity.unwrap_or_default(),
+            unit_cost: unit_cost.unwrap_or_else(|| Money::zero(base_currency)),
+            errors,
+        });
+    }
+
+    let values = results.iter().map(|r| r.unit_cost.times(r.quantity));
+    let report = OpeningBalanceReport {
+        date,
+        total_value: Money::sum(base_currency, values)?,
+        rows: results,
+        has_movements,
+    };
+
+    Ok((report, logs))
+}
+// endregion: Methods
@@ -1,90 +1,485 @@
 use super::{
-    user::{create_user, UserForCreate},
+    inventory_transaction::has_movements,
+    invitation::{create_invitation, deliver_invitation, InvitationDelivery, InvitationForCreate},
+    session::forget_organization_sessions,
+    superuser_action::{record_superuser_action, SuperuserAction},
+    user::{get_superuser_id, get_user_ids},
     ModelManager,
 };
 use crate::{
     ctx::Ctx,
-    model::{permissions::Permissions, Result},
+    model::{Error, Result},
+    money::Currency,
 };
-use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
+use sqlx::PgConnection;
+
+// Organizations are provisioned and suspended by superusers. Suspending ends
+// every session of the organization and keeps its users out, logins and
+// every other call alike, until it is reactivated; its data is left as it is.
+// Scheduling a deletion suspends it the same way and, after a grace period
+// in which it can still be reactivated, the deletion job archives and
+// removes its data.
+
+/// How long a scheduled deletion waits.
+pub const DELETION_GRACE_DAYS: i64 = 30;
 
 // region: Structs
-#[derive(Debug, Clone, FromRow, Serialize)]
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "organization_status", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrganizationStatus {
+    Active,
+    Suspended,
+    PendingDeletion,
+}
+
+/// Whether an organization's users may work, and if not why.
+pub(in crate::model) struct OrganizationState {
+    pub status: OrganizationStatus,
+    pub status_reason: Option<String>,
+    pub deletion_due_at: Option<DateTime<Utc>>,
+}
+
+impl OrganizationState {
+    /// Fails, telling the organization's users why, unless it is active.
+    pub(in crate::model) fn ensure_active(&self, organization_id: i64) -> Result<()> {
+        let reason = self.status_reason.as_deref().unwrap_or("no reason given");
+        let reason = match (self.status, self.deletion_due_at) {
+            (OrganizationStatus::Active, _) => return Ok(()),
+            (OrganizationStatus::PendingDeletion, Some(due)) => format!(
+                "The organization is scheduled for deletion on {}: {reason}",
+                due.format("%Y-%m-%d")
+            ),
+            _ => format!("The organization is suspended: {reason}"),
+        };
+
+        Err(Error::OrganizationSuspended {
+            organization_id,
+            reason,
+        })
+    }
+}
+
+/// An organization as the superuser console lists it.
+#[derive(Debug, Clone)]
 pub struct Organization {
     pub id: i64,
     pub name: String,
     pub display_name: String,
+    pub base_currency: Currency,
+    pub status: OrganizationStatus,
+    pub status_reason: Option<String>,
+    pub deletion_due_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub active_users: i64,
+    pub products: i64,
+    pub transactions: i64,
+    pub last_transaction_at: Option<DateTime<Utc>>,
 }
 
+#[serde_as]
 #[derive(Deserialize)]
 pub struct OrganizationForProvision {
     pub name: String,
     pub display_name: String,
+    /// The first admin, invited like any other user.
+    pub admin_username: String,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub admin_email: Option<String>,
 }
 
 // endregion: Structs
 
 // region: Methods
-pub async fn get_all_organizations(_ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Organization>> {
+/// Every organization with how much it is used. Superusers only.
+pub async fn get_all_organizations(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Organization>> {
     let db = mm.db();
+    get_superuser_id(ctx, mm).await?;
 
-    let organizations = sqlx::query_as!(Organization, "SELECT * from organizations;")
-        .fetch_all(db)
-        .await?;
+    let organizations = sqlx::query_as!(
+        Organization,
+        r#"SELECT
+            o.id,
+            o.name,
+            o.display_name,
+            o.base_currency as "base_currency: Currency",
+            o.status as "status: OrganizationStatus",
+            o.status_reason,
+            o.deletion_due_at,
+            o.created_at,
+            (
+                SELECT COUNT(*) FROM users u
+                WHERE u.organization_id = o.id AND u.active
+            ) as "active_users!",
+            (
+                SELECT COUNT(*) FROM products p
+                WHERE p.organization_id = o.id
+            ) as "products!",
+            (
+                SELECT COUNT(*) FROM inventory_transactions t
+                WHERE t.organization_id = o.id
+            ) as "transactions!",
+            (
+                SELECT MAX(t.timestamp) FROM inventory_transactions t
+                WHERE t.organization_id = o.id
+            ) as last_transaction_at
+        FROM organizations o
+        ORDER BY o.name;"#
+    )
+    .fetch_all(db)
+    .await?;
 
     Ok(organizations)
 }
 
+/// Creates an organization with a default warehouse and invites its first
+/// admin. Superusers only.
 pub async fn register_organization(
     ctx: &Ctx,
     mm: &ModelManager,
     organization_for_provision: OrganizationForProvision,
-) -> Result<()> {
+) -> Result<InvitationDelivery> {
     let db = mm.db();
+    let superuser_id = get_superuser_id(ctx, mm).await?;
 
-    let tx = db.begin().await?;
-    let organization = sqlx::query_as!(
-        Organization,
-        "INSERT INTO organizations (name, display_name) \
-          VALUES ($1, $2) \
-          RETURNING *;",
-        organization_for_provision.name,
-        organization_for_provision.display_name
+    let name = organization_for_provision.name.trim();
+    let display_name = match organization_for_provision.display_name.trim() {
+        "" => name,
+        display_name => display_name,
+    };
+    let invalid = || Error::InvalidOrganization {
+        name: name.to_string(),
+    };
+    if name.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut tx = db.begin().await?;
+    let organization = sqlx::query!(
+        r#"INSERT INTO organizations (name, display_name)
+        VALUES ($1, $2)
+        ON CONFLICT (name) DO NOTHING
+        RETURNING id, display_name;"#,
+        name,
+        display_name
     )
-    .fetch_one(db)
-    .await?;
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(invalid)?;
 
     sqlx::query!(
         "INSERT INTO warehouses (name, organization_id) VALUES ($1, $2);",
         "default",
         organization.id
     )
-    .execute(db)
+    .execute(&mut *tx)
     .await?;
 
-    let user_id = create_user(
-        ctx,
-        mm,
-        UserForCreate {
-            display_name: "admin".to_string(),
-            organization_id: organization.id,
-            username: "admin".to_string(),
-            password: "admin".to_string(),
-        },
+    let admin = InvitationForCreate {
+        display_name: organization_for_provision.admin_username.clone(),
+        username: organization_for_provision.admin_username,
+        email: organization_for_provision.admin_email,
+        admin: true,
+    };
+    let token = create_invitation(&mut tx, organization.id, Some(superuser_id), &admin).await?;
+
+    record_superuser_action(
+        &mut tx,
+        Some(superuser_id),
+        organization.id,
+        SuperuserAction::CreateOrganization,
+        None,
+        None,
     )
     .await?;
 
+    tx.commit().await?;
+
+    deliver_invitation(mm, &organization.display_name, admin.email, &token).await
+}
+
+/// Logs the organization's users out and keeps them out. Calls off a
+/// scheduled deletion. Superusers only.
+pub async fn suspend_organization(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    organization_id: i64,
+    reason: &str,
+) -> Result<()> {
+    let db = mm.db();
+    let superuser_id = get_superuser_id(ctx, mm).await?;
+
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(Error::ReasonRequired);
+    }
+
+    let mut tx = db.begin().await?;
+
+    lock_organization(&mut tx, organization_id).await?;
+
     sqlx::query!(
-        "INSERT INTO user_permissions (user_id, permission_id) \
-        VALUES ($1, $2);",
-        user_id,
-        Permissions::OrganizationAll as i32
+        r#"UPDATE organizations
+        SET status = 'SUSPENDED', status_reason = $2, deletion_due_at = NULL
+        WHERE id = $1;"#,
+        organization_id,
+        reason
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    revoke_organization_sessions(&mut tx, organization_id).await?;
+
+    record_superuser_action(
+        &mut tx,
+        Some(superuser_id),
+        organization_id,
+        SuperuserAction::SuspendOrganization,
+        None,
+        Some(reason),
     )
-    .execute(db)
     .await?;
 
     tx.commit().await?;
+    forget_organization_sessions(mm, organization_id);
+
+    Ok(())
+}
+
+/// Suspends the organization and deletes it once the grace period is over.
+/// Scheduling again keeps the first date. Superusers only.
+pub async fn schedule_organization_deletion(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    organization_id: i64,
+    reason: &str,
+) -> Result<()> {
+    let db = mm.db();
+    let superuser_id = get_superuser_id(ctx, mm).await?;
+
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(Error::ReasonRequired);
+    }
+
+    let mut tx = db.begin().await?;
+
+    lock_organization(&mut tx, organization_id).await?;
+
+    let deletion_due_at = Utc::now() + Duration::days(DELETION_GRACE_DAYS);
+    sqlx::query!(
+        r#"UPDATE organizations
+        SET
+            status = 'PENDING_DELETION',
+            status_reason = $2,
+            deletion_due_at = COALESCE(deletion_due_at, $3)
+        WHERE id = $1;"#,
+        organization_id,
+        reason,
+        deletion_due_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    revoke_organization_sessions(&mut tx, organization_id).await?;
+
+    record_superuser_action(
+        &mut tx,
+        Some(superuser_id),
+        organization_id,
+        SuperuserAction::ScheduleDeletion,
+        None,
+        Some(reason),
+    )
+    .await?;
+
+    tx.commit().await?;
+    forget_organization_sessions(mm, organization_id);
+
+    Ok(())
+}
+
+/// Lets the organization's users log in again, calling off a scheduled
+/// deletion. Superusers only.
+pub async fn reactivate_organization(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    organization_id: i64,
+) -> Result<()> {
+    let db = mm.db();
+    let superuser_id = get_superuser_id(ctx, mm).await?;
+
+    let mut tx = db.begin().await?;
+
+    lock_organization(&mut tx, organization_id).await?;
+
+    sqlx::query!(
+        r#"UPDATE organizations
+        SET status = 'ACTIVE', status_reason = NULL, deletion_due_at = NULL
+        WHERE id = $1;"#,
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_superuser_action(
+        &mut tx,
+        Some(superuser_id),
+        organization_id,
+        SuperuserAction::ReactivateOrganization,
+        None,
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Locks the organization's row and returns its state.
+pub(in crate::model) async fn lock_organization(
+    conn: &mut PgConnection,
+    organization_id: i64,
+) -> Result<OrganizationState> {
+    sqlx::query_as!(
+        OrganizationState,
+        r#"SELECT
+            status as "status: OrganizationStatus",
+            status_reason,
+            deletion_due_at
+        FROM organizations
+        WHERE id = $1
+        FOR UPDATE;"#,
+        organization_id
+    )
+    .fetch_optional(conn)
+    .await?
+    .ok_or(Error::OrganizationNotFound { organization_id })
+}
+
+async fn revoke_organization_sessions(conn: &mut PgConnection, organization_id: i64) -> Result<()> {
+    sqlx::query!(
+        r#"DELETE FROM sessions
+        WHERE user_id IN (SELECT id FROM users WHERE organization_id = $1);"#,
+        organization_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// The currency valuation and reports are in.
+pub async fn get_base_currency(ctx: &Ctx, mm: &ModelManager) -> Result<Currency> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut conn = db.acquire().await?;
+
+    base_currency(&mut conn, organization_id).await
+}
+
+pub(in crate::model) async fn base_currency(
+    conn: &mut PgConnection,
+    organization_id: i64,
+) -> Result<Currency> {
+    let organization = sqlx::query!(
+        r#"SELECT base_currency as "base_currency: Currency"
+        FROM organizations
+        WHERE id = $1;"#,
+        organization_id
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(organization.base_currency)
+}
+
+/// Whether the base currency can still change.
+pub async fn can_change_base_currency(ctx: &Ctx, mm: &ModelManager) -> Result<bool> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut conn = db.acquire().await?;
+
+    Ok(!has_movements(&mut conn, organization_id).await?)
+}
+
+/// Changes the base currency. Recorded base amounts are not converted, so
+/// this is only allowed before any stock has moved.
+pub async fn update_base_currency(ctx: &Ctx, mm: &ModelManager, currency: Currency) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "SELECT id FROM organizations WHERE id = $1 FOR UPDATE;",
+        organization_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if has_movements(&mut tx, organization_id).await? {
+        return Err(Error::BaseCurrencyLocked);
+    }
+
+    sqlx::query!(
+        "UPDATE organizations SET base_currency = $1 WHERE id = $2;",
+        currency as Currency,
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+/// Whether product prices include tax. Price lists have their own switch.
+pub async fn get_prices_include_tax(ctx: &Ctx, mm: &ModelManager) -> Result<bool> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut conn = db.acquire().await?;
+
+    prices_include_tax(&mut conn, organization_id).await
+}
+
+pub(in crate::model) async fn prices_include_tax(
+    conn: &mut PgConnection,
+    organization_id: i64,
+) -> Result<bool> {
+    let organization = sqlx::query!(
+        "SELECT prices_include_tax FROM organizations WHERE id = $1;",
+        organization_id
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(organization.prices_include_tax)
+}
+
+/// Applies to sales from now on; past lines keep the tax they were charged.
+pub async fn update_prices_include_tax(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    prices_include_tax: bool,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query!(
+        "UPDATE organizations SET prices_include_tax = $2 WHERE id = $1;",
+        organization_id,
+        prices_include_tax
+    )
+    .execute(db)
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,319 @@
+use super::{
+    organization::{lock_organization, OrganizationStatus},
+    superuser_action::{record_superuser_action, SuperuserAction},
+    tenant::system,
+    ModelManager,
+};
+use crate::model::{Error, Result};
+use chrono::Utc;
+use futures::TryStreamExt;
+use sqlx::PgConnection;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+// Deleting an organization takes every row it owns, in one transaction. Its
+// rows are first written to an archive, one JSON Lines file per table, which
+// is kept in `ARCHIVE_DIR`; no archive, no deletion. Credentials are left
+// out of the archive. The audit trail keeps the organization's name and
+// points at the archive.
+
+/// How often the job looks for deletions that are due.
+const DELETION_JOB_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const DEFAULT_ARCHIVE_DIR: &str = "archives";
+
+/// Rows read ahead of the archive writer.
+const ARCHIVE_CHANNEL_CAPACITY: usize = 1024;
+
+/// Every table with rows of an organization, in an order they can be deleted
+/// in, and how to pick the rows of organization `$1` out of it as `t`.
+const TENANT_TABLES: &[(&str, &str)] = &[
+    (
+        "inventory_log_serial_numbers",
+        "t.inventory_log_id IN (SELECT id FROM inventory_logs WHERE organization_id = $1)",
+    ),
+    ("inventory_logs", "t.organization_id = $1"),
+    ("serial_numbers", "t.organization_id = $1"),
+    ("lots", "t.organization_id = $1"),
+    ("inventory_transactions", "t.organization_id = $1"),
+    ("invoice_sequences", "t.organization_id = $1"),
+    ("customers", "t.organization_id = $1"),
+    ("price_list_items", "t.organization_id = $1"),
+    ("price_lists", "t.organization_id = $1"),
+    ("exchange_rates", "t.organization_id = $1"),
+    ("product_barcodes", "t.organization_id = $1"),
+    (
+        "product_units",
+        "t.product_id IN (SELECT id FROM products WHERE organization_id = $1)",
+    ),
+    (
+        "product_variant_option_values",
+        "t.product_id IN (SELECT id FROM products WHERE organization_id = $1)",
+    ),
+    (
+        "product_option_values",
+        "t.product_option_id IN (
+            SELECT o.id FROM product_options o
+            JOIN products p ON p.id = o.product_id
+            WHERE p.organization_id = $1
+        )",
+    ),
+    (
+        "product_options",
+        "t.product_id IN (SELECT id FROM products WHERE organization_id = $1)",
+    ),
+    ("products", "t.organization_id = $1"),
+    ("categories", "t.organization_id = $1"),
+    ("tax_classes", "t.organization_id = $1"),
+    ("warehouses", "t.organization_id = $1"),
+    ("invitations", "t.organization_id = $1"),
+    ("api_key_calls", "t.organization_id = $1"),
+    ("login_attempts", "t.organization_id = $1"),
+    (
+        "api_key_permissions",
+        "t.api_key_id IN (SELECT id FROM api_keys WHERE organization_id = $1)",
+    ),
+    ("api_keys", "t.organization_id = $1"),
+    (
+        "password_reset_requests",
+        "t.user_id IN (SELECT id FROM users WHERE organization_id = $1)",
+    ),
+    (
+        "user_recovery_codes",
+        "t.user_id IN (SELECT id FROM users WHERE organization_id = $1)",
+    ),
+    (
+        "sessions",
+        "t.user_id IN (SELECT id FROM users WHERE organization_id = $1)",
+    ),
+    (
+        "login_lockouts",
+        "t.organization_name IN (SELECT name FROM organizations WHERE id = $1)",
+    ),
+    (
+        "user_permissions",
+        "t.user_id IN (SELECT id FROM users WHERE organization_id = $1)",
+    ),
+    ("users", "t.organization_id = $1"),
+    ("organizations", "t.id = $1"),
+];
+
+/// Tables of no use to the organization once it is gone.
+const UNARCHIVED_TABLES: &[&str] = &[
+    "login_lockouts",
+    "password_reset_requests",
+    "sessions",
+    "user_recovery_codes",
+];
+
+// region: Methods
+/// Runs forever, deleting organizations whose grace period is over.
+pub async fn run_deletion_job(mm: ModelManager) {
+    let mut interval = tokio::time::interval(DELETION_JOB_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = system(delete_due_organizations(&mm)).await {
+            println!("Organization deletion failed: {e:?}");
+        }
+    }
+}
+
+/// Deletes every organization pending deletion whose date has come.
+pub async fn delete_due_organizations(mm: &ModelManager) -> Result<()> {
+    let db = mm.db();
+
+    let due = sqlx::query!(
+        r#"SELECT id
+        FROM organizations
+        WHERE status = 'PENDING_DELETION'
+        AND deletion_due_at <= CURRENT_TIMESTAMP
+        ORDER BY deletion_due_at;"#
+    )
+    .fetch_all(db)
+    .await?;
+
+    for organization in due {
+        if let Some(archive) = delete_organization(mm, organization.id).await? {
+            println!(
+                "Deleted organization {}, archived to {}",
+                organization.id,
+                archive.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Archives and deletes the organization, unless it was reactivated or
+/// rescheduled meanwhile. Returns where the archive is.
+async fn delete_organization(mm: &ModelManager, organization_id: i64) -> Result<Option<PathBuf>> {
+    let db = mm.db();
+
+    let mut tx = db.begin().await?;
+
+    let state = lock_organization(&mut tx, organization_id).await?;
+    let due = state
+        .deletion_due_at
+        .is_some_and(|due_at| due_at <= Utc::now());
+    if state.status != OrganizationStatus::PendingDeletion || !due {
+        return Ok(None);
+    }
+
+    let archive = write_archive(&mut tx, organization_id).await?;
+
+    record_superuser_action(
+        &mut tx,
+        None,
+        organization_id,
+        SuperuserAction::DeleteOrganization,
+        None,
+        Some(&format!("Final export in {}", archive.display())),
+    )
+    .await?;
+
+    for (table, rows_of_organization) in TENANT_TABLES {
+        sqlx::query(&format!(
+            "DELETE FROM {table} t WHERE {rows_of_organization};"
+        ))
+        .bind(organization_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(archive))
+}
+
+/// What the archive writer is sent, in order.
+enum ArchiveEntry {
+    /// Starts the file of a table.
+    Table(&'static str),
+    Row(String),
+    /// All rows were sent; without it the archive is thrown away.
+    End,
+}
+
+/// Writes the organization's rows to a new archive, complete or not at all.
+/// The file is written on a blocking thread, fed the rows as they are read.
+async fn write_archive(conn: &mut PgConnection, organization_id: i64) -> Result<PathBuf> {
+    let organization = sqlx::query!(
+        "SELECT name FROM organizations WHERE id = $1;",
+        organization_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let dir = std::env::var("ARCHIVE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_ARCHIVE_DIR));
+    let file_name: String = format!(
+        "{}-{organization_id}-{}.zip",
+        organization.name,
+        Utc::now().format("%Y%m%dT%H%M%S")
+    )
+    .chars()
+    .map(|c| {
+        if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+            c
+        } else {
+            '_'
+        }
+    })
+    .collect();
+    let path = dir.join(file_name);
+
+    let (sender, receiver) = mpsc::channel(ARCHIVE_CHANNEL_CAPACITY);
+    let writer = tokio::task::spawn_blocking({
+        let path = path.clone();
+        move || write_archive_file(&path, receiver)
+    });
+
+    let sent = send_archive_entries(conn, organization_id, sender).await;
+    let written = writer.await.map_err(archive_error)?;
+    // A failed read leaves the archive incomplete, which the writer reports
+    // too; the read error says more. A failed write stops the sending early.
+    sent?;
+    written?;
+
+    Ok(path)
+}
+
+/// Reads the rows to archive and sends them to the writer, until it stops.
+async fn send_archive_entries(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    sender: mpsc::Sender<ArchiveEntry>,
+) -> Result<()> {
+    for (table, rows_of_organization) in TENANT_TABLES.iter().rev() {
+        if UNARCHIVED_TABLES.contains(table) {
+            continue;
+        }
+        if sender.send(ArchiveEntry::Table(table)).await.is_err() {
+            return Ok(());
+        }
+
+        let sql = format!(
+            "SELECT (to_jsonb(t) - 'password' - 'token_hash' - 'totp_secret')::TEXT
+            FROM {table} t
+            WHERE {rows_of_organization};"
+        );
+        let mut rows = sqlx::query_scalar::<_, String>(&sql)
+            .bind(organization_id)
+            .fetch(&mut *conn);
+        while let Some(row) = rows.try_next().await? {
+            if sender.send(ArchiveEntry::Row(row)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+    let _ = sender.send(ArchiveEntry::End).await;
+
+    Ok(())
+}
+
+/// Writes the entries received to `path`, through a partial file that is
+/// removed unless the archive is complete.
+fn write_archive_file(path: &Path, mut entries: mpsc::Receiver<ArchiveEntry>) -> Result<()> {
+    let partial = path.with_extension("zip.part");
+
+    let written = write_zip(&partial, &mut entries)
+        .and_then(|()| std::fs::rename(&partial, path).map_err(archive_error));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+
+    written
+}
+
+fn write_zip(partial: &Path, entries: &mut mpsc::Receiver<ArchiveEntry>) -> Result<()> {
+    if let Some(dir) = partial.parent() {
+        std::fs::create_dir_all(dir).map_err(archive_error)?;
+    }
+    let file = std::fs::File::create(partial).map_err(archive_error)?;
+    let mut zip = ZipWriter::new(file);
+    while let Some(entry) = entries.blocking_recv() {
+        match entry {
+            ArchiveEntry::Table(table) => zip
+                .start_file(format!("{table}.jsonl"), FileOptions::default())
+                .map_err(archive_error)?,
+            ArchiveEntry::Row(row) => writeln!(zip, "{row}").map_err(archive_error)?,
+            ArchiveEntry::End => {
+                zip.finish().map_err(archive_error)?;
+                return Ok(());
+            }
+        }
+    }
+
+    Err(archive_error("the archive was left incomplete"))
+}
+
+fn archive_error(error: impl std::fmt::Display) -> Error {
+    Error::Archive(error.to_string())
+}
+// endregion: Methods
@@ -0,0 +1,258 @@
+use super::{
+    login_attempt::clear_failed_logins,
+    session::{forget_user_sessions, revoke_sessions},
+    tenant::system,
+    user::MIN_PASSWORD_LENGTH,
+    ModelManager,
+};
+use crate::crypt::{self, hash_token, new_token};
+use crate::ctx::Ctx;
+use crate::mail::{link, Mail};
+use crate::model::{Error, Result};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use std::net::IpAddr;
+
+// Whoever asks for a reset gets the same answer, in about the same time,
+// whether the account exists or not: the link is mailed in the background and
+// every request is recorded the same way. Too many requests for one account
+// are dropped silently; too many from one address are refused, which says
+// nothing about any account. Nobody is logged in yet, so all of this looks
+// across organizations.
+
+const RESET_LIFETIME_MINUTES: i64 = 30;
+const REQUESTS_PER_ACCOUNT_PER_HOUR: i64 = 3;
+const REQUESTS_PER_IP_PER_HOUR: i64 = 10;
+
+// region: Structs
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetForRequest {
+    /// Empty for a superuser.
+    #[serde(default)]
+    pub organization: String,
+    /// Username or email.
+    pub login: String,
+}
+
+/// What the holder of a reset link sees.
+#[derive(Debug)]
+pub struct PasswordResetForAccept {
+    pub username: String,
+}
+// endregion: Structs
+
+// region: Methods
+/// Mails a reset link if the account exists, is active and has an email.
+/// Fails only when `ip` asked too often.
+pub async fn request_password_reset(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    request: PasswordResetForRequest,
+    ip: IpAddr,
+) -> Result<()> {
+    system(async {
+        let db = mm.db();
+        let ip = ip.to_string();
+        let mut tx = db.begin().await?;
+
+        // Requests from the same address, then for the same account, wait
+        // for each other here, so concurrent ones cannot all count the same
+        // earlier requests and slip past the limits. Each count runs after
+        // its lock so it sees what the previous holder recorded.
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtextextended('password_reset_ip:' || $1, 0));",
+            ip
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let ip_requests = sqlx::query!(
+            r#"SELECT COUNT(*) as "count!"
+            FROM password_reset_requests
+            WHERE ip = $1
+            AND created_at > CURRENT_TIMESTAMP - INTERVAL '1 hour';"#,
+            ip
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .count;
+        if ip_requests >= REQUESTS_PER_IP_PER_HOUR {
+            return Err(Error::TooManyRequests);
+        }
+
+        let user = sqlx::query!(
+            r#"SELECT u.id, u.email
+            FROM users u
+            LEFT JOIN organizations o
+            ON o.id = u.organization_id
+            WHERE (u.username = $2 OR LOWER(u.email) = LOWER($2))
+            AND u.active
+            AND (o.name = $1 OR ($1 = '' AND u.organization_id IS NULL))
+            ORDER BY u.username = $2 DESC
+            LIMIT 1
+            FOR UPDATE OF u;"#,
+            request.organization.trim(),
+            request.login.trim()
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let delivery = match &user {
+            Some(user) => {
+                let recent_requests = sqlx::query!(
+                    r#"SELECT COUNT(*) as "count!"
+                    FROM password_reset_requests
+                    WHERE user_id = $1
+                    AND created_at > CURRENT_TIMESTAMP - INTERVAL '1 hour';"#,
+                    user.id
+                )
+                .fetch_one(&mut *tx)
+                .await?
+                .count;
+
+                match &user.email {
+                    Some(email) if recent_requests < REQUESTS_PER_ACCOUNT_PER_HOUR => {
+                        Some((email.clone(), new_token()))
+                    }
+                    _ => None,
+                }
+            }
+            None => None,
+        };
+
+        let expires_at = Utc::now() + Duration::minutes(RESET_LIFETIME_MINUTES);
+        sqlx::query!(
+            r#"INSERT INTO password_reset_requests (user_id, ip, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4);"#,
+            user.as_ref().map(|user| user.id),
+            ip,
+            delivery.as_ref().map(|(_, token)| hash_token(token)),
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        if let Some((email, token)) = delivery {
+            let mail = Mail {
+                to: email,
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Someone asked to reset the password of your Distrupify account.\n\n\
+                    Choose a new password here:\n{}\n\n\
+                    The link works once and expires in {RESET_LIFETIME_MINUTES} minutes. \
+                    If it was not you, ignore this mail; your password stays as it is.",
+                    link(&format!("/reset-password/{token}"))
+                ),
+            };
+            let mailer = mm.mailer();
+            tokio::spawn(async move {
+                if let Err(e) = mailer.send(mail).await {
+                    println!("Password reset mail failed: {e}");
+                }
+            });
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// The reset of `token`, if it can still be used.
+pub async fn get_password_reset(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    token: &str,
+) -> Result<PasswordResetForAccept> {
+    system(async {
+        let db = mm.db();
+
+        sqlx::query_as!(
+            PasswordResetForAccept,
+            r#"SELECT u.username
+            FROM password_reset_requests r
+            JOIN users u
+            ON u.id = r.user_id
+            WHERE r.token_hash = $1
+            AND r.used_at IS NULL
+            AND r.expires_at > CURRENT_TIMESTAMP
+            AND u.active;"#,
+            hash_token(token)
+        )
+        .fetch_optional(db)
+        .await?
+        .ok_or(Error::PasswordResetNotFound)
+    })
+    .await
+}
+
+/// Sets the new password, uses up every pending link of the account and ends
+/// its sessions.
+pub async fn complete_password_reset(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    token: &str,
+    password: &str,
+) -> Result<()> {
+    system(async {
+        let db = mm.db();
+
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(Error::InvalidPassword);
+        }
+        let password = crypt::hash_value(password)?;
+
+        let mut tx = db.begin().await?;
+
+        let reset = sqlx::query!(
+            r#"SELECT
+                r.user_id as "user_id!",
+                u.username,
+                COALESCE(o.name, '') as "organization_name!"
+            FROM password_reset_requests r
+            JOIN users u
+            ON u.id = r.user_id
+            LEFT JOIN organizations o
+            ON o.id = u.organization_id
+            WHERE r.token_hash = $1
+            AND r.used_at IS NULL
+            AND r.expires_at > CURRENT_TIMESTAMP
+            AND u.active
+            FOR UPDATE OF r;"#,
+            hash_token(token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::PasswordResetNotFound)?;
+
+        sqlx::query!(
+            "UPDATE users SET password = $1 WHERE id = $2;",
+            password,
+            reset.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"UPDATE password_reset_requests
+            SET used_at = CURRENT_TIMESTAMP
+            WHERE user_id = $1
+            AND used_at IS NULL;"#,
+            reset.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        revoke_sessions(&mut tx, reset.user_id).await?;
+        // Whoever has the link may log in with the new password right away.
+        clear_failed_logins(&mut tx, &reset.organization_name, &reset.username).await?;
+
+        tx.commit().await?;
+        forget_user_sessions(mm, reset.user_id);
+
+        Ok(())
+    })
+    .await
+}
+// endregion: Methods
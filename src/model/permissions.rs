@@ -1,15 +1,60 @@
-use super::ModelManager;
 use crate::{
     ctx::Ctx,
     model::{Error, Result},
 };
 
 // region: Enums
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Permissions {
-    None,
     SuperUser = 1,
     OrganizationAll = 2,
+    /// Sell at another price than the customer's price list gives.
+    OverridePrice = 3,
+    // The scopes of API keys. Users are not limited by them.
+    ReadProducts = 4,
+    ReadInventory = 5,
+    WriteSales = 6,
+    WriteDeposits = 7,
+    ReadReports = 8,
+}
+
+/// What an API key may be granted, in the order they are offered.
+pub const API_SCOPES: &[Permissions] = &[
+    Permissions::ReadProducts,
+    Permissions::ReadInventory,
+    Permissions::WriteSales,
+    Permissions::WriteDeposits,
+    Permissions::ReadReports,
+    Permissions::OverridePrice,
+];
+
+impl Permissions {
+    /// As in the permissions table.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permissions::SuperUser => "superuser",
+            Permissions::OrganizationAll => "organization:*",
+            Permissions::OverridePrice => "sales:override_price",
+            Permissions::ReadProducts => "products:read",
+            Permissions::ReadInventory => "inventory:read",
+            Permissions::WriteSales => "sales:write",
+            Permissions::WriteDeposits => "deposits:write",
+            Permissions::ReadReports => "reports:read",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Permissions::SuperUser => "Manage every organization",
+            Permissions::OrganizationAll => "Manage the organization",
+            Permissions::OverridePrice => "Sell at another price than the price list gives",
+            Permissions::ReadProducts => "Look up products, barcodes and prices",
+            Permissions::ReadInventory => "Read stock movements and transactions",
+            Permissions::WriteSales => "Record sales",
+            Permissions::WriteDeposits => "Record deposits",
+            Permissions::ReadReports => "Read valuation, COGS and tax reports",
+        }
+    }
 }
 
 impl TryFrom<i64> for Permissions {
@@ -19,6 +64,12 @@ impl TryFrom<i64> for Permissions {
         match v {
             x if x == Permissions::SuperUser as i64 => Ok(Permissions::SuperUser),
             x if x == Permissions::OrganizationAll as i64 => Ok(Permissions::OrganizationAll),
+            x if x == Permissions::OverridePrice as i64 => Ok(Permissions::OverridePrice),
+            x if x == Permissions::ReadProducts as i64 => Ok(Permissions::ReadProducts),
+            x if x == Permissions::ReadInventory as i64 => Ok(Permissions::ReadInventory),
+            x if x == Permissions::WriteSales as i64 => Ok(Permissions::WriteSales),
+            x if x == Permissions::WriteDeposits as i64 => Ok(Permissions::WriteDeposits),
+            x if x == Permissions::ReadReports as i64 => Ok(Permissions::ReadReports),
             _ => Err(()),
         }
     }
@@ -26,17 +77,15 @@ impl TryFrom<i64> for Permissions {
 // endregion: Enums
 
 // region: Methods
-pub async fn has_permission(
-    ctx: &Ctx,
-    mm: &ModelManager,
-    permission: Permissions,
-    user_id: i64,
-) -> Result<()> {
-    let permissions = get_permissions_by_user_id(ctx, mm, user_id).await?;
-
-    let valid = permissions
-        .iter()
-        .any(|p| *p == permission || *p == Permissions::SuperUser);
+/// Whether the current user holds `permission`, going by the permissions the
+/// auth layer loaded into `ctx`.
+pub fn has_permission(ctx: &Ctx, permission: Permissions) -> Result<()> {
+    // `organization:*` grants every permission within the organization.
+    let valid = ctx.permissions().iter().any(|p| {
+        *p == permission
+            || *p == Permissions::SuperUser
+            || (*p == Permissions::OrganizationAll && permission != Permissions::SuperUser)
+    });
     if !valid {
         return Err(Error::Unauhtorized("Invalid permission".to_string()));
     }
@@ -44,31 +93,12 @@ pub async fn has_permission(
     Ok(())
 }
 
-async fn get_permissions_by_user_id(
-    _ctx: &Ctx,
-    mm: &ModelManager,
-    id: i64,
-) -> Result<Vec<Permissions>> {
-    let db = mm.db();
-
-    let permissions: Vec<Permissions> = sqlx::query!(
-        r#"SELECT p.id FROM user_permissions up
-           INNER JOIN users u ON u.id = up.user_id
-           INNER JOIN permissions p ON p.id = up.permission_id
-           WHERE up.user_id = $1
-           GROUP BY up.user_id, p.id;"#,
-        id
-    )
-    .fetch_all(db)
-    .await?
-    .into_iter()
-    .map(|p| p.id)
-    .map(|p| match p.try_into() {
-        Err(_) => Permissions::None,
-        Ok(p) => p,
-    })
-    .collect();
-
-    Ok(permissions)
+/// Whether the current API key was granted `scope`. Users are only limited
+/// by `has_permission`.
+pub fn has_scope(ctx: &Ctx, scope: Permissions) -> Result<()> {
+    match ctx.api_key_id() {
+        Some(_) => has_permission(ctx, scope),
+        None => Ok(()),
+    }
 }
 // endregion: Methods
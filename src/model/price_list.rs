@@ -0,0 +1,533 @@
+use super::{
+    barcode::get_product_by_barcode,
+    customer::customer_price_list,
+    inventory_log::InventoryLogForCreate,
+    organization::{base_currency, prices_include_tax},
+    permissions::{has_permission, Permissions},
+    unit::{base_quantity, conversion_factor},
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::money::{Currency, Money};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, NoneAsEmptyString};
+use sqlx::types::BigDecimal;
+use sqlx::PgConnection;
+
+// A customer pays the price of their price list's item for the product with
+// the highest minimum quantity they reach, among the items valid on the day;
+// without one, the product price. Price lists are in the base currency.
+
+// region: Structs
+#[derive(Debug)]
+pub struct PriceList {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    /// Whether the list's prices include tax.
+    pub tax_inclusive: bool,
+    pub item_count: i64,
+    pub customer_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceListForCreate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tax_inclusive: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceListForUpdate {
+    #[serde(default)]
+    pub tax_inclusive: bool,
+}
+
+struct PriceListItemForDbResult {
+    id: i64,
+    sku: String,
+    product_display_name: String,
+    min_quantity: i64,
+    price: BigDecimal,
+    currency: Currency,
+    valid_from: Option<NaiveDate>,
+    valid_to: Option<NaiveDate>,
+}
+
+#[derive(Debug)]
+pub struct PriceListItem {
+    pub id: i64,
+    pub sku: String,
+    pub product_display_name: String,
+    /// In the product's base unit.
+    pub min_quantity: i64,
+    /// Per base unit.
+    pub price: Money,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_to: Option<NaiveDate>,
+}
+
+impl From<PriceListItemForDbResult> for PriceListItem {
+    fn from(value: PriceListItemForDbResult) -> Self {
+        Self {
+            id: value.id,
+            sku: value.sku,
+            product_display_name: value.product_display_name,
+            min_quantity: value.min_quantity,
+            price: Money::new(value.price, value.currency),
+            valid_from: value.valid_from,
+            valid_to: value.valid_to,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct PriceListItemForCreate {
+    /// Barcode or SKU of the product.
+    pub code: String,
+    pub min_quantity: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub price: BigDecimal,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub valid_from: Option<NaiveDate>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub valid_to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PriceSource {
+    PriceList {
+        price_list_id: i64,
+        price_list_name: String,
+        min_quantity: i64,
+    },
+    Product,
+}
+
+#[derive(Debug, Clone)]
+pub struct EffectivePrice {
+    pub price: Money,
+    pub source: PriceSource,
+    /// Whether `price` includes tax, per the price list or the organization.
+    pub tax_inclusive: bool,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct EffectivePriceForSearch {
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub customer_id: Option<i64>,
+    pub product_id: i64,
+    /// In `unit`.
+    pub quantity: i64,
+    /// Defaults to the product's base unit.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Defaults to today.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+}
+// endregion: Structs
+
+// region: Methods
+pub async fn get_price_lists(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<PriceList>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let price_lists = sqlx::query_as!(
+        PriceList,
+        r#"SELECT
+            pl.id,
+            pl.name,
+            pl.description,
+            pl.tax_inclusive,
+            (SELECT COUNT(*) FROM price_list_items pli WHERE pli.price_list_id = pl.id) as "item_count!",
+            (SELECT COUNT(*) FROM customers c WHERE c.price_list_id = pl.id) as "customer_count!"
+        FROM price_lists pl
+        WHERE pl.organization_id = $1
+        ORDER BY pl.name;"#,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(price_lists)
+}
+
+pub async fn get_price_list(ctx: &Ctx, mm: &ModelManager, price_list_id: i64) -> Result<PriceList> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query_as!(
+        PriceList,
+        r#"SELECT
+            pl.id,
+            pl.name,
+            pl.description,
+            pl.tax_inclusive,
+            (SELECT COUNT(*) FROM price_list_items pli WHERE pli.price_list_id = pl.id) as "item_count!",
+            (SELECT COUNT(*) FROM customers c WHERE c.price_list_id = pl.id) as "customer_count!"
+        FROM price_lists pl
+        WHERE pl.id = $1
+        AND pl.organization_id = $2;"#,
+        price_list_id,
+        organization_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(Error::PriceListNotFound { price_list_id })
+}
+
+pub async fn create_price_list(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    price_list_for_create: PriceListForCreate,
+) -> Result<i64> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let name = price_list_for_create.name.trim();
+    if name.is_empty() {
+        return Err(Error::InvalidPriceListName {
+            name: name.to_string(),
+        });
+    }
+
+    sqlx::query!(
+        r#"INSERT INTO price_lists (name, description, tax_inclusive, organization_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (name, organization_id) DO NOTHING
+        RETURNING id;"#,
+        name,
+        price_list_for_create.description.trim(),
+        price_list_for_create.tax_inclusive,
+        organization_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|r| r.id)
+    .ok_or(Error::InvalidPriceListName {
+        name: name.to_string(),
+    })
+}
+
+/// Sets whether the list's prices include tax, for sales from now on.
+pub async fn update_price_list(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    price_list_id: i64,
+    price_list_for_update: PriceListForUpdate,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let result = sqlx::query!(
+        r#"UPDATE price_lists
+        SET tax_inclusive = $3
+        WHERE id = $1
+        AND organization_id = $2;"#,
+        price_list_id,
+        organization_id,
+        price_list_for_update.tax_inclusive
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::PriceListNotFound { price_list_id });
+    }
+
+    Ok(())
+}
+
+/// Customers on the list go back to paying the product price.
+pub async fn delete_price_list(ctx: &Ctx, mm: &ModelManager, price_list_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query!(
+        "DELETE FROM price_lists WHERE id = $1 AND organization_id = $2;",
+        price_list_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_price_list_items(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    price_list_id: i64,
+) -> Result<Vec<PriceListItem>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let items = sqlx::query_as!(
+        PriceListItemForDbResult,
+        r#"SELECT
+            pli.id,
+            p.sku,
+            p.display_name as product_display_name,
+            pli.min_quantity,
+            pli.price,
+            o.base_currency as "currency: Currency",
+            pli.valid_from,
+            pli.valid_to
+        FROM price_list_items pli
+        JOIN products p
+        ON p.id = pli.product_id
+        JOIN organizations o
+        ON o.id = pli.organization_id
+        WHERE pli.price_list_id = $1
+        AND pli.organization_id = $2
+        ORDER BY p.display_name, pli.min_quantity, pli.valid_from NULLS FIRST;"#,
+        price_list_id,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(items.into_iter().map(|i| i.into()).collect())
+}
+
+pub async fn create_price_list_item(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    price_list_id: i64,
+    price_list_item_for_create: PriceListItemForCreate,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let PriceListItemForCreate {
+        code,
+        min_quantity,
+        price,
+        valid_from,
+        valid_to,
+    } = price_list_item_for_create;
+
+    let product = get_product_by_barcode(ctx, mm, &code).await?;
+    let valid_range = match (valid_from, valid_to) {
+        (Some(from), Some(to)) => from <= to,
+        _ => true,
+    };
+    if min_quantity < 1 || price < BigDecimal::from(0) || !valid_range {
+        return Err(Error::InvalidPriceListItem {
+            product_id: product.id,
+        });
+    }
+
+    let result = sqlx::query!(
+        r#"INSERT INTO price_list_items (price_list_id, product_id, min_quantity, price, valid_from, valid_to, organization_id)
+        SELECT id, $2, $3, $4, $5, $6, organization_id FROM price_lists
+        WHERE id = $1
+        AND organization_id = $7;"#,
+        price_list_id,
+        product.id,
+        min_quantity,
+        price,
+        valid_from,
+        valid_to,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::PriceListNotFound { price_list_id });
+    }
+
+    Ok(())
+}
+
+pub async fn delete_price_list_item(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    price_list_id: i64,
+    item_id: i64,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query!(
+        r#"DELETE FROM price_list_items
+        WHERE id = $1
+        AND price_list_id = $2
+        AND organization_id = $3;"#,
+        item_id,
+        price_list_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// The price per `unit` a customer pays for `quantity` of them.
+pub async fn get_effective_price(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    effective_price_for_search: EffectivePriceForSearch,
+) -> Result<EffectivePrice> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let EffectivePriceForSearch {
+        customer_id,
+        product_id,
+        quantity,
+        unit,
+        date,
+    } = effective_price_for_search;
+
+    let mut conn = db.acquire().await?;
+    let conversion_factor = match unit.as_deref().map(str::trim) {
+        None | Some("") => 1,
+        Some(unit) => conversion_factor(&mut conn, organization_id, product_id, unit).await?,
+    };
+    let date = date.unwrap_or_else(|| chrono::Utc::now().date_naive());
+
+    let effective = effective_price(
+        &mut conn,
+        organization_id,
+        customer_id,
+        product_id,
+        base_quantity(product_id, quantity, conversion_factor)?,
+        date,
+    )
+    .await?;
+
+    Ok(EffectivePrice {
+        price: effective.price.times(conversion_factor),
+        ..effective
+    })
+}
+
+/// Whether the user may sell at another price than the effective one.
+pub async fn can_override_price(ctx: &Ctx, mm: &ModelManager) -> Result<bool> {
+    get_user_ids(ctx, mm).await?;
+
+    match has_permission(ctx, Permissions::OverridePrice) {
+        Ok(()) => Ok(true),
+        Err(Error::Unauhtorized(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// The price per base unit for `quantity` base units.
+pub(in crate::model) async fn effective_price(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    customer_id: Option<i64>,
+    product_id: i64,
+    quantity: i64,
+    date: NaiveDate,
+) -> Result<EffectivePrice> {
+    let currency = base_currency(&mut *conn, organization_id).await?;
+
+    let price_list_id = match customer_id {
+        Some(customer_id) => customer_price_list(&mut *conn, organization_id, customer_id).await?,
+        None => None,
+    };
+
+    if let Some(price_list_id) = price_list_id {
+        let item = sqlx::query!(
+            r#"SELECT pli.price, pli.min_quantity, pl.name, pl.tax_inclusive
+            FROM price_list_items pli
+            JOIN price_lists pl
+            ON pl.id = pli.price_list_id
+            WHERE pli.price_list_id = $1
+            AND pli.product_id = $2
+            AND pli.min_quantity <= $3
+            AND (pli.valid_from IS NULL OR pli.valid_from <= $4)
+            AND (pli.valid_to IS NULL OR pli.valid_to >= $4)
+            ORDER BY pli.min_quantity DESC, pli.valid_from DESC NULLS LAST, pli.id DESC
+            LIMIT 1;"#,
+            price_list_id,
+            product_id,
+            quantity,
+            date
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(item) = item {
+            return Ok(EffectivePrice {
+                price: Money::new(item.price, currency),
+                source: PriceSource::PriceList {
+                    price_list_id,
+                    price_list_name: item.name,
+                    min_quantity: item.min_quantity,
+                },
+                tax_inclusive: item.tax_inclusive,
+            });
+        }
+    }
+
+    let product = sqlx::query!(
+        "SELECT price FROM products WHERE id = $1 AND organization_id = $2;",
+        product_id,
+        organization_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::ProductNotFound { product_id })?;
+
+    Ok(EffectivePrice {
+        price: Money::new(product.price, currency),
+        source: PriceSource::Product,
+        tax_inclusive: prices_include_tax(&mut *conn, organization_id).await?,
+    })
+}
+
+/// Fails on the first sold line whose price, in the base currency, is not the
+/// customer's effective price. Logs must be in base units.
+pub(in crate::model) async fn check_prices(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    customer_id: Option<i64>,
+    logs: &[InventoryLogForCreate],
+    exchange_rate: &BigDecimal,
+    date: NaiveDate,
+) -> Result<()> {
+    for log in logs {
+        let effective = effective_price(
+            &mut *conn,
+            organization_id,
+            customer_id,
+            log.product_id,
+            log.quantity,
+            date,
+        )
+        .await?;
+        let price = log
+            .price
+            .exchange(exchange_rate, effective.price.currency());
+
+        if price != effective.price {
+            return Err(Error::PriceOverrideNotAllowed {
+                product_id: log.product_id,
+                price,
+                list_price: effective.price,
+            });
+        }
+    }
+
+    Ok(())
+}
+// endregion: Methods
@@ -0,0 +1,300 @@
+use super::{
+    category::{get_category_tree, CategoryNode},
+    organization::get_base_currency,
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::money::{Currency, Locale, Money};
+use sqlx::types::BigDecimal;
+use std::collections::{HashMap, HashSet};
+
+// region: Structs
+/// One data row of an uploaded file, already mapped to product fields.
+#[derive(Debug, Default)]
+pub struct ProductImportRow {
+    /// Line number in the uploaded file, for the report.
+    pub line: usize,
+    pub sku: String,
+    pub brand: String,
+    pub name: String,
+    pub description: String,
+    pub price: String,
+    /// Category name, or a path such as `Electronics > Phones`.
+    pub category: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProductImportAction {
+    Create,
+    Update,
+}
+
+#[derive(Debug)]
+pub struct ProductImportRowResult {
+    pub line: usize,
+    pub sku: String,
+    pub action: ProductImportAction,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ProductImportReport {
+    pub rows: Vec<ProductImportRowResult>,
+}
+
+impl ProductImportReport {
+    pub fn error_count(&self) -> usize {
+        self.rows.iter().filter(|r| !r.errors.is_empty()).count()
+    }
+
+    pub fn create_count(&self) -> usize {
+        self.count(ProductImportAction::Create)
+    }
+
+    pub fn update_count(&self) -> usize {
+        self.count(ProductImportAction::Update)
+    }
+
+    fn count(&self, action: ProductImportAction) -> usize {
+        self.rows
+            .iter()
+            .filter(|r| r.errors.is_empty() && r.action == action)
+            .count()
+    }
+}
+
+struct ValidatedRow {
+    sku: String,
+    brand: String,
+    name: String,
+    description: String,
+    price: Money,
+    category_id: Option<i64>,
+}
+// endregion: Structs
+
+// region: Methods
+/// Validates every row without writing anything.
+pub async fn dry_run_product_import(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    rows: Vec<ProductImportRow>,
+    locale: Locale,
+) -> Result<ProductImportReport> {
+    let (report, _) = validate_rows(ctx, mm, rows, locale).await?;
+
+    Ok(report)
+}
+
+/// Creates or updates (by SKU) every row in one transaction. Nothing is
+/// written unless every row is valid. Prices are read in `locale`.
+pub async fn apply_product_import(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    rows: Vec<ProductImportRow>,
+    locale: Locale,
+) -> Result<ProductImportReport> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let (report, rows) = validate_rows(ctx, mm, rows, locale).await?;
+    if report.error_count() > 0 {
+        return Err(Error::ProductImportInvalid {
+            error_count: report.error_count(),
+        });
+    }
+
+    let mut tx = db.begin().await?;
+
+    let skus: Vec<String> = rows.iter().map(|r| r.sku.clone()).collect();
+    let brands: Vec<String> = rows.iter().map(|r| r.brand.clone()).collect();
+    let names: Vec<String> = rows.iter().map(|r| r.name.clone()).collect();
+    let descriptions: Vec<String> = rows.iter().map(|r| r.description.clone()).collect();
+    let display_names: Vec<String> = rows
+        .iter()
+        .map(|r| format!("{} {} {}", r.brand, r.name, r.description))
+        .collect();
+    let prices: Vec<BigDecimal> = rows.iter().map(|r| r.price.amount().clone()).collect();
+    let category_ids: Vec<Option<i64>> = rows.iter().map(|r| r.category_id).collect();
+
+    sqlx::query!(
+        r#"INSERT INTO products
+        (sku, brand, name, description, display_name, price, category_id, organization_id)
+        SELECT sku, brand, name, description, display_name, price, category_id, $8
+        FROM UNNEST($1::varchar[], $2::varchar[], $3::varchar[], $4::varchar[], $5::text[], $6::numeric[], $7::int8[])
+            as t(sku, brand, name, description, display_name, price, category_id)
+        ON CONFLICT (sku, organization_id) DO UPDATE
+        SET
+            brand = EXCLUDED.brand,
+            name = EXCLUDED.name,
+            description = EXCLUDED.description,
+            display_name = EXCLUDED.display_name,
+            price = EXCLUDED.price,
+            category_id = EXCLUDED.category_id;"#,
+        &skus,
+        &brands,
+        &names,
+        &descriptions,
+        &display_names,
+        &prices,
+        &category_ids as &[Option<i64>],
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+async fn validate_rows(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    rows: Vec<ProductImportRow>,
+    locale: Locale,
+) -> Result<(ProductImportReport, Vec<ValidatedRow>)> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let skus: Vec<String> = rows.iter().map(|r| r.sku.trim().to_string()).collect();
+    let existing_skus: HashSet<String> = sqlx::query!(
+        "SELECT sku FROM products WHERE organization_id = $1 AND sku = ANY($2);",
+        organization_id,
+        &skus
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|p| p.sku)
+    .collect();
+
+    let categories = CategoryLookup::new(get_category_tree(ctx, mm).await?);
+    let base_currency = get_base_currency(ctx, mm).await?;
+
+    let mut first_seen: HashMap<String, usize> = HashMap::new();
+    let mut results = Vec::with_capacity(rows.len());
+    let mut validated = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let mut errors = Vec::new();
+        let sku = row.sku.trim().to_string();
+        let name = row.name.trim().to_string();
+
+        if sku.is_empty() {
+            errors.push("SKU is required".to_string());
+        } else if let Some(line) = first_seen.get(&sku) {
+            errors.push(format!("Duplicate SKU, already on line {line}"));
+        } else {
+            first_seen.insert(sku.clone(), row.line);
+        }
+
+        if name.is_empty() {
+            errors.push("Name is required".to_string());
+        }
+
+        let price = parse_price(&row.price, base_currency, locale);
+        if price.is_none() {
+            errors.push(format!("Invalid price \"{}\"", row.price.trim()));
+        }
+
+        let category_id = match categories.resolve(&row.category) {
+            Ok(category_id) => category_id,
+            Err(error) => {
+                errors.push(error);
+                None
+            }
+        };
+
+        let action = match existing_skus.contains(&sku) {
+            true => ProductImportAction::Update,
+            false => ProductImportAction::Create,
+        };
+
+        if let (true, Some(price)) = (errors.is_empty(), price) {
+            validated.push(ValidatedRow {
+                sku: sku.clone(),
+                brand: row.brand.trim().to_string(),
+                name,
+                description: row.description.trim().to_string(),
+                price,
+                category_id,
+            });
+        }
+
+        results.push(ProductImportRowResult {
+            line: row.line,
+            sku,
+            action,
+            errors,
+        });
+    }
+
+    Ok((ProductImportReport { rows: results }, validated))
+}
+
+/// Reads a non-negative price as written in `locale`.
+pub(in crate::model) fn parse_price(
+    price: &str,
+    currency: Currency,
+    locale: Locale,
+) -> Option<Money> {
+    Money::parse(price, currency, locale)
+        .ok()
+        .filter(|p| !p.is_negative())
+}
+
+/// Resolves category names and `Parent > Child` paths, case-insensitively.
+struct CategoryLookup {
+    by_path: HashMap<String, i64>,
+    by_name: HashMap<String, Vec<i64>>,
+}
+
+impl CategoryLookup {
+    fn new(tree: Vec<CategoryNode>) -> Self {
+        let mut paths: HashMap<i64, String> = HashMap::new();
+        let mut by_path = HashMap::new();
+        let mut by_name: HashMap<String, Vec<i64>> = HashMap::new();
+
+        // The tree is ordered depth-first, so a parent's path is always known.
+        for node in tree {
+            let name = node.name.trim().to_lowercase();
+            let path = match node.parent_id.and_then(|id| paths.get(&id)) {
+                Some(parent_path) => format!("{parent_path} > {name}"),
+                None => name.clone(),
+            };
+            by_path.insert(path.clone(), node.id);
+            by_name.entry(name).or_default().push(node.id);
+            paths.insert(node.id, path);
+        }
+
+        Self { by_path, by_name }
+    }
+
+    fn resolve(&self, category: &str) -> core::result::Result<Option<i64>, String> {
+        let key = category
+            .split('>')
+            .map(|part| part.trim().to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" > ");
+        if key.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(id) = self.by_path.get(&key) {
+            return Ok(Some(*id));
+        }
+
+        match self.by_name.get(&key).map(Vec::as_slice) {
+            Some([id]) => Ok(Some(*id)),
+            Some(_) => Err(format!(
+                "Category \"{}\" is ambiguous, use its full path",
+                category.trim()
+            )),
+            None => Err(format!("Unknown category \"{}\"", category.trim())),
+        }
+    }
+}
+// endregion: Methods
@@ -0,0 +1,281 @@
+use super::{
+    user::get_user_ids,
+    warehouse::{get_all_warehouses, Warehouse},
+    ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use std::collections::HashMap;
+
+// region: Structs
+#[derive(Debug)]
+pub struct ProductOptionForCreate {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct VariantStockLevel {
+    pub sku: String,
+    pub option_values: Vec<String>,
+    /// Quantities aligned with `VariantMatrix::warehouses`.
+    pub quantities: Vec<i64>,
+    pub total: i64,
+}
+
+#[derive(Debug)]
+pub struct VariantMatrix {
+    pub product_id: i64,
+    pub sku: String,
+    pub display_name: String,
+    pub option_names: Vec<String>,
+    pub warehouses: Vec<Warehouse>,
+    pub variants: Vec<VariantStockLevel>,
+}
+// endregion: Structs
+
+// region: Create
+/// Adds option axes (e.g. size and colour) to a parent product and creates one
+/// child product per combination of option values.
+pub async fn create_variants(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    parent_id: i64,
+    options: Vec<ProductOptionForCreate>,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let options: Vec<ProductOptionForCreate> = options
+        .into_iter()
+        .map(|o| ProductOptionForCreate {
+            name: o.name.trim().to_string(),
+            values: o
+                .values
+                .iter()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect(),
+        })
+        .filter(|o| !o.name.is_empty() && !o.values.is_empty())
+        .collect();
+    if options.is_empty() {
+        return Err(Error::NoVariantOptions);
+    }
+
+    let mut tx = db.begin().await?;
+
+    let parent = sqlx::query!(
+        r#"SELECT sku, brand, name, price, parent_id, base_unit,
+            (SELECT COUNT(*) FROM product_options WHERE product_id = $1) as "option_count!"
+        FROM products
+        WHERE id = $1
+        AND organization_id = $2;"#,
+        parent_id,
+        organization_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::ProductNotFound {
+        product_id: parent_id,
+    })?;
+
+    if parent.parent_id.is_some() {
+        return Err(Error::InvalidVariantParent {
+            product_id: parent_id,
+        });
+    }
+    if parent.option_count > 0 {
+        return Err(Error::VariantsAlreadyExist {
+            product_id: parent_id,
+        });
+    }
+
+    // Every option value as (id, value), grouped per option axis.
+    let mut axes: Vec<Vec<(i64, String)>> = Vec::new();
+    for (position, option) in options.into_iter().enumerate() {
+        let product_option = sqlx::query!(
+            r#"INSERT INTO product_options (product_id, name, position)
+            VALUES ($1, $2, $3)
+            RETURNING id;"#,
+            parent_id,
+            option.name,
+            position as i32
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let mut axis = Vec::new();
+        for (position, value) in option.values.into_iter().enumerate() {
+            let option_value = sqlx::query!(
+                r#"INSERT INTO product_option_values (product_option_id, value, position)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (product_option_id, value) DO NOTHING
+                RETURNING id;"#,
+                product_option.id,
+                value,
+                position as i32
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some(option_value) = option_value {
+                axis.push((option_value.id, value));
+            }
+        }
+        axes.push(axis);
+    }
+
+    let combinations = axes.iter().fold(vec![Vec::new()], |acc, axis| {
+        acc.iter()
+            .flat_map(|combination: &Vec<&(i64, String)>| {
+                axis.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push(value);
+                    combination
+                })
+            })
+            .collect()
+    });
+
+    for combination in combinations {
+        let values: Vec<&str> = combination.iter().map(|(_, v)| v.as_str()).collect();
+        let sku = format!("{}-{}", parent.sku, values.join("-").replace(' ', "-"));
+        let description = values.join(" / ");
+
+        let variant = sqlx::query!(
+            r#"INSERT INTO products
+            (sku, brand, name, description, display_name, price, base_unit, parent_id, organization_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id;"#,
+            sku,
+            parent.brand,
+            parent.name,
+            description,
+            format!("{} {} {}", parent.brand, parent.name, description),
+            parent.price,
+            parent.base_unit,
+            parent_id,
+            organization_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let option_value_ids: Vec<i64> = combination.iter().map(|(id, _)| *id).collect();
+        sqlx::query!(
+            r#"INSERT INTO product_variant_option_values (product_id, product_option_value_id)
+            SELECT $1, * FROM UNNEST($2::int8[]);"#,
+            variant.id,
+            &option_value_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+// endregion: Create
+
+// region: Read
+pub async fn get_variant_matrix(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    parent_id: i64,
+) -> Result<VariantMatrix> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let parent = sqlx::query!(
+        "SELECT sku, display_name FROM products WHERE id = $1 AND organization_id = $2;",
+        parent_id,
+        organization_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(Error::ProductNotFound {
+        product_id: parent_id,
+    })?;
+
+    let option_names: Vec<String> = sqlx::query!(
+        "SELECT name FROM product_options WHERE product_id = $1 ORDER BY position;",
+        parent_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|o| o.name)
+    .collect();
+
+    let variants = sqlx::query!(
+        r#"SELECT
+            p.id,
+            p.sku,
+            ARRAY_AGG(pov.value ORDER BY po.position) as "option_values!"
+        FROM products p
+        JOIN product_variant_option_values pvov
+        ON pvov.product_id = p.id
+        JOIN product_option_values pov
+        ON pov.id = pvov.product_option_value_id
+        JOIN product_options po
+        ON po.id = pov.product_option_id
+        WHERE p.parent_id = $1
+        AND p.organization_id = $2
+        GROUP BY p.id, p.sku
+        ORDER BY p.id;"#,
+        parent_id,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let stock_levels: HashMap<(i64, i64), i64> = sqlx::query!(
+        r#"SELECT
+            il.product_id,
+            il.warehouse_id,
+            SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END) as "quantity!"
+        FROM inventory_logs il
+        JOIN products p
+        ON p.id = il.product_id
+        WHERE p.parent_id = $1
+        AND il.organization_id = $2
+        GROUP BY il.product_id, il.warehouse_id;"#,
+        parent_id,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|s| ((s.product_id, s.warehouse_id), s.quantity))
+    .collect();
+
+    let warehouses = get_all_warehouses(ctx, mm).await?;
+
+    let variants = variants
+        .into_iter()
+        .map(|v| {
+            let quantities: Vec<i64> = warehouses
+                .iter()
+                .map(|w| *stock_levels.get(&(v.id, w.id)).unwrap_or(&0))
+                .collect();
+
+            VariantStockLevel {
+                sku: v.sku,
+                option_values: v.option_values,
+                total: quantities.iter().sum(),
+                quantities,
+            }
+        })
+        .collect();
+
+    Ok(VariantMatrix {
+        product_id: parent_id,
+        sku: parent.sku,
+        display_name: parent.display_name,
+        option_names,
+        warehouses,
+        variants,
+    })
+}
+// endregion: Read
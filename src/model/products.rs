@@ -1,10 +1,15 @@
 use super::{
+    common::spawn_row_stream,
     user::{self, get_user_ids},
     ModelManager, Result,
 };
 use crate::ctx::Ctx;
+use crate::money::{Currency, Money};
+use futures::stream::{BoxStream, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, NoneAsEmptyString};
 use sqlx::types::BigDecimal;
+use sqlx::PgConnection;
 
 // region: Structs
 #[derive(Debug)]
@@ -15,6 +20,15 @@ pub struct ProductStockLevelForDbResult {
     pub name: String,
     pub description: String,
     pub price: BigDecimal,
+    pub currency: Currency,
+    pub lot_tracked: bool,
+    pub serialized: bool,
+    pub parent_id: Option<i64>,
+    pub base_unit: String,
+    pub category_id: Option<i64>,
+    pub category_name: Option<String>,
+    pub tax_class_id: Option<i64>,
+    pub tax_class_name: Option<String>,
     pub quantity: Option<i64>,
 }
 
@@ -25,7 +39,15 @@ pub struct ProductWithStockLevel {
     pub brand: String,
     pub name: String,
     pub description: String,
-    pub price: BigDecimal,
+    pub price: Money,
+    pub lot_tracked: bool,
+    pub serialized: bool,
+    pub parent_id: Option<i64>,
+    pub base_unit: String,
+    pub category_id: Option<i64>,
+    pub category_name: Option<String>,
+    pub tax_class_id: Option<i64>,
+    pub tax_class_name: Option<String>,
     pub quantity: i64,
 }
 
@@ -37,7 +59,15 @@ impl From<ProductStockLevelForDbResult> for ProductWithStockLevel {
             brand: value.brand,
             name: value.name,
             description: value.description,
-            price: value.price,
+            price: Money::new(value.price, value.currency),
+            lot_tracked: value.lot_tracked,
+            serialized: value.serialized,
+            parent_id: value.parent_id,
+            base_unit: value.base_unit,
+            category_id: value.category_id,
+            category_name: value.category_name,
+            tax_class_id: value.tax_class_id,
+            tax_class_name: value.tax_class_name,
             quantity: value.quantity.unwrap_or(0),
         }
     }
@@ -45,30 +75,56 @@ impl From<ProductStockLevelForDbResult> for ProductWithStockLevel {
 // endregion: Structs
 
 // region: Methods
+/// Lists every product, or only those in `category_id` and its subcategories.
 pub async fn get_all_products_with_stock_levels(
     ctx: &Ctx,
     mm: &ModelManager,
+    category_id: Option<i64>,
 ) -> Result<Vec<ProductWithStockLevel>> {
     let db = mm.db();
     let (_, organization_id) = user::get_user_ids(ctx, mm).await?;
 
     let products = sqlx::query_as!(
         ProductStockLevelForDbResult,
-        r#"SELECT
+        r#"WITH RECURSIVE category_tree AS (
+            SELECT id FROM categories WHERE id = $2 AND organization_id = $1
+            UNION ALL
+            SELECT c.id FROM categories c
+            JOIN category_tree ct
+            ON c.parent_id = ct.id
+        )
+        SELECT
             p.id,
             p.sku,
             p.brand,
             p.name,
             p.description,
             p.price,
+            o.base_currency as "currency: Currency",
+            p.lot_tracked,
+            p.serialized,
+            p.parent_id,
+            p.base_unit,
+            p.category_id,
+            c.name as "category_name?",
+            p.tax_class_id,
+            tc.name as "tax_class_name?",
             COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as quantity
         FROM products p
         LEFT JOIN inventory_logs il
         ON p.id = il.product_id
+        LEFT JOIN categories c
+        ON c.id = p.category_id
+        LEFT JOIN tax_classes tc
+        ON tc.id = p.tax_class_id
+        JOIN organizations o
+        ON o.id = p.organization_id
         WHERE p.organization_id = $1
-        GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.display_name, p.price
+        AND ($2::int8 IS NULL OR p.category_id IN (SELECT id FROM category_tree))
+        GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.display_name, p.price, p.lot_tracked, p.serialized, p.parent_id, p.base_unit, p.category_id, c.name, p.tax_class_id, tc.name, o.base_currency
         ORDER BY p.display_name;"#,
-        organization_id
+        organization_id,
+        category_id
     )
     .fetch_all(db)
     .await?;
@@ -93,13 +149,28 @@ pub async fn get_product_with_stock_level(
             p.name,
             p.description,
             p.price,
+            o.base_currency as "currency: Currency",
+            p.lot_tracked,
+            p.serialized,
+            p.parent_id,
+            p.base_unit,
+            p.category_id,
+            c.name as "category_name?",
+            p.tax_class_id,
+            tc.name as "tax_class_name?",
             COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as quantity
         FROM products p
         LEFT JOIN inventory_logs il
         ON p.id = il.product_id
+        LEFT JOIN categories c
+        ON c.id = p.category_id
+        LEFT JOIN tax_classes tc
+        ON tc.id = p.tax_class_id
+        JOIN organizations o
+        ON o.id = p.organization_id
         WHERE p.id = $1 
         AND p.organization_id = $2
-        GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.price;"#,
+        GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.price, p.lot_tracked, p.serialized, p.parent_id, p.base_unit, p.category_id, c.name, p.tax_class_id, tc.name, o.base_currency;"#,
         product_id,
         organization_id
     )
@@ -109,13 +180,25 @@ pub async fn get_product_with_stock_level(
     Ok(product.map(|p| p.into()))
 }
 
+#[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProductForCreate {
     pub sku: String,
     pub brand: String,
     pub name: String,
     pub description: String,
-    pub price: i64,
+    pub price: Money,
+    #[serde(default)]
+    pub lot_tracked: bool,
+    #[serde(default)]
+    pub serialized: bool,
+    pub base_unit: Option<String>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub category_id: Option<i64>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub tax_class_id: Option<i64>,
 }
 pub async fn create_product(
     ctx: &Ctx,
@@ -131,19 +214,31 @@ pub async fn create_product(
         name,
         description,
         price,
+        lot_tracked,
+        serialized,
+        base_unit,
+        category_id,
+        tax_class_id,
     } = product_for_create;
 
     sqlx::query!(
         r#"INSERT INTO products 
-        (sku, brand, name, description, display_name, price, organization_id) 
-        VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+        (sku, brand, name, description, display_name, price, lot_tracked, serialized, base_unit, category_id, tax_class_id, organization_id) 
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, COALESCE(NULLIF(TRIM($9), ''), 'unit'),
+            (SELECT id FROM categories WHERE id = $10 AND organization_id = $11),
+            (SELECT id FROM tax_classes WHERE id = $12 AND organization_id = $11), $11)"#,
         sku,
         brand,
         name,
         description,
         format!("{} {} {}", brand, name, description),
-        BigDecimal::from(price),
-        organization_id
+        price.amount(),
+        lot_tracked,
+        serialized,
+        base_unit,
+        category_id,
+        organization_id,
+        tax_class_id
     )
     .execute(db)
     .await?;
@@ -166,13 +261,25 @@ pub async fn delete_product(ctx: &Ctx, mm: &ModelManager, product_id: i64) -> Re
     Ok(())
 }
 
+#[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProductForUpdate {
     pub sku: String,
     pub brand: String,
     pub name: String,
     pub description: String,
-    pub price: i64,
+    pub price: Money,
+    #[serde(default)]
+    pub lot_tracked: bool,
+    #[serde(default)]
+    pub serialized: bool,
+    pub base_unit: Option<String>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub category_id: Option<i64>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub tax_class_id: Option<i64>,
 }
 pub async fn update_product(
     ctx: &Ctx,
@@ -189,6 +296,11 @@ pub async fn update_product(
         name,
         description,
         price,
+        lot_tracked,
+        serialized,
+        base_unit,
+        category_id,
+        tax_class_id,
     } = product_for_update;
 
     sqlx::query!(
@@ -198,16 +310,28 @@ pub async fn update_product(
             brand = $2,
             name = $3,
             description = $4,
-            price = $5
-        WHERE id = $6
-        AND organization_id = $7;"#,
+            display_name = $13,
+            price = $5,
+            lot_tracked = $6,
+            serialized = $7,
+            base_unit = COALESCE(NULLIF(TRIM($8), ''), base_unit),
+            category_id = (SELECT id FROM categories WHERE id = $9 AND organization_id = $11),
+            tax_class_id = (SELECT id FROM tax_classes WHERE id = $12 AND organization_id = $11)
+        WHERE id = $10
+        AND organization_id = $11;"#,
         sku,
         brand,
         name,
         description,
-        BigDecimal::from(price),
+        price.amount(),
+        lot_tracked,
+        serialized,
+        base_unit,
+        category_id,
         id,
-        organization_id
+        organization_id,
+        tax_class_id,
+        format!("{} {} {}", brand, name, description)
     )
     .execute(db)
     .await?;
@@ -215,22 +339,43 @@ pub async fn update_product(
     Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub enum ProductSearchByType {
-    #[serde(rename = "sku")]
-    Sku,
-    #[serde(rename = "full_name")]
-    FullName,
-    #[serde(rename = "brand")]
-    Brand,
-    #[serde(rename = "name")]
-    Name,
-}
+#[serde_as]
 #[derive(Debug, Deserialize)]
 pub struct ProductForSearch {
+    #[serde(default)]
     pub search: String,
-    pub by: ProductSearchByType,
+    /// Restricts the search to a category and its subcategories.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub category_id: Option<i64>,
+    /// Counts stock in this warehouse only.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub warehouse_id: Option<i64>,
+    #[serde(default)]
+    pub in_stock_only: bool,
 }
+
+impl ProductForSearch {
+    /// The words of the search, used both for the query and for highlighting.
+    pub fn terms(&self) -> Vec<String> {
+        self.search
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_lowercase())
+            .collect()
+    }
+}
+
+/// Minimum `word_similarity` for a trigram match, low enough to forgive a
+/// transposed or missing letter. Set per transaction, since the `<%`
+/// operator, which can use the trigram index, reads it from
+/// `pg_trgm.word_similarity_threshold`.
+const TRIGRAM_SIMILARITY_THRESHOLD: &str = "0.3";
+
+/// Matches SKU, brand, name and description with full-text prefix search,
+/// falling back to trigram similarity for typos. A variant also matches on
+/// its parent product. Results are ordered by relevance.
 pub async fn search_products(
     ctx: &Ctx,
     mm: &ModelManager,
@@ -239,105 +384,145 @@ pub async fn search_products(
     let db = mm.db();
     let (_, organization_id) = get_user_ids(ctx, mm).await?;
 
-    let products = match product_for_search.by {
-        ProductSearchByType::Sku => {
-            sqlx::query_as!(
-                ProductStockLevelForDbResult,
-                r#"SELECT
-                    p.id,
-                    p.sku,
-                    p.brand,
-                    p.name,
-                    p.description,
-                    p.price,
-                    COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as quantity
-                FROM products p
-                LEFT JOIN inventory_logs il
-                ON p.id = il.product_id
-                WHERE p.organization_id = $1
-                AND p.sku LIKE $2
-                GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.price
-                ORDER BY p.sku;"#,
-                organization_id,
-                format!("{}%", product_for_search.search)
-            )
-            .fetch_all(db)
-            .await
-        },
-        ProductSearchByType::FullName => {
-            sqlx::query_as!(
-                ProductStockLevelForDbResult,
-                r#"SELECT
-                    p.id,
-                    p.sku,
-                    p.brand,
-                    p.name,
-                    p.description,
-                    p.price,
-                    COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as quantity
-                FROM products p
-                LEFT JOIN inventory_logs il
-                ON p.id = il.product_id
-                WHERE p.organization_id = $1
-                AND p.display_name LIKE $2
-                GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.display_name, p.price
-                ORDER BY p.display_name;"#,
-                organization_id,
-                format!("{}%", product_for_search.search)
-            )
-            .fetch_all(db)
-            .await
-        },
-        ProductSearchByType::Brand => {
-            sqlx::query_as!(
-                ProductStockLevelForDbResult,
-                r#"SELECT
-                    p.id,
-                    p.sku,
-                    p.brand,
-                    p.name,
-                    p.description,
-                    p.price,
-                    COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as quantity
-                FROM products p
-                LEFT JOIN inventory_logs il
-                ON p.id = il.product_id
-                WHERE p.organization_id = $1
-                AND p.brand LIKE $2
-                GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.display_name, p.price
-                ORDER BY p.display_name;"#,
-                organization_id,
-                format!("{}%", product_for_search.search)
-            )
-            .fetch_all(db)
-            .await
-        },
-        ProductSearchByType::Name => {
-            sqlx::query_as!(
-                ProductStockLevelForDbResult,
-                r#"SELECT
-                    p.id,
-                    p.sku,
-                    p.brand,
-                    p.name,
-                    p.description,
-                    p.price,
-                    COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as quantity
-                FROM products p
-                LEFT JOIN inventory_logs il
-                ON p.id = il.product_id
-                WHERE p.organization_id = $1
-                AND p.name LIKE $2
-                GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.display_name, p.price
-                ORDER BY p.display_name;"#,
-                organization_id,
-                format!("{}%", product_for_search.search)
-            )
-            .fetch_all(db)
-            .await
-        }
-    }?;
+    let mut tx = db.begin().await?;
+    set_trigram_threshold(&mut tx).await?;
+
+    let products: Vec<ProductStockLevelForDbResult> =
+        query_search_products(&mut tx, organization_id, &product_for_search)
+            .try_collect()
+            .await?;
+
+    tx.commit().await?;
 
     Ok(products.into_iter().map(|p| p.into()).collect())
 }
+
+/// Streams the same rows as [`search_products`] without holding them all in
+/// memory.
+pub fn export_products(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_for_search: ProductForSearch,
+) -> BoxStream<'static, Result<ProductWithStockLevel>> {
+    let (ctx, mm) = (ctx.clone(), mm.clone());
+
+    spawn_row_stream(|sender| async move {
+        let db = mm.db();
+        let (_, organization_id) = get_user_ids(&ctx, &mm).await?;
+
+        let mut tx = db.begin().await?;
+        set_trigram_threshold(&mut tx).await?;
+
+        let mut products = query_search_products(&mut tx, organization_id, &product_for_search);
+        while let Some(product) = products.try_next().await? {
+            if sender.send(Ok(product.into())).await.is_err() {
+                break;
+            }
+        }
+        drop(products);
+
+        tx.commit().await?;
+
+        Ok(())
+    })
+}
+
+/// Like `SET LOCAL`, which takes no parameters.
+async fn set_trigram_threshold(conn: &mut PgConnection) -> Result<()> {
+    sqlx::query!(
+        "SELECT set_config('pg_trgm.word_similarity_threshold', $1, true);",
+        TRIGRAM_SIMILARITY_THRESHOLD
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+fn query_search_products<'a>(
+    conn: &'a mut PgConnection,
+    organization_id: i64,
+    product_for_search: &ProductForSearch,
+) -> BoxStream<'a, sqlx::Result<ProductStockLevelForDbResult>> {
+    let terms = product_for_search.terms();
+    let ts_query = terms
+        .iter()
+        .map(|t| format!("{t}:*"))
+        .collect::<Vec<_>>()
+        .join(" & ");
+
+    sqlx::query_as!(
+        ProductStockLevelForDbResult,
+        r#"WITH RECURSIVE category_tree AS (
+            SELECT id FROM categories WHERE id = $4 AND organization_id = $1
+            UNION ALL
+            SELECT c.id FROM categories c
+            JOIN category_tree ct
+            ON c.parent_id = ct.id
+        ),
+        search AS (
+            SELECT to_tsquery('simple', $2) as query, $3::text as text
+        ),
+        ranked AS (
+            SELECT
+                p.id,
+                GREATEST(
+                    ts_rank(p.search_vector, s.query),
+                    ts_rank(pp.search_vector, s.query) * 0.5
+                ) as rank,
+                word_similarity($3, p.sku || ' ' || p.display_name) as similarity
+            FROM products p
+            CROSS JOIN search s
+            LEFT JOIN products pp
+            ON pp.id = p.parent_id
+            WHERE p.organization_id = $1
+            AND ($4::int8 IS NULL OR p.category_id IN (SELECT id FROM category_tree))
+            AND (
+                s.text = ''
+                OR p.search_vector @@ s.query
+                OR pp.search_vector @@ s.query
+                OR $3 <% (p.sku || ' ' || p.display_name)
+            )
+        )
+        SELECT
+            p.id,
+            p.sku,
+            p.brand,
+            p.name,
+            p.description,
+            p.price,
+            o.base_currency as "currency: Currency",
+            p.lot_tracked,
+            p.serialized,
+            p.parent_id,
+            p.base_unit,
+            p.category_id,
+            c.name as "category_name?",
+            p.tax_class_id,
+            tc.name as "tax_class_name?",
+            COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) as quantity
+        FROM ranked r
+        JOIN products p
+        ON p.id = r.id
+        LEFT JOIN inventory_logs il
+        ON p.id = il.product_id
+        AND ($5::int8 IS NULL OR il.warehouse_id = $5)
+        LEFT JOIN categories c
+        ON c.id = p.category_id
+        LEFT JOIN tax_classes tc
+        ON tc.id = p.tax_class_id
+        JOIN organizations o
+        ON o.id = p.organization_id
+        GROUP BY p.id, p.sku, p.brand, p.name, p.description, p.display_name, p.price, p.lot_tracked, p.serialized, p.parent_id, p.base_unit, p.category_id, c.name, p.tax_class_id, tc.name, o.base_currency, r.rank, r.similarity
+        HAVING NOT $6 OR COALESCE(SUM(CASE WHEN action = 'INCOMING' THEN quantity ELSE -quantity END), 0) > 0
+        ORDER BY r.rank DESC, r.similarity DESC, p.display_name;"#,
+        organization_id,
+        ts_query,
+        terms.join(" "),
+        product_for_search.category_id,
+        product_for_search.warehouse_id,
+        product_for_search.in_stock_only
+    )
+    .fetch(conn)
+}
 // endregion: Methods
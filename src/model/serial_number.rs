@@ -0,0 +1,306 @@
+use super::{
+    inventory_log::{InventoryLogAction, InventoryLogForCreate},
+    inventory_transaction::InventoryTransactionAction,
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::money::{Currency, Money};
+use chrono::{DateTime, Utc};
+use sqlx::{types::BigDecimal, PgConnection};
+use std::collections::HashSet;
+
+// region: Structs
+#[derive(Debug)]
+pub struct SerialNumber {
+    pub id: i64,
+    pub serial_number: String,
+    pub product_sku: String,
+    pub product_display_name: String,
+}
+
+struct SerialNumberMovementForDbResult {
+    inventory_log_action: InventoryLogAction,
+    inventory_log_timestamp: DateTime<Utc>,
+    inventory_log_price: BigDecimal,
+    inventory_log_currency: Currency,
+    warehouse_id: i64,
+    inventory_transaction_id: Option<i64>,
+    inventory_transaction_action: Option<InventoryTransactionAction>,
+}
+
+#[derive(Debug)]
+pub struct SerialNumberMovement {
+    pub inventory_log_action: InventoryLogAction,
+    pub inventory_log_timestamp: DateTime<Utc>,
+    pub inventory_log_price: Money,
+    pub warehouse_id: i64,
+    pub inventory_transaction_id: Option<i64>,
+    pub inventory_transaction_action: Option<InventoryTransactionAction>,
+}
+
+impl From<SerialNumberMovementForDbResult> for SerialNumberMovement {
+    fn from(value: SerialNumberMovementForDbResult) -> Self {
+        Self {
+            inventory_log_action: value.inventory_log_action,
+            inventory_log_timestamp: value.inventory_log_timestamp,
+            inventory_log_price: Money::new(
+                value.inventory_log_price,
+                value.inventory_log_currency,
+            ),
+            warehouse_id: value.warehouse_id,
+            inventory_transaction_id: value.inventory_transaction_id,
+            inventory_transaction_action: value.inventory_transaction_action,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SerialNumberHistory {
+    pub serial_number: SerialNumber,
+    pub movements: Vec<SerialNumberMovement>,
+}
+
+impl SerialNumberHistory {
+    /// The warehouse currently holding the unit, if it is in stock.
+    pub fn in_stock_warehouse_id(&self) -> Option<i64> {
+        self.movements
+            .last()
+            .filter(|m| matches!(m.inventory_log_action, InventoryLogAction::Incoming))
+            .map(|m| m.warehouse_id)
+    }
+}
+// endregion: Structs
+
+// region: Read
+pub async fn get_serial_number_history(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    serial_number: &str,
+) -> Result<Option<SerialNumberHistory>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let serial_number = sqlx::query_as!(
+        SerialNumber,
+        r#"SELECT
+            sn.id,
+            sn.serial_number,
+            p.sku as product_sku,
+            p.display_name as product_display_name
+        FROM serial_numbers sn
+        JOIN products p
+        ON p.id = sn.product_id
+        WHERE sn.serial_number = $1
+        AND sn.organization_id = $2;"#,
+        serial_number.trim(),
+        organization_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let Some(serial_number) = serial_number else {
+        return Ok(None);
+    };
+
+    let movements = sqlx::query_as!(
+        SerialNumberMovementForDbResult,
+        r#"SELECT
+            il.action as "inventory_log_action: InventoryLogAction",
+            il.timestamp as inventory_log_timestamp,
+            il.price as inventory_log_price,
+            il.currency as "inventory_log_currency: Currency",
+            il.warehouse_id,
+            it.id as "inventory_transaction_id?",
+            it.action as "inventory_transaction_action?: InventoryTransactionAction"
+        FROM inventory_log_serial_numbers ilsn
+        JOIN inventory_logs il
+        ON il.id = ilsn.inventory_log_id
+        LEFT JOIN inventory_transactions it
+        ON it.id = il.inventory_transaction_id
+        WHERE ilsn.serial_number_id = $1
+        AND il.organization_id = $2
+        ORDER BY il.id;"#,
+        serial_number.id,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(Some(SerialNumberHistory {
+        serial_number,
+        movements: movements.into_iter().map(|m| m.into()).collect(),
+    }))
+}
+// endregion: Read
+
+// region: Create
+/// Validates and links the serial numbers of every serialized product's log.
+/// Must run inside the transaction that inserted `log_ids`, before any other
+/// links are written for them.
+pub(in crate::model) async fn save_serial_numbers(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    logs: &[InventoryLogForCreate],
+    log_ids: &[i64],
+) -> Result<()> {
+    let mut seen = HashSet::new();
+
+    // Whether a serial number is in stock is read before it moves, so
+    // transactions moving the same ones go one at a time.
+    let serial_numbers: Vec<String> = logs
+        .iter()
+        .flat_map(|log| &log.serial_numbers)
+        .map(|serial_number| serial_number.trim().to_string())
+        .collect();
+    lock_serial_numbers(conn, organization_id, &serial_numbers).await?;
+
+    for (log, log_id) in logs.iter().zip(log_ids) {
+        let serialized = sqlx::query!(
+            "SELECT serialized FROM products WHERE id = $1 AND organization_id = $2;",
+            log.product_id,
+            organization_id
+        )
+        .fetch_one(&mut *conn)
+        .await?
+        .serialized;
+
+        if !serialized {
+            continue;
+        }
+
+        if log.serial_numbers.len() as i64 != log.quantity {
+            return Err(Error::SerialNumberCountMismatch {
+                product_id: log.product_id,
+                expected: log.quantity,
+                actual: log.serial_numbers.len() as i64,
+            });
+        }
+
+        for serial_number in &log.serial_numbers {
+            let serial_number = serial_number.trim();
+            if !seen.insert(serial_number.to_string()) {
+                return Err(Error::DuplicateSerialNumber {
+                    serial_number: serial_number.to_string(),
+                });
+            }
+
+            let existing = sqlx::query!(
+                r#"SELECT
+                    sn.id,
+                    sn.product_id,
+                    (
+                        SELECT il.action::TEXT FROM inventory_log_serial_numbers ilsn
+                        JOIN inventory_logs il
+                        ON il.id = ilsn.inventory_log_id
+                        WHERE ilsn.serial_number_id = sn.id
+                        ORDER BY il.id DESC
+                        LIMIT 1
+                    ) as last_action,
+                    (
+                        SELECT il.warehouse_id FROM inventory_log_serial_numbers ilsn
+                        JOIN inventory_logs il
+                        ON il.id = ilsn.inventory_log_id
+                        WHERE ilsn.serial_number_id = sn.id
+                        ORDER BY il.id DESC
+                        LIMIT 1
+                    ) as last_warehouse_id
+                FROM serial_numbers sn
+                WHERE sn.serial_number = $1
+                AND sn.organization_id = $2;"#,
+                serial_number,
+                organization_id
+            )
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            let in_stock_warehouse_id =
+                existing
+                    .as_ref()
+                    .and_then(|e| match e.last_action.as_deref() {
+                        Some("INCOMING") => e.last_warehouse_id,
+                        _ => None,
+                    });
+
+            let serial_number_id = match log.action {
+                InventoryLogAction::Incoming => {
+                    if in_stock_warehouse_id.is_some() {
+                        return Err(Error::SerialNumberAlreadyInStock {
+                            serial_number: serial_number.to_string(),
+                        });
+                    }
+
+                    match existing {
+                        Some(e) if e.product_id != log.product_id => {
+                            return Err(Error::DuplicateSerialNumber {
+                                serial_number: serial_number.to_string(),
+                            });
+                        }
+                        Some(e) => e.id,
+                        None => {
+                            sqlx::query!(
+                                r#"INSERT INTO serial_numbers (serial_number, product_id, organization_id)
+                                VALUES ($1, $2, $3)
+                                RETURNING id;"#,
+                                serial_number,
+                                log.product_id,
+                                organization_id
+                            )
+                            .fetch_one(&mut *conn)
+                            .await?
+                            .id
+                        }
+                    }
+                }
+                InventoryLogAction::Outgoing => match existing {
+                    Some(e)
+                        if e.product_id == log.product_id
+                            && in_stock_warehouse_id == Some(log.warehouse_id) =>
+                    {
+                        e.id
+                    }
+                    _ => {
+                        return Err(Error::SerialNumberNotInStock {
+                            serial_number: serial_number.to_string(),
+                        });
+                    }
+                },
+            };
+
+            sqlx::query!(
+                r#"INSERT INTO inventory_log_serial_numbers (inventory_log_id, serial_number_id)
+                VALUES ($1, $2);"#,
+                log_id,
+                serial_number_id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Holds the serial numbers that exist until the transaction ends; in id
+/// order, so transactions cannot deadlock on them.
+async fn lock_serial_numbers(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    serial_numbers: &[String],
+) -> Result<()> {
+    sqlx::query!(
+        r#"SELECT id FROM serial_numbers
+        WHERE organization_id = $1
+        AND serial_number = ANY($2)
+        ORDER BY id
+        FOR UPDATE;"#,
+        organization_id,
+        serial_numbers
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+// endregion: Create
@@ -0,0 +1,303 @@
+use super::{
+    organization::{lock_organization, OrganizationState, OrganizationStatus},
+    permissions::Permissions,
+    superuser_action::{record_superuser_action, SuperuserAction},
+    tenant::system,
+    user::get_superuser_id,
+    ModelManager,
+};
+use crate::crypt::{hash_token, new_token};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use moka::sync::Cache;
+use sqlx::PgConnection;
+
+// A session is a row per login. The cookie holds a random token and the table
+// its hash, so a leaked table cannot be replayed. Revoking a session deletes
+// its row. A superuser impersonating someone gets a short session of theirs
+// that names the superuser. Users with two-factor authentication first get a
+// pending session, which is no login. Logging in and out happens before the
+// organization is known, so those work across organizations.
+//
+// Every request looks up its session, so live sessions are kept in memory for
+// a little while. Whatever ends a session or changes what its user may do
+// drops it from there too; another process serving the same database notices
+// once the entry expires.
+
+/// How long a login lasts.
+const SESSION_LIFETIME_DAYS: i64 = 7;
+
+/// How long a user has to give their second factor after their password.
+const PENDING_SESSION_LIFETIME_MINUTES: i64 = 10;
+
+/// How long a superuser may act as someone else.
+const IMPERSONATION_LIFETIME_HOURS: i64 = 1;
+
+/// How long a looked up session is trusted without asking the database.
+const SESSION_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+const SESSION_CACHE_CAPACITY: u64 = 10_000;
+
+// region: Structs
+/// Live sessions by token hash.
+pub(in crate::model) type SessionCache = Cache<String, CachedSession>;
+
+#[derive(Clone)]
+pub(in crate::model) struct CachedSession {
+    ctx: Ctx,
+    expires_at: DateTime<Utc>,
+}
+// endregion: Structs
+
+// region: Methods
+/// Starts a session for a user who just authenticated. Returns the token for
+/// the cookie.
+pub async fn create_session(_ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<String> {
+    system(async {
+        let db = mm.db();
+        let mut conn = db.acquire().await?;
+
+        insert_session(&mut conn, user_id).await
+    })
+    .await
+}
+
+/// Starts a session that only lets a user who gave their password give their
+/// second factor, see `two_factor`. Returns the token for the cookie.
+pub async fn create_pending_session(_ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<String> {
+    system(async {
+        let db = mm.db();
+
+        let token = new_token();
+        let expires_at = Utc::now() + Duration::minutes(PENDING_SESSION_LIFETIME_MINUTES);
+
+        sqlx::query!(
+            r#"INSERT INTO sessions (token_hash, user_id, expires_at, two_factor_pending)
+            VALUES ($1, $2, $3, TRUE);"#,
+            hash_token(&token),
+            user_id,
+            expires_at
+        )
+        .execute(db)
+        .await?;
+
+        Ok(token)
+    })
+    .await
+}
+
+pub(in crate::model) async fn insert_session(
+    conn: &mut PgConnection,
+    user_id: i64,
+) -> Result<String> {
+    let token = new_token();
+    let expires_at = Utc::now() + Duration::days(SESSION_LIFETIME_DAYS);
+
+    sqlx::query!(
+        "INSERT INTO sessions (token_hash, user_id, expires_at) VALUES ($1, $2, $3);",
+        hash_token(&token),
+        user_id,
+        expires_at
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(token)
+}
+
+pub(in crate::model) fn new_session_cache() -> SessionCache {
+    Cache::builder()
+        .max_capacity(SESSION_CACHE_CAPACITY)
+        .time_to_live(SESSION_CACHE_TTL)
+        .support_invalidation_closures()
+        .build()
+}
+
+/// Who a live session is for, with what they may do. Fails for users of an
+/// organization that is not active.
+pub async fn get_session_ctx(_ctx: &Ctx, mm: &ModelManager, token: &str) -> Result<Ctx> {
+    let token_hash = hash_token(token);
+    if let Some(session) = mm.sessions().get(&token_hash) {
+        if session.expires_at > Utc::now() {
+            return Ok(session.ctx);
+        }
+        mm.sessions().invalidate(&token_hash);
+    }
+
+    let session = system(async {
+        let db = mm.db();
+
+        sqlx::query!(
+            r#"SELECT
+                s.user_id,
+                s.expires_at,
+                u.organization_id,
+                o.status as "status?: OrganizationStatus",
+                o.status_reason,
+                o.deletion_due_at,
+                ARRAY(
+                    SELECT up.permission_id FROM user_permissions up
+                    WHERE up.user_id = s.user_id
+                ) as "permissions!"
+            FROM sessions s
+            JOIN users u
+            ON u.id = s.user_id
+            LEFT JOIN organizations o
+            ON o.id = u.organization_id
+            WHERE s.token_hash = $1
+            AND NOT s.two_factor_pending
+            AND s.expires_at > CURRENT_TIMESTAMP
+            AND u.active;"#,
+            token_hash
+        )
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| Error::Unauhtorized("Session not found".to_string()))
+    })
+    .await?;
+
+    if let (Some(organization_id), Some(status)) = (session.organization_id, session.status) {
+        OrganizationState {
+            status,
+            status_reason: session.status_reason,
+            deletion_due_at: session.deletion_due_at,
+        }
+        .ensure_active(organization_id)?;
+    }
+
+    let permissions = session
+        .permissions
+        .into_iter()
+        .filter_map(|p| Permissions::try_from(p).ok())
+        .collect();
+    let ctx = Ctx::new(session.user_id, session.organization_id, permissions);
+
+    mm.sessions().insert(
+        token_hash,
+        CachedSession {
+            ctx: ctx.clone(),
+            expires_at: session.expires_at,
+        },
+    );
+
+    Ok(ctx)
+}
+
+/// Starts a session as the longest-standing active admin of an active
+/// organization, recording who did it and why. Superusers only. Returns the
+/// token for the cookie.
+pub async fn impersonate_organization_admin(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    organization_id: i64,
+    reason: &str,
+) -> Result<String> {
+    let db = mm.db();
+    let superuser_id = get_superuser_id(ctx, mm).await?;
+
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(Error::ReasonRequired);
+    }
+
+    let mut tx = db.begin().await?;
+
+    lock_organization(&mut tx, organization_id)
+        .await?
+        .ensure_active(organization_id)?;
+
+    let admin = sqlx::query!(
+        r#"SELECT u.id
+        FROM users u
+        JOIN user_permissions up
+        ON up.user_id = u.id
+        WHERE u.organization_id = $1
+        AND u.active
+        AND up.permission_id = $2
+        ORDER BY u.id
+        LIMIT 1;"#,
+        organization_id,
+        Permissions::OrganizationAll as i64
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| Error::Unauhtorized("Organization has no active admin".to_string()))?;
+
+    let token = new_token();
+    let expires_at = Utc::now() + Duration::hours(IMPERSONATION_LIFETIME_HOURS);
+    sqlx::query!(
+        r#"INSERT INTO sessions (token_hash, user_id, expires_at, impersonated_by)
+        VALUES ($1, $2, $3, $4);"#,
+        hash_token(&token),
+        admin.id,
+        expires_at,
+        superuser_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_superuser_action(
+        &mut tx,
+        Some(superuser_id),
+        organization_id,
+        SuperuserAction::Impersonate,
+        Some(admin.id),
+        Some(reason),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(token)
+}
+
+/// Ends the session of `token`, e.g. on logout.
+pub async fn delete_session(_ctx: &Ctx, mm: &ModelManager, token: &str) -> Result<()> {
+    system(async {
+        let db = mm.db();
+
+        let token_hash = hash_token(token);
+        sqlx::query!("DELETE FROM sessions WHERE token_hash = $1;", token_hash)
+            .execute(db)
+            .await?;
+        mm.sessions().invalidate(&token_hash);
+
+        Ok(())
+    })
+    .await
+}
+
+/// Drops the cached sessions of a user, once their sessions were revoked or
+/// their permissions changed.
+pub(in crate::model) fn forget_user_sessions(mm: &ModelManager, user_id: i64) {
+    forget_sessions(mm, move |ctx| ctx.user_id() == Some(user_id));
+}
+
+/// Drops the cached sessions of an organization's users.
+pub(in crate::model) fn forget_organization_sessions(mm: &ModelManager, organization_id: i64) {
+    forget_sessions(mm, move |ctx| {
+        ctx.organization_id() == Some(organization_id)
+    });
+}
+
+fn forget_sessions(mm: &ModelManager, f: impl Fn(&Ctx) -> bool + Send + Sync + 'static) {
+    // Only fails if the cache was built without invalidation closures.
+    if let Err(e) = mm
+        .sessions()
+        .invalidate_entries_if(move |_, session| f(&session.ctx))
+    {
+        println!("Could not drop cached sessions: {e:?}");
+        mm.sessions().invalidate_all();
+    }
+}
+
+/// Ends every session of a user, logging them out everywhere.
+pub(in crate::model) async fn revoke_sessions(conn: &mut PgConnection, user_id: i64) -> Result<()> {
+    sqlx::query!("DELETE FROM sessions WHERE user_id = $1;", user_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+// endregion: Methods
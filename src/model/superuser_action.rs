@@ -0,0 +1,110 @@
+use super::{user::get_superuser_id, ModelManager};
+use crate::ctx::Ctx;
+use crate::model::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+
+// The audit trail of the superuser console. Each action is recorded in the
+// transaction that does it, with the organization's name copied so the entry
+// still reads after the organization is gone.
+
+/// How many entries the console shows.
+const RECENT_ACTIONS: i64 = 50;
+
+// region: Structs
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "superuser_action", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SuperuserAction {
+    CreateOrganization,
+    SuspendOrganization,
+    ReactivateOrganization,
+    Impersonate,
+    ScheduleDeletion,
+    /// Done by the deletion job.
+    DeleteOrganization,
+}
+
+impl SuperuserAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SuperuserAction::CreateOrganization => "Created",
+            SuperuserAction::SuspendOrganization => "Suspended",
+            SuperuserAction::ReactivateOrganization => "Reactivated",
+            SuperuserAction::Impersonate => "Impersonated",
+            SuperuserAction::ScheduleDeletion => "Scheduled deletion",
+            SuperuserAction::DeleteOrganization => "Deleted",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SuperuserActionEntry {
+    /// None for the deletion job, or once the superuser is deleted.
+    pub superuser: Option<String>,
+    pub organization_name: String,
+    pub action: SuperuserAction,
+    pub target_user: Option<String>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+// endregion: Structs
+
+// region: Methods
+/// The latest actions of every superuser. Superusers only.
+pub async fn get_superuser_actions(
+    ctx: &Ctx,
+    mm: &ModelManager,
+) -> Result<Vec<SuperuserActionEntry>> {
+    let db = mm.db();
+    get_superuser_id(ctx, mm).await?;
+
+    let actions = sqlx::query_as!(
+        SuperuserActionEntry,
+        r#"SELECT
+            s.username as "superuser?",
+            a.organization_name,
+            a.action as "action: SuperuserAction",
+            t.username as "target_user?",
+            a.reason,
+            a.created_at
+        FROM superuser_actions a
+        LEFT JOIN users s
+        ON s.id = a.superuser_id
+        LEFT JOIN users t
+        ON t.id = a.target_user_id
+        ORDER BY a.created_at DESC, a.id DESC
+        LIMIT $1;"#,
+        RECENT_ACTIONS
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(actions)
+}
+
+pub(in crate::model) async fn record_superuser_action(
+    conn: &mut PgConnection,
+    superuser_id: Option<i64>,
+    organization_id: i64,
+    action: SuperuserAction,
+    target_user_id: Option<i64>,
+    reason: Option<&str>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO superuser_actions
+            (superuser_id, organization_id, organization_name, action, target_user_id, reason)
+        SELECT $1, id, name, $3, $4, $5
+        FROM organizations
+        WHERE id = $2;"#,
+        superuser_id,
+        organization_id,
+        action as SuperuserAction,
+        target_user_id,
+        reason
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+// endregion: Methods
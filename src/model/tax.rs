@@ -0,0 +1,342 @@
+use super::{
+    inventory_log::InventoryLogForCreate, organization::base_currency, price_list::effective_price,
+    user::get_user_ids, ModelManager,
+};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use crate::money::{Currency, Money};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+use sqlx::types::BigDecimal;
+use sqlx::PgConnection;
+
+// Sales lines store the tax as charged, so the summary adds up what was
+// invoiced even after a rate or a product's class changes.
+
+// region: Structs
+#[derive(Debug)]
+pub struct TaxClass {
+    pub id: i64,
+    pub name: String,
+    /// Percent, 12 for 12%.
+    pub rate: BigDecimal,
+    pub product_count: i64,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct TaxClassForCreate {
+    pub name: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub rate: BigDecimal,
+}
+
+/// The tax of one sales line.
+#[derive(Debug, Clone)]
+pub struct LineTax {
+    pub tax_class_id: Option<i64>,
+    /// Percent, 12 for 12%.
+    pub rate: BigDecimal,
+    pub net: Money,
+    pub tax: Money,
+    pub gross: Money,
+}
+
+impl LineTax {
+    /// Splits a line amount into net, tax and gross, each rounded to the
+    /// currency's minor units. `amount` is the gross when prices include tax,
+    /// the net otherwise.
+    pub fn new(
+        tax_class_id: Option<i64>,
+        rate: BigDecimal,
+        amount: Money,
+        inclusive: bool,
+    ) -> Self {
+        let currency = amount.currency();
+        let amount = amount.rounded();
+        let hundred = BigDecimal::from(100);
+
+        let (net, tax, gross) = match inclusive {
+            true => {
+                let tax =
+                    Money::new(amount.amount() * &rate / (&hundred + &rate), currency).rounded();
+                let net = Money::new(amount.amount() - tax.amount(), currency);
+                (net, tax, amount)
+            }
+            false => {
+                let tax = Money::new(amount.amount() * &rate / &hundred, currency).rounded();
+                let gross = Money::new(amount.amount() + tax.amount(), currency);
+                (amount, tax, gross)
+            }
+        };
+
+        Self {
+            tax_class_id,
+            rate: rate.normalized(),
+            net,
+            tax,
+            gross,
+        }
+    }
+}
+
+struct TaxSummaryRowForDbResult {
+    tax_class_id: Option<i64>,
+    tax_class_name: Option<String>,
+    tax_rate: BigDecimal,
+    line_count: i64,
+    net: BigDecimal,
+    tax: BigDecimal,
+    gross: BigDecimal,
+    currency: Currency,
+}
+
+#[derive(Debug)]
+pub struct TaxSummaryRow {
+    pub tax_class_id: Option<i64>,
+    /// None once the class is deleted.
+    pub tax_class_name: Option<String>,
+    pub tax_rate: BigDecimal,
+    pub line_count: i64,
+    pub net: Money,
+    pub tax: Money,
+    pub gross: Money,
+}
+
+impl From<TaxSummaryRowForDbResult> for TaxSummaryRow {
+    fn from(value: TaxSummaryRowForDbResult) -> Self {
+        Self {
+            tax_class_id: value.tax_class_id,
+            tax_class_name: value.tax_class_name,
+            tax_rate: value.tax_rate.normalized(),
+            line_count: value.line_count,
+            net: Money::new(value.net, value.currency),
+            tax: Money::new(value.tax, value.currency),
+            gross: Money::new(value.gross, value.currency),
+        }
+    }
+}
+
+/// Sales less rollbacks per class and rate, in the base currency.
+#[derive(Debug)]
+pub struct TaxSummary {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub currency: Currency,
+    pub rows: Vec<TaxSummaryRow>,
+    pub total_net: Money,
+    pub total_tax: Money,
+    pub total_gross: Money,
+}
+// endregion: Structs
+
+// region: Methods
+pub async fn get_tax_classes(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<TaxClass>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let tax_classes = sqlx::query_as!(
+        TaxClass,
+        r#"SELECT
+            tc.id,
+            tc.name,
+            tc.rate,
+            (SELECT COUNT(*) FROM products p WHERE p.tax_class_id = tc.id) as "product_count!"
+        FROM tax_classes tc
+        WHERE tc.organization_id = $1
+        ORDER BY tc.name;"#,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(tax_classes
+        .into_iter()
+        .map(|tc| TaxClass {
+            rate: tc.rate.normalized(),
+            ..tc
+        })
+        .collect())
+}
+
+/// Records a class, or changes the rate of the one with the same name. Past
+/// sales keep the rate they were charged.
+pub async fn create_tax_class(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    tax_class_for_create: TaxClassForCreate,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let name = tax_class_for_create.name.trim();
+    let rate = tax_class_for_create.rate;
+    if name.is_empty() || rate < BigDecimal::from(0) || rate >= BigDecimal::from(100) {
+        return Err(Error::InvalidTaxClass {
+            name: name.to_string(),
+        });
+    }
+
+    sqlx::query!(
+        r#"INSERT INTO tax_classes (name, rate, organization_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (name, organization_id) DO UPDATE
+        SET rate = EXCLUDED.rate;"#,
+        name,
+        rate,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_tax_class(ctx: &Ctx, mm: &ModelManager, tax_class_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let mut tx = db.begin().await?;
+
+    let tax_class = sqlx::query!(
+        r#"SELECT
+            tc.name,
+            (SELECT COUNT(*) FROM products p WHERE p.tax_class_id = tc.id) as "product_count!"
+        FROM tax_classes tc
+        WHERE tc.id = $1
+        AND tc.organization_id = $2
+        FOR UPDATE;"#,
+        tax_class_id,
+        organization_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(tax_class) = tax_class else {
+        return Ok(());
+    };
+    if tax_class.product_count > 0 {
+        return Err(Error::TaxClassHasProducts {
+            tax_class_id,
+            name: tax_class.name,
+            product_count: tax_class.product_count,
+        });
+    }
+
+    sqlx::query!("DELETE FROM tax_classes WHERE id = $1;", tax_class_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Net, tax and gross from `from` to `to`, both included.
+pub async fn get_tax_summary(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<TaxSummary> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let rows = sqlx::query_as!(
+        TaxSummaryRowForDbResult,
+        r#"SELECT
+            il.tax_class_id,
+            tc.name as "tax_class_name?",
+            il.tax_rate as "tax_rate!",
+            COUNT(*) as "line_count!",
+            SUM(CASE WHEN it.action = 'SALES' THEN 1 ELSE -1 END * il.net_amount * il.exchange_rate) as "net!",
+            SUM(CASE WHEN it.action = 'SALES' THEN 1 ELSE -1 END * il.tax_amount * il.exchange_rate) as "tax!",
+            SUM(CASE WHEN it.action = 'SALES' THEN 1 ELSE -1 END * il.gross_amount * il.exchange_rate) as "gross!",
+            o.base_currency as "currency: Currency"
+        FROM inventory_logs il
+        JOIN inventory_transactions it
+        ON it.id = il.inventory_transaction_id
+        JOIN organizations o
+        ON o.id = il.organization_id
+        LEFT JOIN tax_classes tc
+        ON tc.id = il.tax_class_id
+        WHERE il.organization_id = $1
+        AND it.action IN ('SALES', 'SALES_ROLLBACK')
+        AND il.tax_rate IS NOT NULL
+        AND il.timestamp >= $2::date
+        AND il.timestamp < $3::date + 1
+        GROUP BY il.tax_class_id, tc.name, il.tax_rate, o.base_currency
+        ORDER BY il.tax_rate DESC, tc.name;"#,
+        organization_id,
+        from,
+        to
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut conn = db.acquire().await?;
+    let currency = base_currency(&mut conn, organization_id).await?;
+
+    let rows: Vec<TaxSummaryRow> = rows.into_iter().map(|r| r.into()).collect();
+
+    Ok(TaxSummary {
+        from,
+        to,
+        currency,
+        total_net: Money::sum(currency, rows.iter().map(|r| r.net.clone()))?,
+        total_tax: Money::sum(currency, rows.iter().map(|r| r.tax.clone()))?,
+        total_gross: Money::sum(currency, rows.iter().map(|r| r.gross.clone()))?,
+        rows,
+    })
+}
+
+/// The tax of every sold line, at its product's current class. A line's price
+/// includes tax if the price it would be quoted does. Logs must be in base
+/// units.
+pub(in crate::model) async fn line_taxes(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    customer_id: Option<i64>,
+    logs: &[InventoryLogForCreate],
+    date: NaiveDate,
+) -> Result<Vec<LineTax>> {
+    let mut taxes = Vec::with_capacity(logs.len());
+    for log in logs {
+        let product = sqlx::query!(
+            r#"SELECT p.tax_class_id, tc.rate as "rate?"
+            FROM products p
+            LEFT JOIN tax_classes tc
+            ON tc.id = p.tax_class_id
+            WHERE p.id = $1
+            AND p.organization_id = $2;"#,
+            log.product_id,
+            organization_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(Error::ProductNotFound {
+            product_id: log.product_id,
+        })?;
+
+        let effective = effective_price(
+            &mut *conn,
+            organization_id,
+            customer_id,
+            log.product_id,
+            log.quantity,
+            date,
+        )
+        .await?;
+
+        taxes.push(LineTax::new(
+            product.tax_class_id,
+            product.rate.unwrap_or_else(|| BigDecimal::from(0)),
+            log.price.times(log.quantity),
+            effective.tax_inclusive,
+        ));
+    }
+
+    Ok(taxes)
+}
+// endregion: Methods
@@ -0,0 +1,222 @@
+use crate::ctx::Ctx;
+use sqlx::PgConnection;
+use std::future::Future;
+
+// Row-level security keeps each organization to its own rows even where a
+// query forgets its `organization_id` filter. The policies read `app.tenant`,
+// which the pool sets on every connection it hands out from the tenant of the
+// task asking: a request runs scoped to the organization of its user, see
+// `web::mw_auth`. Without a tenant no tenant rows are visible at all. Code that
+// has to look across organizations, e.g. to find who is logging in, runs
+// scoped to `Tenant::System`.
+
+/// Who a database connection works for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tenant {
+    Organization(i64),
+    /// Every organization, for superusers and work done before or without a
+    /// user.
+    System,
+}
+
+tokio::task_local! {
+    static TENANT: Tenant;
+}
+
+impl Tenant {
+    /// The tenant of a user's requests. Only superusers have no organization.
+    pub fn of(ctx: &Ctx) -> Option<Tenant> {
+        match (ctx.user_id(), ctx.organization_id()) {
+            (_, Some(organization_id)) => Some(Tenant::Organization(organization_id)),
+            (Some(_), None) => Some(Tenant::System),
+            (None, None) => None,
+        }
+    }
+
+    /// The tenant of the current task, if any.
+    pub fn current() -> Option<Tenant> {
+        TENANT.try_with(|tenant| *tenant).ok()
+    }
+
+    /// Runs `f` with the connections it uses working for this tenant.
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        TENANT.scope(self, f).await
+    }
+
+    /// What the policies read from `app.tenant`.
+    fn setting(&self) -> String {
+        match self {
+            Tenant::Organization(organization_id) => organization_id.to_string(),
+            Tenant::System => "system".to_string(),
+        }
+    }
+}
+
+/// Runs `f` scoped to every organization.
+pub(in crate::model) async fn system<F: Future>(f: F) -> F::Output {
+    Tenant::System.scope(f).await
+}
+
+/// Makes `conn` work for the current task's tenant. Called by the pool
+/// whenever it hands out a connection.
+pub(in crate::model) async fn apply_tenant(conn: &mut PgConnection) -> sqlx::Result<()> {
+    let setting = Tenant::current()
+        .map(|tenant| tenant.setting())
+        .unwrap_or_default();
+
+    sqlx::query("SELECT set_config('app.tenant', $1, false);")
+        .bind(setting)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::store::{new_db_pool, Db};
+    use chrono::Utc;
+
+    /// Two organizations with a category each, committed so the pool's own
+    /// connections see them.
+    struct Seed {
+        a: i64,
+        b: i64,
+        a_category: i64,
+        b_category: i64,
+    }
+
+    async fn seed(db: &Db, test: &str) -> Seed {
+        system(async {
+            let suffix = format!("{test}-{}", Utc::now().timestamp_nanos_opt().unwrap());
+            let mut ids = Vec::new();
+            for name in ["a", "b"] {
+                let organization: (i64,) = sqlx::query_as(
+                    "INSERT INTO organizations (name, display_name) VALUES ($1, $1) RETURNING id;",
+                )
+                .bind(format!("rls-{name}-{suffix}"))
+                .fetch_one(db)
+                .await
+                .unwrap();
+                let category: (i64,) = sqlx::query_as(
+                    "INSERT INTO categories (name, organization_id) VALUES ($1, $2) RETURNING id;",
+                )
+                .bind(format!("rls-{name}"))
+                .bind(organization.0)
+                .fetch_one(db)
+                .await
+                .unwrap();
+                ids.push((organization.0, category.0));
+            }
+
+            Seed {
+                a: ids[0].0,
+                b: ids[1].0,
+                a_category: ids[0].1,
+                b_category: ids[1].1,
+            }
+        })
+        .await
+    }
+
+    async fn clean_up(db: &Db, seed: &Seed) {
+        system(async {
+            sqlx::query("DELETE FROM organizations WHERE id IN ($1, $2);")
+                .bind(seed.a)
+                .bind(seed.b)
+                .execute(db)
+                .await
+                .unwrap();
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn unfiltered_selects_see_only_the_tenants_rows() {
+        let db = new_db_pool().await.unwrap();
+        let seed = seed(&db, "select").await;
+
+        let (role, organizations, categories) = Tenant::Organization(seed.a)
+            .scope(async {
+                let role: (String,) = sqlx::query_as("SELECT current_user::TEXT;")
+                    .fetch_one(&db)
+                    .await
+                    .unwrap();
+                let organizations: Vec<(i64,)> = sqlx::query_as("SELECT id FROM organizations;")
+                    .fetch_all(&db)
+                    .await
+                    .unwrap();
+                let categories: Vec<(i64, i64)> =
+                    sqlx::query_as("SELECT id, organization_id FROM categories;")
+                        .fetch_all(&db)
+                        .await
+                        .unwrap();
+                (role.0, organizations, categories)
+            })
+            .await;
+        let untenanted: Vec<(i64,)> = sqlx::query_as("SELECT id FROM categories;")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        clean_up(&db, &seed).await;
+
+        assert_eq!(role, "distrupify_app");
+        assert_eq!(organizations, vec![(seed.a,)]);
+        assert!(categories.contains(&(seed.a_category, seed.a)));
+        assert!(categories
+            .iter()
+            .all(|(_, organization_id)| *organization_id == seed.a));
+        assert!(untenanted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn writes_to_another_tenants_rows_fail() {
+        let db = new_db_pool().await.unwrap();
+        let seed = seed(&db, "write").await;
+
+        let (insert, update, move_away) = Tenant::Organization(seed.a)
+            .scope(async {
+                let insert =
+                    sqlx::query("INSERT INTO categories (name, organization_id) VALUES ($1, $2);")
+                        .bind("rls-intruder")
+                        .bind(seed.b)
+                        .execute(&db)
+                        .await;
+                let update = sqlx::query("UPDATE categories SET name = $1 WHERE id = $2;")
+                    .bind("rls-renamed")
+                    .bind(seed.b_category)
+                    .execute(&db)
+                    .await;
+                let move_away =
+                    sqlx::query("UPDATE categories SET organization_id = $1 WHERE id = $2;")
+                        .bind(seed.b)
+                        .bind(seed.a_category)
+                        .execute(&db)
+                        .await;
+                (insert, update, move_away)
+            })
+            .await;
+        let b_categories: Vec<(String,)> = system(async {
+            sqlx::query_as("SELECT name FROM categories WHERE organization_id = $1;")
+                .bind(seed.b)
+                .fetch_all(&db)
+                .await
+                .unwrap()
+        })
+        .await;
+        clean_up(&db, &seed).await;
+
+        assert!(insert
+            .unwrap_err()
+            .to_string()
+            .contains("row-level security"));
+        // Rows of other tenants cannot even be found to update.
+        assert_eq!(update.unwrap().rows_affected(), 0);
+        assert!(move_away
+            .unwrap_err()
+            .to_string()
+            .contains("row-level security"));
+        assert_eq!(b_categories, vec![("rls-b".to_string(),)]);
+    }
+}
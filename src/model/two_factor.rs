@@ -0,0 +1,674 @@
+use super::{
+    permissions::{has_permission, Permissions},
+    session::{
+        forget_organization_sessions, forget_user_sessions, insert_session, revoke_sessions,
+    },
+    tenant::system,
+    user::get_user_ids,
+    ModelManager,
+};
+use crate::crypt::hash_token;
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sqlx::PgConnection;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// Two-factor authentication with TOTP, the codes of authenticator apps. A user
+// enrols by scanning a secret and entering a code from it, and gets one-time
+// recovery codes for when the app is lost; only their hashes are kept. Each
+// TOTP code works once. At login a user with two-factor authentication, or
+// who must set it up because their organization requires it, first gets a
+// pending session, which lets them do nothing but give a code. Pending
+// sessions are known before the organization is, so they work across
+// organizations.
+
+const ISSUER: &str = "Distrupify";
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const SECRET_BYTES: usize = 20;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_BYTES: usize = 8;
+
+/// Wrong codes a pending session takes before it ends.
+const MAX_PENDING_ATTEMPTS: i32 = 5;
+
+// region: Structs
+/// What a user whose password checked out still has to do.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoginStep {
+    Granted,
+    VerifyCode,
+    /// Their organization requires two-factor authentication.
+    Enrol,
+}
+
+/// A secret to scan, not in use until a code from it is entered.
+#[derive(Debug)]
+pub struct TwoFactorEnrolment {
+    /// Base32, for apps that cannot scan.
+    pub secret: String,
+    pub otpauth_url: String,
+}
+
+#[derive(Debug)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub required: bool,
+    pub recovery_codes_left: i64,
+}
+
+/// A full session, after a pending one.
+#[derive(Debug)]
+pub struct CompletedLogin {
+    pub token: String,
+    /// None for a superuser.
+    pub organization_id: Option<i64>,
+    /// New codes when the user just enrolled, shown once.
+    pub recovery_codes: Vec<String>,
+}
+
+struct TwoFactorUser {
+    id: i64,
+    username: String,
+    organization_id: Option<i64>,
+    organization_name: Option<String>,
+    two_factor_required: bool,
+    totp_secret: Option<String>,
+    totp_enabled_at: Option<DateTime<Utc>>,
+    totp_last_step: Option<i64>,
+}
+
+impl TwoFactorUser {
+    fn enabled(&self) -> bool {
+        self.totp_enabled_at.is_some()
+    }
+}
+// endregion: Structs
+
+// region: Methods
+/// What a user who just gave their password has to do before they are logged
+/// in.
+pub async fn get_login_step(_ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<LoginStep> {
+    system(async {
+        let db = mm.db();
+        let mut conn = db.acquire().await?;
+
+        let user = two_factor_user(&mut conn, user_id, false).await?;
+        let step = if user.enabled() {
+            LoginStep::VerifyCode
+        } else if user.two_factor_required {
+            LoginStep::Enrol
+        } else {
+            LoginStep::Granted
+        };
+
+        Ok(step)
+    })
+    .await
+}
+
+/// What the user of a pending session has to do.
+pub async fn get_pending_login_step(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    token: &str,
+) -> Result<LoginStep> {
+    system(async {
+        let db = mm.db();
+        let mut tx = db.begin().await?;
+
+        let (_, user) = lock_pending_session(&mut tx, token).await?;
+        let step = match user.enabled() {
+            true => LoginStep::VerifyCode,
+            false => LoginStep::Enrol,
+        };
+
+        Ok(step)
+    })
+    .await
+}
+
+/// The secret the user of a pending session enrols with, which stays the same
+/// until they do.
+pub async fn start_pending_enrolment(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    token: &str,
+) -> Result<TwoFactorEnrolment> {
+    system(async {
+        let db = mm.db();
+        let mut tx = db.begin().await?;
+
+        let (_, user) = lock_pending_session(&mut tx, token).await?;
+        if user.enabled() {
+            return Err(Error::TwoFactorAlreadyEnabled);
+        }
+        let enrolment = enrolment(&mut tx, &user, true).await?;
+
+        tx.commit().await?;
+
+        Ok(enrolment)
+    })
+    .await
+}
+
+/// Swaps a pending session for a full one once the code checks out. A user
+/// who was enrolling is enrolled, and gets their recovery codes.
+pub async fn complete_pending_login(
+    _ctx: &Ctx,
+    mm: &ModelManager,
+    token: &str,
+    code: &str,
+) -> Result<CompletedLogin> {
+    system(async {
+        let db = mm.db();
+        let mut tx = db.begin().await?;
+
+        let (session_id, user) = lock_pending_session(&mut tx, token).await?;
+
+        let checked = match user.enabled() {
+            true => check_code(&mut tx, &user, code).await,
+            false => confirm_enrolment(&mut tx, &user, code).await,
+        };
+        let recovery_codes = match checked {
+            Ok(recovery_codes) => recovery_codes,
+            Err(Error::InvalidTwoFactorCode) => {
+                count_failed_attempt(&mut tx, session_id).await?;
+                tx.commit().await?;
+                return Err(Error::InvalidTwoFactorCode);
+            }
+            Err(e) => return Err(e),
+        };
+
+        sqlx::query!("DELETE FROM sessions WHERE id = $1;", session_id)
+            .execute(&mut *tx)
+            .await?;
+        let token = insert_session(&mut tx, user.id).await?;
+
+        tx.commit().await?;
+
+        Ok(CompletedLogin {
+            token,
+            organization_id: user.organization_id,
+            recovery_codes,
+        })
+    })
+    .await
+}
+
+/// Whether the current user has two-factor authentication.
+pub async fn get_two_factor_status(ctx: &Ctx, mm: &ModelManager) -> Result<TwoFactorStatus> {
+    let db = mm.db();
+    let user_id = current_user_id(ctx)?;
+
+    let mut conn = db.acquire().await?;
+    let user = two_factor_user(&mut conn, user_id, false).await?;
+
+    let recovery_codes_left = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!"
+        FROM user_recovery_codes
+        WHERE user_id = $1
+        AND used_at IS NULL;"#,
+        user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(TwoFactorStatus {
+        enabled: user.enabled(),
+        required: user.two_factor_required,
+        recovery_codes_left,
+    })
+}
+
+/// A new secret for the current user to enrol with.
+pub async fn start_two_factor_enrolment(
+    ctx: &Ctx,
+    mm: &ModelManager,
+) -> Result<TwoFactorEnrolment> {
+    let db = mm.db();
+    let user_id = current_user_id(ctx)?;
+
+    let mut tx = db.begin().await?;
+
+    let user = two_factor_user(&mut tx, user_id, true).await?;
+    if user.enabled() {
+        return Err(Error::TwoFactorAlreadyEnabled);
+    }
+    let enrolment = enrolment(&mut tx, &user, false).await?;
+
+    tx.commit().await?;
+
+    Ok(enrolment)
+}
+
+/// Enrols the current user once a code from their new secret checks out.
+/// Returns their recovery codes, which are not shown again.
+pub async fn confirm_two_factor_enrolment(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    code: &str,
+) -> Result<Vec<String>> {
+    let db = mm.db();
+    let user_id = current_user_id(ctx)?;
+
+    let mut tx = db.begin().await?;
+
+    let user = two_factor_user(&mut tx, user_id, true).await?;
+    if user.enabled() {
+        return Err(Error::TwoFactorAlreadyEnabled);
+    }
+    let recovery_codes = confirm_enrolment(&mut tx, &user, code).await?;
+
+    tx.commit().await?;
+
+    Ok(recovery_codes)
+}
+
+/// Replaces the current user's recovery codes, given a code.
+pub async fn regenerate_recovery_codes(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    code: &str,
+) -> Result<Vec<String>> {
+    let db = mm.db();
+    let user_id = current_user_id(ctx)?;
+
+    let mut tx = db.begin().await?;
+
+    let user = two_factor_user(&mut tx, user_id, true).await?;
+    check_code(&mut tx, &user, code).await?;
+    let recovery_codes = replace_recovery_codes(&mut tx, user_id).await?;
+
+    tx.commit().await?;
+
+    Ok(recovery_codes)
+}
+
+/// Turns two-factor authentication off for the current user, given a code,
+/// unless their organization requires it.
+pub async fn disable_two_factor(ctx: &Ctx, mm: &ModelManager, code: &str) -> Result<()> {
+    let db = mm.db();
+    let user_id = current_user_id(ctx)?;
+
+    let mut tx = db.begin().await?;
+
+    let user = two_factor_user(&mut tx, user_id, true).await?;
+    if user.two_factor_required {
+        return Err(Error::TwoFactorRequired);
+    }
+    check_code(&mut tx, &user, code).await?;
+    clear_two_factor(&mut tx, user_id).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Takes two-factor authentication off a user who lost their authenticator
+/// and recovery codes, and logs them out everywhere. They set it up again at
+/// their next login if the organization requires it. Admins only.
+pub async fn reset_two_factor(ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let mut tx = db.begin().await?;
+
+    let user = two_factor_user(&mut tx, user_id, true).await?;
+    if user.organization_id != Some(organization_id) {
+        return Err(Error::UserNotFound { user_id });
+    }
+    clear_two_factor(&mut tx, user_id).await?;
+    revoke_sessions(&mut tx, user_id).await?;
+
+    tx.commit().await?;
+    forget_user_sessions(mm, user_id);
+
+    Ok(())
+}
+
+/// Makes everyone in the organization use two-factor authentication, or not.
+/// Requiring it logs out those who have not set it up, which the admin must
+/// have done first. Admins only.
+pub async fn set_two_factor_required(ctx: &Ctx, mm: &ModelManager, required: bool) -> Result<()> {
+    let db = mm.db();
+    let (user_id, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let mut tx = db.begin().await?;
+
+    if required {
+        let user_id =
+            user_id.ok_or_else(|| Error::Unauhtorized("User id not found".to_string()))?;
+        if !two_factor_user(&mut tx, user_id, false).await?.enabled() {
+            return Err(Error::TwoFactorSetupRequired);
+        }
+
+        sqlx::query!(
+            r#"DELETE FROM sessions
+            WHERE user_id IN (
+                SELECT id FROM users
+                WHERE organization_id = $1
+                AND totp_enabled_at IS NULL
+            );"#,
+            organization_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query!(
+        "UPDATE organizations SET two_factor_required = $1 WHERE id = $2;",
+        required,
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    if required {
+        forget_organization_sessions(mm, organization_id);
+    }
+
+    Ok(())
+}
+
+/// Superusers have no organization, but may use two-factor authentication
+/// too.
+fn current_user_id(ctx: &Ctx) -> Result<i64> {
+    ctx.user_id()
+        .ok_or_else(|| Error::Unauhtorized("User id not found".to_string()))
+}
+
+async fn two_factor_user(
+    conn: &mut PgConnection,
+    user_id: i64,
+    for_update: bool,
+) -> Result<TwoFactorUser> {
+    // Locked before it is read, so a code checked against `totp_last_step`
+    // here cannot also be accepted by a request holding an older read.
+    if for_update {
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE;", user_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    }
+
+    let user = sqlx::query_as!(
+        TwoFactorUser,
+        r#"SELECT
+            u.id,
+            u.username,
+            u.organization_id,
+            o.name as "organization_name?",
+            COALESCE(o.two_factor_required, FALSE) as "two_factor_required!",
+            u.totp_secret,
+            u.totp_enabled_at,
+            u.totp_last_step
+        FROM users u
+        LEFT JOIN organizations o
+        ON o.id = u.organization_id
+        WHERE u.id = $1;"#,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::UserNotFound { user_id })?;
+
+    Ok(user)
+}
+
+/// The pending session of `token` and its user, held until the transaction
+/// ends.
+async fn lock_pending_session(
+    conn: &mut PgConnection,
+    token: &str,
+) -> Result<(i64, TwoFactorUser)> {
+    let session = sqlx::query!(
+        r#"SELECT s.id, s.user_id
+        FROM sessions s
+        JOIN users u
+        ON u.id = s.user_id
+        WHERE s.token_hash = $1
+        AND s.two_factor_pending
+        AND s.expires_at > CURRENT_TIMESTAMP
+        AND u.active
+        FOR UPDATE OF s;"#,
+        hash_token(token)
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| Error::Unauhtorized("Pending session not found".to_string()))?;
+
+    let user = two_factor_user(conn, session.user_id, true).await?;
+
+    Ok((session.id, user))
+}
+
+/// Ends the pending session after too many wrong codes.
+async fn count_failed_attempt(conn: &mut PgConnection, session_id: i64) -> Result<()> {
+    let session = sqlx::query!(
+        r#"UPDATE sessions
+        SET two_factor_attempts = two_factor_attempts + 1
+        WHERE id = $1
+        RETURNING two_factor_attempts;"#,
+        session_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if session.two_factor_attempts >= MAX_PENDING_ATTEMPTS {
+        sqlx::query!("DELETE FROM sessions WHERE id = $1;", session_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The user's secret waiting to be confirmed, a new one unless `reuse`.
+async fn enrolment(
+    conn: &mut PgConnection,
+    user: &TwoFactorUser,
+    reuse: bool,
+) -> Result<TwoFactorEnrolment> {
+    let secret = match (&user.totp_secret, reuse) {
+        (Some(secret), true) => secret.clone(),
+        _ => {
+            let mut bytes = [0u8; SECRET_BYTES];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let secret = Secret::Raw(bytes.to_vec()).to_encoded().to_string();
+
+            sqlx::query!(
+                "UPDATE users SET totp_secret = $1, totp_last_step = NULL WHERE id = $2;",
+                secret,
+                user.id
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            secret
+        }
+    };
+
+    Ok(TwoFactorEnrolment {
+        otpauth_url: totp(user, &secret)?.get_url(),
+        secret,
+    })
+}
+
+/// Enables the secret waiting to be confirmed if `code` comes from it.
+/// Returns new recovery codes.
+async fn confirm_enrolment(
+    conn: &mut PgConnection,
+    user: &TwoFactorUser,
+    code: &str,
+) -> Result<Vec<String>> {
+    let secret = user
+        .totp_secret
+        .as_deref()
+        .ok_or(Error::InvalidTwoFactorCode)?;
+    let step =
+        accepted_step(&totp(user, secret)?, code, None).ok_or(Error::InvalidTwoFactorCode)?;
+
+    sqlx::query!(
+        r#"UPDATE users
+        SET totp_enabled_at = CURRENT_TIMESTAMP, totp_last_step = $1
+        WHERE id = $2;"#,
+        step,
+        user.id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    replace_recovery_codes(conn, user.id).await
+}
+
+/// Accepts a TOTP code, or uses up a recovery code. Returns no new recovery
+/// codes.
+async fn check_code(
+    conn: &mut PgConnection,
+    user: &TwoFactorUser,
+    code: &str,
+) -> Result<Vec<String>> {
+    let secret = match (&user.totp_secret, user.enabled()) {
+        (Some(secret), true) => secret,
+        _ => return Err(Error::InvalidTwoFactorCode),
+    };
+
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+        let step = accepted_step(&totp(user, secret)?, &code, user.totp_last_step)
+            .ok_or(Error::InvalidTwoFactorCode)?;
+        // Only ever moves forward, so each code works once.
+        let result = sqlx::query!(
+            r#"UPDATE users
+            SET totp_last_step = $1
+            WHERE id = $2
+            AND (totp_last_step IS NULL OR totp_last_step < $1);"#,
+            step,
+            user.id
+        )
+        .execute(&mut *conn)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::InvalidTwoFactorCode);
+        }
+    } else {
+        let result = sqlx::query!(
+            r#"UPDATE user_recovery_codes
+            SET used_at = CURRENT_TIMESTAMP
+            WHERE user_id = $1
+            AND code_hash = $2
+            AND used_at IS NULL;"#,
+            user.id,
+            hash_token(&normalize_recovery_code(&code))
+        )
+        .execute(&mut *conn)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(Error::InvalidTwoFactorCode);
+        }
+    }
+
+    Ok(Vec::new())
+}
+
+async fn clear_two_factor(conn: &mut PgConnection, user_id: i64) -> Result<()> {
+    sqlx::query!(
+        r#"UPDATE users
+        SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL
+        WHERE id = $1;"#,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM user_recovery_codes WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the new codes; the old ones stop working.
+async fn replace_recovery_codes(conn: &mut PgConnection, user_id: i64) -> Result<Vec<String>> {
+    sqlx::query!(
+        "DELETE FROM user_recovery_codes WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let mut bytes = [0u8; RECOVERY_CODE_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let code = hex::encode(bytes);
+
+        sqlx::query!(
+            r#"INSERT INTO user_recovery_codes (user_id, code_hash)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING;"#,
+            user_id,
+            hash_token(&code)
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        // Shown in groups of four, which are ignored when entered.
+        let groups: Vec<&str> = (0..code.len())
+            .step_by(4)
+            .map(|i| &code[i..i + 4])
+            .collect();
+        codes.push(groups.join("-"));
+    }
+
+    Ok(codes)
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+fn totp(user: &TwoFactorUser, secret: &str) -> Result<TOTP> {
+    let account = match &user.organization_name {
+        Some(organization) => format!("{}@{organization}", user.username),
+        None => user.username.clone(),
+    }
+    .replace(':', "_");
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| Error::Totp(format!("{e:?}")))?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(ISSUER.to_string()),
+        account,
+    )
+    .map_err(|e| Error::Totp(e.to_string()))
+}
+
+/// The time step `code` is for, allowing for a clock a step off either way,
+/// if it is later than `last_step`.
+fn accepted_step(totp: &TOTP, code: &str, last_step: Option<i64>) -> Option<i64> {
+    let current = Utc::now().timestamp() / TOTP_STEP_SECONDS as i64;
+
+    (current - 1..=current + 1)
+        .filter(|step| last_step.is_none_or(|last_step| *step > last_step))
+        .find(|step| totp.check(code, *step as u64 * TOTP_STEP_SECONDS))
+}
+// endregion: Methods
@@ -0,0 +1,217 @@
+use super::{inventory_log::InventoryLogForCreate, user::get_user_ids, ModelManager};
+use crate::ctx::Ctx;
+use crate::model::{Error, Result};
+use serde::Deserialize;
+use sqlx::PgConnection;
+
+// region: Structs
+#[derive(Debug)]
+pub struct ProductUnit {
+    pub id: i64,
+    pub name: String,
+    /// Number of base units in one of this unit, e.g. 24 for a case of 24.
+    pub conversion_factor: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductUnitForCreate {
+    pub name: String,
+    pub conversion_factor: i64,
+}
+// endregion: Structs
+
+// region: Methods
+pub async fn get_product_units(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+) -> Result<Vec<ProductUnit>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let units = sqlx::query_as!(
+        ProductUnit,
+        r#"SELECT pu.id, pu.name, pu.conversion_factor
+        FROM product_units pu
+        JOIN products p
+        ON p.id = pu.product_id
+        WHERE pu.product_id = $1
+        AND p.organization_id = $2
+        ORDER BY pu.conversion_factor, pu.name;"#,
+        product_id,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(units)
+}
+
+pub async fn create_product_unit(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+    product_unit_for_create: ProductUnitForCreate,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let ProductUnitForCreate {
+        name,
+        conversion_factor,
+    } = product_unit_for_create;
+
+    let name = name.trim();
+    if name.is_empty() || conversion_factor < 1 {
+        return Err(Error::InvalidUnit {
+            product_id,
+            unit: name.to_string(),
+        });
+    }
+
+    let result = sqlx::query!(
+        r#"INSERT INTO product_units (product_id, name, conversion_factor)
+        SELECT id, $2::varchar, $3 FROM products
+        WHERE id = $1
+        AND organization_id = $4
+        AND base_unit <> $2::varchar
+        ON CONFLICT (product_id, name) DO NOTHING;"#,
+        product_id,
+        name,
+        conversion_factor,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::InvalidUnit {
+            product_id,
+            unit: name.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+pub async fn delete_product_unit(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    product_id: i64,
+    unit_id: i64,
+) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    sqlx::query!(
+        r#"DELETE FROM product_units pu
+        USING products p
+        WHERE p.id = pu.product_id
+        AND pu.id = $1
+        AND pu.product_id = $2
+        AND p.organization_id = $3;"#,
+        unit_id,
+        product_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Converts every log given in an alternative unit to the product's base unit.
+/// The original unit and quantity are kept on the log for display.
+pub(in crate::model) async fn resolve_units(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    logs: Vec<InventoryLogForCreate>,
+) -> Result<Vec<InventoryLogForCreate>> {
+    let mut resolved = Vec::with_capacity(logs.len());
+
+    for log in logs {
+        let unit_name = log
+            .unit_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|u| !u.is_empty());
+
+        let Some(unit_name) = unit_name else {
+            resolved.push(InventoryLogForCreate {
+                unit_name: None,
+                unit_quantity: None,
+                unit_conversion_factor: None,
+                ..log
+            });
+            continue;
+        };
+
+        let conversion_factor =
+            conversion_factor(&mut *conn, organization_id, log.product_id, unit_name).await?;
+
+        let unit_name = unit_name.to_string();
+        resolved.push(InventoryLogForCreate {
+            quantity: base_quantity(log.product_id, log.quantity, conversion_factor)?,
+            price: log.price.per_unit(conversion_factor),
+            unit_name: Some(unit_name),
+            unit_quantity: Some(log.quantity),
+            unit_conversion_factor: Some(conversion_factor),
+            ..log
+        });
+    }
+
+    Ok(resolved)
+}
+
+/// Number of base units in one `unit_name` of the product.
+/// `quantity` units of `conversion_factor` in the base unit, as long as an
+/// inventory log can hold it.
+pub(in crate::model) fn base_quantity(
+    product_id: i64,
+    quantity: i64,
+    conversion_factor: i64,
+) -> Result<i64> {
+    quantity
+        .checked_mul(conversion_factor)
+        .filter(|base_quantity| i32::try_from(*base_quantity).is_ok())
+        .ok_or(Error::QuantityTooLarge {
+            product_id,
+            quantity,
+        })
+}
+
+pub(in crate::model) async fn conversion_factor(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    product_id: i64,
+    unit_name: &str,
+) -> Result<i64> {
+    let unit = sqlx::query!(
+        r#"SELECT
+            p.base_unit,
+            pu.name as "name?",
+            pu.conversion_factor as "conversion_factor?"
+        FROM products p
+        LEFT JOIN product_units pu
+        ON pu.product_id = p.id
+        AND pu.name = $2
+        WHERE p.id = $1
+        AND p.organization_id = $3;"#,
+        product_id,
+        unit_name,
+        organization_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::ProductNotFound { product_id })?;
+
+    match (unit.conversion_factor, unit.base_unit == unit_name) {
+        (_, true) => Ok(1),
+        (Some(conversion_factor), false) => Ok(conversion_factor),
+        (None, false) => Err(Error::InvalidUnit {
+            product_id,
+            unit: unit_name.to_string(),
+        }),
+    }
+}
+// endregion: Methods
@@ -1,102 +1,481 @@
 use crate::crypt;
 use crate::ctx::Ctx;
-use crate::model::common::RowWithId;
 use crate::model::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgConnection;
+use std::net::IpAddr;
 
+use super::login_attempt::{
+    check_login_allowed, clear_failed_logins, count_failed_login, record_login_attempt,
+    LoginAttemptForCreate, LoginOutcome,
+};
+use super::organization::{OrganizationState, OrganizationStatus};
+use super::permissions::{has_permission, Permissions};
+use super::session::{forget_user_sessions, revoke_sessions};
+use super::tenant::system;
 use super::ModelManager;
 
+// An organization always keeps an active admin: changes that could remove the
+// last one lock the organization's users and count the others first.
+
+/// Shortest password a user may set.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Compared against when a login names no user, so that takes as long as a
+/// wrong password.
+const TIMING_HASH: &str = "$2b$12$e1RNRrjdu7b6jeg0AMN.9u3TgvfeqjSdc8uqGdIkdmRs6jh7JU0hi";
+
 // region: Structs
-pub struct UserForCreate {
+#[derive(Debug)]
+pub struct User {
+    pub id: i64,
     pub display_name: String,
     pub username: String,
-    pub password: String,
-    pub organization_id: i64,
+    pub active: bool,
+    /// Has `organization:*`.
+    pub admin: bool,
+    pub override_price: bool,
+    pub two_factor_enabled: bool,
+    pub session_count: i64,
+    /// Set while locked out after too many failed logins.
+    pub locked_until: Option<DateTime<Utc>>,
 }
 
+/// The permissions an admin can give within the organization.
+#[derive(Debug, Deserialize)]
+pub struct UserRoles {
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(default)]
+    pub override_price: bool,
+}
+
+struct OrganizationUser {
+    active: bool,
+    admin: bool,
+}
 // endregion: Structs
 
 // region: Methods
-pub async fn create_user(
+/// The current user, None for an API key, and their organization. The auth
+/// layer verified both, and that the organization is active, when it loaded
+/// the request's session or key.
+pub async fn get_user_ids(ctx: &Ctx, _mm: &ModelManager) -> Result<(Option<i64>, i64)> {
+    let organization_id = ctx
+        .organization_id()
+        .ok_or_else(|| Error::Unauhtorized("Organization id not found".to_string()))?;
+    if ctx.user_id().is_none() && ctx.api_key_id().is_none() {
+        return Err(Error::Unauhtorized("User id not found".to_string()));
+    }
+
+    Ok((ctx.user_id(), organization_id))
+}
+
+/// The current user, if they are a superuser.
+pub(in crate::model) async fn get_superuser_id(ctx: &Ctx, _mm: &ModelManager) -> Result<i64> {
+    let user_id = ctx
+        .user_id()
+        .ok_or_else(|| Error::Unauhtorized("User id not found".to_string()))?;
+    has_permission(ctx, Permissions::SuperUser)?;
+
+    Ok(user_id)
+}
+
+/// The user `username` of the organization named `organization`, or a
+/// superuser if `organization` is empty. Fails the same way whatever is
+/// wrong, so logins do not reveal which users exist; only with the right
+/// password are users told that their organization is suspended. Names that
+/// failed too often in a row are throttled or locked out before the password
+/// is checked. Every attempt is recorded with the address it came from.
+/// Looks across organizations.
+pub async fn authenticate(
     _ctx: &Ctx,
     mm: &ModelManager,
-    user_for_create: UserForCreate,
+    organization: &str,
+    username: &str,
+    password: &str,
+    ip: IpAddr,
 ) -> Result<i64> {
+    system(async {
+        let db = mm.db();
+        let (organization, username) = (organization.trim(), username.trim());
+
+        let mut tx = db.begin().await?;
+
+        let user = sqlx::query!(
+            r#"SELECT
+                u.id,
+                u.password,
+                u.organization_id,
+                o.status as "status?: OrganizationStatus",
+                o.status_reason,
+                o.deletion_due_at
+            FROM users u
+            LEFT JOIN organizations o
+            ON o.id = u.organization_id
+            WHERE u.username = $2
+            AND u.active
+            AND (o.name = $1 OR ($1 = '' AND u.organization_id IS NULL));"#,
+            organization,
+            username
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let organization_id = match &user {
+            Some(user) => user.organization_id,
+            None => {
+                sqlx::query_scalar!(
+                    "SELECT id FROM organizations WHERE name = $1;",
+                    organization
+                )
+                .fetch_optional(&mut *tx)
+                .await?
+            }
+        };
+        let attempt = || LoginAttemptForCreate {
+            organization_name: organization,
+            username,
+            organization_id,
+            user_id: user.as_ref().map(|user| user.id),
+            ip,
+        };
+
+        if let Err(e) = check_login_allowed(&mut tx, organization, username).await {
+            let outcome = match e {
+                Error::LoginLocked { .. } => LoginOutcome::Locked,
+                _ => LoginOutcome::Throttled,
+            };
+            record_login_attempt(&mut tx, attempt(), outcome).await?;
+            tx.commit().await?;
+            return Err(e);
+        }
+
+        let user_id = match &user {
+            Some(user) => crypt::compare_hash(&user.password, password)
+                .ok()
+                .map(|_| user.id),
+            None => {
+                let _ = crypt::compare_hash(TIMING_HASH, password);
+                None
+            }
+        };
+        let Some(user_id) = user_id else {
+            count_failed_login(&mut tx, organization, username).await?;
+            record_login_attempt(&mut tx, attempt(), LoginOutcome::Failed).await?;
+            tx.commit().await?;
+            return Err(Error::Unauhtorized("Invalid credentials".to_string()));
+        };
+
+        clear_failed_logins(&mut tx, organization, username).await?;
+        record_login_attempt(&mut tx, attempt(), LoginOutcome::Succeeded).await?;
+        tx.commit().await?;
+
+        if let Some(user) = user {
+            if let (Some(organization_id), Some(status)) = (user.organization_id, user.status) {
+                OrganizationState {
+                    status,
+                    status_reason: user.status_reason,
+                    deletion_due_at: user.deletion_due_at,
+                }
+                .ensure_active(organization_id)?;
+            }
+        }
+
+        Ok(user_id)
+    })
+    .await
+}
+
+/// The organization's users. Admins only.
+pub async fn get_users(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<User>> {
     let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
 
-    let user = sqlx::query_as!(
-        RowWithId,
-        "INSERT INTO users (display_name, username, password, organization_id) \
-          VALUES ($1, $2, $3, $4)\
-          RETURNING id;",
-        user_for_create.display_name,
-        user_for_create.username,
-        user_for_create.password,
-        user_for_create.organization_id
+    let users = sqlx::query_as!(
+        User,
+        r#"SELECT
+            u.id,
+            u.display_name,
+            u.username,
+            u.active,
+            EXISTS(
+                SELECT 1 FROM user_permissions up
+                WHERE up.user_id = u.id AND up.permission_id = $2
+            ) as "admin!",
+            EXISTS(
+                SELECT 1 FROM user_permissions up
+                WHERE up.user_id = u.id AND up.permission_id = $3
+            ) as "override_price!",
+            u.totp_enabled_at IS NOT NULL as "two_factor_enabled!",
+            (
+                SELECT COUNT(*) FROM sessions s
+                WHERE s.user_id = u.id
+                AND s.expires_at > CURRENT_TIMESTAMP
+                AND NOT s.two_factor_pending
+            ) as "session_count!",
+            (
+                SELECT l.locked_until FROM login_lockouts l
+                JOIN organizations o
+                ON o.name = l.organization_name
+                WHERE o.id = u.organization_id
+                AND l.username = u.username
+                AND l.locked_until > CURRENT_TIMESTAMP
+            ) as locked_until
+        FROM users u
+        WHERE u.organization_id = $1
+        ORDER BY u.active DESC, u.username;"#,
+        organization_id,
+        Permissions::OrganizationAll as i64,
+        Permissions::OverridePrice as i64
     )
-    .fetch_one(db)
+    .fetch_all(db)
     .await?;
 
-    Ok(user.id)
+    Ok(users)
+}
+
+/// Blocks a user from logging in and ends their sessions.
+pub async fn deactivate_user(ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let mut tx = db.begin().await?;
+
+    lock_users(&mut tx, organization_id).await?;
+    let user = organization_user(&mut tx, organization_id, user_id).await?;
+    if user.active && user.admin {
+        ensure_other_active_admin(&mut tx, organization_id, user_id).await?;
+    }
+
+    sqlx::query!("UPDATE users SET active = FALSE WHERE id = $1;", user_id)
+        .execute(&mut *tx)
+        .await?;
+    revoke_sessions(&mut tx, user_id).await?;
+
+    tx.commit().await?;
+    forget_user_sessions(mm, user_id);
+
+    Ok(())
 }
 
-pub async fn verify_password(
-    _ctx: Ctx,
+pub async fn reactivate_user(ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let result = sqlx::query!(
+        "UPDATE users SET active = TRUE WHERE id = $1 AND organization_id = $2;",
+        user_id,
+        organization_id
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::UserNotFound { user_id });
+    }
+
+    Ok(())
+}
+
+/// Lifts the lockout of a user after too many failed logins, and lets them
+/// try again right away.
+pub async fn unlock_user(ctx: &Ctx, mm: &ModelManager, user_id: i64) -> Result<()> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
+
+    let mut tx = db.begin().await?;
+
+    let user = sqlx::query!(
+        r#"SELECT u.username, o.name as organization_name
+        FROM users u
+        JOIN organizations o
+        ON o.id = u.organization_id
+        WHERE u.id = $1
+        AND u.organization_id = $2;"#,
+        user_id,
+        organization_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::UserNotFound { user_id })?;
+
+    clear_failed_logins(&mut tx, &user.organization_name, &user.username).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn update_user_roles(
+    ctx: &Ctx,
     mm: &ModelManager,
-    id: i64,
-    password: String,
+    user_id: i64,
+    roles: UserRoles,
 ) -> Result<()> {
     let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
 
-    let password_hash = sqlx::query!(r#"SELECT password FROM users WHERE id = $1;"#, id)
-        .fetch_one(db)
-        .await?
-        .password;
+    let mut tx = db.begin().await?;
 
-    match crypt::compare_hash(&password_hash, &password) {
-        Err(_) => Err(Error::Unauhtorized("Password does not match".to_string())),
-        Ok(_) => Ok(()),
+    lock_users(&mut tx, organization_id).await?;
+    let user = organization_user(&mut tx, organization_id, user_id).await?;
+    if user.active && user.admin && !roles.admin {
+        ensure_other_active_admin(&mut tx, organization_id, user_id).await?;
     }
+
+    set_permission(&mut tx, user_id, Permissions::OrganizationAll, roles.admin).await?;
+    set_permission(
+        &mut tx,
+        user_id,
+        Permissions::OverridePrice,
+        roles.override_price,
+    )
+    .await?;
+
+    tx.commit().await?;
+    forget_user_sessions(mm, user_id);
+
+    Ok(())
 }
 
-pub async fn verify_organization(
-    _ctx: &Ctx,
+/// Sets a user's password and ends their sessions.
+pub async fn reset_password(
+    ctx: &Ctx,
     mm: &ModelManager,
     user_id: i64,
-    org_id: i64,
+    password: &str,
 ) -> Result<()> {
     let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+    has_permission(ctx, Permissions::OrganizationAll)?;
 
-    let query_result = sqlx::query!("SELECT organization_id FROM users WHERE id = $1;", user_id)
-        .fetch_one(db)
-        .await?;
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Error::InvalidPassword);
+    }
+    let password = crypt::hash_value(password)?;
 
-    match query_result.organization_id {
-        None => Err(Error::Unauhtorized("Organization id not found".to_string())),
-        Some(result_id) => {
-            if result_id != org_id {
-                return Err(Error::Unauhtorized("Invalid organization id".to_string()));
-            }
+    let mut tx = db.begin().await?;
 
-            Ok(())
-        }
+    let result = sqlx::query!(
+        "UPDATE users SET password = $1 WHERE id = $2 AND organization_id = $3;",
+        password,
+        user_id,
+        organization_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::UserNotFound { user_id });
     }
+    revoke_sessions(&mut tx, user_id).await?;
+
+    tx.commit().await?;
+    forget_user_sessions(mm, user_id);
+
+    Ok(())
 }
 
-pub async fn get_user_ids(ctx: &Ctx, mm: &ModelManager) -> Result<(i64, i64)> {
-    let db = mm.db();
+/// Holds the organization's users until the transaction ends.
+async fn lock_users(conn: &mut PgConnection, organization_id: i64) -> Result<()> {
+    sqlx::query!(
+        "SELECT id FROM users WHERE organization_id = $1 FOR UPDATE;",
+        organization_id
+    )
+    .fetch_all(conn)
+    .await?;
 
-    let user_id = ctx
-        .user_id()
-        .ok_or_else(|| Error::Unauhtorized("User id not found".to_string()))?;
+    Ok(())
+}
 
-    let query_result = sqlx::query!("SELECT organization_id FROM users WHERE id = $1;", user_id)
-        .fetch_one(db)
-        .await?;
+async fn organization_user(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    user_id: i64,
+) -> Result<OrganizationUser> {
+    sqlx::query_as!(
+        OrganizationUser,
+        r#"SELECT
+            u.active,
+            EXISTS(
+                SELECT 1 FROM user_permissions up
+                WHERE up.user_id = u.id AND up.permission_id = $3
+            ) as "admin!"
+        FROM users u
+        WHERE u.id = $1
+        AND u.organization_id = $2;"#,
+        user_id,
+        organization_id,
+        Permissions::OrganizationAll as i64
+    )
+    .fetch_optional(conn)
+    .await?
+    .ok_or(Error::UserNotFound { user_id })
+}
+
+async fn ensure_other_active_admin(
+    conn: &mut PgConnection,
+    organization_id: i64,
+    user_id: i64,
+) -> Result<()> {
+    let others = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!"
+        FROM users u
+        WHERE u.organization_id = $1
+        AND u.id <> $2
+        AND u.active
+        AND EXISTS(
+            SELECT 1 FROM user_permissions up
+            WHERE up.user_id = u.id AND up.permission_id = $3
+        );"#,
+        organization_id,
+        user_id,
+        Permissions::OrganizationAll as i64
+    )
+    .fetch_one(conn)
+    .await?;
 
-    match query_result.organization_id {
-        None => Err(Error::Unauhtorized("Organization id not found".to_string())),
-        Some(org_id) => Ok((user_id, org_id)),
+    match others.count {
+        0 => Err(Error::LastActiveAdmin { user_id }),
+        _ => Ok(()),
     }
 }
 
+async fn set_permission(
+    conn: &mut PgConnection,
+    user_id: i64,
+    permission: Permissions,
+    granted: bool,
+) -> Result<()> {
+    match granted {
+        true => {
+            sqlx::query!(
+                r#"INSERT INTO user_permissions (user_id, permission_id)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, permission_id) DO NOTHING;"#,
+                user_id,
+                permission as i64
+            )
+            .execute(conn)
+            .await?
+        }
+        false => {
+            sqlx::query!(
+                "DELETE FROM user_permissions WHERE user_id = $1 AND permission_id = $2;",
+                user_id,
+                permission as i64
+            )
+            .execute(conn)
+            .await?
+        }
+    };
+
+    Ok(())
+}
 // endregion: Methods
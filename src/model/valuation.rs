@@ -0,0 +1,250 @@
+use super::{organization::get_base_currency, user::get_user_ids, ModelManager};
+use crate::ctx::Ctx;
+use crate::model::Result;
+use crate::money::{Currency, Money};
+use chrono::NaiveDate;
+use sqlx::types::BigDecimal;
+
+// Costs are the weighted average of what was paid for the stock received up
+// to the end of the period, in the base currency: deposits and the opening
+// balance count, sales rollbacks (which carry the selling price) do not.
+
+// region: Structs
+struct ProductCostForDbResult {
+    product_id: i64,
+    sku: String,
+    display_name: String,
+    quantity: i64,
+    revenue: BigDecimal,
+    cost_total: BigDecimal,
+    cost_quantity: i64,
+    currency: Currency,
+}
+
+impl ProductCostForDbResult {
+    fn average_cost(&self) -> Money {
+        match self.cost_quantity {
+            0 => Money::zero(self.currency),
+            cost_quantity => {
+                Money::new(self.cost_total.clone(), self.currency).per_unit(cost_quantity)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProductValuation {
+    pub product_id: i64,
+    pub sku: String,
+    pub display_name: String,
+    pub quantity: i64,
+    pub average_cost: Money,
+    pub value: Money,
+}
+
+impl From<ProductCostForDbResult> for ProductValuation {
+    fn from(value: ProductCostForDbResult) -> Self {
+        let average_cost = value.average_cost();
+        Self {
+            product_id: value.product_id,
+            sku: value.sku,
+            display_name: value.display_name,
+            quantity: value.quantity,
+            value: average_cost.times(value.quantity),
+            average_cost,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InventoryValuation {
+    pub as_of: NaiveDate,
+    pub currency: Currency,
+    pub products: Vec<ProductValuation>,
+    pub total_value: Money,
+}
+
+#[derive(Debug)]
+pub struct ProductCostOfGoodsSold {
+    pub product_id: i64,
+    pub sku: String,
+    pub display_name: String,
+    /// Sold less rolled back.
+    pub quantity: i64,
+    pub revenue: Money,
+    pub average_cost: Money,
+    pub cost: Money,
+}
+
+impl From<ProductCostForDbResult> for ProductCostOfGoodsSold {
+    fn from(value: ProductCostForDbResult) -> Self {
+        let average_cost = value.average_cost();
+        Self {
+            product_id: value.product_id,
+            sku: value.sku,
+            display_name: value.display_name,
+            quantity: value.quantity,
+            revenue: Money::new(value.revenue, value.currency),
+            cost: average_cost.times(value.quantity),
+            average_cost,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CostOfGoodsSold {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub currency: Currency,
+    pub products: Vec<ProductCostOfGoodsSold>,
+    pub total_revenue: Money,
+    pub total_cost: Money,
+}
+// endregion: Structs
+
+// region: Methods
+/// Stock on hand at the end of `as_of`, valued at average cost; only of
+/// `category_id` and its subcategories when given.
+pub async fn get_inventory_valuation(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    as_of: NaiveDate,
+    category_id: Option<i64>,
+) -> Result<InventoryValuation> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let products = sqlx::query_as!(
+        ProductCostForDbResult,
+        r#"WITH RECURSIVE category_tree AS (
+            SELECT id FROM categories WHERE id = $3 AND organization_id = $1
+            UNION ALL
+            SELECT c.id FROM categories c
+            JOIN category_tree ct
+            ON c.parent_id = ct.id
+        )
+        SELECT
+            p.id as product_id,
+            p.sku,
+            p.display_name,
+            COALESCE(SUM(CASE WHEN il.action = 'INCOMING' THEN il.quantity ELSE -il.quantity END), 0)::int8 as "quantity!",
+            0::numeric as "revenue!",
+            COALESCE(SUM(il.quantity * il.base_price) FILTER (
+                WHERE il.action = 'INCOMING'
+                AND (it.action IS NULL OR it.action IN ('DEPOSIT', 'OPENING_BALANCE'))
+            ), 0) as "cost_total!",
+            COALESCE(SUM(il.quantity) FILTER (
+                WHERE il.action = 'INCOMING'
+                AND (it.action IS NULL OR it.action IN ('DEPOSIT', 'OPENING_BALANCE'))
+            ), 0)::int8 as "cost_quantity!",
+            o.base_currency as "currency: Currency"
+        FROM products p
+        JOIN organizations o
+        ON o.id = p.organization_id
+        JOIN inventory_logs il
+        ON il.product_id = p.id
+        AND il.timestamp < $2::date + 1
+        LEFT JOIN inventory_transactions it
+        ON it.id = il.inventory_transaction_id
+        WHERE p.organization_id = $1
+        AND ($3::int8 IS NULL OR p.category_id IN (SELECT id FROM category_tree))
+        GROUP BY p.id, p.sku, p.display_name, o.base_currency
+        ORDER BY p.display_name;"#,
+        organization_id,
+        as_of,
+        category_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let currency = get_base_currency(ctx, mm).await?;
+    let products: Vec<ProductValuation> = products.into_iter().map(|p| p.into()).collect();
+
+    Ok(InventoryValuation {
+        as_of,
+        currency,
+        total_value: Money::sum(currency, products.iter().map(|p| p.value.clone()))?,
+        products,
+    })
+}
+
+/// Net sales from `from` to `to`, both included, with their cost at the
+/// average cost up to `to`; only of `category_id` and its subcategories when
+/// given.
+pub async fn get_cost_of_goods_sold(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    from: NaiveDate,
+    to: NaiveDate,
+    category_id: Option<i64>,
+) -> Result<CostOfGoodsSold> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let products = sqlx::query_as!(
+        ProductCostForDbResult,
+        r#"WITH RECURSIVE category_tree AS (
+            SELECT id FROM categories WHERE id = $4 AND organization_id = $1
+            UNION ALL
+            SELECT c.id FROM categories c
+            JOIN category_tree ct
+            ON c.parent_id = ct.id
+        )
+        SELECT
+            p.id as product_id,
+            p.sku,
+            p.display_name,
+            COALESCE(SUM(CASE WHEN it.action = 'SALES' THEN il.quantity ELSE -il.quantity END) FILTER (
+                WHERE it.action IN ('SALES', 'SALES_ROLLBACK')
+                AND il.timestamp >= $2::date
+            ), 0)::int8 as "quantity!",
+            COALESCE(SUM(CASE WHEN it.action = 'SALES' THEN il.quantity ELSE -il.quantity END * il.base_price) FILTER (
+                WHERE it.action IN ('SALES', 'SALES_ROLLBACK')
+                AND il.timestamp >= $2::date
+            ), 0) as "revenue!",
+            COALESCE(SUM(il.quantity * il.base_price) FILTER (
+                WHERE il.action = 'INCOMING'
+                AND (it.action IS NULL OR it.action IN ('DEPOSIT', 'OPENING_BALANCE'))
+            ), 0) as "cost_total!",
+            COALESCE(SUM(il.quantity) FILTER (
+                WHERE il.action = 'INCOMING'
+                AND (it.action IS NULL OR it.action IN ('DEPOSIT', 'OPENING_BALANCE'))
+            ), 0)::int8 as "cost_quantity!",
+            o.base_currency as "currency: Currency"
+        FROM products p
+        JOIN organizations o
+        ON o.id = p.organization_id
+        JOIN inventory_logs il
+        ON il.product_id = p.id
+        AND il.timestamp < $3::date + 1
+        LEFT JOIN inventory_transactions it
+        ON it.id = il.inventory_transaction_id
+        WHERE p.organization_id = $1
+        AND ($4::int8 IS NULL OR p.category_id IN (SELECT id FROM category_tree))
+        GROUP BY p.id, p.sku, p.display_name, o.base_currency
+        HAVING COUNT(*) FILTER (
+            WHERE it.action IN ('SALES', 'SALES_ROLLBACK')
+            AND il.timestamp >= $2::date
+        ) > 0
+        ORDER BY p.display_name;"#,
+        organization_id,
+        from,
+        to,
+        category_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let currency = get_base_currency(ctx, mm).await?;
+    let products: Vec<ProductCostOfGoodsSold> = products.into_iter().map(|p| p.into()).collect();
+
+    Ok(CostOfGoodsSold {
+        from,
+        to,
+        currency,
+        total_revenue: Money::sum(currency, products.iter().map(|p| p.revenue.clone()))?,
+        total_cost: Money::sum(currency, products.iter().map(|p| p.cost.clone()))?,
+        products,
+    })
+}
+// endregion: Methods
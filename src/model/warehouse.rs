@@ -0,0 +1,29 @@
+use super::{user::get_user_ids, ModelManager};
+use crate::ctx::Ctx;
+use crate::model::Result;
+
+// region: Structs
+#[derive(Debug)]
+pub struct Warehouse {
+    pub id: i64,
+    pub name: Option<String>,
+}
+// endregion: Structs
+
+// region: Methods
+pub async fn get_all_warehouses(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Warehouse>> {
+    let db = mm.db();
+    let (_, organization_id) = get_user_ids(ctx, mm).await?;
+
+    let warehouses = sqlx::query_as!(
+        Warehouse,
+        r#"SELECT id, name FROM warehouses WHERE organization_id = $1
+            ORDER BY id;"#,
+        organization_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(warehouses)
+}
+// endregion: Methods
@@ -0,0 +1,140 @@
+use super::{Error, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueRef};
+use sqlx::{Decode, Encode, Postgres, Type};
+use std::str::FromStr;
+
+/// Symbol and number of minor units of the currencies we format specially.
+/// Any other ISO 4217 code is accepted, shown by its code with two decimals.
+const KNOWN_CURRENCIES: [(&str, &str, u32); 16] = [
+    ("PHP", "₱", 2),
+    ("USD", "$", 2),
+    ("EUR", "€", 2),
+    ("GBP", "£", 2),
+    ("JPY", "¥", 0),
+    ("CNY", "¥", 2),
+    ("KRW", "₩", 0),
+    ("INR", "₹", 2),
+    ("IDR", "Rp", 2),
+    ("MYR", "RM", 2),
+    ("SGD", "S$", 2),
+    ("THB", "฿", 2),
+    ("VND", "₫", 0),
+    ("AUD", "A$", 2),
+    ("CAD", "C$", 2),
+    ("HKD", "HK$", 2),
+];
+
+/// An ISO 4217 currency code.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const PHP: Currency = Currency(*b"PHP");
+
+    pub fn code(&self) -> &str {
+        // Only ever built from ASCII letters.
+        std::str::from_utf8(&self.0).unwrap_or_default()
+    }
+
+    pub fn symbol(&self) -> &str {
+        self.known()
+            .map(|(_, symbol, _)| symbol)
+            .unwrap_or(self.code())
+    }
+
+    /// Decimal places of the smallest unit, e.g. 2 for cents.
+    pub fn minor_units(&self) -> u32 {
+        self.known().map(|(_, _, units)| units).unwrap_or(2)
+    }
+
+    fn known(&self) -> Option<(&'static str, &'static str, u32)> {
+        KNOWN_CURRENCIES
+            .iter()
+            .find(|(code, _, _)| code.as_bytes() == self.0)
+            .copied()
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency::PHP
+    }
+}
+
+impl FromStr for Currency {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let code = s.trim().to_ascii_uppercase();
+        match <[u8; 3]>::try_from(code.as_bytes()) {
+            Ok(bytes) if bytes.iter().all(u8::is_ascii_uppercase) => Ok(Currency(bytes)),
+            _ => Err(Error::InvalidCurrency(s.to_string())),
+        }
+    }
+}
+
+impl core::fmt::Debug for Currency {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Currency({})", self.code())
+    }
+}
+
+impl core::fmt::Display for Currency {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+// region: Serde
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Currency::from_str(&code).map_err(serde::de::Error::custom)
+    }
+}
+// endregion: Serde
+
+// region: Sqlx
+/// Stored as its code in a `VARCHAR(3)` column.
+impl Type<Postgres> for Currency {
+    fn type_info() -> PgTypeInfo {
+        <&str as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl PgHasArrayType for Currency {
+    fn array_type_info() -> PgTypeInfo {
+        <&str as PgHasArrayType>::array_type_info()
+    }
+
+    fn array_compatible(ty: &PgTypeInfo) -> bool {
+        <&str as PgHasArrayType>::array_compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Currency {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <&str as Encode<Postgres>>::encode(self.code(), buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Currency {
+    fn decode(value: PgValueRef<'r>) -> core::result::Result<Self, BoxDynError> {
+        let code = <&str as Decode<Postgres>>::decode(value)?;
+        Ok(Currency::from_str(code)?)
+    }
+}
+// endregion: Sqlx
@@ -0,0 +1,22 @@
+use super::Currency;
+use serde::Serialize;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, Serialize)]
+pub enum Error {
+    InvalidAmount(String),
+    InvalidCurrency(String),
+    CurrencyMismatch {
+        expected: Currency,
+        actual: Currency,
+    },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,235 @@
+use super::{Error, Result};
+use sqlx::types::BigDecimal;
+use std::str::FromStr;
+
+/// How numbers are written: `1,234.50` in English, `1.234,50` in German.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    decimal: char,
+    group: char,
+    /// Whether the currency symbol follows the amount, as in `12,50 €`.
+    symbol_after: bool,
+}
+
+impl Locale {
+    /// `₱1,234.50`
+    pub const EN: Locale = Locale {
+        decimal: '.',
+        group: ',',
+        symbol_after: false,
+    };
+    /// `1.234,50 €`
+    pub const DE: Locale = Locale {
+        decimal: ',',
+        group: '.',
+        symbol_after: true,
+    };
+    /// `1 234,50 €`, grouped with a narrow no-break space.
+    pub const FR: Locale = Locale {
+        decimal: ',',
+        group: '\u{202f}',
+        symbol_after: true,
+    };
+    /// `CHF 1'234.50`
+    pub const CH: Locale = Locale {
+        decimal: '.',
+        group: '\'',
+        symbol_after: false,
+    };
+
+    /// Picks the number format for a BCP 47 tag such as `de-DE`.
+    pub fn from_tag(tag: &str) -> Locale {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        let mut subtags = tag.split('-');
+        let language = subtags.next().unwrap_or_default();
+        let region = subtags.next_back().unwrap_or_default();
+
+        match (language, region) {
+            ("de" | "it" | "fr", "ch") => Locale::CH,
+            // Mexico, the US and Latin America write `1,234.50`.
+            ("es", "mx" | "us" | "419") => Locale::EN,
+            ("de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" | "vi", _) => Locale::DE,
+            ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "nb" | "no" | "fi" | "uk" | "hu", _) => {
+                Locale::FR
+            }
+            _ => Locale::EN,
+        }
+    }
+
+    /// Uses the first language of an `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Locale {
+        let tag = header.split([',', ';']).next().unwrap_or_default();
+        Locale::from_tag(tag)
+    }
+
+    /// Parses an amount as a person would type it in this locale. A currency
+    /// symbol or code before or after the number is ignored, and thousands
+    /// separators must be in the right places, so `1.234,50` is rejected in
+    /// English rather than read as 1.2345.
+    pub fn parse_amount(&self, input: &str) -> Result<BigDecimal> {
+        let invalid = || Error::InvalidAmount(input.trim().to_string());
+
+        let mut negative = false;
+        let number = input.trim_matches(|c: char| {
+            negative |= c == '-';
+            !(c.is_ascii_digit() || c == self.decimal)
+        });
+
+        let (integer, fraction) = number.split_once(self.decimal).unwrap_or((number, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let groups: Vec<&str> = integer
+            .split(|c: char| c == self.group || (self.group.is_whitespace() && c.is_whitespace()))
+            .collect();
+        if groups.len() > 1
+            && (groups[0].is_empty()
+                || groups[0].len() > 3
+                || groups[1..].iter().any(|g| g.len() != 3))
+        {
+            return Err(invalid());
+        }
+        let integer = groups.concat();
+        if !integer.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let canonical = format!(
+            "{}{}.{}",
+            if negative { "-" } else { "" },
+            if integer.is_empty() { "0" } else { &integer },
+            if fraction.is_empty() { "0" } else { fraction }
+        );
+        BigDecimal::from_str(&canonical).map_err(|_| invalid())
+    }
+
+    /// Formats `amount` rounded to `scale` decimal places, with thousands
+    /// separators.
+    pub fn format_amount(&self, amount: &BigDecimal, scale: u32) -> String {
+        let rounded = amount
+            .round(scale as i64)
+            .with_scale(scale as i64)
+            .to_string();
+        let (sign, digits) = match rounded.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", rounded.as_str()),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let mut grouped = String::with_capacity(integer.len() + integer.len() / 3);
+        for (i, c) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                grouped.push(self.group);
+            }
+            grouped.push(c);
+        }
+
+        match fraction {
+            "" => format!("{sign}{grouped}"),
+            fraction => format!("{sign}{grouped}{}{fraction}", self.decimal),
+        }
+    }
+
+    pub fn symbol_after(&self) -> bool {
+        self.symbol_after
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::EN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn picks_the_format_for_a_tag() {
+        assert_eq!(Locale::from_tag("en-US"), Locale::EN);
+        assert_eq!(Locale::from_tag("de_DE"), Locale::DE);
+        assert_eq!(Locale::from_tag("de-CH"), Locale::CH);
+        assert_eq!(Locale::from_tag("fr-FR"), Locale::FR);
+        assert_eq!(Locale::from_tag("fr-CH"), Locale::CH);
+        assert_eq!(Locale::from_tag("es-ES"), Locale::DE);
+        assert_eq!(Locale::from_tag("es"), Locale::DE);
+        assert_eq!(Locale::from_tag("es-MX"), Locale::EN);
+        assert_eq!(Locale::from_tag("es-US"), Locale::EN);
+        assert_eq!(Locale::from_tag("es-419"), Locale::EN);
+        assert_eq!(Locale::from_tag(""), Locale::EN);
+        assert_eq!(
+            Locale::from_accept_language("es-MX,es;q=0.9,en;q=0.8"),
+            Locale::EN
+        );
+    }
+
+    #[test]
+    fn parses_english_amounts() {
+        let en = Locale::EN;
+        assert_eq!(en.parse_amount("1,234.50").unwrap(), decimal("1234.5"));
+        assert_eq!(en.parse_amount("₱1,234.50").unwrap(), decimal("1234.5"));
+        assert_eq!(en.parse_amount("-12").unwrap(), decimal("-12"));
+        assert_eq!(en.parse_amount(".5").unwrap(), decimal("0.5"));
+        assert!(en.parse_amount("1.234,50").is_err());
+        assert!(en.parse_amount("12,34").is_err());
+        assert!(en.parse_amount("abc").is_err());
+    }
+
+    #[test]
+    fn parses_german_amounts() {
+        let de = Locale::DE;
+        assert_eq!(de.parse_amount("1.234,50 €").unwrap(), decimal("1234.5"));
+        assert_eq!(de.parse_amount("0,99").unwrap(), decimal("0.99"));
+        assert!(de.parse_amount("1,234.50").is_err());
+    }
+
+    #[test]
+    fn parses_french_amounts() {
+        let fr = Locale::FR;
+        assert_eq!(
+            fr.parse_amount("1\u{202f}234,50 €").unwrap(),
+            decimal("1234.5")
+        );
+        // People type an ordinary space for the group separator.
+        assert_eq!(fr.parse_amount("1 234,50").unwrap(), decimal("1234.5"));
+        assert!(fr.parse_amount("1.234,50").is_err());
+    }
+
+    #[test]
+    fn parses_swiss_amounts() {
+        let ch = Locale::CH;
+        assert_eq!(ch.parse_amount("CHF 1'234.50").unwrap(), decimal("1234.5"));
+        assert!(ch.parse_amount("1.234,50").is_err());
+    }
+
+    #[test]
+    fn formats_amounts() {
+        let amount = decimal("-1234567.891");
+        assert_eq!(Locale::EN.format_amount(&amount, 2), "-1,234,567.89");
+        assert_eq!(Locale::DE.format_amount(&amount, 2), "-1.234.567,89");
+        assert_eq!(
+            Locale::FR.format_amount(&amount, 2),
+            "-1\u{202f}234\u{202f}567,89"
+        );
+        assert_eq!(Locale::CH.format_amount(&amount, 2), "-1'234'567.89");
+        assert_eq!(Locale::EN.format_amount(&decimal("999.5"), 0), "1,000");
+    }
+
+    #[test]
+    fn formatted_amounts_parse_back() {
+        let amount = decimal("1234.5");
+        for locale in [Locale::EN, Locale::DE, Locale::FR, Locale::CH] {
+            let formatted = locale.format_amount(&amount, 2);
+            assert_eq!(locale.parse_amount(&formatted).unwrap(), amount);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+mod currency;
+mod error;
+mod locale;
+
+pub use self::currency::Currency;
+pub use self::error::{Error, Result};
+pub use self::locale::Locale;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::types::BigDecimal;
+use std::str::FromStr;
+
+/// Decimal places kept when a price is split across units, e.g. the base-unit
+/// cost of a box of three.
+const UNIT_PRICE_SCALE: i64 = 6;
+
+/// An exact decimal amount in a currency. Amounts are stored as `NUMERIC`
+/// and only rounded to the currency's minor units for display.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Money {
+    amount: BigDecimal,
+    currency: Currency,
+}
+
+impl Money {
+    /// Trailing zeros are dropped, so `12.50` read back from the database
+    /// as `12.5000` compares and prints as `12.5`.
+    pub fn new(amount: BigDecimal, currency: Currency) -> Self {
+        Self {
+            amount: amount.normalized(),
+            currency,
+        }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(BigDecimal::from(0), currency)
+    }
+
+    /// Parses user input such as `₱1,234.50` or `1.234,50 €`.
+    pub fn parse(input: &str, currency: Currency, locale: Locale) -> Result<Self> {
+        Ok(Self::new(locale.parse_amount(input)?, currency))
+    }
+
+    pub fn amount(&self) -> &BigDecimal {
+        &self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount < BigDecimal::from(0)
+    }
+
+    /// The price of one of `count` units.
+    pub fn per_unit(&self, count: i64) -> Self {
+        let amount = (&self.amount / BigDecimal::from(count)).round(UNIT_PRICE_SCALE);
+        Self::new(amount, self.currency)
+    }
+
+    /// Adds `other`, which must be in the same currency.
+    pub fn plus(&self, other: &Money) -> Result<Self> {
+        if self.currency != other.currency {
+            return Err(Error::CurrencyMismatch {
+                expected: self.currency,
+                actual: other.currency,
+            });
+        }
+        Ok(Self::new(&self.amount + &other.amount, self.currency))
+    }
+
+    /// Adds up `moneys`, all in `currency`; an empty sum is zero.
+    pub fn sum(currency: Currency, moneys: impl IntoIterator<Item = Money>) -> Result<Self> {
+        moneys
+            .into_iter()
+            .try_fold(Money::zero(currency), |sum, money| sum.plus(&money))
+    }
+
+    /// The price of `quantity` units.
+    pub fn times(&self, quantity: i64) -> Self {
+        Self::new(&self.amount * BigDecimal::from(quantity), self.currency)
+    }
+
+    /// Rounded to the currency's minor units, e.g. for an amount charged.
+    pub fn rounded(&self) -> Self {
+        let amount = self.amount.round(self.currency.minor_units() as i64);
+        Self::new(amount, self.currency)
+    }
+
+    /// The same value in `currency`, at `rate` units of it per unit of this
+    /// money's currency.
+    pub fn exchange(&self, rate: &BigDecimal, currency: Currency) -> Self {
+        Self::new(&self.amount * rate, currency)
+    }
+
+    /// Formats with the currency symbol, rounded to its minor units.
+    pub fn format(&self, locale: Locale) -> String {
+        let amount = locale.format_amount(&self.amount, self.currency.minor_units());
+        match (locale.symbol_after(), amount.strip_prefix('-')) {
+            (true, _) => format!("{amount} {}", self.currency.symbol()),
+            (false, Some(amount)) => format!("-{}{amount}", self.currency.symbol()),
+            (false, None) => format!("{}{amount}", self.currency.symbol()),
+        }
+    }
+}
+
+impl core::fmt::Display for Money {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(&self.format(Locale::default()))
+    }
+}
+
+// region: Serde
+/// Serialized as `{"amount": "1234.50", "currency": "PHP"}`; the amount is a
+/// string so JSON clients never see it as a float.
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct MoneyJson<'a> {
+            amount: String,
+            currency: &'a str,
+        }
+
+        MoneyJson {
+            amount: self.amount.to_string(),
+            currency: self.currency.code(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AmountInput {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MoneyInput {
+    Object {
+        amount: AmountInput,
+        currency: Option<String>,
+    },
+    Amount(AmountInput),
+}
+
+/// Accepts the serialized object, or a bare amount in the default currency as
+/// sent by forms and older API clients. Text is read as `1,234.50`; numbers
+/// go through their shortest decimal form, never binary floating point.
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        let (amount, currency) = match MoneyInput::deserialize(deserializer)? {
+            MoneyInput::Object { amount, currency } => (amount, currency),
+            MoneyInput::Amount(amount) => (amount, None),
+        };
+
+        let amount = match amount {
+            AmountInput::Text(text) => Locale::EN.parse_amount(&text),
+            AmountInput::Integer(integer) => Ok(BigDecimal::from(integer)),
+            AmountInput::Float(float) => BigDecimal::from_str(&float.to_string())
+                .map_err(|_| Error::InvalidAmount(float.to_string())),
+        }
+        .map_err(serde::de::Error::custom)?;
+        let currency = match currency {
+            Some(currency) => Currency::from_str(&currency).map_err(serde::de::Error::custom)?,
+            None => Currency::default(),
+        };
+
+        Ok(Money::new(amount, currency))
+    }
+}
+// endregion: Serde
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str, currency: Currency) -> Money {
+        Money::new(BigDecimal::from_str(amount).unwrap(), currency)
+    }
+
+    #[test]
+    fn sums_in_the_given_currency() {
+        let currency = Currency::default();
+        let total = Money::sum(currency, [money("1.25", currency), money("2.50", currency)]);
+        assert_eq!(total.unwrap(), money("3.75", currency));
+        assert_eq!(Money::sum(currency, []).unwrap(), Money::zero(currency));
+    }
+
+    #[test]
+    fn refuses_to_sum_mixed_currencies() {
+        let (php, eur) = (
+            Currency::from_str("PHP").unwrap(),
+            Currency::from_str("EUR").unwrap(),
+        );
+        assert!(matches!(
+            Money::sum(php, [money("1", php), money("1", eur)]),
+            Err(Error::CurrencyMismatch { expected, actual }) if expected == php && actual == eur
+        ));
+    }
+
+    #[test]
+    fn formats_with_the_symbol_in_each_locale() {
+        let eur = Currency::from_str("EUR").unwrap();
+        let amount = money("-1234.5", eur);
+        assert_eq!(amount.format(Locale::EN), "-€1,234.50");
+        assert_eq!(amount.format(Locale::DE), "-1.234,50 €");
+        assert_eq!(amount.format(Locale::FR), "-1\u{202f}234,50 €");
+    }
+
+    #[test]
+    fn parses_in_each_locale() {
+        let eur = Currency::from_str("EUR").unwrap();
+        for (input, locale) in [
+            ("€1,234.50", Locale::EN),
+            ("1.234,50 €", Locale::DE),
+            ("1 234,50 €", Locale::FR),
+            ("EUR 1'234.50", Locale::CH),
+        ] {
+            assert_eq!(
+                Money::parse(input, eur, locale).unwrap(),
+                money("1234.5", eur)
+            );
+        }
+    }
+}
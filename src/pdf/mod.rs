@@ -0,0 +1,156 @@
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+// Documents use the standard Helvetica fonts every PDF reader has, so nothing
+// is embedded. Text is encoded as WinAnsi; characters outside Latin-1 print
+// as `?`, so callers write amounts with the currency code, not its symbol.
+
+/// A4 in points.
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource_name(&self) -> Name<'static> {
+        match self {
+            Font::Regular => Name(b"F1"),
+            Font::Bold => Name(b"F2"),
+        }
+    }
+}
+
+/// Pages of text and rules, drawn from the bottom-left corner in points.
+pub struct PdfDocument {
+    title: String,
+    pages: Vec<Content>,
+}
+
+impl PdfDocument {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            pages: vec![Content::new()],
+        }
+    }
+
+    pub fn new_page(&mut self) {
+        self.pages.push(Content::new());
+    }
+
+    fn page(&mut self) -> &mut Content {
+        self.pages.last_mut().expect("a document has a page")
+    }
+
+    pub fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+        let encoded = encode(text);
+        let page = self.page();
+        page.begin_text();
+        page.set_font(font.resource_name(), size);
+        page.next_line(x, y);
+        page.show(Str(&encoded));
+        page.end_text();
+    }
+
+    /// Text ending at `x`, for amounts.
+    pub fn text_right(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+        self.text(x - text_width(text, size), y, size, font, text);
+    }
+
+    /// A horizontal line from `x1` to `x2`.
+    pub fn rule(&mut self, x1: f32, x2: f32, y: f32) {
+        let page = self.page();
+        page.set_line_width(0.5);
+        page.move_to(x1, y);
+        page.line_to(x2, y);
+        page.stroke();
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let regular_id = Ref::new(3);
+        let bold_id = Ref::new(4);
+        let info_id = Ref::new(5);
+        let first_page_id = 6;
+
+        let page_ids: Vec<Ref> = (0..self.pages.len() as i32)
+            .map(|i| Ref::new(first_page_id + 2 * i))
+            .collect();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(page_tree_id);
+        pdf.pages(page_tree_id)
+            .kids(page_ids.iter().copied())
+            .count(page_ids.len() as i32);
+        pdf.type1_font(regular_id)
+            .base_font(Name(b"Helvetica"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.type1_font(bold_id)
+            .base_font(Name(b"Helvetica-Bold"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.document_info(info_id).title(TextStr(&self.title));
+
+        for (page_id, content) in page_ids.into_iter().zip(self.pages) {
+            let content_id = Ref::new(page_id.get() + 1);
+
+            let mut page = pdf.page(page_id);
+            page.parent(page_tree_id)
+                .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .contents(content_id);
+            page.resources()
+                .fonts()
+                .pair(Font::Regular.resource_name(), regular_id)
+                .pair(Font::Bold.resource_name(), bold_id);
+            page.finish();
+
+            pdf.stream(content_id, &content.finish());
+        }
+
+        pdf.finish()
+    }
+}
+
+/// The width of `text` in Helvetica at `size` points. Bold is slightly
+/// wider for letters but the same for digits, which is what gets aligned.
+pub fn text_width(text: &str, size: f32) -> f32 {
+    let units: u32 = text.chars().map(char_width).sum();
+    units as f32 * size / 1000.0
+}
+
+/// Helvetica advance widths in thousandths of an em, from its AFM metrics.
+fn char_width(c: char) -> u32 {
+    const ASCII: [u32; 95] = [
+        278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278,
+        278, // ' '../
+        556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584,
+        556, // 0..?
+        1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722,
+        778, // @..O
+        667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469,
+        556, // P.._
+        333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556,
+        556, // `..o
+        556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p..~
+    ];
+
+    match c as u32 {
+        code @ 32..=126 => ASCII[(code - 32) as usize],
+        0xa0 | 0x2009 | 0x202f => 278,
+        _ => 556,
+    }
+}
+
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            code @ (32..=126 | 160..=255) => code as u8,
+            // Thin and narrow spaces group digits in some locales.
+            0x2009 | 0x202f => 0xa0,
+            _ => b'?',
+        })
+        .collect()
+}
@@ -0,0 +1,79 @@
+mod error;
+
+pub use self::error::{Error, Result};
+use calamine::{open_workbook_auto_from_rs, Data, Reader};
+use std::io::Cursor;
+
+/// Reads the first sheet of an uploaded CSV or XLSX file as rows of cells.
+/// The format is picked from the file extension, defaulting to CSV.
+pub fn read_table(file_name: &str, bytes: Vec<u8>) -> Result<Vec<Vec<String>>> {
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .unwrap_or_default();
+
+    let rows = match extension.as_str() {
+        "xlsx" | "xlsm" | "xls" | "ods" => read_workbook(bytes)?,
+        _ => read_csv(&bytes)?,
+    };
+
+    Ok(rows
+        .into_iter()
+        .filter(|row| row.iter().any(|cell| !cell.trim().is_empty()))
+        .collect())
+}
+
+pub fn read_csv(bytes: &[u8]) -> Result<Vec<Vec<String>>> {
+    // Spreadsheet programs often prepend a byte order mark.
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+
+    reader
+        .records()
+        .map(|record| {
+            record
+                .map(|r| r.iter().map(|cell| cell.trim().to_string()).collect())
+                .map_err(|e| Error::Csv(e.to_string()))
+        })
+        .collect()
+}
+
+pub fn write_csv(rows: &[Vec<String>]) -> Result<String> {
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(Vec::new());
+    for row in rows {
+        writer
+            .write_record(row)
+            .map_err(|e| Error::Csv(e.to_string()))?;
+    }
+    let bytes = writer.into_inner().map_err(|e| Error::Csv(e.to_string()))?;
+
+    String::from_utf8(bytes).map_err(|e| Error::Csv(e.to_string()))
+}
+
+fn read_workbook(bytes: Vec<u8>) -> Result<Vec<Vec<String>>> {
+    let mut workbook = open_workbook_auto_from_rs(Cursor::new(bytes))
+        .map_err(|e| Error::Workbook(e.to_string()))?;
+
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or(Error::EmptyWorkbook)?
+        .map_err(|e| Error::Workbook(e.to_string()))?;
+
+    Ok(range
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|cell| match cell {
+                    Data::Empty => String::new(),
+                    cell => cell.to_string().trim().to_string(),
+                })
+                .collect()
+        })
+        .collect())
+}
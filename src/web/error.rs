@@ -1,10 +1,13 @@
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
+    Json,
 };
+use serde_json::json;
 use serde_with::serde_as;
 
 use crate::model;
+use crate::web::mw_auth::CtxExtError;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -12,6 +15,7 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
     Model(model::Error),
+    CtxExt(CtxExtError),
 }
 
 impl From<model::Error> for Error {
@@ -22,8 +26,92 @@ impl From<model::Error> for Error {
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        // Create a placeholder Axum reponse.
-        let mut response = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        let status = match &self {
+            Error::Model(
+                model::Error::LotNumberRequired { .. }
+                | model::Error::LotNotFound { .. }
+                | model::Error::InvalidQuantity { .. }
+                | model::Error::InsufficientStock { .. }
+                | model::Error::SerialNumberCountMismatch { .. }
+                | model::Error::DuplicateSerialNumber { .. }
+                | model::Error::SerialNumberAlreadyInStock { .. }
+                | model::Error::SerialNumberNotInStock { .. }
+                | model::Error::InvalidVariantParent { .. }
+                | model::Error::VariantsAlreadyExist { .. }
+                | model::Error::NoVariantOptions
+                | model::Error::InvalidUnit { .. }
+                | model::Error::QuantityTooLarge { .. }
+                | model::Error::InvalidCategoryParent { .. }
+                | model::Error::CategoryHasProducts { .. }
+                | model::Error::InvalidBarcode { .. }
+                | model::Error::DuplicateBarcode { .. }
+                | model::Error::ProductImportInvalid { .. }
+                | model::Error::OpeningBalanceNotAllowed
+                | model::Error::OpeningBalanceInvalid { .. }
+                | model::Error::InvalidOpeningBalanceDate { .. }
+                | model::Error::BaseCurrencyLocked
+                | model::Error::ExchangeRateNotFound { .. }
+                | model::Error::InvalidExchangeRate { .. }
+                | model::Error::CurrencyMismatch { .. }
+                | model::Error::InvalidPriceListName { .. }
+                | model::Error::InvalidPriceListItem { .. }
+                | model::Error::InvalidCustomerName { .. }
+                | model::Error::InvalidTaxClass { .. }
+                | model::Error::TaxClassHasProducts { .. }
+                | model::Error::InvalidUser { .. }
+                | model::Error::InvalidEmail { .. }
+                | model::Error::InvalidPassword
+                | model::Error::LastActiveAdmin { .. }
+                | model::Error::InvalidOrganization { .. }
+                | model::Error::ReasonRequired
+                | model::Error::InvalidApiKeyName { .. }
+                | model::Error::InvalidApiKeyScopes
+                | model::Error::InvalidTwoFactorCode
+                | model::Error::TwoFactorAlreadyEnabled
+                | model::Error::TwoFactorRequired
+                | model::Error::TwoFactorSetupRequired,
+            ) => StatusCode::BAD_REQUEST,
+            Error::Model(
+                model::Error::Unauhtorized(_)
+                | model::Error::PriceOverrideNotAllowed { .. }
+                | model::Error::OrganizationSuspended { .. },
+            ) => StatusCode::FORBIDDEN,
+            Error::Model(
+                model::Error::ProductNotFound { .. }
+                | model::Error::CategoryNotFound { .. }
+                | model::Error::BarcodeNotFound { .. }
+                | model::Error::PriceListNotFound { .. }
+                | model::Error::CustomerNotFound { .. }
+                | model::Error::InvoiceNotFound { .. }
+                | model::Error::UserNotFound { .. }
+                | model::Error::InvitationNotFound
+                | model::Error::PasswordResetNotFound
+                | model::Error::OrganizationNotFound { .. }
+                | model::Error::ApiKeyNotFound { .. },
+            ) => StatusCode::NOT_FOUND,
+            Error::Model(
+                model::Error::TooManyRequests
+                | model::Error::LoginThrottled { .. }
+                | model::Error::LoginLocked { .. },
+            ) => StatusCode::TOO_MANY_REQUESTS,
+            Error::CtxExt(CtxExtError::SessionLookupFailed | CtxExtError::CtxNotInRequestExt) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Error::CtxExt(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        // Create a placeholder Axum reponse. Users of a suspended
+        // organization are told why.
+        let mut response = match &self {
+            Error::Model(model::Error::OrganizationSuspended { reason, .. })
+            | Error::CtxExt(CtxExtError::OrganizationSuspended { reason, .. }) => (
+                status,
+                Json(json!({ "error": "ORGANIZATION_SUSPENDED", "reason": reason })),
+            )
+                .into_response(),
+            _ => status.into_response(),
+        };
 
         // Insert the Error into the reponse.
         response.extensions_mut().insert(self);
@@ -0,0 +1,25 @@
+use crate::money::Locale;
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::request::Parts;
+use std::convert::Infallible;
+
+/// The number format of the browser, from its `Accept-Language` header.
+pub struct RequestLocale(pub Locale);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestLocale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let locale = parts
+            .headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|header| header.to_str().ok())
+            .map(Locale::from_accept_language)
+            .unwrap_or_default();
+
+        Ok(RequestLocale(locale))
+    }
+}
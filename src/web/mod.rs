@@ -1,6 +1,16 @@
 mod error;
+pub mod locale;
+pub mod mw_auth;
+pub mod mw_rate_limit;
 pub mod page_test;
 pub mod pages;
 pub mod routes_auth;
+pub mod routes_export;
 pub mod routes_inventory_deposit;
+pub mod routes_inventory_logs;
+pub mod routes_inventory_sales;
+pub mod routes_prices;
+pub mod routes_products;
+pub mod routes_tax;
 pub mod routes_test;
+pub mod routes_valuation;
@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+use axum::extract::{FromRequestParts, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::Serialize;
+use tower_cookies::Cookies;
+
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::api_key::{get_api_key_ctx, record_api_key_call};
+use crate::model::permissions::{has_scope, Permissions};
+use crate::model::session::get_session_ctx;
+use crate::model::tenant::Tenant;
+use crate::model::ModelManager;
+use crate::web::error::{Error, Result};
+use crate::web::routes_auth::AUTH_TOKEN;
+
+// The session of a request is looked up once, here, and handlers take the
+// resulting `Ctx` as an argument. Handlers of logged in users fail without
+// one; the rest ignore it. Calls to /api/v1 may bring an API key as a bearer
+// token instead, which then takes the place of any session.
+
+pub type CtxExtResult = core::result::Result<Ctx, CtxExtError>;
+
+/// Why a request has no `Ctx`.
+#[derive(Clone, Debug, Serialize)]
+pub enum CtxExtError {
+    TokenNotInCookie,
+    SessionNotFound,
+    ApiKeyNotFound,
+    OrganizationSuspended {
+        organization_id: i64,
+        reason: String,
+    },
+    SessionLookupFailed,
+    CtxNotInRequestExt,
+}
+
+/// Resolves the `Ctx` of whoever is logged in on this browser and runs the
+/// request for their tenant, so the database shows it only their
+/// organization's rows. Requests without a session see none.
+pub async fn mw_ctx_resolve<B>(
+    State(mm): State<ModelManager>,
+    cookies: Cookies,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let result_ctx = ctx_resolve(&mm, &cookies).await;
+    let tenant = result_ctx.as_ref().ok().and_then(Tenant::of);
+
+    req.extensions_mut().insert(result_ctx);
+
+    match tenant {
+        Some(tenant) => tenant.scope(next.run(req)).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn ctx_resolve(mm: &ModelManager, cookies: &Cookies) -> CtxExtResult {
+    let token = cookies
+        .get(AUTH_TOKEN)
+        .ok_or(CtxExtError::TokenNotInCookie)?;
+
+    get_session_ctx(&Ctx::empty(), mm, token.value())
+        .await
+        .map_err(|e| match e {
+            model::Error::Unauhtorized(_) => CtxExtError::SessionNotFound,
+            model::Error::OrganizationSuspended {
+                organization_id,
+                reason,
+            } => CtxExtError::OrganizationSuspended {
+                organization_id,
+                reason,
+            },
+            e => {
+                println!("Session lookup failed: {e:?}");
+                CtxExtError::SessionLookupFailed
+            }
+        })
+}
+
+/// Resolves the `Ctx` of the API key a call brings, runs the call for the
+/// key's organization and records it. Calls without a key keep the session's.
+pub async fn mw_api_key_resolve<B>(
+    State(mm): State<ModelManager>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(authorization) = req.headers().get(AUTHORIZATION) else {
+        return next.run(req).await;
+    };
+    let token = authorization
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .unwrap_or_default();
+
+    let result_ctx = get_api_key_ctx(&Ctx::empty(), &mm, &token)
+        .await
+        .map_err(|e| match e {
+            model::Error::Unauhtorized(_) => CtxExtError::ApiKeyNotFound,
+            model::Error::OrganizationSuspended {
+                organization_id,
+                reason,
+            } => CtxExtError::OrganizationSuspended {
+                organization_id,
+                reason,
+            },
+            e => {
+                println!("API key lookup failed: {e:?}");
+                CtxExtError::SessionLookupFailed
+            }
+        });
+    req.extensions_mut().insert(result_ctx.clone());
+
+    let Some(ctx) = result_ctx.ok() else {
+        return next.run(req).await;
+    };
+    let Some(tenant) = Tenant::of(&ctx) else {
+        return next.run(req).await;
+    };
+
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    tenant
+        .scope(async {
+            let response = next.run(req).await;
+            let status = response.status().as_u16();
+            if let Err(e) = record_api_key_call(&ctx, &mm, &method, &path, status).await {
+                println!("Recording API key call failed: {e:?}");
+            }
+            response
+        })
+        .await
+}
+
+/// Lets an API key through only if it was granted `scope`.
+pub async fn mw_require_scope<B>(
+    State(scope): State<Permissions>,
+    ctx: Ctx,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response> {
+    has_scope(&ctx, scope)?;
+
+    Ok(next.run(req).await)
+}
+
+// region: Ctx Extractor
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Ctx {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        parts
+            .extensions
+            .get::<CtxExtResult>()
+            .ok_or(Error::CtxExt(CtxExtError::CtxNotInRequestExt))?
+            .clone()
+            .map_err(Error::CtxExt)
+    }
+}
+// endregion: Ctx Extractor
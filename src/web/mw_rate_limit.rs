@@ -0,0 +1,118 @@
+use axum::extract::{ConnectInfo, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use moka::sync::Cache;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crate::web::pages::toasts::{toast_only_response, ToastSeverity};
+
+// Slows down addresses that keep failing at something, such as logging in or
+// opening links with guessed tokens. Handlers mark the responses that count as
+// a failure with `AttemptFailed`. Past a few failures each attempt must wait
+// twice as long as the last; the wait is answered without running the
+// handler. Counts are kept in memory per limit, and forgotten after a while
+// without failures.
+
+/// How many addresses a limit keeps counts for.
+const MAX_ADDRESSES: u64 = 100_000;
+
+/// Marks a response as a failed attempt.
+#[derive(Clone, Copy, Debug)]
+pub struct AttemptFailed;
+
+/// How often an address may fail at the routes it is layered on.
+#[derive(Clone)]
+pub struct RateLimit {
+    free_failures: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    failures: Cache<IpAddr, Failures>,
+}
+
+#[derive(Clone, Copy)]
+struct Failures {
+    count: u32,
+    last_failed_at: Instant,
+}
+
+impl RateLimit {
+    pub fn new(
+        free_failures: u32,
+        base_delay: Duration,
+        max_delay: Duration,
+        forget_after: Duration,
+    ) -> Self {
+        Self {
+            free_failures,
+            base_delay,
+            max_delay,
+            failures: Cache::builder()
+                .max_capacity(MAX_ADDRESSES)
+                .time_to_idle(forget_after)
+                .build(),
+        }
+    }
+
+    /// How long `ip` must still wait, if at all.
+    fn retry_after(&self, ip: IpAddr) -> Option<Duration> {
+        let failures = self.failures.get(&ip)?;
+        if failures.count < self.free_failures {
+            return None;
+        }
+        let doublings = (failures.count - self.free_failures).min(16);
+        let delay = (self.base_delay * 2u32.pow(doublings)).min(self.max_delay);
+
+        (failures.last_failed_at + delay).checked_duration_since(Instant::now())
+    }
+
+    fn count_failure(&self, ip: IpAddr) {
+        self.failures
+            .entry(ip)
+            .and_upsert_with(|failures| Failures {
+                count: failures.map_or(0, |f| f.value().count) + 1,
+                last_failed_at: Instant::now(),
+            });
+    }
+}
+
+/// Marks `response` as a failed attempt.
+pub fn attempt_failed(response: impl IntoResponse) -> Response {
+    let mut response = response.into_response();
+    response.extensions_mut().insert(AttemptFailed);
+    response
+}
+
+/// Answers an address that must wait without running the handler, and counts
+/// the responses marked as failed.
+pub async fn mw_rate_limit<B>(
+    State(limit): State<RateLimit>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let ip = addr.ip();
+
+    if let Some(retry_after) = limit.retry_after(ip) {
+        let seconds = retry_after.as_secs().max(1);
+        let message = format!("Too many attempts, try again in {seconds} seconds");
+        // Forms show it as a toast; pages opened directly get it as text.
+        let mut response = match req.headers().contains_key("HX-Request") {
+            true => toast_only_response(ToastSeverity::Failure, &message),
+            false => (StatusCode::TOO_MANY_REQUESTS, message).into_response(),
+        };
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, seconds.to_string().parse().unwrap());
+        return response;
+    }
+
+    let response = next.run(req).await;
+    if response.extensions().get::<AttemptFailed>().is_some() {
+        limit.count_failure(ip);
+    }
+
+    response
+}
@@ -0,0 +1,141 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::api_key::{
+    create_api_key, get_api_key_calls, get_api_keys, revoke_api_key, ApiKey, ApiKeyCall,
+    ApiKeyForCreate,
+};
+use crate::model::permissions::{Permissions, API_SCOPES};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post};
+use axum::{Form, Router};
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_api_keys(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/settings/api-keys", get(api_keys_page))
+        .route("/settings/api-keys", post(create_api_key_form))
+        .route("/settings/api-keys/:id", delete(revoke_api_key_row))
+        .with_state(mm)
+}
+
+/// The fields of the form; every checked scope is a `scope` field.
+struct ApiKeyForm(Vec<(String, String)>);
+
+impl From<ApiKeyForm> for ApiKeyForCreate {
+    fn from(ApiKeyForm(fields): ApiKeyForm) -> Self {
+        let mut api_key_for_create = ApiKeyForCreate {
+            name: String::new(),
+            scopes: Vec::new(),
+            expires_in_days: None,
+        };
+        for (field, value) in fields {
+            match field.as_str() {
+                "name" => api_key_for_create.name = value,
+                "expires_in_days" => api_key_for_create.expires_in_days = value.parse().ok(),
+                "scope" => {
+                    if let Some(scope) = API_SCOPES.iter().find(|s| s.name() == value) {
+                        api_key_for_create.scopes.push(*scope);
+                    }
+                }
+                _ => {}
+            }
+        }
+        api_key_for_create
+    }
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "api_keys/pages_api_keys.html")]
+pub struct ApiKeysPage {
+    pub scopes: &'static [Permissions],
+    pub api_keys: Vec<ApiKey>,
+    pub calls: Vec<ApiKeyCall>,
+    pub key: Option<String>,
+}
+
+/// The keys and their latest calls, after a new key, which is not shown
+/// again.
+#[derive(Template)]
+#[template(path = "api_keys/fragments/api_keys.html")]
+pub struct ApiKeysFragment {
+    pub api_keys: Vec<ApiKey>,
+    pub calls: Vec<ApiKeyCall>,
+    pub key: Option<String>,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_api_keys(ctx: &Ctx, mm: &ModelManager, key: Option<String>) -> Result<String> {
+    let template = ApiKeysFragment {
+        api_keys: get_api_keys(ctx, mm).await?,
+        calls: get_api_key_calls(ctx, mm).await?,
+        key,
+    };
+    Ok(template.render().unwrap())
+}
+
+/// The message for an error an admin can act on.
+fn failure_message(error: &model::Error) -> Option<String> {
+    match error {
+        model::Error::InvalidApiKeyName { .. } => Some("Enter a name".to_string()),
+        model::Error::InvalidApiKeyScopes => Some("Choose at least one scope".to_string()),
+        _ => None,
+    }
+}
+
+pub async fn api_keys_page(State(mm): State<ModelManager>, ctx: Ctx) -> Result<impl IntoResponse> {
+    let template = ApiKeysPage {
+        scopes: API_SCOPES,
+        api_keys: get_api_keys(&ctx, &mm).await?,
+        calls: get_api_key_calls(&ctx, &mm).await?,
+        key: None,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_api_key_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse> {
+    let key = match create_api_key(&ctx, &mm, ApiKeyForm(fields).into()).await {
+        Ok(key) => key,
+        Err(e) => {
+            return match failure_message(&e) {
+                Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+                None => Err(e.into()),
+            }
+        }
+    };
+
+    let reply_html = with_toast_response(
+        render_api_keys(&ctx, &mm, Some(key)).await?,
+        ToastSeverity::Succes,
+        "API Key Created",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn revoke_api_key_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    revoke_api_key(&ctx, &mm, id).await?;
+
+    let reply_html = with_toast_response(
+        render_api_keys(&ctx, &mm, None).await?,
+        ToastSeverity::Succes,
+        "API Key Revoked",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
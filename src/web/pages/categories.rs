@@ -1,6 +1,8 @@
 use crate::ctx::Ctx;
 use crate::model;
-use crate::model::category::{get_all_categories, get_category_by_id, Category};
+use crate::model::category::{
+    get_category_by_id, get_category_node_by_id, get_category_tree, Category, CategoryNode,
+};
 use crate::model::ModelManager;
 use crate::web::error::Result;
 use askama::Template;
@@ -10,6 +12,7 @@ use axum::response::{Html, IntoResponse};
 use axum::routing::{delete, get, post, put};
 use axum::{Form, Router};
 use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
 
 use super::toasts::{with_toast_response, ToastSeverity};
 
@@ -30,52 +33,85 @@ pub fn pages_cateogries(mm: ModelManager) -> Router {
 #[derive(Template)]
 #[template(path = "categories/fragments/table_entries.html")]
 pub struct TableEntries {
-    pub categories: Vec<Category>,
+    pub categories: Vec<CategoryNode>,
 }
 
 #[derive(Template)]
 #[template(path = "categories/fragments/table_entry.html")]
 pub struct TableEntry {
-    pub category: Category,
+    pub category: CategoryNode,
 }
 
 #[derive(Template)]
 #[template(path = "categories/fragments/edit_row.html")]
 pub struct EditRowFragment {
     pub category: Category,
+    pub parents: Vec<CategoryNode>,
 }
 // endregion: Table templates
 
+/// Categories that `category_id` can be moved under: everything except the
+/// category itself and its subtree.
+fn parent_candidates(tree: Vec<CategoryNode>, category_id: i64) -> Vec<CategoryNode> {
+    let mut subtree_depth = None;
+    tree.into_iter()
+        .filter(|node| {
+            if let Some(depth) = subtree_depth {
+                if node.depth > depth {
+                    return false;
+                }
+                subtree_depth = None;
+            }
+            if node.id == category_id {
+                subtree_depth = Some(node.depth);
+                return false;
+            }
+            true
+        })
+        .collect()
+}
+
 // region: Handlers
 #[derive(Template)]
 #[template(path = "categories/pages_categories.html")]
 pub struct CategoriesPage {
-    pub categories: Vec<Category>,
+    pub categories: Vec<CategoryNode>,
 }
-pub async fn categories_page(State(mm): State<ModelManager>) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
-    let categories = get_all_categories(&ctx, &mm).await?;
+pub async fn categories_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let categories = get_category_tree(&ctx, &mm).await?;
 
     let template = CategoriesPage { categories };
     let reply_html = template.render().unwrap();
     Ok((StatusCode::OK, Html(reply_html).into_response()))
 }
 
+#[serde_as]
 #[derive(Deserialize)]
 pub struct CategoryForCreate {
     name: String,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    parent_id: Option<i64>,
 }
 pub async fn create_category(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Form(create): Form<CategoryForCreate>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-    model::category::create_category(&ctx, &mm, create.name).await?;
+    model::category::create_category(
+        &ctx,
+        &mm,
+        model::category::CategoryForCreate {
+            name: create.name,
+            parent_id: create.parent_id,
+        },
+    )
+    .await?;
 
-    let categories = get_all_categories(&ctx, &mm).await?;
+    let categories = get_category_tree(&ctx, &mm).await?;
     let template = TableEntries { categories };
     let reply_html = template.render().unwrap();
 
@@ -92,12 +128,10 @@ pub async fn create_category(
 
 pub async fn get_category_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
-    let category = get_category_by_id(&ctx, &mm, id).await?;
+    let category = get_category_node_by_id(&ctx, &mm, id).await?;
 
     let template = TableEntry { category };
     let reply_html = template.render().unwrap();
@@ -106,26 +140,32 @@ pub async fn get_category_row(
 
 pub async fn delete_category_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-    println!("category");
-    println!("{}", id);
-    model::category::delete_category(&ctx, &mm, id).await?;
+    let deleted = model::category::delete_category(&ctx, &mm, id).await;
 
-    let categories = get_all_categories(&ctx, &mm).await?;
+    let categories = get_category_tree(&ctx, &mm).await?;
     let template = TableEntries { categories };
     let reply_html = template.render().unwrap();
-    Ok((
-        StatusCode::OK,
-        Html(with_toast_response(
+
+    let reply_html = match deleted {
+        Ok(()) => with_toast_response(reply_html, ToastSeverity::Succes, "Category Deleted"),
+        Err(model::Error::CategoryHasProducts {
+            name,
+            product_count,
+            ..
+        }) => with_toast_response(
             reply_html,
-            ToastSeverity::Succes,
-            "Category Deleted",
-        ))
-        .into_response(),
-    ))
+            ToastSeverity::Failure,
+            &format!(
+                "Cannot delete {name}: {product_count} product(s) still assigned. Move them to another category first."
+            ),
+        ),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
 }
 
 #[derive(Template)]
@@ -135,10 +175,9 @@ pub struct DeleteRowAction {
 }
 pub async fn delete_category_row_action(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
     let category = model::category::get_category_by_id(&ctx, &mm, id).await?;
 
     let template = DeleteRowAction { category };
@@ -146,28 +185,34 @@ pub async fn delete_category_row_action(
     Ok((StatusCode::OK, Html(reply_html).into_response()))
 }
 
+#[serde_as]
 #[derive(Deserialize)]
 pub struct CategoryForUpdate {
     name: String,
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    parent_id: Option<i64>,
 }
 pub async fn update_category_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
     Form(new_category): Form<CategoryForUpdate>,
 ) -> Result<impl IntoResponse> {
-    let ctx = Ctx::new(1, 1);
-    println!("category");
-    let category = model::category::update_category(
+    model::category::update_category(
         &ctx,
         &mm,
         model::category::CategoryForUpdate {
             id,
             name: new_category.name,
+            parent_id: new_category.parent_id,
         },
     )
     .await?;
 
-    let template = TableEntry { category };
+    // Moving a category reorders the tree, so the whole table is replaced.
+    let categories = get_category_tree(&ctx, &mm).await?;
+    let template = TableEntries { categories };
     let reply_html = template.render().unwrap();
     Ok((
         StatusCode::OK,
@@ -182,14 +227,13 @@ pub async fn update_category_row(
 
 pub async fn edit_category_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
     let category = get_category_by_id(&ctx, &mm, id).await?;
+    let parents = parent_candidates(get_category_tree(&ctx, &mm).await?, id);
 
-    let template = EditRowFragment { category };
+    let template = EditRowFragment { category, parents };
     let reply_html = template.render().unwrap();
     Ok((StatusCode::OK, Html(reply_html).into_response()))
 }
@@ -200,11 +244,9 @@ pub struct CategoryForSearch {
 }
 pub async fn search_category(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Form(category_for_search): Form<CategoryForSearch>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
     let categories =
         model::category::search_category(&ctx, &mm, category_for_search.search).await?;
 
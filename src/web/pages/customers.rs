@@ -0,0 +1,110 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::customer::{
+    create_customer, delete_customer, get_customers, update_customer, Customer, CustomerForCreate,
+    CustomerForUpdate,
+};
+use crate::model::price_list::{get_price_lists, PriceList};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post, put};
+use axum::{Form, Router};
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_customers(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/customers", get(customers_page))
+        .route("/customers", post(create_customer_row))
+        .route("/customers/:id", put(update_customer_price_list))
+        .route("/customers/:id", delete(delete_customer_row))
+        .with_state(mm)
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "customers/pages_customers.html")]
+pub struct CustomersPage {
+    pub customers: Vec<Customer>,
+    pub price_lists: Vec<PriceList>,
+}
+
+#[derive(Template)]
+#[template(path = "customers/fragments/table_entries.html")]
+pub struct TableEntries {
+    pub customers: Vec<Customer>,
+    pub price_lists: Vec<PriceList>,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_table(ctx: &Ctx, mm: &ModelManager) -> Result<String> {
+    let template = TableEntries {
+        customers: get_customers(ctx, mm).await?,
+        price_lists: get_price_lists(ctx, mm).await?,
+    };
+    Ok(template.render().unwrap())
+}
+
+pub async fn customers_page(State(mm): State<ModelManager>, ctx: Ctx) -> Result<impl IntoResponse> {
+    let template = CustomersPage {
+        customers: get_customers(&ctx, &mm).await?,
+        price_lists: get_price_lists(&ctx, &mm).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_customer_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(customer_for_create): Form<CustomerForCreate>,
+) -> Result<impl IntoResponse> {
+    match create_customer(&ctx, &mm, customer_for_create).await {
+        Ok(()) => {}
+        Err(model::Error::InvalidCustomerName { name }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Enter a name no other customer has, not \"{name}\""),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "Customer Created",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn update_customer_price_list(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(customer_for_update): Form<CustomerForUpdate>,
+) -> Result<impl IntoResponse> {
+    update_customer(&ctx, &mm, id, customer_for_update).await?;
+
+    Ok(toast_only_response(
+        ToastSeverity::Succes,
+        "Price List Assigned",
+    ))
+}
+
+pub async fn delete_customer_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    delete_customer(&ctx, &mm, id).await?;
+
+    let reply_html = render_table(&ctx, &mm).await?;
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
@@ -0,0 +1,259 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::exchange_rate::{
+    create_exchange_rate, delete_exchange_rate, get_exchange_rates, import_exchange_rates,
+    ExchangeRate, ExchangeRateForCreate, ExchangeRateImportReport, ExchangeRateImportRow,
+};
+use crate::model::organization::{
+    can_change_base_currency, get_base_currency, update_base_currency,
+};
+use crate::model::ModelManager;
+use crate::money::Currency;
+use crate::spreadsheet::read_table;
+use crate::web::error::Result;
+use crate::web::locale::RequestLocale;
+use askama::Template;
+use axum::extract::{Multipart, Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post, put};
+use axum::{Form, Router};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_exchange_rates(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/exchange-rates", get(exchange_rates_page))
+        .route("/exchange-rates", post(create_exchange_rate_row))
+        .route("/exchange-rates/:id", delete(delete_exchange_rate_row))
+        .route("/exchange-rates/import", post(import_exchange_rates_file))
+        .route(
+            "/exchange-rates/base-currency",
+            put(update_base_currency_form),
+        )
+        .with_state(mm)
+}
+
+// region: Columns
+/// Header names recognised for each column, all required.
+const COLUMNS: [&[&str]; 3] = [
+    &["currency", "code", "currency code"],
+    &["rate", "exchange rate", "exchange_rate"],
+    &["date", "effective date", "effective_date", "valid from"],
+];
+
+/// Maps a table with a header row to exchange rate rows.
+fn rows_from_table(
+    table: &[Vec<String>],
+) -> core::result::Result<Vec<ExchangeRateImportRow>, String> {
+    let header: Vec<String> = table
+        .first()
+        .map(|h| h.iter().map(|c| c.trim().to_lowercase()).collect())
+        .unwrap_or_default();
+    let columns: Vec<usize> = COLUMNS
+        .iter()
+        .filter_map(|aliases| header.iter().position(|h| aliases.contains(&h.as_str())))
+        .collect();
+    if columns.len() < COLUMNS.len() {
+        return Err("The header row needs Currency, Rate and Date columns".to_string());
+    }
+
+    let cell = |row: &[String], column: usize| row.get(column).cloned().unwrap_or_default();
+
+    Ok(table
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, row)| ExchangeRateImportRow {
+            line: i + 1,
+            currency: cell(row, columns[0]),
+            rate: cell(row, columns[1]),
+            effective_date: cell(row, columns[2]),
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BaseCurrencyForm {
+    currency: String,
+}
+// endregion: Columns
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "exchange_rates/pages_exchange_rates.html")]
+pub struct ExchangeRatesPage {
+    pub base_currency: Currency,
+    /// The base currency can only change before any stock has moved.
+    pub base_currency_locked: bool,
+    pub today: NaiveDate,
+    pub rates: Vec<ExchangeRate>,
+}
+
+#[derive(Template)]
+#[template(path = "exchange_rates/fragments/table_entries.html")]
+pub struct TableEntries {
+    pub base_currency: Currency,
+    pub rates: Vec<ExchangeRate>,
+}
+
+#[derive(Template)]
+#[template(path = "exchange_rates/fragments/import_report.html")]
+pub struct ImportReportTemplate {
+    pub report: ExchangeRateImportReport,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_table(ctx: &Ctx, mm: &ModelManager) -> Result<String> {
+    let base_currency = get_base_currency(ctx, mm).await?;
+    let rates = get_exchange_rates(ctx, mm).await?;
+
+    let template = TableEntries {
+        base_currency,
+        rates,
+    };
+    Ok(template.render().unwrap())
+}
+
+pub async fn exchange_rates_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let template = ExchangeRatesPage {
+        base_currency: get_base_currency(&ctx, &mm).await?,
+        base_currency_locked: !can_change_base_currency(&ctx, &mm).await?,
+        today: Utc::now().date_naive(),
+        rates: get_exchange_rates(&ctx, &mm).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_exchange_rate_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(exchange_rate_for_create): Form<ExchangeRateForCreate>,
+) -> Result<impl IntoResponse> {
+    match create_exchange_rate(&ctx, &mm, exchange_rate_for_create).await {
+        Ok(()) => {}
+        Err(model::Error::InvalidExchangeRate { currency }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!(
+                    "Enter a positive rate for a currency other than the base, not {currency}"
+                ),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "Exchange Rate Saved",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn delete_exchange_rate_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    delete_exchange_rate(&ctx, &mm, id).await?;
+
+    let reply_html = render_table(&ctx, &mm).await?;
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+/// Records every rate of an uploaded file, or shows which rows are wrong.
+pub async fn import_exchange_rates_file(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    RequestLocale(locale): RequestLocale,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let mut table = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            table = match field.bytes().await {
+                Ok(bytes) => read_table(&file_name, bytes.to_vec()).ok(),
+                Err(_) => None,
+            };
+        }
+    }
+
+    let Some(table) = table.filter(|t| t.len() > 1) else {
+        return Ok(toast_only_response(
+            ToastSeverity::Failure,
+            "Choose a CSV or XLSX file with a header and at least one row",
+        ));
+    };
+    let rows = match rows_from_table(&table) {
+        Ok(rows) => rows,
+        Err(message) => return Ok(toast_only_response(ToastSeverity::Failure, &message)),
+    };
+
+    let report = import_exchange_rates(&ctx, &mm, rows, locale).await?;
+    if report.error_count() > 0 {
+        // Swap the report in instead of the rates table so the errors are visible.
+        let error_count = report.error_count();
+        let template = ImportReportTemplate { report };
+        let reply_html = with_toast_response(
+            template.render().unwrap(),
+            ToastSeverity::Failure,
+            &format!("Nothing imported, {error_count} rows have errors"),
+        );
+        return Ok((
+            StatusCode::OK,
+            [
+                ("HX-Retarget", "#exchange-rate-import-report"),
+                ("HX-Reswap", "innerHTML"),
+            ],
+            Html(reply_html),
+        )
+            .into_response());
+    }
+
+    // Clear the report of an earlier, failed upload.
+    let reply_html = format!(
+        r#"{}<div id="exchange-rate-import-report" hx-swap-oob="true"></div>"#,
+        render_table(&ctx, &mm).await?
+    );
+    let reply_html = with_toast_response(
+        reply_html,
+        ToastSeverity::Succes,
+        &format!("Imported {} exchange rates", report.rows.len()),
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn update_base_currency_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(form): Form<BaseCurrencyForm>,
+) -> Result<impl IntoResponse> {
+    let Ok(currency) = form.currency.parse::<Currency>() else {
+        return Ok(toast_only_response(
+            ToastSeverity::Failure,
+            &format!("\"{}\" is not a currency code", form.currency.trim()),
+        ));
+    };
+
+    match update_base_currency(&ctx, &mm, currency).await {
+        Ok(()) => Ok(toast_only_response(
+            ToastSeverity::Succes,
+            &format!("Base currency set to {currency}"),
+        )),
+        Err(model::Error::BaseCurrencyLocked) => Ok(toast_only_response(
+            ToastSeverity::Failure,
+            "Stock has already moved, the base currency can no longer change",
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+// endregion: Handlers
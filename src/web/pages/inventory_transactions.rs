@@ -1,34 +1,520 @@
-use axum::Router;
-
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::customer::{get_customers, Customer};
+use crate::model::exchange_rate::get_exchange_rates;
+use crate::model::inventory_transaction::{
+    InventoryTransactionAction, InventoryTransactionForCreate, InventoryTransactionLogForCreate,
+};
+use crate::model::organization::get_base_currency;
+use crate::model::price_list::{can_override_price, get_effective_price, EffectivePriceForSearch};
+use crate::model::products::{get_product_with_stock_level, ProductWithStockLevel};
+use crate::model::warehouse::{get_all_warehouses, Warehouse};
 use crate::model::ModelManager;
+use crate::money::{self, Currency, Locale, Money};
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use chrono::NaiveDate;
+
+use super::toasts::{with_toast_response, ToastSeverity};
 
-pub fn pages_products(mm: ModelManager) -> Router {
+pub fn pages_inventory_transactions(mm: ModelManager) -> Router {
     Router::new()
         // read
-        .route("/inventories/transactions/deposits", get(products_page))
+        .route("/inventories/transactions/deposits", get(deposit_page))
+        .route("/inventories/transactions/sales", get(sales_page))
+        // scan
+        .route(
+            "/inventories/transactions/deposits/scan",
+            post(scan_deposit),
+        )
+        .route("/inventories/transactions/sales/scan", post(scan_sales))
+        // price
+        .route(
+            "/inventories/transactions/sales/prices",
+            post(reprice_sales),
+        )
         // create
-        .route("/products", post(create_category))
+        .route("/inventories/transactions/deposits", post(create_deposit))
+        .route("/inventories/transactions/sales", post(create_sales))
         .with_state(mm)
 }
 
-// region: Table templates
-// region:  Deposit
+// region: Form lines
+#[derive(Clone, Copy, PartialEq)]
+pub enum TransactionForm {
+    Deposit,
+    Sales,
+}
+
+impl TransactionForm {
+    pub fn path(&self) -> &'static str {
+        match self {
+            TransactionForm::Deposit => "deposits",
+            TransactionForm::Sales => "sales",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            TransactionForm::Deposit => "Deposit",
+            TransactionForm::Sales => "Sales",
+        }
+    }
+}
+
+pub struct FormLine {
+    pub product: ProductWithStockLevel,
+    pub quantity: i64,
+    pub price: String,
+    /// The customer's effective price when the line was last priced, to tell
+    /// an overridden price from one to recalculate.
+    pub list_price: String,
+    pub lot_number: String,
+    pub expiry_date: String,
+    pub serial_numbers: String,
+}
+
+#[derive(Default)]
+struct RawFormLine {
+    product_id: i64,
+    quantity: i64,
+    price: String,
+    list_price: String,
+    lot_number: String,
+    expiry_date: String,
+    serial_numbers: String,
+}
+
+/// The submitted form: each line starts at its `product_id` field, the fields
+/// following it belong to that line.
+#[derive(Default)]
+struct RawForm {
+    warehouse_id: Option<i64>,
+    customer_id: Option<i64>,
+    code: String,
+    currency: String,
+    exchange_rate: String,
+    lines: Vec<RawFormLine>,
+}
+
+impl From<Vec<(String, String)>> for RawForm {
+    fn from(fields: Vec<(String, String)>) -> Self {
+        let mut form = RawForm::default();
+        for (name, value) in fields {
+            match (name.as_str(), form.lines.last_mut()) {
+                ("warehouse_id", _) => form.warehouse_id = value.parse().ok(),
+                ("customer_id", _) => form.customer_id = value.parse().ok(),
+                ("code", _) => form.code = value,
+                ("currency", _) => form.currency = value,
+                ("exchange_rate", _) => form.exchange_rate = value,
+                ("product_id", _) => {
+                    if let Ok(product_id) = value.parse() {
+                        form.lines.push(RawFormLine {
+                            product_id,
+                            ..Default::default()
+                        });
+                    }
+                }
+                ("quantity", Some(line)) => line.quantity = value.parse().unwrap_or_default(),
+                ("price", Some(line)) => line.price = value,
+                ("list_price", Some(line)) => line.list_price = value,
+                ("lot_number", Some(line)) => line.lot_number = value,
+                ("expiry_date", Some(line)) => line.expiry_date = value,
+                ("serial_numbers", Some(line)) => line.serial_numbers = value,
+                _ => {}
+            }
+        }
+        form
+    }
+}
+
+async fn resolve_lines(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    raw_lines: Vec<RawFormLine>,
+) -> Result<Vec<FormLine>> {
+    let mut lines = Vec::with_capacity(raw_lines.len());
+    for raw in raw_lines {
+        let Some(product) = get_product_with_stock_level(ctx, mm, raw.product_id).await? else {
+            continue;
+        };
+        lines.push(FormLine {
+            product,
+            quantity: raw.quantity,
+            price: raw.price,
+            list_price: raw.list_price,
+            lot_number: raw.lot_number,
+            expiry_date: raw.expiry_date,
+            serial_numbers: raw.serial_numbers,
+        });
+    }
+    Ok(lines)
+}
+
+/// Prices every line at the customer's effective price for its quantity,
+/// keeping prices the user has overridden if they may.
+async fn reprice_lines(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    customer_id: Option<i64>,
+    can_override_price: bool,
+    lines: &mut [FormLine],
+) -> Result<()> {
+    for line in lines.iter_mut() {
+        let effective_price_for_search = EffectivePriceForSearch {
+            customer_id,
+            product_id: line.product.id,
+            quantity: line.quantity.max(1),
+            unit: None,
+            date: None,
+        };
+        let list_price = get_effective_price(ctx, mm, effective_price_for_search)
+            .await?
+            .price
+            .amount()
+            .to_string();
+
+        let overridden = can_override_price
+            && !line.price.trim().is_empty()
+            && line.price.trim() != line.list_price;
+        if !overridden {
+            line.price = list_price.clone();
+        }
+        line.list_price = list_price;
+    }
+    Ok(())
+}
+// endregion: Form lines
+
+// region: Templates
 #[derive(Template)]
-#[template(path = "products/fragments/table_entries.html")]
-pub struct TableEntries {
-    pub products: Vec<ProductWithStockLevel>,
+#[template(path = "inventories/transactions/pages_transaction_form.html")]
+pub struct TransactionFormPage {
+    pub form: TransactionForm,
+    pub warehouses: Vec<Warehouse>,
+    pub base_currency: Currency,
+    /// Currencies with an exchange rate, offered for deposits.
+    pub currencies: Vec<Currency>,
+    /// Offered for sales.
+    pub customers: Vec<Customer>,
+    pub can_override_price: bool,
+    pub lines: Vec<FormLine>,
 }
 
 #[derive(Template)]
-#[template(path = "products/fragments/table_entry.html")]
-pub struct TableEntry {
-    pub product: ProductWithStockLevel,
+#[template(path = "inventories/transactions/fragments/form_lines.html")]
+pub struct FormLines {
+    pub form: TransactionForm,
+    pub can_override_price: bool,
+    pub lines: Vec<FormLine>,
+}
+
+/// Links to the documents of the sale just saved, swapped in out of band.
+#[derive(Template)]
+#[template(path = "inventories/transactions/fragments/sale_documents.html")]
+pub struct SaleDocuments {
+    pub transaction_id: i64,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_page(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    form: TransactionForm,
+) -> Result<impl IntoResponse> {
+    let warehouses = get_all_warehouses(ctx, mm).await?;
+    let mut currencies: Vec<Currency> = get_exchange_rates(ctx, mm)
+        .await?
+        .into_iter()
+        .map(|r| r.currency)
+        .collect();
+    currencies.sort_by(|a, b| a.code().cmp(b.code()));
+    currencies.dedup();
+
+    let template = TransactionFormPage {
+        form,
+        warehouses,
+        base_currency: get_base_currency(ctx, mm).await?,
+        currencies,
+        customers: get_customers(ctx, mm).await?,
+        can_override_price: can_override_price(ctx, mm).await?,
+        lines: Vec::new(),
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
 }
-// endregion:   Deposit
-// endregion: Table templates
 
-// region: Read
-// region:  Deposit
+pub async fn deposit_page(State(mm): State<ModelManager>, ctx: Ctx) -> Result<impl IntoResponse> {
+    render_page(&ctx, &mm, TransactionForm::Deposit).await
+}
+
+pub async fn sales_page(State(mm): State<ModelManager>, ctx: Ctx) -> Result<impl IntoResponse> {
+    render_page(&ctx, &mm, TransactionForm::Sales).await
+}
+
+/// Adds the scanned product as a new line, or bumps its quantity if it is
+/// already on the form.
+async fn scan(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    form: TransactionForm,
+    fields: Vec<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let raw_form = RawForm::from(fields);
+    let code = raw_form.code.trim().to_string();
+    let mut lines = resolve_lines(ctx, mm, raw_form.lines).await?;
+
+    let scanned = match model::barcode::get_product_by_barcode(ctx, mm, &code).await {
+        Ok(product) => Some(product),
+        Err(model::Error::BarcodeNotFound { .. }) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let found = scanned.is_some();
+
+    if let Some(product) = scanned {
+        match lines.iter_mut().find(|l| l.product.id == product.id) {
+            Some(line) => line.quantity += 1,
+            None => lines.push(FormLine {
+                price: product.price.amount().to_string(),
+                list_price: product.price.amount().to_string(),
+                product,
+                quantity: 1,
+                lot_number: String::new(),
+                expiry_date: String::new(),
+                serial_numbers: String::new(),
+            }),
+        }
+    }
+
+    let can_override_price = can_override_price(ctx, mm).await?;
+    if form == TransactionForm::Sales {
+        reprice_lines(
+            ctx,
+            mm,
+            raw_form.customer_id,
+            can_override_price,
+            &mut lines,
+        )
+        .await?;
+    }
+
+    let template = FormLines {
+        form,
+        can_override_price,
+        lines,
+    };
+    let reply_html = template.render().unwrap();
+    let reply_html = if found {
+        reply_html
+    } else {
+        with_toast_response(
+            reply_html,
+            ToastSeverity::Failure,
+            &format!("No product found for {code}"),
+        )
+    };
+
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+
+pub async fn scan_deposit(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse> {
+    scan(&ctx, &mm, TransactionForm::Deposit, fields).await
+}
+
+pub async fn scan_sales(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse> {
+    scan(&ctx, &mm, TransactionForm::Sales, fields).await
+}
 
-// endregion:   Deposit
-// endregion: Read
+/// Reprices the sales lines after the customer or a quantity changed.
+pub async fn reprice_sales(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse> {
+    let raw_form = RawForm::from(fields);
+    let mut lines = resolve_lines(&ctx, &mm, raw_form.lines).await?;
+
+    let can_override_price = can_override_price(&ctx, &mm).await?;
+    reprice_lines(
+        &ctx,
+        &mm,
+        raw_form.customer_id,
+        can_override_price,
+        &mut lines,
+    )
+    .await?;
+
+    let template = FormLines {
+        form: TransactionForm::Sales,
+        can_override_price,
+        lines,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+
+async fn create(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    form: TransactionForm,
+    fields: Vec<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let raw_form = RawForm::from(fields);
+    let mut lines = resolve_lines(ctx, mm, raw_form.lines).await?;
+
+    // Without the permission, sales are at the customer's prices whatever
+    // was submitted.
+    let can_override_price = can_override_price(ctx, mm).await?;
+    if form == TransactionForm::Sales {
+        reprice_lines(
+            ctx,
+            mm,
+            raw_form.customer_id,
+            can_override_price,
+            &mut lines,
+        )
+        .await?;
+    }
+
+    let currency = match raw_form.currency.trim() {
+        "" => Ok(get_base_currency(ctx, mm).await?),
+        currency => currency.parse::<Currency>(),
+    };
+    // Number inputs always submit `1234.5`, whatever the browser's locale.
+    let exchange_rate = match raw_form.exchange_rate.trim() {
+        "" => Ok(None),
+        exchange_rate => Locale::EN.parse_amount(exchange_rate).map(Some),
+    };
+    let prices = match &currency {
+        Ok(currency) => lines
+            .iter()
+            .map(|line| match line.price.trim() {
+                "" => Ok(Money::zero(*currency)),
+                price => Money::parse(price, *currency, Locale::EN),
+            })
+            .collect::<core::result::Result<Vec<_>, _>>(),
+        Err(_) => Ok(Vec::new()),
+    };
+
+    let saved = match (
+        raw_form.warehouse_id,
+        lines.is_empty(),
+        currency,
+        exchange_rate,
+        prices,
+    ) {
+        (None, ..) => Err("Select a warehouse".to_string()),
+        (_, true, ..) => Err("Scan or add at least one product".to_string()),
+        (_, _, Err(money::Error::InvalidCurrency(currency)), ..) => {
+            Err(format!("\"{}\" is not a currency code", currency.trim()))
+        }
+        (_, _, _, Err(money::Error::InvalidAmount(rate)), _) => {
+            Err(format!("Invalid exchange rate \"{rate}\""))
+        }
+        (.., Err(money::Error::InvalidAmount(price))) => Err(format!("Invalid price \"{price}\"")),
+        (_, _, Err(e), ..) | (_, _, _, Err(e), _) | (.., Err(e)) => Err(e.to_string()),
+        (Some(warehouse_id), false, Ok(currency), Ok(exchange_rate), Ok(prices)) => {
+            let action = match form {
+                TransactionForm::Deposit => InventoryTransactionAction::Deposit,
+                TransactionForm::Sales => InventoryTransactionAction::Sales,
+            };
+            let mut transaction = InventoryTransactionForCreate::new(action);
+            transaction.currency = Some(currency);
+            transaction.exchange_rate = exchange_rate;
+            transaction.customer_id = raw_form.customer_id;
+            for (line, price) in lines.iter().zip(prices) {
+                transaction.add_log(InventoryTransactionLogForCreate {
+                    quantity: line.quantity,
+                    product_id: line.product.id,
+                    price,
+                    warehouse_id,
+                    lot_id: None,
+                    lot_number: Some(line.lot_number.trim().to_string()).filter(|l| !l.is_empty()),
+                    expiry_date: NaiveDate::parse_from_str(&line.expiry_date, "%Y-%m-%d").ok(),
+                    serial_numbers: line
+                        .serial_numbers
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                    unit_name: None,
+                });
+            }
+            match transaction.save(ctx, mm).await {
+                Err(model::Error::ExchangeRateNotFound { currency, date }) => Err(format!(
+                    "No {currency} exchange rate on or before {date}, enter one"
+                )),
+                Err(model::Error::InvalidQuantity { quantity, .. }) => {
+                    Err(format!("Quantities must be at least 1, not {quantity}"))
+                }
+                Err(model::Error::QuantityTooLarge { quantity, .. }) => {
+                    Err(format!("{quantity} is more than can be recorded at once"))
+                }
+                Err(model::Error::PriceOverrideNotAllowed { list_price, .. }) => Err(format!(
+                    "You may not change prices, the price is {list_price}"
+                )),
+                saved => saved.map_err(|e| e.to_string()),
+            }
+        }
+    };
+
+    let reply_html = match saved {
+        Ok(transaction_id) => {
+            let template = FormLines {
+                form,
+                can_override_price,
+                lines: Vec::new(),
+            };
+            let mut reply_html = template.render().unwrap();
+            if form == TransactionForm::Sales {
+                reply_html.push_str(&SaleDocuments { transaction_id }.render().unwrap());
+            }
+            with_toast_response(
+                reply_html,
+                ToastSeverity::Succes,
+                &format!("{} Saved", form.title()),
+            )
+        }
+        Err(message) => {
+            let template = FormLines {
+                form,
+                can_override_price,
+                lines,
+            };
+            with_toast_response(template.render().unwrap(), ToastSeverity::Failure, &message)
+        }
+    };
+
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+
+pub async fn create_deposit(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse> {
+    create(&ctx, &mm, TransactionForm::Deposit, fields).await
+}
+
+pub async fn create_sales(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse> {
+    create(&ctx, &mm, TransactionForm::Sales, fields).await
+}
+// endregion: Handlers
@@ -0,0 +1,118 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::invitation::{
+    accept_invitation, get_invitation, InvitationForAccept, InvitationForAcceptForm,
+};
+use crate::model::session::create_pending_session;
+use crate::model::two_factor::{get_login_step, LoginStep};
+use crate::model::user::MIN_PASSWORD_LENGTH;
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use crate::web::mw_rate_limit::{attempt_failed, mw_rate_limit, RateLimit};
+use crate::web::routes_auth::{set_session_cookie, start_session, HOME};
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::get;
+use axum::{middleware, Form, Router};
+use std::time::Duration;
+use tower_cookies::Cookies;
+
+use super::toasts::{toast_only_response, ToastSeverity};
+
+pub fn pages_invitations(mm: ModelManager) -> Router {
+    // Links that do not work, per address, so tokens cannot be guessed.
+    let link_limit = RateLimit::new(
+        5,
+        Duration::from_secs(2),
+        Duration::from_secs(15 * 60),
+        Duration::from_secs(60 * 60),
+    );
+
+    Router::new()
+        .route(
+            "/invite/:token",
+            get(invitation_page)
+                .post(accept_invitation_form)
+                .route_layer(middleware::from_fn_with_state(link_limit, mw_rate_limit)),
+        )
+        .with_state(mm)
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "invitations/pages_accept.html")]
+pub struct AcceptPage {
+    pub token: String,
+    /// None once used or expired.
+    pub invitation: Option<InvitationForAccept>,
+    pub min_password_length: usize,
+}
+// endregion: Templates
+
+// region: Handlers
+pub async fn invitation_page(
+    State(mm): State<ModelManager>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    let (status, invitation) = match get_invitation(&ctx, &mm, &token).await {
+        Ok(invitation) => (StatusCode::OK, Some(invitation)),
+        Err(model::Error::InvitationNotFound) => (StatusCode::NOT_FOUND, None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let template = AcceptPage {
+        token,
+        invitation,
+        min_password_length: MIN_PASSWORD_LENGTH,
+    };
+    let reply_html = template.render().unwrap();
+    let response = (status, Html(reply_html)).into_response();
+    match status {
+        StatusCode::NOT_FOUND => Ok(attempt_failed(response)),
+        _ => Ok(response),
+    }
+}
+
+/// Creates the user and logs them in, or on to their second factor.
+pub async fn accept_invitation_form(
+    State(mm): State<ModelManager>,
+    cookies: Cookies,
+    Path(token): Path<String>,
+    Form(form): Form<InvitationForAcceptForm>,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    let message = match accept_invitation(&ctx, &mm, &token, form).await {
+        Ok(user_id) => {
+            // An organization requiring two-factor authentication has the new
+            // user set it up before they are in.
+            if get_login_step(&ctx, &mm, user_id).await? != LoginStep::Granted {
+                let token = create_pending_session(&ctx, &mm, user_id).await?;
+                set_session_cookie(&cookies, token);
+                return Ok((StatusCode::OK, [("HX-Redirect", "/login/two-factor")]).into_response());
+            }
+            start_session(&ctx, &mm, &cookies, user_id).await?;
+            return Ok((StatusCode::OK, [("HX-Redirect", HOME)]).into_response());
+        }
+        Err(model::Error::InvalidPassword) => {
+            format!("Passwords need at least {MIN_PASSWORD_LENGTH} characters")
+        }
+        Err(model::Error::InvitationNotFound) => {
+            return Ok(attempt_failed(toast_only_response(
+                ToastSeverity::Failure,
+                "This invitation was used or has expired",
+            )))
+        }
+        Err(model::Error::InvalidUser { username }) => {
+            format!("{username} is already taken, ask for a new invitation")
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(toast_only_response(ToastSeverity::Failure, &message))
+}
+// endregion: Handlers
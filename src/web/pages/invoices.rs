@@ -0,0 +1,395 @@
+use crate::ctx::Ctx;
+use crate::model::invoice::{get_invoice, Invoice};
+use crate::model::ModelManager;
+use crate::money::{Locale, Money};
+use crate::pdf::{text_width, Font, PdfDocument, PAGE_HEIGHT, PAGE_WIDTH};
+use crate::web::error::Result;
+use crate::web::locale::RequestLocale;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+
+pub fn pages_invoices(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/inventories/transactions/:id/invoice", get(invoice_page))
+        .route(
+            "/inventories/transactions/:id/invoice/pdf",
+            get(invoice_pdf),
+        )
+        .route(
+            "/inventories/transactions/:id/delivery-note",
+            get(delivery_note_page),
+        )
+        .route(
+            "/inventories/transactions/:id/delivery-note/pdf",
+            get(delivery_note_pdf),
+        )
+        .with_state(mm)
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "invoices/pages_invoice.html")]
+pub struct InvoicePage {
+    pub invoice: Invoice,
+}
+
+#[derive(Template)]
+#[template(path = "invoices/pages_delivery_note.html")]
+pub struct DeliveryNotePage {
+    pub invoice: Invoice,
+}
+// endregion: Templates
+
+// region: PDF
+const MARGIN: f32 = 50.0;
+const LEFT: f32 = MARGIN;
+const RIGHT: f32 = PAGE_WIDTH - MARGIN;
+const TOP: f32 = PAGE_HEIGHT - MARGIN;
+const ROW: f32 = 16.0;
+const SIZE: f32 = 10.0;
+const SMALL: f32 = 8.0;
+
+/// Amounts without the symbol, which the standard fonts may not have; the
+/// currency code is printed once in the header.
+fn amount(money: &Money, locale: Locale) -> String {
+    locale.format_amount(money.amount(), money.currency().minor_units())
+}
+
+/// Cuts `text` to fit `width`, marking the cut with an ellipsis.
+fn fit(text: &str, width: f32, size: f32) -> String {
+    if text_width(text, size) <= width {
+        return text.to_string();
+    }
+    let mut fitted = text.to_string();
+    while !fitted.is_empty() && text_width(&format!("{fitted}..."), size) > width {
+        fitted.pop();
+    }
+    format!("{fitted}...")
+}
+
+/// Title, organization, number, date and customer. Returns where the next
+/// line goes.
+fn document_header(doc: &mut PdfDocument, title: &str, invoice: &Invoice, party: &str) -> f32 {
+    let mut y = TOP;
+    doc.text(LEFT, y - 14.0, 20.0, Font::Bold, title);
+    y -= 40.0;
+
+    doc.text(LEFT, y, SIZE, Font::Bold, &invoice.organization_name);
+    doc.text_right(
+        RIGHT,
+        y,
+        SIZE,
+        Font::Bold,
+        &format!("Invoice {}", invoice.display_number()),
+    );
+    y -= ROW;
+    doc.text_right(
+        RIGHT,
+        y,
+        SIZE,
+        Font::Regular,
+        &format!("Date {}", invoice.timestamp.format("%Y-%m-%d")),
+    );
+    y -= 2.0 * ROW;
+
+    doc.text(LEFT, y, SMALL, Font::Regular, party);
+    y -= ROW;
+    let customer_name = invoice
+        .customer_name
+        .as_deref()
+        .unwrap_or("Walk-in customer");
+    doc.text(LEFT, y, SIZE, Font::Regular, customer_name);
+
+    y - 2.0 * ROW
+}
+
+/// Starts a new page for the next `rows` rows if they do not fit, and
+/// returns where they go.
+fn ensure_rows(
+    doc: &mut PdfDocument,
+    y: f32,
+    rows: f32,
+    header: impl Fn(&mut PdfDocument) -> f32,
+) -> f32 {
+    match y - rows * ROW < MARGIN {
+        true => {
+            doc.new_page();
+            header(doc)
+        }
+        false => y,
+    }
+}
+
+fn invoice_to_pdf(invoice: &Invoice, locale: Locale) -> Vec<u8> {
+    const QUANTITY: f32 = 330.0;
+    const UNIT_PRICE: f32 = 420.0;
+    const TAX: f32 = 465.0;
+
+    let table_header = |doc: &mut PdfDocument, y: f32| {
+        doc.text(LEFT, y, SIZE, Font::Bold, "Item");
+        doc.text_right(QUANTITY, y, SIZE, Font::Bold, "Quantity");
+        doc.text_right(UNIT_PRICE, y, SIZE, Font::Bold, "Unit Price");
+        doc.text_right(TAX, y, SIZE, Font::Bold, "Tax");
+        doc.text_right(RIGHT, y, SIZE, Font::Bold, "Amount");
+        doc.rule(LEFT, RIGHT, y - 5.0);
+        y - ROW - 4.0
+    };
+
+    let title = format!("Invoice {}", invoice.display_number());
+    let mut doc = PdfDocument::new(&title);
+    let mut y = document_header(&mut doc, "Invoice", invoice, "Bill to");
+    doc.text_right(
+        RIGHT,
+        y + 2.0 * ROW,
+        SMALL,
+        Font::Regular,
+        &format!("Amounts in {}", invoice.currency),
+    );
+    y = table_header(&mut doc, y);
+
+    for line in &invoice.lines {
+        y = ensure_rows(&mut doc, y, 2.0, |doc| table_header(doc, TOP));
+
+        let name_width = QUANTITY - LEFT - 70.0;
+        doc.text(
+            LEFT,
+            y,
+            SIZE,
+            Font::Regular,
+            &fit(&line.product_display_name, name_width, SIZE),
+        );
+        doc.text_right(
+            QUANTITY,
+            y,
+            SIZE,
+            Font::Regular,
+            &format!("{} {}", line.quantity, line.unit),
+        );
+        doc.text_right(
+            UNIT_PRICE,
+            y,
+            SIZE,
+            Font::Regular,
+            &amount(&line.unit_price, locale),
+        );
+        let rate = match &line.tax_rate {
+            Some(rate) => format!("{rate}%"),
+            None => "-".to_string(),
+        };
+        doc.text_right(TAX, y, SIZE, Font::Regular, &rate);
+        doc.text_right(RIGHT, y, SIZE, Font::Regular, &amount(&line.net, locale));
+        doc.text(LEFT, y - 10.0, SMALL, Font::Regular, &line.sku);
+        y -= ROW + 10.0;
+    }
+
+    let tax_rates = &invoice.tax_rates;
+    y = ensure_rows(&mut doc, y, 3.0 + tax_rates.len() as f32, |_| TOP);
+    doc.rule(LEFT, RIGHT, y + ROW - 6.0);
+    y -= 4.0;
+
+    doc.text(TAX - 120.0, y, SIZE, Font::Regular, "Net");
+    doc.text_right(
+        RIGHT,
+        y,
+        SIZE,
+        Font::Regular,
+        &amount(&invoice.total_net, locale),
+    );
+    y -= ROW;
+    for tax_rate in tax_rates {
+        let label = format!(
+            "Tax {}% on {}",
+            tax_rate.rate,
+            amount(&tax_rate.net, locale)
+        );
+        doc.text(TAX - 120.0, y, SIZE, Font::Regular, &label);
+        doc.text_right(
+            RIGHT,
+            y,
+            SIZE,
+            Font::Regular,
+            &amount(&tax_rate.tax, locale),
+        );
+        y -= ROW;
+    }
+    doc.text(
+        TAX - 120.0,
+        y,
+        SIZE,
+        Font::Bold,
+        &format!("Total {}", invoice.currency),
+    );
+    doc.text_right(
+        RIGHT,
+        y,
+        SIZE,
+        Font::Bold,
+        &amount(&invoice.total_gross, locale),
+    );
+
+    doc.finish()
+}
+
+fn delivery_note_to_pdf(invoice: &Invoice) -> Vec<u8> {
+    const ITEM: f32 = LEFT + 100.0;
+
+    let table_header = |doc: &mut PdfDocument, y: f32| {
+        doc.text(LEFT, y, SIZE, Font::Bold, "SKU");
+        doc.text(ITEM, y, SIZE, Font::Bold, "Item");
+        doc.text_right(RIGHT, y, SIZE, Font::Bold, "Quantity");
+        doc.rule(LEFT, RIGHT, y - 5.0);
+        y - ROW - 4.0
+    };
+
+    let title = format!("Delivery Note {}", invoice.display_number());
+    let mut doc = PdfDocument::new(&title);
+    let mut y = document_header(&mut doc, "Delivery Note", invoice, "Deliver to");
+    y = table_header(&mut doc, y);
+
+    let item_width = RIGHT - ITEM - 80.0;
+    for line in &invoice.lines {
+        // Serial numbers wrap onto as many lines as they need.
+        let mut details: Vec<String> = line
+            .lot_number
+            .iter()
+            .map(|lot_number| format!("Lot {lot_number}"))
+            .collect();
+        let mut serials = String::new();
+        for serial_number in &line.serial_numbers {
+            let candidate = match serials.is_empty() {
+                true => format!("S/N {serial_number}"),
+                false => format!("{serials}, {serial_number}"),
+            };
+            match text_width(&candidate, SMALL) > item_width && !serials.is_empty() {
+                true => {
+                    details.push(serials);
+                    serials = serial_number.clone();
+                }
+                false => serials = candidate,
+            }
+        }
+        if !serials.is_empty() {
+            details.push(serials);
+        }
+
+        let rows = 1.0 + details.len() as f32 * 10.0 / ROW;
+        y = ensure_rows(&mut doc, y, rows + 1.0, |doc| table_header(doc, TOP));
+
+        doc.text(
+            LEFT,
+            y,
+            SIZE,
+            Font::Regular,
+            &fit(&line.sku, ITEM - LEFT - 8.0, SIZE),
+        );
+        doc.text(
+            ITEM,
+            y,
+            SIZE,
+            Font::Regular,
+            &fit(&line.product_display_name, item_width, SIZE),
+        );
+        doc.text_right(
+            RIGHT,
+            y,
+            SIZE,
+            Font::Regular,
+            &format!("{} {}", line.quantity, line.unit),
+        );
+        for detail in &details {
+            y -= 10.0;
+            doc.text(
+                ITEM,
+                y,
+                SMALL,
+                Font::Regular,
+                &fit(detail, item_width, SMALL),
+            );
+        }
+        y -= ROW;
+    }
+
+    y = ensure_rows(&mut doc, y, 5.0, |_| TOP);
+    y -= 3.0 * ROW;
+    let signature_width = 200.0;
+    doc.rule(LEFT, LEFT + signature_width, y);
+    doc.rule(RIGHT - signature_width, RIGHT, y);
+    doc.text(LEFT, y - 12.0, SMALL, Font::Regular, "Delivered by");
+    doc.text(
+        RIGHT - signature_width,
+        y - 12.0,
+        SMALL,
+        Font::Regular,
+        "Received by",
+    );
+
+    doc.finish()
+}
+
+fn pdf_response(name: &str, pdf: Vec<u8>) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("inline; filename=\"{name}.pdf\""),
+            ),
+        ],
+        pdf,
+    )
+        .into_response()
+}
+// endregion: PDF
+
+// region: Handlers
+pub async fn invoice_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let template = InvoicePage {
+        invoice: get_invoice(&ctx, &mm, id).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn invoice_pdf(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    RequestLocale(locale): RequestLocale,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let invoice = get_invoice(&ctx, &mm, id).await?;
+    let pdf = invoice_to_pdf(&invoice, locale);
+    Ok(pdf_response(&invoice.display_number(), pdf))
+}
+
+pub async fn delivery_note_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let template = DeliveryNotePage {
+        invoice: get_invoice(&ctx, &mm, id).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn delivery_note_pdf(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let invoice = get_invoice(&ctx, &mm, id).await?;
+    let pdf = delivery_note_to_pdf(&invoice);
+    Ok(pdf_response(&format!("DN-{:06}", invoice.number), pdf))
+}
+// endregion: Handlers
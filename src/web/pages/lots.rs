@@ -0,0 +1,107 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::category::{get_category_tree, CategoryNode};
+use crate::model::lot::{ExpiringLot, LotStockLevel};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
+
+const DEFAULT_EXPIRY_WINDOW_DAYS: i32 = 30;
+
+pub fn pages_lots(mm: ModelManager) -> Router {
+    Router::new()
+        // read
+        .route("/products/:id/lots", get(get_product_lots))
+        .route("/inventories/lots/expiring", get(expiring_lots_page))
+        // search
+        .route(
+            "/inventories/lots/expiring/search",
+            post(search_expiring_lots),
+        )
+        .with_state(mm)
+}
+
+// region: Table templates
+#[derive(Template)]
+#[template(path = "lots/fragments/product_lots.html")]
+pub struct ProductLots {
+    pub lots: Vec<LotStockLevel>,
+}
+
+#[derive(Template)]
+#[template(path = "lots/fragments/table_entries.html")]
+pub struct TableEntries {
+    pub lots: Vec<ExpiringLot>,
+}
+// endregion: Table templates
+
+// region: Handlers
+// region: Read
+pub async fn get_product_lots(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let lots = model::lot::get_stock_by_lot(&ctx, &mm, id).await?;
+
+    let template = ProductLots { lots };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+
+#[derive(Template)]
+#[template(path = "lots/pages_expiring_lots.html")]
+pub struct ExpiringLotsPage {
+    pub days: i32,
+    pub categories: Vec<CategoryNode>,
+    pub lots: Vec<ExpiringLot>,
+}
+pub async fn expiring_lots_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let days = DEFAULT_EXPIRY_WINDOW_DAYS;
+    let lots = model::lot::get_expiring_lots(&ctx, &mm, days, None).await?;
+    let categories = get_category_tree(&ctx, &mm).await?;
+
+    let template = ExpiringLotsPage {
+        days,
+        categories,
+        lots,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+// endregion: Read
+
+// region: Search
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct ExpiringLotsForSearch {
+    days: i32,
+    /// Only lots of products in this category and its subcategories.
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    category_id: Option<i64>,
+}
+pub async fn search_expiring_lots(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(expiring_lots_for_search): Form<ExpiringLotsForSearch>,
+) -> Result<impl IntoResponse> {
+    let ExpiringLotsForSearch { days, category_id } = expiring_lots_for_search;
+    let lots = model::lot::get_expiring_lots(&ctx, &mm, days, category_id).await?;
+
+    let template = TableEntries { lots };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+// endregion: Search
+// endregion: Handlers
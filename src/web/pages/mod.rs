@@ -1,4 +1,22 @@
+pub mod api_keys;
 pub mod categories;
+pub mod customers;
+pub mod exchange_rates;
 pub mod inventory_transactions;
+pub mod invitations;
+pub mod invoices;
+pub mod lots;
+pub mod opening_balance;
+pub mod password_reset;
+pub mod price_lists;
+pub mod product_barcodes;
+pub mod product_import;
+pub mod product_units;
+pub mod product_variants;
 pub mod products;
+pub mod serial_numbers;
+pub mod superuser;
+pub mod taxes;
 pub mod toasts;
+pub mod two_factor;
+pub mod users;
@@ -0,0 +1,247 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::opening_balance::{
+    apply_opening_balance, can_record_opening_balance, dry_run_opening_balance,
+    OpeningBalanceReport, OpeningBalanceRow,
+};
+use crate::model::ModelManager;
+use crate::spreadsheet::{read_csv, read_table, write_csv};
+use crate::web::error::Result;
+use crate::web::locale::RequestLocale;
+use askama::Template;
+use axum::extract::{Multipart, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_opening_balance(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/inventories/opening-balance", get(opening_balance_page))
+        .route(
+            "/inventories/opening-balance/preview",
+            post(preview_opening_balance),
+        )
+        .route("/inventories/opening-balance", post(create_opening_balance))
+        .with_state(mm)
+}
+
+// region: Columns
+/// Header names recognised for each column; the first four are required.
+const COLUMNS: [(&str, &[&str]); 6] = [
+    ("SKU", &["sku", "code", "item code", "product code"]),
+    ("Warehouse", &["warehouse", "location", "warehouse id"]),
+    ("Quantity", &["quantity", "qty", "on hand", "stock"]),
+    ("Unit cost", &["unit cost", "unit_cost", "cost", "price"]),
+    ("Lot number", &["lot number", "lot_number", "lot", "batch"]),
+    (
+        "Expiry date",
+        &["expiry date", "expiry_date", "expiry", "best before"],
+    ),
+];
+const REQUIRED_COLUMNS: usize = 4;
+
+/// Maps a table with a header row to opening balance rows.
+fn rows_from_table(table: &[Vec<String>]) -> core::result::Result<Vec<OpeningBalanceRow>, String> {
+    let header: Vec<String> = table
+        .first()
+        .map(|h| h.iter().map(|c| c.trim().to_lowercase()).collect())
+        .unwrap_or_default();
+    let columns: Vec<Option<usize>> = COLUMNS
+        .iter()
+        .map(|(_, aliases)| header.iter().position(|h| aliases.contains(&h.as_str())))
+        .collect();
+
+    let missing: Vec<&str> = COLUMNS[..REQUIRED_COLUMNS]
+        .iter()
+        .zip(&columns)
+        .filter(|(_, column)| column.is_none())
+        .map(|((name, _), _)| *name)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("The header row is missing: {}", missing.join(", ")));
+    }
+
+    let cell = |row: &[String], column: Option<usize>| {
+        column.and_then(|c| row.get(c)).cloned().unwrap_or_default()
+    };
+
+    Ok(table
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, row)| OpeningBalanceRow {
+            line: i + 1,
+            sku: cell(row, columns[0]),
+            warehouse: cell(row, columns[1]),
+            quantity: cell(row, columns[2]),
+            unit_cost: cell(row, columns[3]),
+            lot_number: cell(row, columns[4]),
+            expiry_date: cell(row, columns[5]),
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpeningBalanceForm {
+    /// The uploaded table, re-serialized as CSV by the preview step.
+    data: String,
+    date: NaiveDate,
+}
+// endregion: Columns
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "inventories/opening_balance/pages_opening_balance.html")]
+pub struct OpeningBalancePage {
+    pub allowed: bool,
+    pub today: NaiveDate,
+}
+
+#[derive(Template)]
+#[template(path = "inventories/opening_balance/fragments/report.html")]
+pub struct ReportTemplate {
+    pub data: String,
+    pub report: OpeningBalanceReport,
+}
+
+#[derive(Template)]
+#[template(path = "inventories/opening_balance/fragments/recorded.html")]
+pub struct RecordedTemplate {
+    pub report: OpeningBalanceReport,
+}
+// endregion: Templates
+
+// region: Handlers
+pub async fn opening_balance_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let allowed = can_record_opening_balance(&ctx, &mm).await?;
+
+    let template = OpeningBalancePage {
+        allowed,
+        today: Utc::now().date_naive(),
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+/// Reads the uploaded file and reports what would be recorded.
+pub async fn preview_opening_balance(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    RequestLocale(locale): RequestLocale,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let mut table = None;
+    let mut date = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        match field.name() {
+            Some("date") => {
+                date = field
+                    .text()
+                    .await
+                    .ok()
+                    .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok());
+            }
+            Some("file") => {
+                let file_name = field.file_name().unwrap_or_default().to_string();
+                table = match field.bytes().await {
+                    Ok(bytes) => read_table(&file_name, bytes.to_vec()).ok(),
+                    Err(_) => None,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let Some(date) = date else {
+        return Ok(toast_only_response(
+            ToastSeverity::Failure,
+            "Choose the date of the opening balance",
+        ));
+    };
+    let Some(table) = table.filter(|t| t.len() > 1) else {
+        return Ok(toast_only_response(
+            ToastSeverity::Failure,
+            "Choose a CSV or XLSX file with a header and at least one row",
+        ));
+    };
+    let rows = match rows_from_table(&table) {
+        Ok(rows) => rows,
+        Err(message) => return Ok(toast_only_response(ToastSeverity::Failure, &message)),
+    };
+    let Ok(data) = write_csv(&table) else {
+        return Ok(toast_only_response(
+            ToastSeverity::Failure,
+            "Could not read file",
+        ));
+    };
+
+    let report = match dry_run_opening_balance(&ctx, &mm, date, rows, locale).await {
+        Ok(report) => report,
+        Err(model::Error::InvalidOpeningBalanceDate { date }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("{date} is in the future"),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let template = ReportTemplate { data, report };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_opening_balance(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    RequestLocale(locale): RequestLocale,
+    Form(form): Form<OpeningBalanceForm>,
+) -> Result<impl IntoResponse> {
+    let rows = match read_csv(form.data.as_bytes())
+        .map_err(|e| e.to_string())
+        .and_then(|table| rows_from_table(&table))
+    {
+        Ok(rows) => rows,
+        Err(message) => return Ok(toast_only_response(ToastSeverity::Failure, &message)),
+    };
+
+    let report = match apply_opening_balance(&ctx, &mm, form.date, rows, locale).await {
+        Ok(report) => report,
+        Err(model::Error::OpeningBalanceNotAllowed) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                "Stock has already moved, the opening balance can no longer be recorded",
+            ))
+        }
+        Err(model::Error::OpeningBalanceInvalid { error_count }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Nothing recorded, {error_count} rows have errors"),
+            ))
+        }
+        Err(model::Error::InvalidOpeningBalanceDate { date }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("{date} is in the future"),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let template = RecordedTemplate { report };
+    let reply_html = with_toast_response(
+        template.render().unwrap(),
+        ToastSeverity::Succes,
+        "Opening Balance Recorded",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
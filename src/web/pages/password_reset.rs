@@ -0,0 +1,142 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::password_reset::{
+    complete_password_reset, get_password_reset, request_password_reset, PasswordResetForAccept,
+    PasswordResetForRequest,
+};
+use crate::model::user::MIN_PASSWORD_LENGTH;
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use crate::web::mw_rate_limit::{attempt_failed, mw_rate_limit, RateLimit};
+use askama::Template;
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::get;
+use axum::{middleware, Form, Router};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use super::toasts::{toast_only_response, ToastSeverity};
+
+pub fn pages_password_reset(mm: ModelManager) -> Router {
+    // Links that do not work, per address, so tokens cannot be guessed.
+    let link_limit = RateLimit::new(
+        5,
+        Duration::from_secs(2),
+        Duration::from_secs(15 * 60),
+        Duration::from_secs(60 * 60),
+    );
+
+    Router::new()
+        .route(
+            "/forgot-password",
+            get(forgot_password_page).post(request_password_reset_form),
+        )
+        .route(
+            "/reset-password/:token",
+            get(reset_password_page)
+                .post(reset_password_form)
+                .route_layer(middleware::from_fn_with_state(link_limit, mw_rate_limit)),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordForm {
+    password: String,
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "password_reset/pages_forgot.html")]
+pub struct ForgotPage {}
+
+#[derive(Template)]
+#[template(path = "password_reset/pages_reset.html")]
+pub struct ResetPage {
+    pub token: String,
+    /// None once used or expired.
+    pub reset: Option<PasswordResetForAccept>,
+    pub min_password_length: usize,
+}
+// endregion: Templates
+
+// region: Handlers
+pub async fn forgot_password_page() -> Result<impl IntoResponse> {
+    let reply_html = ForgotPage {}.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+/// Answers the same whether or not the account exists.
+pub async fn request_password_reset_form(
+    State(mm): State<ModelManager>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Form(request): Form<PasswordResetForRequest>,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    match request_password_reset(&ctx, &mm, request, addr.ip()).await {
+        Ok(()) => Ok(toast_only_response(
+            ToastSeverity::Succes,
+            "If the account has an email, a link is on its way",
+        )),
+        Err(model::Error::TooManyRequests) => Ok(toast_only_response(
+            ToastSeverity::Failure,
+            "Too many requests, try again later",
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn reset_password_page(
+    State(mm): State<ModelManager>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    let (status, reset) = match get_password_reset(&ctx, &mm, &token).await {
+        Ok(reset) => (StatusCode::OK, Some(reset)),
+        Err(model::Error::PasswordResetNotFound) => (StatusCode::NOT_FOUND, None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let template = ResetPage {
+        token,
+        reset,
+        min_password_length: MIN_PASSWORD_LENGTH,
+    };
+    let reply_html = template.render().unwrap();
+    let response = (status, Html(reply_html)).into_response();
+    match status {
+        StatusCode::NOT_FOUND => Ok(attempt_failed(response)),
+        _ => Ok(response),
+    }
+}
+
+/// Sets the password and sends the user to log in with it.
+pub async fn reset_password_form(
+    State(mm): State<ModelManager>,
+    Path(token): Path<String>,
+    Form(form): Form<PasswordForm>,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    let message = match complete_password_reset(&ctx, &mm, &token, &form.password).await {
+        Ok(()) => return Ok((StatusCode::OK, [("HX-Redirect", "/login")]).into_response()),
+        Err(model::Error::InvalidPassword) => {
+            format!("Passwords need at least {MIN_PASSWORD_LENGTH} characters")
+        }
+        Err(model::Error::PasswordResetNotFound) => {
+            return Ok(attempt_failed(toast_only_response(
+                ToastSeverity::Failure,
+                "This link was used or has expired",
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(toast_only_response(ToastSeverity::Failure, &message))
+}
+// endregion: Handlers
@@ -0,0 +1,206 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::organization::get_base_currency;
+use crate::model::price_list::{
+    create_price_list, create_price_list_item, delete_price_list, delete_price_list_item,
+    get_price_list, get_price_list_items, get_price_lists, update_price_list, PriceList,
+    PriceListForCreate, PriceListForUpdate, PriceListItem, PriceListItemForCreate,
+};
+use crate::model::ModelManager;
+use crate::money::Currency;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post, put};
+use axum::{Form, Router};
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_price_lists(mm: ModelManager) -> Router {
+    Router::new()
+        // read
+        .route("/price-lists", get(price_lists_page))
+        .route("/price-lists/:id", get(price_list_page))
+        // create
+        .route("/price-lists", post(create_price_list_row))
+        .route("/price-lists/:id/items", post(create_price_list_item_row))
+        // update
+        .route("/price-lists/:id", put(update_price_list_tax))
+        // delete
+        .route("/price-lists/:id", delete(delete_price_list_row))
+        .route(
+            "/price-lists/:id/items/:item_id",
+            delete(delete_price_list_item_row),
+        )
+        .with_state(mm)
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "price_lists/pages_price_lists.html")]
+pub struct PriceListsPage {
+    pub price_lists: Vec<PriceList>,
+}
+
+#[derive(Template)]
+#[template(path = "price_lists/fragments/table_entries.html")]
+pub struct TableEntries {
+    pub price_lists: Vec<PriceList>,
+}
+
+#[derive(Template)]
+#[template(path = "price_lists/pages_price_list.html")]
+pub struct PriceListPage {
+    pub price_list: PriceList,
+    pub base_currency: Currency,
+    pub items: Vec<PriceListItem>,
+}
+
+#[derive(Template)]
+#[template(path = "price_lists/fragments/item_entries.html")]
+pub struct ItemEntries {
+    pub price_list_id: i64,
+    pub items: Vec<PriceListItem>,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_table(ctx: &Ctx, mm: &ModelManager) -> Result<String> {
+    let template = TableEntries {
+        price_lists: get_price_lists(ctx, mm).await?,
+    };
+    Ok(template.render().unwrap())
+}
+
+async fn render_items(ctx: &Ctx, mm: &ModelManager, price_list_id: i64) -> Result<String> {
+    let template = ItemEntries {
+        price_list_id,
+        items: get_price_list_items(ctx, mm, price_list_id).await?,
+    };
+    Ok(template.render().unwrap())
+}
+
+pub async fn price_lists_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let template = PriceListsPage {
+        price_lists: get_price_lists(&ctx, &mm).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn price_list_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let template = PriceListPage {
+        price_list: get_price_list(&ctx, &mm, id).await?,
+        base_currency: get_base_currency(&ctx, &mm).await?,
+        items: get_price_list_items(&ctx, &mm, id).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_price_list_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(price_list_for_create): Form<PriceListForCreate>,
+) -> Result<impl IntoResponse> {
+    match create_price_list(&ctx, &mm, price_list_for_create).await {
+        Ok(_) => {}
+        Err(model::Error::InvalidPriceListName { name }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Enter a name no other price list has, not \"{name}\""),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "Price List Created",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn update_price_list_tax(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(price_list_for_update): Form<PriceListForUpdate>,
+) -> Result<impl IntoResponse> {
+    let message = match price_list_for_update.tax_inclusive {
+        true => "Prices Include Tax",
+        false => "Prices Exclude Tax",
+    };
+    update_price_list(&ctx, &mm, id, price_list_for_update).await?;
+
+    Ok(toast_only_response(ToastSeverity::Succes, message))
+}
+
+pub async fn delete_price_list_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    delete_price_list(&ctx, &mm, id).await?;
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "Price List Deleted",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_price_list_item_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(price_list_item_for_create): Form<PriceListItemForCreate>,
+) -> Result<impl IntoResponse> {
+    let code = price_list_item_for_create.code.trim().to_string();
+    match create_price_list_item(&ctx, &mm, id, price_list_item_for_create).await {
+        Ok(()) => {}
+        Err(model::Error::BarcodeNotFound { .. }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("No product found for {code}"),
+            ))
+        }
+        Err(model::Error::InvalidPriceListItem { .. }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                "Enter a minimum quantity of at least 1, a price of 0 or more and a valid range",
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let reply_html = with_toast_response(
+        render_items(&ctx, &mm, id).await?,
+        ToastSeverity::Succes,
+        "Price Added",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn delete_price_list_item_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path((id, item_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse> {
+    delete_price_list_item(&ctx, &mm, id, item_id).await?;
+
+    let reply_html = render_items(&ctx, &mm, id).await?;
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
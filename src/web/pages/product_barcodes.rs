@@ -0,0 +1,167 @@
+use crate::barcode::{code128_svg, ean13_svg};
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::barcode::ProductBarcode;
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+
+use super::toasts::{with_toast_response, ToastSeverity};
+
+pub fn pages_product_barcodes(mm: ModelManager) -> Router {
+    Router::new()
+        // read
+        .route("/products/:id/barcodes", get(get_product_barcodes))
+        .route("/products/:id/label.svg", get(get_product_label))
+        .route(
+            "/products/:id/barcodes/:barcode_id/label.svg",
+            get(get_barcode_label),
+        )
+        // create
+        .route("/products/:id/barcodes", post(create_product_barcode))
+        // delete
+        .route(
+            "/products/:id/barcodes/:barcode_id",
+            delete(delete_product_barcode),
+        )
+        .with_state(mm)
+}
+
+// region: Fragment templates
+#[derive(Template)]
+#[template(path = "products/barcodes/fragments/barcodes.html")]
+pub struct Barcodes {
+    pub product_id: i64,
+    pub barcodes: Vec<ProductBarcode>,
+}
+// endregion: Fragment templates
+
+fn svg_response(svg: String) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "image/svg+xml")],
+        svg,
+    )
+}
+
+// region: Handlers
+pub async fn get_product_barcodes(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let barcodes = model::barcode::get_product_barcodes(&ctx, &mm, id).await?;
+
+    let template = Barcodes {
+        product_id: id,
+        barcodes,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+
+/// Code 128 label of the product's SKU.
+pub async fn get_product_label(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let product = model::products::get_product_with_stock_level(&ctx, &mm, id)
+        .await?
+        .ok_or(model::Error::ProductNotFound { product_id: id })?;
+
+    let svg = code128_svg(&product.sku).ok_or(model::Error::InvalidBarcode {
+        code: product.sku.clone(),
+    })?;
+
+    Ok(svg_response(svg))
+}
+
+/// EAN-13 label for UPC-A and EAN-13 codes, Code 128 for the other GTIN lengths.
+pub async fn get_barcode_label(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path((id, barcode_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse> {
+    let barcode = model::barcode::get_product_barcode(&ctx, &mm, id, barcode_id).await?;
+
+    let svg = match barcode.code.len() {
+        12 | 13 => ean13_svg(&barcode.code),
+        _ => code128_svg(&barcode.code),
+    }
+    .ok_or(model::Error::InvalidBarcode {
+        code: barcode.code.clone(),
+    })?;
+
+    Ok(svg_response(svg))
+}
+
+#[derive(Deserialize)]
+pub struct ProductBarcodeForCreate {
+    code: String,
+}
+pub async fn create_product_barcode(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(barcode_for_create): Form<ProductBarcodeForCreate>,
+) -> Result<impl IntoResponse> {
+    let created =
+        model::barcode::create_product_barcode(&ctx, &mm, id, &barcode_for_create.code).await;
+
+    let barcodes = model::barcode::get_product_barcodes(&ctx, &mm, id).await?;
+    let template = Barcodes {
+        product_id: id,
+        barcodes,
+    };
+    let reply_html = template.render().unwrap();
+
+    let reply_html = match created {
+        Ok(()) => with_toast_response(reply_html, ToastSeverity::Succes, "Barcode Added"),
+        Err(model::Error::InvalidBarcode { code }) => with_toast_response(
+            reply_html,
+            ToastSeverity::Failure,
+            &format!("{code} is not a valid GTIN, EAN or UPC (check digit mismatch?)"),
+        ),
+        Err(model::Error::DuplicateBarcode { code }) => with_toast_response(
+            reply_html,
+            ToastSeverity::Failure,
+            &format!("{code} is already assigned to a product"),
+        ),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+
+pub async fn delete_product_barcode(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path((id, barcode_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse> {
+    model::barcode::delete_product_barcode(&ctx, &mm, id, barcode_id).await?;
+
+    let barcodes = model::barcode::get_product_barcodes(&ctx, &mm, id).await?;
+    let template = Barcodes {
+        product_id: id,
+        barcodes,
+    };
+    let reply_html = template.render().unwrap();
+
+    Ok((
+        StatusCode::OK,
+        Html(with_toast_response(
+            reply_html,
+            ToastSeverity::Succes,
+            "Barcode Deleted",
+        ))
+        .into_response(),
+    ))
+}
+// endregion: Handlers
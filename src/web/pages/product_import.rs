@@ -0,0 +1,276 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::product_import::{
+    apply_product_import, dry_run_product_import, ProductImportReport, ProductImportRow,
+};
+use crate::model::products::get_all_products_with_stock_levels;
+use crate::model::ModelManager;
+use crate::spreadsheet::{self, read_csv, read_table, write_csv};
+use crate::web::error::Result;
+use crate::web::locale::RequestLocale;
+use askama::Template;
+use axum::extract::{Multipart, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::post;
+use axum::{Form, Router};
+use serde::Deserialize;
+use serde_with::{serde_as, NoneAsEmptyString};
+
+use super::products::TableEntries;
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_product_import(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/products/import/preview", post(preview_import))
+        .route("/products/import/dry-run", post(dry_run_import))
+        .route("/products/import", post(apply_import))
+        .with_state(mm)
+}
+
+// region: Column mapping
+/// The product fields a column can be mapped to, with the header names that
+/// are recognised for each.
+const IMPORT_FIELDS: [(&str, &str, &[&str]); 6] = [
+    ("sku", "SKU", &["sku", "code", "item code", "product code"]),
+    ("brand", "Brand", &["brand", "manufacturer"]),
+    ("name", "Name", &["name", "product", "product name", "item"]),
+    (
+        "description",
+        "Description",
+        &["description", "desc", "details"],
+    ),
+    ("price", "Price", &["price", "unit price", "sell price"]),
+    (
+        "category",
+        "Category",
+        &["category", "category path", "group"],
+    ),
+];
+
+pub struct MappingField {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub column: Option<usize>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct ProductImportForm {
+    /// The uploaded table, re-serialized as CSV by the preview step.
+    data: String,
+    #[serde(default)]
+    has_header: bool,
+    #[serde_as(as = "NoneAsEmptyString")]
+    sku_column: Option<usize>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    brand_column: Option<usize>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    name_column: Option<usize>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    description_column: Option<usize>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    price_column: Option<usize>,
+    #[serde_as(as = "NoneAsEmptyString")]
+    category_column: Option<usize>,
+}
+
+impl ProductImportForm {
+    fn rows(&self) -> spreadsheet::Result<Vec<ProductImportRow>> {
+        let table = read_csv(self.data.as_bytes())?;
+        let skip = usize::from(self.has_header);
+
+        let cell = |row: &[String], column: Option<usize>| {
+            column.and_then(|c| row.get(c)).cloned().unwrap_or_default()
+        };
+
+        Ok(table
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(i, row)| ProductImportRow {
+                line: i + 1,
+                sku: cell(row, self.sku_column),
+                brand: cell(row, self.brand_column),
+                name: cell(row, self.name_column),
+                description: cell(row, self.description_column),
+                price: cell(row, self.price_column),
+                category: cell(row, self.category_column),
+            })
+            .collect())
+    }
+}
+
+/// Maps each field to the first column whose header matches one of its names.
+fn guess_mapping(header: &[String]) -> Vec<MappingField> {
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_lowercase()).collect();
+
+    IMPORT_FIELDS
+        .iter()
+        .map(|(name, label, aliases)| MappingField {
+            name,
+            label,
+            column: header.iter().position(|h| aliases.contains(&h.as_str())),
+        })
+        .collect()
+}
+// endregion: Column mapping
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "products/import/fragments/mapping.html")]
+pub struct MappingTemplate {
+    pub data: String,
+    pub has_header: bool,
+    pub columns: Vec<String>,
+    pub fields: Vec<MappingField>,
+    pub row_count: usize,
+}
+
+#[derive(Template)]
+#[template(path = "products/import/fragments/report.html")]
+pub struct ReportTemplate {
+    pub report: ProductImportReport,
+}
+// endregion: Templates
+
+// region: Handlers
+/// Reads the uploaded file and suggests a column mapping from its header.
+pub async fn preview_import(mut multipart: Multipart) -> Result<impl IntoResponse> {
+    let mut table = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        table = match field.bytes().await {
+            Ok(bytes) => Some(read_table(&file_name, bytes.to_vec())),
+            Err(e) => Some(Err(spreadsheet::Error::Csv(e.to_string()))),
+        };
+    }
+
+    let table = match table {
+        Some(Ok(table)) if !table.is_empty() => table,
+        Some(Err(e)) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Could not read file: {e}"),
+            ))
+        }
+        _ => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                "Choose a CSV or XLSX file with at least one row",
+            ))
+        }
+    };
+    let data = match write_csv(&table) {
+        Ok(data) => data,
+        Err(e) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Could not read file: {e}"),
+            ))
+        }
+    };
+
+    let fields = guess_mapping(&table[0]);
+    let has_header = fields.iter().any(|f| f.column.is_some());
+    let column_count = table.iter().map(Vec::len).max().unwrap_or_default();
+    let columns = (0..column_count)
+        .map(|i| match table[0].get(i) {
+            Some(header) if has_header && !header.is_empty() => header.clone(),
+            _ => format!("Column {}", i + 1),
+        })
+        .collect();
+
+    let template = MappingTemplate {
+        data,
+        has_header,
+        columns,
+        fields,
+        row_count: table.len() - usize::from(has_header),
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn dry_run_import(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    RequestLocale(locale): RequestLocale,
+    Form(form): Form<ProductImportForm>,
+) -> Result<impl IntoResponse> {
+    let rows = match form.rows() {
+        Ok(rows) => rows,
+        Err(e) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Could not read rows: {e}"),
+            ))
+        }
+    };
+    let report = dry_run_product_import(&ctx, &mm, rows, locale).await?;
+
+    let template = ReportTemplate { report };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn apply_import(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    RequestLocale(locale): RequestLocale,
+    Form(form): Form<ProductImportForm>,
+) -> Result<impl IntoResponse> {
+    let rows = match form.rows() {
+        Ok(rows) => rows,
+        Err(e) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Could not read rows: {e}"),
+            ))
+        }
+    };
+    let report = match apply_product_import(&ctx, &mm, rows, locale).await {
+        Ok(report) => report,
+        Err(model::Error::ProductImportInvalid { error_count }) => {
+            // Swap the report in instead of the products table so the errors are visible.
+            let rows = form.rows().unwrap_or_default();
+            let report = dry_run_product_import(&ctx, &mm, rows, locale).await?;
+            let template = ReportTemplate { report };
+            let reply_html = with_toast_response(
+                template.render().unwrap(),
+                ToastSeverity::Failure,
+                &format!("Nothing imported, {error_count} rows have errors"),
+            );
+            return Ok((
+                StatusCode::OK,
+                [
+                    ("HX-Retarget", "#product-import-report"),
+                    ("HX-Reswap", "innerHTML"),
+                ],
+                Html(reply_html),
+            )
+                .into_response());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let products = get_all_products_with_stock_levels(&ctx, &mm, None).await?;
+    let template = TableEntries {
+        products,
+        search_terms: Vec::new(),
+    };
+    let reply_html = with_toast_response(
+        template.render().unwrap(),
+        ToastSeverity::Succes,
+        &format!(
+            "Imported: {} created, {} updated",
+            report.create_count(),
+            report.update_count()
+        ),
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
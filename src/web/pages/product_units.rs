@@ -0,0 +1,101 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::unit::{ProductUnit, ProductUnitForCreate};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post};
+use axum::{Form, Router};
+
+use super::toasts::{with_toast_response, ToastSeverity};
+
+pub fn pages_product_units(mm: ModelManager) -> Router {
+    Router::new()
+        // read
+        .route("/products/:id/units", get(get_product_units))
+        // create
+        .route("/products/:id/units", post(create_product_unit))
+        // delete
+        .route("/products/:id/units/:unit_id", delete(delete_product_unit))
+        .with_state(mm)
+}
+
+// region: Fragment templates
+#[derive(Template)]
+#[template(path = "products/units/fragments/units.html")]
+pub struct Units {
+    pub product_id: i64,
+    pub units: Vec<ProductUnit>,
+}
+// endregion: Fragment templates
+
+// region: Handlers
+pub async fn get_product_units(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let units = model::unit::get_product_units(&ctx, &mm, id).await?;
+
+    let template = Units {
+        product_id: id,
+        units,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+
+pub async fn create_product_unit(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(product_unit_for_create): Form<ProductUnitForCreate>,
+) -> Result<impl IntoResponse> {
+    model::unit::create_product_unit(&ctx, &mm, id, product_unit_for_create).await?;
+
+    let units = model::unit::get_product_units(&ctx, &mm, id).await?;
+    let template = Units {
+        product_id: id,
+        units,
+    };
+    let reply_html = template.render().unwrap();
+
+    Ok((
+        StatusCode::OK,
+        Html(with_toast_response(
+            reply_html,
+            ToastSeverity::Succes,
+            "Unit Created",
+        ))
+        .into_response(),
+    ))
+}
+
+pub async fn delete_product_unit(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path((id, unit_id)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse> {
+    model::unit::delete_product_unit(&ctx, &mm, id, unit_id).await?;
+
+    let units = model::unit::get_product_units(&ctx, &mm, id).await?;
+    let template = Units {
+        product_id: id,
+        units,
+    };
+    let reply_html = template.render().unwrap();
+
+    Ok((
+        StatusCode::OK,
+        Html(with_toast_response(
+            reply_html,
+            ToastSeverity::Succes,
+            "Unit Deleted",
+        ))
+        .into_response(),
+    ))
+}
+// endregion: Handlers
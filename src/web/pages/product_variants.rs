@@ -0,0 +1,105 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::product_variant::{ProductOptionForCreate, VariantMatrix};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+
+use super::toasts::{with_toast_response, ToastSeverity};
+
+pub fn pages_product_variants(mm: ModelManager) -> Router {
+    Router::new()
+        // read
+        .route("/products/:id/variants", get(product_variants_page))
+        // create
+        .route("/products/:id/variants", post(create_product_variants))
+        .with_state(mm)
+}
+
+// region: Fragment templates
+#[derive(Template)]
+#[template(path = "products/variants/fragments/matrix.html")]
+pub struct Matrix {
+    pub matrix: VariantMatrix,
+}
+// endregion: Fragment templates
+
+// region: Handlers
+// region: Read
+#[derive(Template)]
+#[template(path = "products/variants/pages_product_variants.html")]
+pub struct ProductVariantsPage {
+    pub matrix: VariantMatrix,
+}
+pub async fn product_variants_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    let matrix = model::product_variant::get_variant_matrix(&ctx, &mm, id).await?;
+
+    let template = ProductVariantsPage { matrix };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+// endregion: Read
+
+// region: Create
+#[derive(Debug, Deserialize)]
+pub struct ProductOptionsForCreate {
+    option_1_name: Option<String>,
+    option_1_values: Option<String>,
+    option_2_name: Option<String>,
+    option_2_values: Option<String>,
+    option_3_name: Option<String>,
+    option_3_values: Option<String>,
+}
+
+impl From<ProductOptionsForCreate> for Vec<ProductOptionForCreate> {
+    fn from(value: ProductOptionsForCreate) -> Self {
+        [
+            (value.option_1_name, value.option_1_values),
+            (value.option_2_name, value.option_2_values),
+            (value.option_3_name, value.option_3_values),
+        ]
+        .into_iter()
+        .filter_map(|(name, values)| {
+            Some(ProductOptionForCreate {
+                name: name?,
+                values: values?.split(',').map(str::to_string).collect(),
+            })
+        })
+        .collect()
+    }
+}
+
+pub async fn create_product_variants(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(options_for_create): Form<ProductOptionsForCreate>,
+) -> Result<impl IntoResponse> {
+    model::product_variant::create_variants(&ctx, &mm, id, options_for_create.into()).await?;
+
+    let matrix = model::product_variant::get_variant_matrix(&ctx, &mm, id).await?;
+    let template = Matrix { matrix };
+    let reply_html = template.render().unwrap();
+
+    Ok((
+        StatusCode::OK,
+        Html(with_toast_response(
+            reply_html,
+            ToastSeverity::Succes,
+            "Variants Created",
+        ))
+        .into_response(),
+    ))
+}
+// endregion: Create
+// endregion: Handlers
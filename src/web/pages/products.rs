@@ -1,17 +1,21 @@
 use crate::ctx::Ctx;
 use crate::model;
+use crate::model::category::{get_category_tree, CategoryNode};
 use crate::model::products::{
     get_all_products_with_stock_levels, get_product_with_stock_level, ProductForCreate,
     ProductForSearch, ProductForUpdate, ProductWithStockLevel,
 };
+use crate::model::tax::{get_tax_classes, TaxClass};
+use crate::model::warehouse::{get_all_warehouses, Warehouse};
 use crate::model::ModelManager;
 use crate::web::error::Result;
 use askama::Template;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::{Html, IntoResponse};
 use axum::routing::{delete, get, post, put};
 use axum::{Form, Router};
+use serde::Deserialize;
 
 use super::toasts::{with_toast_response, ToastSeverity};
 
@@ -38,12 +42,58 @@ pub fn pages_products(mm: ModelManager) -> Router {
 #[template(path = "products/fragments/table_entries.html")]
 pub struct TableEntries {
     pub products: Vec<ProductWithStockLevel>,
+    pub search_terms: Vec<String>,
 }
 
 #[derive(Template)]
 #[template(path = "products/fragments/table_entry.html")]
 pub struct TableEntry {
     pub product: ProductWithStockLevel,
+    pub search_terms: Vec<String>,
+}
+
+mod filters {
+    /// Escapes `s` and wraps every case-insensitive occurrence of a search term in `<mark>`.
+    pub fn highlight<T: std::fmt::Display>(s: T, terms: &[String]) -> askama::Result<String> {
+        let chars: Vec<char> = s.to_string().chars().collect();
+        let lowercase: Vec<char> = chars
+            .iter()
+            .map(|c| c.to_lowercase().next().unwrap_or(*c))
+            .collect();
+
+        let mut marked = vec![false; chars.len()];
+        for term in terms {
+            let term: Vec<char> = term.chars().collect();
+            if term.is_empty() || term.len() > lowercase.len() {
+                continue;
+            }
+            for start in 0..=lowercase.len() - term.len() {
+                if lowercase[start..start + term.len()] == term[..] {
+                    marked[start..start + term.len()].fill(true);
+                }
+            }
+        }
+
+        let mut html = String::with_capacity(chars.len());
+        for (i, c) in chars.iter().enumerate() {
+            if marked[i] && (i == 0 || !marked[i - 1]) {
+                html.push_str("<mark>");
+            }
+            match c {
+                '&' => html.push_str("&amp;"),
+                '<' => html.push_str("&lt;"),
+                '>' => html.push_str("&gt;"),
+                '"' => html.push_str("&quot;"),
+                '\'' => html.push_str("&#x27;"),
+                c => html.push(*c),
+            }
+            if marked[i] && (i + 1 == chars.len() || !marked[i + 1]) {
+                html.push_str("</mark>");
+            }
+        }
+
+        Ok(html)
+    }
 }
 // endregion: Table templates
 
@@ -53,29 +103,48 @@ pub struct TableEntry {
 #[template(path = "products/pages_products.html")]
 pub struct ProductsPage {
     pub products: Vec<ProductWithStockLevel>,
+    pub categories: Vec<CategoryNode>,
+    pub category_id: Option<i64>,
+    pub warehouses: Vec<Warehouse>,
+    pub tax_classes: Vec<TaxClass>,
+    pub search_terms: Vec<String>,
 }
-pub async fn products_page(State(mm): State<ModelManager>) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
-    let products = get_all_products_with_stock_levels(&ctx, &mm).await?;
+#[derive(Deserialize)]
+pub struct ProductsPageQuery {
+    category_id: Option<i64>,
+}
+pub async fn products_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<ProductsPageQuery>,
+) -> Result<impl IntoResponse> {
+    let products = get_all_products_with_stock_levels(&ctx, &mm, query.category_id).await?;
+    let categories = get_category_tree(&ctx, &mm).await?;
+    let warehouses = get_all_warehouses(&ctx, &mm).await?;
+    let tax_classes = get_tax_classes(&ctx, &mm).await?;
 
-    let template = ProductsPage { products };
+    let template = ProductsPage {
+        products,
+        categories,
+        category_id: query.category_id,
+        warehouses,
+        tax_classes,
+        search_terms: Vec::new(),
+    };
     let reply_html = template.render().unwrap();
     Ok((StatusCode::OK, Html(reply_html).into_response()))
 }
 
 pub async fn get_product_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
     let product = get_product_with_stock_level(&ctx, &mm, id).await?;
 
     let template = TableEntry {
         product: product.unwrap(),
+        search_terms: Vec::new(),
     };
     let reply_html = template.render().unwrap();
     Ok((StatusCode::OK, Html(reply_html).into_response()))
@@ -85,15 +154,17 @@ pub async fn get_product_row(
 // region: Create
 pub async fn create_category(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Form(product_for_create): Form<ProductForCreate>,
 ) -> Result<impl IntoResponse> {
     println!("{:?}", product_for_create);
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
     model::products::create_product(&ctx, &mm, product_for_create).await?;
 
-    let products = get_all_products_with_stock_levels(&ctx, &mm).await?;
-    let template = TableEntries { products };
+    let products = get_all_products_with_stock_levels(&ctx, &mm, None).await?;
+    let template = TableEntries {
+        products,
+        search_terms: Vec::new(),
+    };
     let reply_html = template.render().unwrap();
 
     Ok((
@@ -111,14 +182,16 @@ pub async fn create_category(
 // region: Delete
 pub async fn delete_category_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
     model::products::delete_product(&ctx, &mm, id).await?;
 
-    let products = get_all_products_with_stock_levels(&ctx, &mm).await?;
-    let template = TableEntries { products };
+    let products = get_all_products_with_stock_levels(&ctx, &mm, None).await?;
+    let template = TableEntries {
+        products,
+        search_terms: Vec::new(),
+    };
     let reply_html = template.render().unwrap();
     Ok((
         StatusCode::OK,
@@ -138,10 +211,9 @@ pub struct DeleteRowAction {
 }
 pub async fn delete_product_row_action(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
     let product = model::products::get_product_with_stock_level(&ctx, &mm, id).await?;
 
     let template = DeleteRowAction {
@@ -155,16 +227,16 @@ pub async fn delete_product_row_action(
 // region: Update
 pub async fn update_product_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
     Form(product_for_update): Form<ProductForUpdate>,
 ) -> Result<impl IntoResponse> {
-    let ctx = Ctx::new(1, 1);
-
     model::products::update_product(&ctx, &mm, id, product_for_update).await?;
 
     let product = model::products::get_product_with_stock_level(&ctx, &mm, id).await?;
     let template = TableEntry {
         product: product.unwrap(),
+        search_terms: Vec::new(),
     };
 
     let reply_html = template.render().unwrap();
@@ -183,18 +255,23 @@ pub async fn update_product_row(
 #[template(path = "products/fragments/editable_row.html")]
 pub struct EditableRow {
     pub product: ProductWithStockLevel,
+    pub categories: Vec<CategoryNode>,
+    pub tax_classes: Vec<TaxClass>,
 }
 pub async fn get_editable_product_row(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse> {
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
     let product = get_product_with_stock_level(&ctx, &mm, id).await?;
 
+    let categories = get_category_tree(&ctx, &mm).await?;
+    let tax_classes = get_tax_classes(&ctx, &mm).await?;
+
     let template = EditableRow {
         product: product.unwrap(),
+        categories,
+        tax_classes,
     };
     let reply_html = template.render().unwrap();
     Ok((StatusCode::OK, Html(reply_html).into_response()))
@@ -204,15 +281,16 @@ pub async fn get_editable_product_row(
 // region: Search
 pub async fn search_products(
     State(mm): State<ModelManager>,
+    ctx: Ctx,
     Form(product_for_search): Form<ProductForSearch>,
 ) -> Result<impl IntoResponse> {
-    println!("{:?}", product_for_search);
-    // Check authorization
-    let ctx = Ctx::new(1, 1);
-
+    let search_terms = product_for_search.terms();
     let products = model::products::search_products(&ctx, &mm, product_for_search).await?;
 
-    let template = TableEntries { products };
+    let template = TableEntries {
+        products,
+        search_terms,
+    };
     let reply_html = template.render().unwrap();
     Ok((StatusCode::OK, Html(reply_html).into_response()))
 }
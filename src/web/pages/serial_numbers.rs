@@ -0,0 +1,69 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::serial_number::SerialNumberHistory;
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+
+pub fn pages_serial_numbers(mm: ModelManager) -> Router {
+    Router::new()
+        // read
+        .route("/serial-numbers", get(serial_numbers_page))
+        // search
+        .route("/serial-numbers/search", post(search_serial_number))
+        .with_state(mm)
+}
+
+// region: Fragment templates
+#[derive(Template)]
+#[template(path = "serial_numbers/fragments/history.html")]
+pub struct History {
+    pub search: String,
+    pub history: Option<SerialNumberHistory>,
+}
+// endregion: Fragment templates
+
+// region: Handlers
+// region: Read
+#[derive(Template)]
+#[template(path = "serial_numbers/pages_serial_numbers.html")]
+pub struct SerialNumbersPage {}
+pub async fn serial_numbers_page() -> Result<impl IntoResponse> {
+    let template = SerialNumbersPage {};
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+// endregion: Read
+
+// region: Search
+#[derive(Debug, Deserialize)]
+pub struct SerialNumberForSearch {
+    search: String,
+}
+pub async fn search_serial_number(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(serial_number_for_search): Form<SerialNumberForSearch>,
+) -> Result<impl IntoResponse> {
+    let history = model::serial_number::get_serial_number_history(
+        &ctx,
+        &mm,
+        &serial_number_for_search.search,
+    )
+    .await?;
+
+    let template = History {
+        search: serial_number_for_search.search,
+        history,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html).into_response()))
+}
+// endregion: Search
+// endregion: Handlers
@@ -0,0 +1,241 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::invitation::InvitationDelivery;
+use crate::model::organization::{
+    get_all_organizations, reactivate_organization, register_organization,
+    schedule_organization_deletion, suspend_organization, Organization, OrganizationForProvision,
+    OrganizationStatus, DELETION_GRACE_DAYS,
+};
+use crate::model::session::{delete_session, impersonate_organization_admin};
+use crate::model::superuser_action::{get_superuser_actions, SuperuserActionEntry};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use crate::web::routes_auth::{set_session_cookie, AUTH_TOKEN, HOME};
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+use tower_cookies::Cookies;
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_superuser(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/superuser", get(superuser_page))
+        .route("/superuser/organization", post(create_organization_form))
+        .route(
+            "/superuser/organization/fragment/list",
+            post(organization_list_fragment),
+        )
+        .route(
+            "/superuser/organization/:id/suspend",
+            post(suspend_organization_form),
+        )
+        .route(
+            "/superuser/organization/:id/delete",
+            post(schedule_deletion_form),
+        )
+        .route(
+            "/superuser/organization/:id/reactivate",
+            post(reactivate_organization_row),
+        )
+        .route(
+            "/superuser/organization/:id/impersonate",
+            post(impersonate_form),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReasonForm {
+    #[serde(default)]
+    reason: String,
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "superuser_admin_page.html")]
+pub struct SuperuserPage {
+    pub grace_days: i64,
+}
+
+/// The organizations and what was last done to them, after the link of a new
+/// admin's invitation that was not mailed.
+#[derive(Template)]
+#[template(path = "fragments/superuser_admin_organization_list.html")]
+pub struct OrganizationList {
+    pub organizations: Vec<Organization>,
+    pub actions: Vec<SuperuserActionEntry>,
+    pub link: Option<String>,
+    pub grace_days: i64,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_list(ctx: &Ctx, mm: &ModelManager, link: Option<String>) -> Result<String> {
+    let template = OrganizationList {
+        organizations: get_all_organizations(ctx, mm).await?,
+        actions: get_superuser_actions(ctx, mm).await?,
+        link,
+        grace_days: DELETION_GRACE_DAYS,
+    };
+    Ok(template.render().unwrap())
+}
+
+/// The message for an error a superuser can act on.
+fn failure_message(error: &model::Error) -> Option<String> {
+    match error {
+        model::Error::InvalidOrganization { name } if name.is_empty() => {
+            Some("Enter a name".to_string())
+        }
+        model::Error::InvalidOrganization { name } => {
+            Some(format!("{name} is already an organization"))
+        }
+        model::Error::InvalidUser { .. } => Some("Enter an admin username".to_string()),
+        model::Error::InvalidEmail { email } => Some(format!("{email} is not an email address")),
+        model::Error::ReasonRequired => Some("Enter a reason".to_string()),
+        model::Error::OrganizationSuspended { .. } => {
+            Some("Reactivate the organization first".to_string())
+        }
+        _ => None,
+    }
+}
+
+pub async fn superuser_page(State(mm): State<ModelManager>, ctx: Ctx) -> Result<impl IntoResponse> {
+    // Only superusers may list organizations.
+    get_all_organizations(&ctx, &mm).await?;
+
+    let template = SuperuserPage {
+        grace_days: DELETION_GRACE_DAYS,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn organization_list_fragment(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let reply_html = render_list(&ctx, &mm, None).await?;
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_organization_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(organization_for_provision): Form<OrganizationForProvision>,
+) -> Result<impl IntoResponse> {
+    let (link, severity, message) =
+        match register_organization(&ctx, &mm, organization_for_provision).await {
+            Ok(InvitationDelivery::Mailed { email }) => (
+                None,
+                ToastSeverity::Succes,
+                format!("Organization Created, Invitation Sent to {email}"),
+            ),
+            Ok(InvitationDelivery::Link(link)) => (
+                Some(link),
+                ToastSeverity::Succes,
+                "Organization Created".to_string(),
+            ),
+            // The organization is saved; its admin can be invited again.
+            Err(model::Error::Mail(_)) => (
+                None,
+                ToastSeverity::Failure,
+                "Organization created, but the invitation could not be mailed".to_string(),
+            ),
+            Err(e) => match failure_message(&e) {
+                Some(message) => return Ok(toast_only_response(ToastSeverity::Failure, &message)),
+                None => return Err(e.into()),
+            },
+        };
+
+    let reply_html = with_toast_response(render_list(&ctx, &mm, link).await?, severity, &message);
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn suspend_organization_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(form): Form<ReasonForm>,
+) -> Result<impl IntoResponse> {
+    if let Err(e) = suspend_organization(&ctx, &mm, id, &form.reason).await {
+        return match failure_message(&e) {
+            Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+            None => Err(e.into()),
+        };
+    }
+
+    let reply_html = with_toast_response(
+        render_list(&ctx, &mm, None).await?,
+        ToastSeverity::Succes,
+        "Organization Suspended",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn schedule_deletion_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(form): Form<ReasonForm>,
+) -> Result<impl IntoResponse> {
+    if let Err(e) = schedule_organization_deletion(&ctx, &mm, id, &form.reason).await {
+        return match failure_message(&e) {
+            Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+            None => Err(e.into()),
+        };
+    }
+
+    let reply_html = with_toast_response(
+        render_list(&ctx, &mm, None).await?,
+        ToastSeverity::Succes,
+        "Deletion Scheduled",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn reactivate_organization_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    reactivate_organization(&ctx, &mm, id).await?;
+
+    let reply_html = with_toast_response(
+        render_list(&ctx, &mm, None).await?,
+        ToastSeverity::Succes,
+        "Organization Reactivated",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+/// Swaps this browser's session for one as the organization's admin; the
+/// superuser logs in again afterwards.
+pub async fn impersonate_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+    Form(form): Form<ReasonForm>,
+) -> Result<impl IntoResponse> {
+    let token = match impersonate_organization_admin(&ctx, &mm, id, &form.reason).await {
+        Ok(token) => token,
+        Err(e) => {
+            return match failure_message(&e) {
+                Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+                None => Err(e.into()),
+            }
+        }
+    };
+    if let Some(cookie) = cookies.get(AUTH_TOKEN) {
+        delete_session(&ctx, &mm, cookie.value()).await?;
+    }
+    set_session_cookie(&cookies, token);
+
+    Ok((StatusCode::OK, [("HX-Redirect", HOME)]).into_response())
+}
+// endregion: Handlers
@@ -0,0 +1,163 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::organization::{get_prices_include_tax, update_prices_include_tax};
+use crate::model::tax::{
+    create_tax_class, delete_tax_class, get_tax_classes, get_tax_summary, TaxClass,
+    TaxClassForCreate, TaxSummary,
+};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post, put};
+use axum::{Form, Router};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::Deserialize;
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_taxes(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/taxes", get(taxes_page))
+        .route("/taxes", post(create_tax_class_row))
+        .route("/taxes/:id", delete(delete_tax_class_row))
+        .route(
+            "/taxes/prices-include-tax",
+            put(update_prices_include_tax_form),
+        )
+        .route("/taxes/summary", get(tax_summary_fragment))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PricesIncludeTaxForm {
+    #[serde(default)]
+    prices_include_tax: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaxSummaryQuery {
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "taxes/pages_taxes.html")]
+pub struct TaxesPage {
+    pub tax_classes: Vec<TaxClass>,
+    pub prices_include_tax: bool,
+    pub summary: TaxSummary,
+}
+
+#[derive(Template)]
+#[template(path = "taxes/fragments/table_entries.html")]
+pub struct TableEntries {
+    pub tax_classes: Vec<TaxClass>,
+}
+
+#[derive(Template)]
+#[template(path = "taxes/fragments/summary.html")]
+pub struct SummaryFragment {
+    pub summary: TaxSummary,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_table(ctx: &Ctx, mm: &ModelManager) -> Result<String> {
+    let template = TableEntries {
+        tax_classes: get_tax_classes(ctx, mm).await?,
+    };
+    Ok(template.render().unwrap())
+}
+
+pub async fn taxes_page(State(mm): State<ModelManager>, ctx: Ctx) -> Result<impl IntoResponse> {
+    // The month so far.
+    let to = Utc::now().date_naive();
+    let from = to.with_day(1).unwrap_or(to);
+
+    let template = TaxesPage {
+        tax_classes: get_tax_classes(&ctx, &mm).await?,
+        prices_include_tax: get_prices_include_tax(&ctx, &mm).await?,
+        summary: get_tax_summary(&ctx, &mm, from, to).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn create_tax_class_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(tax_class_for_create): Form<TaxClassForCreate>,
+) -> Result<impl IntoResponse> {
+    match create_tax_class(&ctx, &mm, tax_class_for_create).await {
+        Ok(()) => {}
+        Err(model::Error::InvalidTaxClass { .. }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                "Enter a name and a rate from 0 up to 100",
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "Tax Class Saved",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn delete_tax_class_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    match delete_tax_class(&ctx, &mm, id).await {
+        Ok(()) => {}
+        Err(model::Error::TaxClassHasProducts {
+            name,
+            product_count,
+            ..
+        }) => {
+            return Ok(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("{name} is used by {product_count} product(s)"),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let reply_html = render_table(&ctx, &mm).await?;
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn update_prices_include_tax_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(form): Form<PricesIncludeTaxForm>,
+) -> Result<impl IntoResponse> {
+    update_prices_include_tax(&ctx, &mm, form.prices_include_tax).await?;
+
+    let message = match form.prices_include_tax {
+        true => "Product Prices Include Tax",
+        false => "Product Prices Exclude Tax",
+    };
+    Ok(toast_only_response(ToastSeverity::Succes, message))
+}
+
+pub async fn tax_summary_fragment(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<TaxSummaryQuery>,
+) -> Result<impl IntoResponse> {
+    let template = SummaryFragment {
+        summary: get_tax_summary(&ctx, &mm, query.from, query.to).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
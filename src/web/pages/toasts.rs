@@ -1,4 +1,6 @@
 use askama::Template;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
 
 pub enum ToastSeverity {
     Succes,
@@ -31,3 +33,10 @@ pub fn with_toast_response(reply_html: String, severity: ToastSeverity, message:
 
     [reply_html, toast_reply].join("\n\n")
 }
+
+/// A toast on its own, leaving the page as it is. For requests whose target
+/// should not be swapped when they fail.
+pub fn toast_only_response(severity: ToastSeverity, message: &str) -> Response {
+    let reply_html = with_toast_response(String::new(), severity, message);
+    (StatusCode::OK, [("HX-Reswap", "none")], Html(reply_html)).into_response()
+}
@@ -0,0 +1,187 @@
+use crate::barcode::qr_svg;
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::two_factor::{
+    confirm_two_factor_enrolment, disable_two_factor, get_two_factor_status,
+    regenerate_recovery_codes, start_two_factor_enrolment, TwoFactorEnrolment, TwoFactorStatus,
+};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use crate::web::routes_auth::RecoveryCodesFragment;
+use askama::Template;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+const TWO_FACTOR_PAGE: &str = "/settings/two-factor";
+
+pub fn pages_two_factor(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/settings/two-factor", get(two_factor_page))
+        .route("/settings/two-factor/enrol", post(start_enrolment_form))
+        .route("/settings/two-factor/confirm", post(confirm_enrolment_form))
+        .route(
+            "/settings/two-factor/recovery-codes",
+            post(regenerate_recovery_codes_form),
+        )
+        .route("/settings/two-factor/disable", post(disable_form))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeForm {
+    code: String,
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "two_factor/pages_two_factor.html")]
+pub struct TwoFactorPage {
+    pub status: TwoFactorStatus,
+}
+
+#[derive(Template)]
+#[template(path = "two_factor/fragments/status.html")]
+pub struct StatusFragment {
+    pub status: TwoFactorStatus,
+}
+
+/// A new secret to scan, and the code that confirms it.
+#[derive(Template)]
+#[template(path = "two_factor/fragments/enrolment.html")]
+pub struct EnrolmentFragment {
+    pub enrolment: TwoFactorEnrolment,
+    pub qr_code: String,
+}
+// endregion: Templates
+
+// region: Handlers
+fn render_recovery_codes(recovery_codes: Vec<String>) -> String {
+    let template = RecoveryCodesFragment {
+        recovery_codes,
+        continue_to: TWO_FACTOR_PAGE,
+    };
+    template.render().unwrap()
+}
+
+/// The message for an error a user can act on.
+fn failure_message(error: &model::Error) -> Option<String> {
+    match error {
+        model::Error::InvalidTwoFactorCode => Some("Wrong code".to_string()),
+        model::Error::TwoFactorAlreadyEnabled => {
+            Some("Two-factor authentication is already on".to_string())
+        }
+        model::Error::TwoFactorRequired => {
+            Some("Your organization requires two-factor authentication".to_string())
+        }
+        _ => None,
+    }
+}
+
+pub async fn two_factor_page(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let template = TwoFactorPage {
+        status: get_two_factor_status(&ctx, &mm).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn start_enrolment_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+) -> Result<impl IntoResponse> {
+    let enrolment = match start_two_factor_enrolment(&ctx, &mm).await {
+        Ok(enrolment) => enrolment,
+        Err(e) => {
+            return match failure_message(&e) {
+                Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+                None => Err(e.into()),
+            }
+        }
+    };
+
+    let template = EnrolmentFragment {
+        qr_code: qr_svg(&enrolment.otpauth_url).unwrap_or_default(),
+        enrolment,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn confirm_enrolment_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(form): Form<CodeForm>,
+) -> Result<impl IntoResponse> {
+    let recovery_codes = match confirm_two_factor_enrolment(&ctx, &mm, &form.code).await {
+        Ok(recovery_codes) => recovery_codes,
+        Err(e) => {
+            return match failure_message(&e) {
+                Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+                None => Err(e.into()),
+            }
+        }
+    };
+
+    let reply_html = with_toast_response(
+        render_recovery_codes(recovery_codes),
+        ToastSeverity::Succes,
+        "Two-Factor Authentication On",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn regenerate_recovery_codes_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(form): Form<CodeForm>,
+) -> Result<impl IntoResponse> {
+    let recovery_codes = match regenerate_recovery_codes(&ctx, &mm, &form.code).await {
+        Ok(recovery_codes) => recovery_codes,
+        Err(e) => {
+            return match failure_message(&e) {
+                Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+                None => Err(e.into()),
+            }
+        }
+    };
+
+    let reply_html = with_toast_response(
+        render_recovery_codes(recovery_codes),
+        ToastSeverity::Succes,
+        "New Recovery Codes, the old ones no longer work",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn disable_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(form): Form<CodeForm>,
+) -> Result<impl IntoResponse> {
+    if let Err(e) = disable_two_factor(&ctx, &mm, &form.code).await {
+        return match failure_message(&e) {
+            Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+            None => Err(e.into()),
+        };
+    }
+
+    let template = StatusFragment {
+        status: get_two_factor_status(&ctx, &mm).await?,
+    };
+    let reply_html = with_toast_response(
+        template.render().unwrap(),
+        ToastSeverity::Succes,
+        "Two-Factor Authentication Off",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
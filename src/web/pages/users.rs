@@ -0,0 +1,325 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::invitation::{
+    get_invitations, invite_user, revoke_invitation, Invitation, InvitationDelivery,
+    InvitationForCreate,
+};
+use crate::model::login_attempt::{get_login_attempts, LoginAttempt, LoginOutcome};
+use crate::model::two_factor::{get_two_factor_status, reset_two_factor, set_two_factor_required};
+use crate::model::user::{
+    deactivate_user, get_users, reactivate_user, reset_password, unlock_user, update_user_roles,
+    User, UserRoles, MIN_PASSWORD_LENGTH,
+};
+use crate::model::ModelManager;
+use crate::web::error::Result;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse};
+use axum::routing::{delete, get, post, put};
+use axum::{Form, Router};
+use serde::Deserialize;
+
+use super::toasts::{toast_only_response, with_toast_response, ToastSeverity};
+
+pub fn pages_users(mm: ModelManager) -> Router {
+    Router::new()
+        .route("/settings/users", get(users_page))
+        .route("/settings/users", post(invite_user_form))
+        .route("/settings/invitations/:id", delete(revoke_invitation_row))
+        .route("/settings/users/:id/roles", put(update_roles_form))
+        .route("/settings/users/:id/password", put(reset_password_form))
+        .route("/settings/users/:id/deactivate", post(deactivate_user_row))
+        .route("/settings/users/:id/reactivate", post(reactivate_user_row))
+        .route("/settings/users/:id/unlock", post(unlock_user_row))
+        .route(
+            "/settings/users/:id/two-factor/reset",
+            post(reset_two_factor_row),
+        )
+        .route(
+            "/settings/two-factor/required",
+            put(two_factor_required_form),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordForm {
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorRequiredForm {
+    #[serde(default)]
+    required: bool,
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "users/pages_users.html")]
+pub struct UsersPage {
+    pub users: Vec<User>,
+    pub invitations: Vec<Invitation>,
+    pub link: Option<String>,
+    pub min_password_length: usize,
+    pub two_factor_required: bool,
+    pub login_attempts: Vec<LoginAttempt>,
+}
+
+/// Pending invitations, after the link of a new one that was not mailed.
+#[derive(Template)]
+#[template(path = "users/fragments/invitations.html")]
+pub struct InvitationsFragment {
+    pub invitations: Vec<Invitation>,
+    pub link: Option<String>,
+}
+
+/// The setting as it is, after a change or a failed one.
+#[derive(Template)]
+#[template(path = "users/fragments/two_factor_required.html")]
+pub struct TwoFactorRequiredFragment {
+    pub two_factor_required: bool,
+}
+
+#[derive(Template)]
+#[template(path = "users/fragments/table_entries.html")]
+pub struct TableEntries {
+    pub users: Vec<User>,
+    pub min_password_length: usize,
+}
+// endregion: Templates
+
+// region: Handlers
+async fn render_table(ctx: &Ctx, mm: &ModelManager) -> Result<String> {
+    let template = TableEntries {
+        users: get_users(ctx, mm).await?,
+        min_password_length: MIN_PASSWORD_LENGTH,
+    };
+    Ok(template.render().unwrap())
+}
+
+async fn render_invitations(ctx: &Ctx, mm: &ModelManager, link: Option<String>) -> Result<String> {
+    let template = InvitationsFragment {
+        invitations: get_invitations(ctx, mm).await?,
+        link,
+    };
+    Ok(template.render().unwrap())
+}
+
+/// The message for an error an admin can act on.
+fn failure_message(error: &model::Error) -> Option<String> {
+    match error {
+        model::Error::InvalidUser { username } if username.is_empty() => {
+            Some("Enter a name and a username".to_string())
+        }
+        model::Error::InvalidUser { username } => Some(format!("{username} is already a user")),
+        model::Error::InvalidEmail { email } => Some(format!("{email} is not an email address")),
+        model::Error::InvalidPassword => Some(format!(
+            "Passwords need at least {MIN_PASSWORD_LENGTH} characters"
+        )),
+        model::Error::LastActiveAdmin { .. } => {
+            Some("The organization needs another active admin first".to_string())
+        }
+        model::Error::TwoFactorSetupRequired => {
+            Some("Set up two-factor authentication for yourself first".to_string())
+        }
+        _ => None,
+    }
+}
+
+pub async fn users_page(State(mm): State<ModelManager>, ctx: Ctx) -> Result<impl IntoResponse> {
+    let template = UsersPage {
+        users: get_users(&ctx, &mm).await?,
+        invitations: get_invitations(&ctx, &mm).await?,
+        link: None,
+        min_password_length: MIN_PASSWORD_LENGTH,
+        two_factor_required: get_two_factor_status(&ctx, &mm).await?.required,
+        login_attempts: get_login_attempts(&ctx, &mm).await?,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn invite_user_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(invitation_for_create): Form<InvitationForCreate>,
+) -> Result<impl IntoResponse> {
+    let (link, severity, message) = match invite_user(&ctx, &mm, invitation_for_create).await {
+        Ok(InvitationDelivery::Mailed { email }) => (
+            None,
+            ToastSeverity::Succes,
+            format!("Invitation Sent to {email}"),
+        ),
+        Ok(InvitationDelivery::Link(link)) => (
+            Some(link),
+            ToastSeverity::Succes,
+            "Invitation Created".to_string(),
+        ),
+        // The invitation is saved; inviting again sends a new link.
+        Err(model::Error::Mail(_)) => (
+            None,
+            ToastSeverity::Failure,
+            "The invitation could not be mailed, invite again to resend".to_string(),
+        ),
+        Err(e) => match failure_message(&e) {
+            Some(message) => return Ok(toast_only_response(ToastSeverity::Failure, &message)),
+            None => return Err(e.into()),
+        },
+    };
+
+    let reply_html = with_toast_response(
+        render_invitations(&ctx, &mm, link).await?,
+        severity,
+        &message,
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn revoke_invitation_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    revoke_invitation(&ctx, &mm, id).await?;
+
+    let reply_html = render_invitations(&ctx, &mm, None).await?;
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+/// Answers with a toast; on failure also with the table, so the checkboxes
+/// show the roles as they are.
+pub async fn update_roles_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(roles): Form<UserRoles>,
+) -> Result<impl IntoResponse> {
+    if let Err(e) = update_user_roles(&ctx, &mm, id, roles).await {
+        let Some(message) = failure_message(&e) else {
+            return Err(e.into());
+        };
+        let reply_html = with_toast_response(
+            render_table(&ctx, &mm).await?,
+            ToastSeverity::Failure,
+            &message,
+        );
+        return Ok((StatusCode::OK, Html(reply_html)).into_response());
+    }
+
+    Ok(toast_only_response(ToastSeverity::Succes, "Roles Saved"))
+}
+
+pub async fn reset_password_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Form(form): Form<PasswordForm>,
+) -> Result<impl IntoResponse> {
+    if let Err(e) = reset_password(&ctx, &mm, id, &form.password).await {
+        return match failure_message(&e) {
+            Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+            None => Err(e.into()),
+        };
+    }
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "Password Reset, the user was logged out",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn deactivate_user_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    if let Err(e) = deactivate_user(&ctx, &mm, id).await {
+        return match failure_message(&e) {
+            Some(message) => Ok(toast_only_response(ToastSeverity::Failure, &message)),
+            None => Err(e.into()),
+        };
+    }
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "User Deactivated",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn reactivate_user_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    reactivate_user(&ctx, &mm, id).await?;
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "User Reactivated",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn unlock_user_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    unlock_user(&ctx, &mm, id).await?;
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "User Unlocked",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn reset_two_factor_row(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse> {
+    reset_two_factor(&ctx, &mm, id).await?;
+
+    let reply_html = with_toast_response(
+        render_table(&ctx, &mm).await?,
+        ToastSeverity::Succes,
+        "Two-Factor Authentication Reset, the user was logged out",
+    );
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+pub async fn two_factor_required_form(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Form(form): Form<TwoFactorRequiredForm>,
+) -> Result<impl IntoResponse> {
+    let (severity, message) = match set_two_factor_required(&ctx, &mm, form.required).await {
+        Ok(()) if form.required => (
+            ToastSeverity::Succes,
+            "Two-Factor Authentication Required".to_string(),
+        ),
+        Ok(()) => (
+            ToastSeverity::Succes,
+            "Two-Factor Authentication Optional".to_string(),
+        ),
+        Err(e) => match failure_message(&e) {
+            Some(message) => (ToastSeverity::Failure, message),
+            None => return Err(e.into()),
+        },
+    };
+
+    let template = TwoFactorRequiredFragment {
+        two_factor_required: get_two_factor_status(&ctx, &mm).await?.required,
+    };
+    let reply_html = with_toast_response(template.render().unwrap(), severity, &message);
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+// endregion: Handlers
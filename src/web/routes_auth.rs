@@ -1,19 +1,283 @@
-use axum::extract::State;
+use askama::Template;
+use axum::extract::{ConnectInfo, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Redirect};
+use axum::routing::{get, post};
+use axum::{middleware, Form, Router};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::time::Duration;
 use tower_cookies::{Cookie, Cookies};
 
+use crate::barcode::qr_svg;
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::session::{create_pending_session, create_session, delete_session};
+use crate::model::two_factor::{
+    complete_pending_login, get_login_step, get_pending_login_step, start_pending_enrolment,
+    LoginStep, TwoFactorEnrolment,
+};
+use crate::model::user::authenticate;
 use crate::model::ModelManager;
+use crate::web::error::Result;
+use crate::web::mw_rate_limit::{attempt_failed, mw_rate_limit, RateLimit};
+use crate::web::pages::toasts::{toast_only_response, ToastSeverity};
 
+pub const AUTH_TOKEN: &str = "auth-token";
+
+/// Where a login lands.
+pub const HOME: &str = "/products";
+
+/// Where a superuser's login lands.
+pub const SUPERUSER_HOME: &str = "/superuser";
+
+pub fn routes_auth(mm: ModelManager) -> Router {
+    // Wrong passwords and codes, per address; the model also counts wrong
+    // passwords per user.
+    let login_limit = RateLimit::new(
+        10,
+        Duration::from_secs(1),
+        Duration::from_secs(5 * 60),
+        Duration::from_secs(60 * 60),
+    );
+
+    Router::new()
+        .route("/login", get(login_page))
+        .route(
+            "/login",
+            post(login_handler).route_layer(middleware::from_fn_with_state(
+                login_limit.clone(),
+                mw_rate_limit,
+            )),
+        )
+        .route("/login/two-factor", get(two_factor_page))
+        .route(
+            "/login/two-factor",
+            post(two_factor_handler)
+                .route_layer(middleware::from_fn_with_state(login_limit, mw_rate_limit)),
+        )
+        .route("/logout", post(logout_handler))
+        .with_state(mm)
+}
+
+#[derive(Deserialize)]
 pub struct LoginCredentials {
+    /// Empty for a superuser.
+    #[serde(default)]
+    organization: String,
     username: String,
     password: String,
 }
 
-pub const AUTH_TOKEN: &str = "auth-token";
+#[derive(Deserialize)]
+pub struct TwoFactorCode {
+    code: String,
+}
+
+// region: Templates
+#[derive(Template)]
+#[template(path = "auth/pages_login.html")]
+pub struct LoginPage {}
 
-async fn login_handler(State(_mm): State<ModelManager>, cookies: Cookies) {
-    let token = "t";
-    let mut cookie = Cookie::new(AUTH_TOKEN, token.to_string());
+/// Asks for a code, or first has the user set up their authenticator.
+#[derive(Template)]
+#[template(path = "auth/pages_two_factor.html")]
+pub struct TwoFactorPage {
+    /// None when the user already has two-factor authentication.
+    pub enrolment: Option<TwoFactorEnrolment>,
+    pub qr_code: String,
+}
+
+/// Recovery codes, shown once.
+#[derive(Template)]
+#[template(path = "auth/fragments/recovery_codes.html")]
+pub struct RecoveryCodesFragment {
+    pub recovery_codes: Vec<String>,
+    /// Where the user goes once they have saved the codes.
+    pub continue_to: &'static str,
+}
+// endregion: Templates
+
+/// Logs `user_id` in on this browser.
+pub async fn start_session(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    cookies: &Cookies,
+    user_id: i64,
+) -> Result<()> {
+    let token = create_session(ctx, mm, user_id).await?;
+    set_session_cookie(cookies, token);
+
+    Ok(())
+}
+
+/// Makes `token` the session of this browser.
+pub fn set_session_cookie(cookies: &Cookies, token: String) {
+    let mut cookie = Cookie::new(AUTH_TOKEN, token);
     cookie.set_http_only(true);
     cookie.set_path("/");
     cookies.add(cookie);
 }
+
+// region: Handlers
+async fn login_page() -> Result<impl IntoResponse> {
+    let reply_html = LoginPage {}.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+async fn login_handler(
+    State(mm): State<ModelManager>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    cookies: Cookies,
+    Form(credentials): Form<LoginCredentials>,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    let user_id = match authenticate(
+        &ctx,
+        &mm,
+        &credentials.organization,
+        &credentials.username,
+        &credentials.password,
+        addr.ip(),
+    )
+    .await
+    {
+        Ok(user_id) => user_id,
+        Err(model::Error::Unauhtorized(_)) => {
+            return Ok(attempt_failed(toast_only_response(
+                ToastSeverity::Failure,
+                "Wrong organization, username or password",
+            )))
+        }
+        Err(model::Error::LoginThrottled {
+            retry_after_seconds,
+        }) => {
+            return Ok(attempt_failed(toast_only_response(
+                ToastSeverity::Failure,
+                &format!("Too many failed logins, try again in {retry_after_seconds} seconds"),
+            )))
+        }
+        Err(model::Error::LoginLocked { locked_until }) => {
+            return Ok(attempt_failed(toast_only_response(
+                ToastSeverity::Failure,
+                &format!(
+                    "Too many failed logins, try again after {} UTC or ask an admin to unlock",
+                    locked_until.format("%H:%M")
+                ),
+            )))
+        }
+        Err(model::Error::OrganizationSuspended { reason, .. }) => {
+            return Ok(toast_only_response(ToastSeverity::Failure, &reason))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Users with two-factor authentication give a code before they are in.
+    if get_login_step(&ctx, &mm, user_id).await? != LoginStep::Granted {
+        let token = create_pending_session(&ctx, &mm, user_id).await?;
+        set_session_cookie(&cookies, token);
+        return Ok((StatusCode::OK, [("HX-Redirect", "/login/two-factor")]).into_response());
+    }
+
+    start_session(&ctx, &mm, &cookies, user_id).await?;
+
+    // Only superusers log in without an organization.
+    let home = match credentials.organization.trim() {
+        "" => SUPERUSER_HOME,
+        _ => HOME,
+    };
+    Ok((StatusCode::OK, [("HX-Redirect", home)]).into_response())
+}
+
+async fn two_factor_page(
+    State(mm): State<ModelManager>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    let Some(token) = cookies.get(AUTH_TOKEN).map(|c| c.value().to_string()) else {
+        return Ok(Redirect::to("/login").into_response());
+    };
+    let step = match get_pending_login_step(&ctx, &mm, &token).await {
+        Ok(step) => step,
+        Err(model::Error::Unauhtorized(_)) => return Ok(Redirect::to("/login").into_response()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let template = match step {
+        LoginStep::Enrol => {
+            let enrolment = start_pending_enrolment(&ctx, &mm, &token).await?;
+            TwoFactorPage {
+                qr_code: qr_svg(&enrolment.otpauth_url).unwrap_or_default(),
+                enrolment: Some(enrolment),
+            }
+        }
+        _ => TwoFactorPage {
+            enrolment: None,
+            qr_code: String::new(),
+        },
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+async fn two_factor_handler(
+    State(mm): State<ModelManager>,
+    cookies: Cookies,
+    Form(form): Form<TwoFactorCode>,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    let token = cookies
+        .get(AUTH_TOKEN)
+        .map(|c| c.value().to_string())
+        .unwrap_or_default();
+    let login = match complete_pending_login(&ctx, &mm, &token, &form.code).await {
+        Ok(login) => login,
+        Err(model::Error::InvalidTwoFactorCode) => {
+            return Ok(attempt_failed(toast_only_response(
+                ToastSeverity::Failure,
+                "Wrong code",
+            )))
+        }
+        // Expired, or ended after too many wrong codes.
+        Err(model::Error::Unauhtorized(_)) => {
+            return Ok((StatusCode::OK, [("HX-Redirect", "/login")]).into_response())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    set_session_cookie(&cookies, login.token);
+
+    let home = match login.organization_id {
+        Some(_) => HOME,
+        None => SUPERUSER_HOME,
+    };
+    if login.recovery_codes.is_empty() {
+        return Ok((StatusCode::OK, [("HX-Redirect", home)]).into_response());
+    }
+
+    let template = RecoveryCodesFragment {
+        recovery_codes: login.recovery_codes,
+        continue_to: home,
+    };
+    let reply_html = template.render().unwrap();
+    Ok((StatusCode::OK, Html(reply_html)).into_response())
+}
+
+async fn logout_handler(
+    State(mm): State<ModelManager>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse> {
+    let ctx = Ctx::empty();
+
+    if let Some(cookie) = cookies.get(AUTH_TOKEN) {
+        delete_session(&ctx, &mm, cookie.value()).await?;
+    }
+    let mut removal = Cookie::named(AUTH_TOKEN);
+    removal.set_path("/");
+    cookies.remove(removal);
+
+    Ok((StatusCode::OK, [("HX-Redirect", "/login")]).into_response())
+}
+// endregion: Handlers
@@ -0,0 +1,283 @@
+use crate::ctx::Ctx;
+use crate::model;
+use crate::model::inventory_log::{export_logs, InventoryLog, InventoryLogForSearch};
+use crate::model::inventory_transaction::{
+    export_transactions, InventoryTransactionForSearch, InventoryTransactionSummary,
+};
+use crate::model::permissions::Permissions;
+use crate::model::products::{export_products, ProductForSearch, ProductWithStockLevel};
+use crate::model::ModelManager;
+use crate::spreadsheet::write_csv;
+use crate::web::mw_auth::mw_require_scope;
+
+use axum::body::StreamBody;
+use axum::extract::{Query, State};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{middleware, BoxError, Router};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub fn routes_export(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/export/products",
+            get(products_export_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadProducts,
+                mw_require_scope,
+            )),
+        )
+        .route(
+            "/api/v1/export/inventory/logs",
+            get(logs_export_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadInventory,
+                mw_require_scope,
+            )),
+        )
+        .route(
+            "/api/v1/export/inventory/transactions",
+            get(transactions_export_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadInventory,
+                mw_require_scope,
+            )),
+        )
+        .with_state(mm)
+}
+
+// region: Formats
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// A row of an export. JSON Lines get `to_json` as is; CSV takes the values
+/// of `COLUMNS`, which is also the header, from it.
+trait ExportRow: Send + 'static {
+    const COLUMNS: &'static [&'static str];
+
+    fn to_json(&self) -> Value;
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn encode_row<T: ExportRow>(format: ExportFormat, row: &T) -> Result<String, BoxError> {
+    let value = row.to_json();
+    match format {
+        ExportFormat::Csv => {
+            let cells = T::COLUMNS.iter().map(|c| csv_cell(&value[*c])).collect();
+            Ok(write_csv(&[cells])?)
+        }
+        ExportFormat::Jsonl => Ok(format!("{value}\n")),
+    }
+}
+
+/// Encodes rows as they arrive from the database, so memory use does not
+/// grow with the size of the export.
+fn export_response<T: ExportRow>(
+    name: &str,
+    format: ExportFormat,
+    rows: BoxStream<'static, model::Result<T>>,
+) -> Response {
+    let header = match format {
+        ExportFormat::Csv => {
+            let header = T::COLUMNS.iter().map(|c| c.to_string()).collect();
+            Some(write_csv(&[header]).map_err(BoxError::from))
+        }
+        ExportFormat::Jsonl => None,
+    };
+    let lines = rows.map(move |row| encode_row(format, &row?));
+    let body = stream::iter(header).chain(lines);
+
+    (
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{name}.{}\"", format.extension()),
+            ),
+        ],
+        StreamBody::new(body),
+    )
+        .into_response()
+}
+// endregion: Formats
+
+// region: Rows
+impl ExportRow for ProductWithStockLevel {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "sku",
+        "brand",
+        "name",
+        "description",
+        "category",
+        "price",
+        "currency",
+        "base_unit",
+        "quantity",
+    ];
+
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "sku": self.sku,
+            "brand": self.brand,
+            "name": self.name,
+            "description": self.description,
+            "category": self.category_name,
+            "price": self.price.amount().to_string(),
+            "currency": self.price.currency().code(),
+            "base_unit": self.base_unit,
+            "quantity": self.quantity,
+        })
+    }
+}
+
+impl ExportRow for InventoryLog {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "timestamp",
+        "transaction_id",
+        "action",
+        "product_id",
+        "product_display_name",
+        "warehouse_id",
+        "quantity",
+        "unit",
+        "unit_quantity",
+        "price",
+        "currency",
+        "base_price",
+        "base_currency",
+        "lot_number",
+        "tax_rate",
+        "net_amount",
+        "tax_amount",
+        "gross_amount",
+    ];
+
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "timestamp": self.timestamp,
+            "transaction_id": self.transaction_id,
+            "action": format!("{:?}", self.action),
+            "product_id": self.product_id,
+            "product_display_name": self.product_display_name,
+            "warehouse_id": self.warehouse_id,
+            "quantity": self.quantity,
+            "unit": self.unit_name,
+            "unit_quantity": self.unit_quantity,
+            "price": self.price.amount().to_string(),
+            "currency": self.price.currency().code(),
+            "base_price": self.base_price.amount().to_string(),
+            "base_currency": self.base_price.currency().code(),
+            "lot_number": self.lot_number,
+            "tax_rate": self.tax.as_ref().map(|t| t.rate.to_string()),
+            "net_amount": self.tax.as_ref().map(|t| t.net.amount().to_string()),
+            "tax_amount": self.tax.as_ref().map(|t| t.tax.amount().to_string()),
+            "gross_amount": self.tax.as_ref().map(|t| t.gross.amount().to_string()),
+        })
+    }
+}
+
+impl ExportRow for InventoryTransactionSummary {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "timestamp",
+        "action",
+        "line_count",
+        "quantity",
+        "value",
+        "currency",
+        "exchange_rate",
+        "base_value",
+        "base_currency",
+    ];
+
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "timestamp": self.timestamp,
+            "action": format!("{:?}", self.action),
+            "line_count": self.line_count,
+            "quantity": self.quantity,
+            "value": self.value.amount().to_string(),
+            "currency": self.value.currency().code(),
+            "exchange_rate": self.exchange_rate.normalized().to_string(),
+            "base_value": self.base_value.amount().to_string(),
+            "base_currency": self.base_value.currency().code(),
+        })
+    }
+}
+// endregion: Rows
+
+// region: Handlers
+async fn products_export_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<ExportQuery>,
+    Query(product_for_search): Query<ProductForSearch>,
+) -> Response {
+    let products = export_products(&ctx, &mm, product_for_search);
+
+    export_response("products", query.format, products)
+}
+
+async fn logs_export_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<ExportQuery>,
+    Query(log_for_search): Query<InventoryLogForSearch>,
+) -> Response {
+    let logs = export_logs(&ctx, &mm, log_for_search);
+
+    export_response("inventory-logs", query.format, logs)
+}
+
+async fn transactions_export_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<ExportQuery>,
+    Query(transaction_for_search): Query<InventoryTransactionForSearch>,
+) -> Response {
+    let transactions = export_transactions(&ctx, &mm, transaction_for_search);
+
+    export_response("inventory-transactions", query.format, transactions)
+}
+// endregion: Handlers
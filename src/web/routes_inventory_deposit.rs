@@ -1,48 +1,101 @@
 use crate::ctx::Ctx;
+use crate::model::inventory_transaction::{
+    InventoryTransactionAction, InventoryTransactionForCreate, InventoryTransactionLogForCreate,
+};
+use crate::model::organization::get_base_currency;
+use crate::model::permissions::Permissions;
 use crate::model::ModelManager;
+use crate::money::{Currency, Money};
+use crate::web::mw_auth::mw_require_scope;
 
 use super::error::Result;
 use axum::extract::State;
 use axum::routing::post;
-use axum::{Json, Router};
+use axum::{middleware, Json, Router};
+use axum_valid::Valid;
+use chrono::NaiveDate;
 use serde::Deserialize;
 use serde_json::{json, Value};
+use serde_with::{serde_as, DisplayFromStr};
+use sqlx::types::BigDecimal;
 use validator::Validate;
 
 pub fn routes_inventory_deposit(mm: ModelManager) -> Router {
     Router::new()
-        .route("/api/v1/inventory/deposits", post(deposit_handler))
+        .route(
+            "/api/v1/inventory/deposits",
+            post(deposit_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::WriteDeposits,
+                mw_require_scope,
+            )),
+        )
         .with_state(mm)
 }
 
 #[derive(Debug, Deserialize, Validate)]
 struct InventoryDepositPayloadItem {
-    #[validate(required)]
+    #[validate(required, range(min = 1))]
     quantity: Option<i64>,
     #[validate(required)]
     product_id: Option<i64>,
     #[validate(required)]
-    price: Option<f64>,
+    price: Option<Money>,
     #[validate(required(message = "is required"))]
     warehouse_id: Option<i64>,
+    /// One per unit, required for serialized products.
+    serial_numbers: Option<Vec<String>>,
+    /// Unit the quantity and price are given in, defaults to the base unit.
+    unit: Option<String>,
+    lot_number: Option<String>,
+    expiry_date: Option<NaiveDate>,
 }
 
+#[serde_as]
 #[derive(Debug, Deserialize, Validate)]
 struct InventoryDepositPayload {
+    /// Currency of every price, defaults to the base currency.
+    currency: Option<Currency>,
+    /// Units of the base currency per unit of `currency`, as a decimal
+    /// string. Defaults to the exchange rate in effect today.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    exchange_rate: Option<BigDecimal>,
     #[validate]
     items: Vec<InventoryDepositPayloadItem>,
 }
 
 async fn deposit_handler(
-    State(_mm): State<ModelManager>,
-    Json(body): Json<InventoryDepositPayload>,
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Valid(Json(body)): Valid<Json<InventoryDepositPayload>>,
 ) -> Result<Json<Value>> {
-    let ctx = Ctx::new(1, 1);
+    let currency = match body.currency {
+        Some(currency) => currency,
+        None => get_base_currency(&ctx, &mm).await?,
+    };
+
+    let mut deposit = InventoryTransactionForCreate::new(InventoryTransactionAction::Deposit);
+    deposit.currency = Some(currency);
+    deposit.exchange_rate = body.exchange_rate;
+    for item in body.items {
+        let price = item.price.unwrap_or_default();
+        deposit.add_log(InventoryTransactionLogForCreate {
+            quantity: item.quantity.unwrap_or_default(),
+            product_id: item.product_id.unwrap_or_default(),
+            price: Money::new(price.amount().clone(), currency),
+            warehouse_id: item.warehouse_id.unwrap_or_default(),
+            lot_id: None,
+            lot_number: item.lot_number,
+            expiry_date: item.expiry_date,
+            serial_numbers: item.serial_numbers.unwrap_or_default(),
+            unit_name: item.unit,
+        });
+    }
+    deposit.save(&ctx, &mm).await?;
 
-    println!("{:?}", body);
     let response = Json(json!({
         "result": {
-            "healthy": true
+            "success": true
         }
     }));
 
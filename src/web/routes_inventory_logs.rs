@@ -0,0 +1,77 @@
+use crate::ctx::Ctx;
+use crate::model::inventory_log::get_logs;
+use crate::model::pageable::Pageable;
+use crate::model::permissions::Permissions;
+use crate::model::ModelManager;
+use crate::web::mw_auth::mw_require_scope;
+
+use super::error::Result;
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{middleware, Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub fn routes_inventory_logs(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/inventory/logs",
+            get(logs_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadInventory,
+                mw_require_scope,
+            )),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+struct InventoryLogsQuery {
+    page: Option<i64>,
+    items_per_page: Option<i64>,
+}
+
+async fn logs_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<InventoryLogsQuery>,
+) -> Result<Json<Value>> {
+    let pageable = Pageable::new(query.page.unwrap_or(1), query.items_per_page.unwrap_or(100));
+    let logs = get_logs(&ctx, &mm, pageable).await?;
+
+    let logs: Vec<Value> = logs
+        .into_iter()
+        .map(|l| {
+            json!({
+                "id": l.id,
+                "product_id": l.product_id,
+                "product_display_name": l.product_display_name,
+                "action": format!("{:?}", l.action),
+                "timestamp": l.timestamp,
+                "quantity": l.quantity,
+                "price": l.price,
+                "base_price": l.base_price,
+                "unit": l.unit_name,
+                "unit_quantity": l.unit_quantity,
+                "warehouse_id": l.warehouse_id,
+                "transaction_id": l.transaction_id,
+                "lot_id": l.lot_id,
+                "lot_number": l.lot_number,
+                "tax": l.tax.as_ref().map(|t| json!({
+                    "tax_class_id": t.tax_class_id,
+                    "rate": t.rate.to_string(),
+                    "net": t.net,
+                    "tax": t.tax,
+                    "gross": t.gross,
+                })),
+            })
+        })
+        .collect();
+
+    let response = Json(json!({
+        "result": {
+            "logs": logs
+        }
+    }));
+
+    Ok(response)
+}
@@ -0,0 +1,123 @@
+use crate::ctx::Ctx;
+use crate::model::inventory_transaction::{
+    InventoryTransactionAction, InventoryTransactionForCreate, InventoryTransactionLogForCreate,
+};
+use crate::model::organization::get_base_currency;
+use crate::model::permissions::Permissions;
+use crate::model::price_list::{get_effective_price, EffectivePriceForSearch};
+use crate::model::ModelManager;
+use crate::money::{Currency, Money};
+use crate::web::mw_auth::mw_require_scope;
+
+use super::error::Result;
+use axum::extract::State;
+use axum::routing::post;
+use axum::{middleware, Json, Router};
+use axum_valid::Valid;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use serde_with::{serde_as, DisplayFromStr};
+use sqlx::types::BigDecimal;
+use validator::Validate;
+
+pub fn routes_inventory_sales(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/inventory/sales",
+            post(sales_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::WriteSales,
+                mw_require_scope,
+            )),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct InventorySalesPayloadItem {
+    #[validate(required, range(min = 1))]
+    quantity: Option<i64>,
+    #[validate(required)]
+    product_id: Option<i64>,
+    /// Defaults to the customer's effective price.
+    price: Option<Money>,
+    #[validate(required(message = "is required"))]
+    warehouse_id: Option<i64>,
+    /// One per unit, required for serialized products.
+    serial_numbers: Option<Vec<String>>,
+    /// Unit the quantity and price are given in, defaults to the base unit.
+    unit: Option<String>,
+    /// Sell from this lot instead of first-expiring-first-out.
+    lot_id: Option<i64>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize, Validate)]
+struct InventorySalesPayload {
+    /// Whose price list prices the items.
+    customer_id: Option<i64>,
+    /// Currency of every price, defaults to the base currency.
+    currency: Option<Currency>,
+    /// Units of the base currency per unit of `currency`, as a decimal
+    /// string. Defaults to the exchange rate in effect today.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    exchange_rate: Option<BigDecimal>,
+    #[validate]
+    items: Vec<InventorySalesPayloadItem>,
+}
+
+async fn sales_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Valid(Json(body)): Valid<Json<InventorySalesPayload>>,
+) -> Result<Json<Value>> {
+    let currency = match body.currency {
+        Some(currency) => currency,
+        None => get_base_currency(&ctx, &mm).await?,
+    };
+
+    let mut sales = InventoryTransactionForCreate::new(InventoryTransactionAction::Sales);
+    sales.currency = Some(currency);
+    sales.exchange_rate = body.exchange_rate;
+    sales.customer_id = body.customer_id;
+    for item in body.items {
+        let quantity = item.quantity.unwrap_or_default();
+        let product_id = item.product_id.unwrap_or_default();
+        let price = match item.price {
+            Some(price) => Money::new(price.amount().clone(), currency),
+            None => {
+                let effective_price_for_search = EffectivePriceForSearch {
+                    customer_id: body.customer_id,
+                    product_id,
+                    quantity,
+                    unit: item.unit.clone(),
+                    date: None,
+                };
+                get_effective_price(&ctx, &mm, effective_price_for_search)
+                    .await?
+                    .price
+            }
+        };
+        sales.add_log(InventoryTransactionLogForCreate {
+            quantity,
+            product_id,
+            price,
+            warehouse_id: item.warehouse_id.unwrap_or_default(),
+            lot_id: item.lot_id,
+            lot_number: None,
+            expiry_date: None,
+            serial_numbers: item.serial_numbers.unwrap_or_default(),
+            unit_name: item.unit,
+        });
+    }
+    let transaction_id = sales.save(&ctx, &mm).await?;
+
+    let response = Json(json!({
+        "result": {
+            "success": true,
+            "transaction_id": transaction_id
+        }
+    }));
+
+    Ok(response)
+}
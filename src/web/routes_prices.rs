@@ -0,0 +1,56 @@
+use crate::ctx::Ctx;
+use crate::model::permissions::Permissions;
+use crate::model::price_list::{get_effective_price, EffectivePriceForSearch, PriceSource};
+use crate::model::ModelManager;
+use crate::web::mw_auth::mw_require_scope;
+
+use super::error::Result;
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{middleware, Json, Router};
+use serde_json::{json, Value};
+
+pub fn routes_prices(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/prices/effective",
+            get(effective_price_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadProducts,
+                mw_require_scope,
+            )),
+        )
+        .with_state(mm)
+}
+
+/// The price per `unit` a customer pays for `quantity` of a product on `date`.
+async fn effective_price_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<EffectivePriceForSearch>,
+) -> Result<Json<Value>> {
+    let effective = get_effective_price(&ctx, &mm, query).await?;
+
+    let source = match effective.source {
+        PriceSource::PriceList {
+            price_list_id,
+            price_list_name,
+            min_quantity,
+        } => json!({
+            "type": "price_list",
+            "price_list_id": price_list_id,
+            "price_list_name": price_list_name,
+            "min_quantity": min_quantity,
+        }),
+        PriceSource::Product => json!({ "type": "product" }),
+    };
+
+    let response = Json(json!({
+        "result": {
+            "price": effective.price,
+            "tax_inclusive": effective.tax_inclusive,
+            "source": source
+        }
+    }));
+
+    Ok(response)
+}
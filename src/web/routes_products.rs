@@ -0,0 +1,50 @@
+use crate::ctx::Ctx;
+use crate::model::barcode::get_product_by_barcode;
+use crate::model::permissions::Permissions;
+use crate::model::ModelManager;
+use crate::web::mw_auth::mw_require_scope;
+
+use super::error::Result;
+use axum::extract::{Path, State};
+use axum::routing::get;
+use axum::{middleware, Json, Router};
+use serde_json::{json, Value};
+
+pub fn routes_products(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/products/by-barcode/:code",
+            get(by_barcode_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadProducts,
+                mw_require_scope,
+            )),
+        )
+        .with_state(mm)
+}
+
+async fn by_barcode_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Path(code): Path<String>,
+) -> Result<Json<Value>> {
+    let product = get_product_by_barcode(&ctx, &mm, &code).await?;
+
+    let response = Json(json!({
+        "result": {
+            "product": {
+                "id": product.id,
+                "sku": product.sku,
+                "brand": product.brand,
+                "name": product.name,
+                "description": product.description,
+                "price": product.price,
+                "base_unit": product.base_unit,
+                "lot_tracked": product.lot_tracked,
+                "serialized": product.serialized,
+                "quantity": product.quantity,
+            }
+        }
+    }));
+
+    Ok(response)
+}
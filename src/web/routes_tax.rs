@@ -0,0 +1,71 @@
+use crate::ctx::Ctx;
+use crate::model::permissions::Permissions;
+use crate::model::tax::get_tax_summary;
+use crate::model::ModelManager;
+use crate::web::mw_auth::mw_require_scope;
+
+use super::error::Result;
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{middleware, Json, Router};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub fn routes_tax(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/reports/tax",
+            get(tax_summary_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadReports,
+                mw_require_scope,
+            )),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+struct TaxSummaryQuery {
+    from: NaiveDate,
+    /// Defaults to today.
+    to: Option<NaiveDate>,
+}
+
+async fn tax_summary_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<TaxSummaryQuery>,
+) -> Result<Json<Value>> {
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let summary = get_tax_summary(&ctx, &mm, query.from, to).await?;
+
+    let rows: Vec<Value> = summary
+        .rows
+        .iter()
+        .map(|r| {
+            json!({
+                "tax_class_id": r.tax_class_id,
+                "tax_class_name": r.tax_class_name,
+                "tax_rate": r.tax_rate.to_string(),
+                "line_count": r.line_count,
+                "net": r.net,
+                "tax": r.tax,
+                "gross": r.gross,
+            })
+        })
+        .collect();
+
+    let response = Json(json!({
+        "result": {
+            "from": summary.from,
+            "to": summary.to,
+            "currency": summary.currency,
+            "total_net": summary.total_net,
+            "total_tax": summary.total_tax,
+            "total_gross": summary.total_gross,
+            "rows": rows
+        }
+    }));
+
+    Ok(response)
+}
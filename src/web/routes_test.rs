@@ -1,5 +1,5 @@
 use crate::model::ModelManager;
-pub use crate::web::error::{Error, Result};
+use crate::web::error::Result;
 use axum::routing::get;
 use axum::{extract::State, Json, Router};
 use serde_json::{json, Value};
@@ -0,0 +1,122 @@
+use crate::ctx::Ctx;
+use crate::model::permissions::Permissions;
+use crate::model::valuation::{get_cost_of_goods_sold, get_inventory_valuation};
+use crate::model::ModelManager;
+use crate::web::mw_auth::mw_require_scope;
+
+use super::error::Result;
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{middleware, Json, Router};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub fn routes_valuation(mm: ModelManager) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/inventory/valuation",
+            get(valuation_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadReports,
+                mw_require_scope,
+            )),
+        )
+        .route(
+            "/api/v1/inventory/cogs",
+            get(cogs_handler).route_layer(middleware::from_fn_with_state(
+                Permissions::ReadReports,
+                mw_require_scope,
+            )),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize)]
+struct ValuationQuery {
+    /// Defaults to today.
+    as_of: Option<NaiveDate>,
+    /// Only products of this category and its subcategories.
+    category_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CostOfGoodsSoldQuery {
+    from: NaiveDate,
+    /// Defaults to today.
+    to: Option<NaiveDate>,
+    /// Only products of this category and its subcategories.
+    category_id: Option<i64>,
+}
+
+async fn valuation_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<ValuationQuery>,
+) -> Result<Json<Value>> {
+    let as_of = query.as_of.unwrap_or_else(|| Utc::now().date_naive());
+    let valuation = get_inventory_valuation(&ctx, &mm, as_of, query.category_id).await?;
+
+    let products: Vec<Value> = valuation
+        .products
+        .iter()
+        .map(|p| {
+            json!({
+                "product_id": p.product_id,
+                "sku": p.sku,
+                "display_name": p.display_name,
+                "quantity": p.quantity,
+                "average_cost": p.average_cost,
+                "value": p.value,
+            })
+        })
+        .collect();
+
+    let response = Json(json!({
+        "result": {
+            "as_of": valuation.as_of,
+            "currency": valuation.currency,
+            "total_value": valuation.total_value,
+            "products": products
+        }
+    }));
+
+    Ok(response)
+}
+
+async fn cogs_handler(
+    State(mm): State<ModelManager>,
+    ctx: Ctx,
+    Query(query): Query<CostOfGoodsSoldQuery>,
+) -> Result<Json<Value>> {
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let cogs = get_cost_of_goods_sold(&ctx, &mm, query.from, to, query.category_id).await?;
+
+    let products: Vec<Value> = cogs
+        .products
+        .iter()
+        .map(|p| {
+            json!({
+                "product_id": p.product_id,
+                "sku": p.sku,
+                "display_name": p.display_name,
+                "quantity": p.quantity,
+                "revenue": p.revenue,
+                "average_cost": p.average_cost,
+                "cost": p.cost,
+            })
+        })
+        .collect();
+
+    let response = Json(json!({
+        "result": {
+            "from": cogs.from,
+            "to": cogs.to,
+            "currency": cogs.currency,
+            "total_revenue": cogs.total_revenue,
+            "total_cost": cogs.total_cost,
+            "products": products
+        }
+    }));
+
+    Ok(response)
+}